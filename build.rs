@@ -0,0 +1,24 @@
+// Generates the C header for the `ffi` feature.
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+// The header goes to OUT_DIR, never into the source tree; the committed copy in
+// include/ is refreshed with `cbindgen --config cbindgen.toml --output include/prime_generator.h`
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Failed to generate C header")
+        .write_to_file(format!("{}/prime_generator.h", out_dir));
+}
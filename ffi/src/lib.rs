@@ -0,0 +1,5 @@
+//! The `prime_generator` C API (`include/prime_generator.h`) as a shared and
+//! a static library: `cargo build --release -p prime_generator_ffi` writes
+//! `libprime_generator_ffi.so` (`.dylib`, `.dll`) and `libprime_generator_ffi.a`.
+
+pub use prime_generator::ffi::*;
@@ -0,0 +1,60 @@
+#ifndef PRIME_GENERATOR_H
+#define PRIME_GENERATOR_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdint.h>
+
+/**
+ * Callback invoked for each prime by `pg_primes_in_range`; return non-zero to continue, zero to stop.
+ */
+typedef int (*PgPrimeCallback)(uint64_t prime, void *user_data);
+
+/**
+ * Tests a decimal number for primality.
+ *
+ * Returns 1 if `n` is prime, 0 if it is not, and -1 if `n` is not a valid
+ * decimal integer or is larger than 2^128 - 1, so every call returns promptly.
+ *
+ * # Safety
+ *
+ * `n` must be null or point to a NUL-terminated string.
+ */
+int pg_is_prime(const char *n);
+
+/**
+ * Returns the smallest prime greater than the decimal number `n` as a newly
+ * allocated decimal string, or null if `n` is invalid or the result exceeds 128 bits.
+ *
+ * # Safety
+ *
+ * `n` must be null or point to a NUL-terminated string. The result must be
+ * released with `pg_string_free`.
+ */
+char *pg_next_prime(const char *n);
+
+/**
+ * Releases a string returned by this library.
+ *
+ * # Safety
+ *
+ * `s` must be null or a pointer returned by this library that hasn't been freed yet.
+ */
+void pg_string_free(char *s);
+
+/**
+ * Streams every prime in `[start, end]` to `callback` in increasing order.
+ *
+ * Enumeration stops early when the callback returns zero. Returns the number
+ * of primes delivered.
+ *
+ * # Safety
+ *
+ * `callback` must be safe to call with `user_data`, which is passed through untouched.
+ */
+uint64_t pg_primes_in_range(uint64_t start,
+                            uint64_t end,
+                            PgPrimeCallback callback,
+                            void *user_data);
+
+#endif /* PRIME_GENERATOR_H */
@@ -0,0 +1,313 @@
+// Running aggregates over the primes of a run (their sum, count, largest gap,
+// counts per residue class and per decade), kept while records are written so
+// huge outputs never need a second pass.
+
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Largest modulus `residues:<m>` accepts, bounding its table of counts
+const MAX_RESIDUE_MODULUS: u128 = 1 << 20;
+
+// Decades [10^k, 10^(k+1)) that hold u128 values
+const DECADES: usize = 39;
+
+// Euler-Mascheroni constant
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+/// An aggregate a run can keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// `sum`: the sum of the primes, exact at any size.
+    Sum,
+    /// `count`: the number of primes.
+    Count,
+    /// `max_gap`: the largest gap between consecutive primes. Needs the
+    /// records in increasing order.
+    MaxGap,
+    /// `residues:<m>`: the number of primes in each residue class modulo `m`.
+    Residues(u128),
+    /// `decades`: the number of primes in each decade `[10^k, 10^(k+1))`,
+    /// against the number the logarithmic integral predicts.
+    Decades,
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Aggregate, String> {
+        match s.split_once(':') {
+            None if s == "sum" => Ok(Aggregate::Sum),
+            None if s == "count" => Ok(Aggregate::Count),
+            None if s == "max_gap" => Ok(Aggregate::MaxGap),
+            None if s == "decades" => Ok(Aggregate::Decades),
+            Some(("residues", modulus)) => match modulus.parse() {
+                Ok(modulus @ 1..=MAX_RESIDUE_MODULUS) => Ok(Aggregate::Residues(modulus)),
+                _ => Err(format!("Invalid aggregate {}: the modulus must be between 1 and {}", s, MAX_RESIDUE_MODULUS)),
+            },
+            _ => Err(format!("Unknown aggregate: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Aggregate::Sum => f.write_str("sum"),
+            Aggregate::Count => f.write_str("count"),
+            Aggregate::MaxGap => f.write_str("max_gap"),
+            Aggregate::Residues(modulus) => write!(f, "residues:{}", modulus),
+            Aggregate::Decades => f.write_str("decades"),
+        }
+    }
+}
+
+/// A gap between consecutive primes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PrimeGap {
+    pub gap: u128,
+    /// The prime before the gap.
+    pub from: u128,
+    /// The prime after the gap.
+    pub to: u128,
+}
+
+/// How many primes fell into each residue class modulo `modulus`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResidueCounts {
+    pub modulus: u128,
+    /// The count for residue `r` at index `r`.
+    pub counts: Vec<u64>,
+}
+
+/// The primes found in the part of a decade a run covered, against the
+/// prediction of the prime number theorem.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecadeDensity {
+    /// The decade `[10^k, 10^(k+1))`.
+    pub k: u32,
+    /// How many numbers of the decade the run covered.
+    pub numbers: u128,
+    pub primes: u64,
+    /// The primes `li(x)` predicts for the covered numbers: the sum of
+    /// `li(b + 1) - li(a)` over the covered intervals `[a, b]`.
+    pub expected: f64,
+}
+
+impl DecadeDensity {
+    /// Returns the share of covered numbers that are prime.
+    pub fn density(&self) -> f64 {
+        self.primes as f64 / self.numbers as f64
+    }
+
+    /// Returns the share `li(x)` predicts.
+    pub fn expected_density(&self) -> f64 {
+        self.expected / self.numbers as f64
+    }
+}
+
+/// The aggregates a run kept; those that weren't asked for are `None` or empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AggregateReport {
+    /// The sum in decimal, since it outgrows every integer type.
+    pub sum: Option<String>,
+    pub count: Option<u64>,
+    pub max_gap: Option<PrimeGap>,
+    pub residues: Vec<ResidueCounts>,
+    /// The decades the run covered part of, in increasing order.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub decades: Vec<DecadeDensity>,
+}
+
+/// Accumulates aggregates over primes as they are written.
+#[derive(Debug, Clone)]
+pub struct Aggregates {
+    aggregates: Vec<Aggregate>,
+    // The sum, carried into the BigInt whenever the u128 would overflow
+    sum_low: u128,
+    sum_high: BigInt,
+    count: u64,
+    // Inclusive ranges the run covers, in increasing order; gaps across the
+    // holes between them aren't gaps between consecutive primes
+    ranges: Vec<(u128, u128)>,
+    range_index: usize,
+    previous: Option<(usize, u128)>,
+    max_gap: Option<PrimeGap>,
+    residues: Vec<ResidueCounts>,
+    decades: [u64; DECADES],
+}
+
+impl Aggregates {
+    /// Starts empty aggregates for a run covering the disjoint inclusive
+    /// `ranges`, given in increasing order.
+    pub fn new(aggregates: &[Aggregate], ranges: &[(u128, u128)]) -> Aggregates {
+        let residues = aggregates
+            .iter()
+            .filter_map(|aggregate| match aggregate {
+                Aggregate::Residues(modulus) => Some(ResidueCounts { modulus: *modulus, counts: vec![0; *modulus as usize] }),
+                _ => None,
+            })
+            .collect();
+        Aggregates {
+            aggregates: aggregates.to_vec(),
+            sum_low: 0,
+            sum_high: BigInt::default(),
+            count: 0,
+            ranges: ranges.to_vec(),
+            range_index: 0,
+            previous: None,
+            max_gap: None,
+            residues,
+            decades: [0; DECADES],
+        }
+    }
+
+    /// Adds the next prime. For `max_gap`, primes must come in increasing order.
+    pub fn add(&mut self, prime: u128) {
+        self.count += 1;
+        self.sum_low = match self.sum_low.checked_add(prime) {
+            Some(sum) => sum,
+            None => {
+                self.sum_high += self.sum_low;
+                prime
+            }
+        };
+        self.decades[prime.max(1).ilog10() as usize] += 1;
+        for residues in &mut self.residues {
+            residues.counts[(prime % residues.modulus) as usize] += 1;
+        }
+        while self.ranges.get(self.range_index).is_some_and(|&(_, end)| end < prime) {
+            self.range_index += 1;
+        }
+        // Primes arriving out of order (only without max_gap) have no gap to measure
+        if let Some((range_index, previous)) = self.previous.filter(|&(_, previous)| previous < prime) {
+            let gap = prime - previous;
+            if range_index == self.range_index && self.max_gap.is_none_or(|max| gap > max.gap) {
+                self.max_gap = Some(PrimeGap { gap, from: previous, to: prime });
+            }
+        }
+        self.previous = Some((self.range_index, prime));
+    }
+
+    /// Returns the aggregates asked for.
+    pub fn report(&self) -> AggregateReport {
+        let wanted = |aggregate: Aggregate| self.aggregates.contains(&aggregate);
+        AggregateReport {
+            sum: wanted(Aggregate::Sum).then(|| (&self.sum_high + self.sum_low).to_string()),
+            count: wanted(Aggregate::Count).then_some(self.count),
+            max_gap: if wanted(Aggregate::MaxGap) { self.max_gap } else { None },
+            residues: self.residues.clone(),
+            decades: if wanted(Aggregate::Decades) { self.decade_densities() } else { Vec::new() },
+        }
+    }
+
+    // Function to compare the primes per decade with li(x) over the covered ranges
+    fn decade_densities(&self) -> Vec<DecadeDensity> {
+        (0..DECADES as u32)
+            .filter_map(|k| {
+                let low = 10u128.pow(k);
+                let high = 10u128.checked_pow(k + 1).map_or(u128::MAX, |next| next - 1);
+                let (mut numbers, mut expected) = (0, 0.0);
+                for &(from, to) in &self.ranges {
+                    let (from, to) = (from.max(low), to.min(high));
+                    if from <= to {
+                        numbers += to - from + 1;
+                        expected += logarithmic_integral(to as f64 + 1.0) - logarithmic_integral(from.max(2) as f64);
+                    }
+                }
+                (numbers > 0).then(|| DecadeDensity { k, numbers, primes: self.decades[k as usize], expected: expected.max(0.0) })
+            })
+            .collect()
+    }
+}
+
+/// Returns the logarithmic integral `li(x)`, the prime number theorem's
+/// estimate of the primes up to `x`, for `x > 1`, by Ramanujan's series.
+pub fn logarithmic_integral(x: f64) -> f64 {
+    let ln_x = x.ln();
+    // Terms (-1)^(n-1) (ln x)^n / (n! 2^(n-1)) times the sum of 1/(2k+1) for k <= (n-1)/2
+    let (mut sum, mut term, mut inner) = (0.0, -1.0, 0.0);
+    for n in 1..1000 {
+        term *= -ln_x / (n as f64 * if n > 1 { 2.0 } else { 1.0 });
+        if (n - 1) % 2 == 0 {
+            inner += 1.0 / n as f64;
+        }
+        let next = sum + term * inner;
+        if next == sum {
+            break;
+        }
+        sum = next;
+    }
+    EULER_GAMMA + ln_x.ln() + x.sqrt() * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn aggregates_primes() {
+        let wanted: Vec<Aggregate> = "sum,count,max_gap,residues:4".split(',').map(|a| a.parse().unwrap()).collect();
+        let mut aggregates = Aggregates::new(&wanted, &[(2, 1000)]);
+        for p in primes_in_range(2, 1000) {
+            aggregates.add(p.into());
+        }
+        let report = aggregates.report();
+        assert_eq!(report.sum.as_deref(), Some("76127"));
+        assert_eq!(report.count, Some(168));
+        // The largest gap below 1000 follows 887
+        assert_eq!(report.max_gap, Some(PrimeGap { gap: 20, from: 887, to: 907 }));
+        assert_eq!(report.residues, [ResidueCounts { modulus: 4, counts: vec![0, 80, 1, 87] }]);
+    }
+
+    #[test]
+    fn sums_beyond_u128_and_skips_holes() {
+        let mut aggregates = Aggregates::new(&[Aggregate::Sum, Aggregate::MaxGap], &[(2, 10), (100, 120), (u128::MAX - 200, u128::MAX)]);
+        for p in [2, 3, 5, 7, 101, 103, 107, 109, 113, u128::MAX - 158, u128::MAX - 82] {
+            aggregates.add(p);
+        }
+        let report = aggregates.report();
+        let expected = BigInt::from(u128::MAX) * 2u32 - 240u32 + (2 + 3 + 5 + 7 + 101 + 103 + 107 + 109 + 113u32);
+        assert_eq!(report.sum, Some(expected.to_string()));
+        assert_eq!(report.max_gap, Some(PrimeGap { gap: 76, from: u128::MAX - 158, to: u128::MAX - 82 }));
+        assert_eq!(report.count, None);
+        assert!("residues:0".parse::<Aggregate>().is_err() && "median".parse::<Aggregate>().is_err());
+    }
+
+    #[test]
+    fn accepts_primes_out_of_order() {
+        let mut aggregates = Aggregates::new(&[Aggregate::Count], &[(2, 100)]);
+        for p in [97, 2, 53] {
+            aggregates.add(p);
+        }
+        assert_eq!(aggregates.report().count, Some(3));
+    }
+
+    #[test]
+    fn compares_decades_with_li() {
+        // li(10^k) - li(2) + 1 approximates pi(10^k): 1229 and 78498 for k = 4 and 6
+        assert!((logarithmic_integral(1e4) - 1246.137).abs() < 1e-3);
+        assert!((logarithmic_integral(1e6) - 78_627.549).abs() < 1e-3);
+        assert!((logarithmic_integral(1e20) / 2_220_819_602_783_663_483.55 - 1.0).abs() < 1e-12);
+
+        let mut aggregates = Aggregates::new(&[Aggregate::Decades], &[(2, 999), (5000, 20_000)]);
+        let primes: Vec<u64> = primes_in_range(2, 999).into_iter().chain(primes_in_range(5000, 20_000)).collect();
+        for &p in &primes {
+            aggregates.add(p.into());
+        }
+        let decades = aggregates.report().decades;
+        let covered: Vec<(u32, u128, u64)> = decades.iter().map(|decade| (decade.k, decade.numbers, decade.primes)).collect();
+        assert_eq!(covered, [(0, 8, 4), (1, 90, 21), (2, 900, 143), (3, 5000, 560), (4, 10_001, 1033)]);
+        for decade in &decades[2..] {
+            assert!((decade.density() / decade.expected_density() - 1.0).abs() < 0.1, "{:?}", decade);
+        }
+    }
+}
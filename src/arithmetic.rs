@@ -0,0 +1,273 @@
+// Segmented sieves of arithmetic functions over windows of integers, built on
+// the same sieving primes as the prime generator.
+
+use crate::number_theory::prime_powers;
+use crate::sieve::{for_each_prime, isqrt, sieving_primes};
+
+// Numbers sieved at a time when streaming values
+const SEGMENT_SIZE: u64 = 1 << 20;
+
+/// Returns the Möbius function `μ(n)` for each `n` in the inclusive window
+/// `[start, end]`: 0 where `n` has a square factor (and for 0), otherwise
+/// `(-1)^k` for `n` with `k` prime factors. `base_primes` must include every
+/// prime up to `isqrt(end)`.
+pub fn mobius_segment(start: u64, end: u64, base_primes: &[u32]) -> Vec<i8> {
+    if end < start {
+        return Vec::new();
+    }
+    let len = (end - start + 1) as usize;
+    let mut mobius = vec![1i8; len];
+    // Product of the prime factors up to sqrt(end) found so far
+    let mut found = vec![1u64; len];
+    let limit = isqrt(end);
+    for p in base_primes.iter().map(|&p| p as u64).take_while(|&p| p <= limit) {
+        if let Some(mut multiple) = start.div_ceil(p).checked_mul(p) {
+            while multiple <= end {
+                let i = (multiple - start) as usize;
+                mobius[i] = -mobius[i];
+                found[i] *= p;
+                match multiple.checked_add(p) {
+                    Some(next) => multiple = next,
+                    None => break,
+                }
+            }
+        }
+        // p^2 <= end, as p <= isqrt(end)
+        let square = p * p;
+        let Some(mut multiple) = start.div_ceil(square).checked_mul(square) else {
+            continue;
+        };
+        while multiple <= end {
+            mobius[(multiple - start) as usize] = 0;
+            match multiple.checked_add(square) {
+                Some(next) => multiple = next,
+                None => break,
+            }
+        }
+    }
+    for (offset, (value, product)) in mobius.iter_mut().zip(found).enumerate() {
+        let n = start + offset as u64;
+        if n == 0 {
+            *value = 0;
+        } else if product != n {
+            // What's left is a single prime above sqrt(end)
+            *value = -*value;
+        }
+    }
+    mobius
+}
+
+/// Calls `f` with each `n` in `[start, end]` and `μ(n)` in increasing order,
+/// sieving one segment at a time so memory use doesn't grow with the range.
+///
+/// Enumeration stops early as soon as `f` returns `false`.
+pub fn for_each_mobius<F: FnMut(u64, i8) -> bool>(start: u64, end: u64, mut f: F) {
+    let base_primes = sieving_primes(end);
+    let mut from = Some(start);
+    while let Some(segment_start) = from.filter(|&from| from <= end) {
+        let segment_end = segment_start.saturating_add(SEGMENT_SIZE - 1).min(end);
+        for (offset, mobius) in mobius_segment(segment_start, segment_end, &base_primes).into_iter().enumerate() {
+            if !f(segment_start + offset as u64, mobius) {
+                return;
+            }
+        }
+        from = segment_end.checked_add(1);
+    }
+}
+
+/// Sieves the inclusive window `[start, end]` with `primes`, which must be in
+/// increasing order. Returns, for each `n` in the window, its smallest prime
+/// factor among `primes` (0 if none divides it) and its cofactor, what is left
+/// of `n` once every power of those primes is divided out. With the primes
+/// below `k`, the `k`-rough numbers (no prime factor below `k`) are those
+/// without a smallest factor; with the primes up to `B`, the `B`-smooth
+/// numbers (no prime factor above `B`) are those with cofactor 1. 0 keeps
+/// cofactor 0.
+pub fn sieve_factors(start: u64, end: u64, primes: &[u64]) -> (Vec<u64>, Vec<u64>) {
+    if end < start {
+        return (Vec::new(), Vec::new());
+    }
+    let mut smallest = vec![0; (end - start + 1) as usize];
+    let mut cofactors: Vec<u64> = (start..=end).collect();
+    for &p in primes {
+        let Some(mut multiple) = start.div_ceil(p).checked_mul(p) else {
+            continue;
+        };
+        while multiple <= end {
+            let i = (multiple - start) as usize;
+            if smallest[i] == 0 {
+                smallest[i] = p;
+            }
+            while cofactors[i] != 0 && cofactors[i].is_multiple_of(p) {
+                cofactors[i] /= p;
+            }
+            match multiple.checked_add(p) {
+                Some(next) => multiple = next,
+                None => break,
+            }
+        }
+    }
+    (smallest, cofactors)
+}
+
+/// Calls `f` with each checkpoint `x` and Chebyshev's functions
+/// `θ(x) = Σ ln p` over the primes `p <= x` and `ψ(x) = Σ ln p` over the
+/// prime powers `p^k <= x`. The primes come from the segmented sieve and the
+/// prime powers from [`prime_powers`]; the logarithms are summed with
+/// Neumaier's compensated summation, so rounding errors don't grow with `x`.
+/// `checkpoints` must be in increasing order.
+///
+/// Enumeration stops early as soon as `f` returns `false`.
+pub fn chebyshev<F: FnMut(u64, f64, f64) -> bool>(checkpoints: &[u64], mut f: F) {
+    let Some(&end) = checkpoints.last() else {
+        return;
+    };
+    let mut powers = prime_powers(2, end as u128).into_iter().map(|(power, base, _)| (power as u64, base)).peekable();
+    let (mut theta, mut psi) = (Sum::default(), Sum::default());
+    let mut checkpoints = checkpoints.iter().copied().peekable();
+    let mut stopped = false;
+    // Function to report the checkpoints below n (all that are left for
+    // None), returning false to stop
+    let mut report_below = |n: Option<u64>, theta: &Sum, psi: &Sum| -> bool {
+        while let Some(x) = checkpoints.next_if(|&x| n.is_none_or(|n| x < n)) {
+            if !f(x, theta.value(), psi.value()) {
+                return false;
+            }
+        }
+        true
+    };
+    for_each_prime(2, end, |p| {
+        while let Some((power, base)) = powers.next_if(|&(power, _)| power < p) {
+            if !report_below(Some(power), &theta, &psi) {
+                stopped = true;
+                return false;
+            }
+            psi.add((base as f64).ln());
+        }
+        if !report_below(Some(p), &theta, &psi) {
+            stopped = true;
+            return false;
+        }
+        let ln_p = (p as f64).ln();
+        theta.add(ln_p);
+        psi.add(ln_p);
+        true
+    });
+    if stopped {
+        return;
+    }
+    for (power, base) in powers {
+        if !report_below(Some(power), &theta, &psi) {
+            return;
+        }
+        psi.add((base as f64).ln());
+    }
+    report_below(None, &theta, &psi);
+}
+
+// Neumaier's compensated sum of floats
+#[derive(Default)]
+struct Sum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl Sum {
+    // Function to add a term, keeping the low-order bits the sum loses
+    fn add(&mut self, term: f64) {
+        let total = self.sum + term;
+        self.compensation += match self.sum.abs() >= term.abs() {
+            true => (self.sum - total) + term,
+            false => (term - total) + self.sum,
+        };
+        self.sum = total;
+    }
+
+    // Function to get the compensated value
+    fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factor::factor_with_multiplicity;
+
+    // Function to compute μ(n) from the factorization of n
+    fn expected_mobius(n: u64) -> i8 {
+        if n == 0 {
+            return 0;
+        }
+        let factors = factor_with_multiplicity(n as u128);
+        match factors.iter().all(|&(_, exponent)| exponent == 1) {
+            true if factors.len().is_multiple_of(2) => 1,
+            true => -1,
+            false => 0,
+        }
+    }
+
+    #[test]
+    fn sieves_the_mobius_function() {
+        // OEIS A008683
+        assert_eq!(mobius_segment(1, 20, &sieving_primes(20)), [1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0, -1, 1, 1, 0, -1, 0, -1, 0]);
+        for (start, end) in [(0, 1000), (999_000, 1_001_000), (1 << 40, (1 << 40) + 1000)] {
+            let mobius = mobius_segment(start, end, &sieving_primes(end));
+            for (offset, &value) in mobius.iter().enumerate() {
+                let n = start + offset as u64;
+                assert_eq!(value, expected_mobius(n), "{}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn sums_the_mertens_function() {
+        // OEIS A084237: M(10^k) for k = 0..6
+        let mut mertens = 0i64;
+        let mut at_powers = Vec::new();
+        for_each_mobius(1, 1_000_000, |n, mobius| {
+            mertens += mobius as i64;
+            if n == 10u64.pow(n.ilog10()) {
+                at_powers.push(mertens);
+            }
+            true
+        });
+        assert_eq!(at_powers, [1, -1, 1, 2, -23, -48, 212]);
+    }
+
+    #[test]
+    fn sieves_rough_and_smooth_numbers() {
+        // OEIS A007310 (5-rough) and A051037 (5-smooth)
+        let (smallest, _) = sieve_factors(1, 40, &[2, 3]);
+        let rough: Vec<u64> = (1..=40).filter(|&n| smallest[n as usize - 1] == 0).collect();
+        assert_eq!(rough, [1, 5, 7, 11, 13, 17, 19, 23, 25, 29, 31, 35, 37]);
+        let (smallest, cofactors) = sieve_factors(1, 40, &[2, 3, 5]);
+        let smooth: Vec<u64> = (1..=40).filter(|&n| cofactors[n as usize - 1] == 1).collect();
+        assert_eq!(smooth, [1, 2, 3, 4, 5, 6, 8, 9, 10, 12, 15, 16, 18, 20, 24, 25, 27, 30, 32, 36, 40]);
+        assert_eq!((smallest[34], cofactors[34]), (5, 7));
+
+        let (smallest, cofactors) = sieve_factors(0, 0, &[2]);
+        assert_eq!((smallest, cofactors), (vec![2], vec![0]));
+        let (smallest, cofactors) = sieve_factors(u64::MAX - 2, u64::MAX, &[2, 3, 5]);
+        // 2^64 - 1 = 3 * 5 * 17 * 257 * 641 * 65537 * 6700417
+        assert_eq!(smallest, [0, 2, 3]);
+        assert_eq!(cofactors[2], u64::MAX / 15);
+    }
+
+    #[test]
+    fn sums_chebyshev_functions() {
+        let mut values = Vec::new();
+        chebyshev(&[1, 4, 100, 1_000_000], |x, theta, psi| {
+            values.push((x, theta, psi));
+            true
+        });
+        let expected = [(1, 0.0, 0.0), (4, 2.0f64.ln() + 3.0f64.ln(), 2.0 * 2.0f64.ln() + 3.0f64.ln())]
+            .into_iter()
+            .chain([(100, 83.728_390_399_063_9, 94.045_311_229_357_4), (1_000_000, 998_484.175_025_634_3, 999_586.597_495_633)]);
+        assert_eq!(values.len(), 4);
+        for ((x, theta, psi), (expected_x, expected_theta, expected_psi)) in values.into_iter().zip(expected) {
+            assert_eq!(x, expected_x);
+            assert!((theta - expected_theta).abs() < 1e-9 && (psi - expected_psi).abs() < 1e-9, "{} {} {}", x, theta, psi);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// Appending and reading `PrimeRecord`s in Avro object container files.
+//
+// A container starts with a header holding the schema and a random 16-byte
+// sync marker, followed by blocks of rows that each end with the marker.
+// Appending to an existing file reuses its header, so it has to carry the same
+// schema. Every field is an Avro `string`, as the primes and powers overflow
+// Avro's 64-bit `long`. The header's metadata also records the CSV schema
+// version (see `csv_file`) the fields follow, and their names.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+use crate::bignum::{powers, Integer};
+use crate::csv_file::{check_schema_version, CsvOptions, SCHEMA_VERSION};
+use crate::record::PrimeRecord;
+
+// First bytes of every object container file
+const MAGIC: &[u8; 4] = b"Obj\x01";
+
+// Metadata keys of the header
+const SCHEMA_KEY: &str = "avro.schema";
+const CODEC_KEY: &str = "avro.codec";
+const VERSION_KEY: &str = "prime_generator.schema_version";
+const FIELDS_KEY: &str = "prime_generator.fields";
+
+/// Appends blocks of rows to an Avro object container file.
+pub struct AvroWriter {
+    file: File,
+    sync: [u8; 16],
+    options: CsvOptions,
+    // Buffers holding each field of the row being encoded
+    fields: Vec<Vec<u8>>,
+}
+
+impl AvroWriter {
+    /// Opens `path` for appending rows with the fields `options` describes
+    /// (the dialect aside), creating it with a header if needed. Fails with
+    /// [`ErrorKind::InvalidData`] if the file holds rows of another schema.
+    pub fn append<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<AvroWriter> {
+        let path = path.as_ref();
+        let names = options.field_names();
+        let schema = schema_json(&names);
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let sync = if file.metadata()?.len() == 0 {
+            let sync = random_sync();
+            let metadata = [
+                (SCHEMA_KEY, schema),
+                (CODEC_KEY, "null".to_string()),
+                (VERSION_KEY, SCHEMA_VERSION.to_string()),
+                (FIELDS_KEY, names.join(",")),
+            ];
+            let mut header = MAGIC.to_vec();
+            write_long(&mut header, metadata.len() as i64);
+            for (key, value) in &metadata {
+                write_bytes(&mut header, key.as_bytes());
+                write_bytes(&mut header, value.as_bytes());
+            }
+            write_long(&mut header, 0);
+            header.extend_from_slice(&sync);
+            file.write_all(&header)?;
+            sync
+        } else {
+            let header = read_header(&mut BufReader::new(&file))?;
+            if header.schema != schema {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} holds records of another schema: {}", path.display(), header.schema),
+                ));
+            }
+            header.sync
+        };
+        Ok(AvroWriter { file, sync, options: options.clone(), fields: vec![Vec::new(); names.len()] })
+    }
+
+    /// Appends primes with their `[squared, cubed, to_fourth_power]` powers as
+    /// one block.
+    pub fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let mut data = Vec::new();
+        for (prime, powers) in batch {
+            self.options.write_fields(*prime, powers, &mut self.fields);
+            for field in &self.fields {
+                write_bytes(&mut data, field);
+            }
+        }
+        let mut block = Vec::with_capacity(data.len() + 36);
+        write_long(&mut block, batch.len() as i64);
+        write_long(&mut block, data.len() as i64);
+        block.extend_from_slice(&data);
+        block.extend_from_slice(&self.sync);
+        self.file.write_all(&block)
+    }
+
+    /// Appends `records` as one block. Their powers are recomputed, so they
+    /// are written in the format the file uses.
+    pub fn write_records(&mut self, records: &[PrimeRecord]) -> Result<()> {
+        let batch: Vec<(u128, Vec<Integer>)> = records.iter().map(|record| (record.prime, powers(record.prime).to_vec())).collect();
+        self.write_powers(&batch)
+    }
+}
+
+// What the header of a container says about its rows
+struct Header {
+    schema: String,
+    fields: Vec<String>,
+    sync: [u8; 16],
+}
+
+/// Reads every record from the Avro container file at `path`.
+pub fn read_records<P: AsRef<Path>>(path: P) -> Result<Vec<PrimeRecord>> {
+    let mut records = Vec::new();
+    for_each_record(path, |record| {
+        records.push(record);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+/// Calls `f` with every record of the Avro container file at `path` in file
+/// order, a block at a time. Files without powers get them derived from the
+/// primes. Fails with [`ErrorKind::InvalidData`] if the file is damaged or
+/// was written with a newer schema version than [`SCHEMA_VERSION`].
+pub fn for_each_record<P, F>(path: P, mut f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(PrimeRecord) -> Result<()>,
+{
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = read_header(&mut reader)?;
+    // Positions of the powers among the fields, if they were written
+    let position = |name: &str| header.fields.iter().position(|field| field.starts_with(name));
+    let powers = match (position("squared"), position("cubed"), position("to_fourth_power")) {
+        (Some(squared), Some(cubed), Some(to_fourth_power)) => Some([squared, cubed, to_fourth_power]),
+        _ => None,
+    };
+
+    let mut values = vec![String::new(); header.fields.len()];
+    let mut first = [0u8; 1];
+    // Blocks run until the end of the file
+    while reader.read(&mut first)? == 1 {
+        let count = read_long_after(&mut reader, first[0])?;
+        let size = read_long(&mut reader)?;
+        if count < 0 || size < 0 {
+            return Err(invalid("Negative block length"));
+        }
+        let mut data = vec![0; size as usize];
+        reader.read_exact(&mut data)?;
+        let mut data = data.as_slice();
+        for _ in 0..count {
+            for value in values.iter_mut() {
+                *value = String::from_utf8(read_bytes(&mut data)?).map_err(|_| invalid("Field isn't UTF-8"))?;
+            }
+            let prime = values[0].parse().map_err(|_| invalid(&format!("Invalid prime: {:?}", values[0])))?;
+            f(match powers {
+                Some([squared, cubed, to_fourth_power]) => PrimeRecord {
+                    prime,
+                    squared: values[squared].clone(),
+                    cubed: values[cubed].clone(),
+                    to_fourth_power: values[to_fourth_power].clone(),
+                },
+                None => PrimeRecord::from_prime(prime),
+            })?;
+        }
+        let mut sync = [0u8; 16];
+        reader.read_exact(&mut sync)?;
+        if sync != header.sync || !data.is_empty() {
+            return Err(invalid("Block doesn't end with the file's sync marker"));
+        }
+    }
+    Ok(())
+}
+
+// Function to read and check the header at the start of a container
+fn read_header<R: Read>(reader: &mut R) -> Result<Header> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("Not an Avro object container file"));
+    }
+    let (mut schema, mut fields, mut version, mut codec) = (None, None, None, None);
+    loop {
+        let count = read_long(reader)?;
+        if count == 0 {
+            break;
+        }
+        // A negative count is followed by the block's size in bytes
+        if count < 0 {
+            read_long(reader)?;
+        }
+        for _ in 0..count.unsigned_abs() {
+            let key = String::from_utf8(read_bytes(reader)?).map_err(|_| invalid("Metadata key isn't UTF-8"))?;
+            let value = String::from_utf8(read_bytes(reader)?).map_err(|_| invalid("Metadata value isn't UTF-8"))?;
+            match key.as_str() {
+                SCHEMA_KEY => schema = Some(value),
+                FIELDS_KEY => fields = Some(value),
+                VERSION_KEY => version = Some(value),
+                CODEC_KEY => codec = Some(value),
+                _ => {}
+            }
+        }
+    }
+    if codec.as_deref().is_some_and(|codec| codec != "null") {
+        return Err(invalid("Only uncompressed Avro files can be read"));
+    }
+    check_schema_version(&version.ok_or_else(|| invalid("No schema version in the Avro header"))?)?;
+    let fields: Vec<String> = fields.ok_or_else(|| invalid("No field names in the Avro header"))?.split(',').map(String::from).collect();
+    let mut sync = [0u8; 16];
+    reader.read_exact(&mut sync)?;
+    Ok(Header { schema: schema.ok_or_else(|| invalid("No schema in the Avro header"))?, fields, sync })
+}
+
+// Function to build the JSON schema of rows with the given fields, all strings
+fn schema_json(names: &[String]) -> String {
+    let fields: Vec<String> = names.iter().map(|name| format!(r#"{{"name":"{}","type":"string"}}"#, avro_name(name))).collect();
+    format!(r#"{{"type":"record","name":"PrimeRecord","namespace":"prime_generator","fields":[{}]}}"#, fields.join(","))
+}
+
+// Function to turn a column name into a valid Avro name, which only allows
+// letters, digits and underscores: `legendre_-1` becomes `legendre__1`
+fn avro_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+// Function to pick a sync marker unlikely to occur in the data
+fn random_sync() -> [u8; 16] {
+    let mut sync = [0u8; 16];
+    for half in sync.chunks_mut(8) {
+        // Every RandomState is seeded with fresh random keys
+        half.copy_from_slice(&RandomState::new().build_hasher().finish().to_le_bytes());
+    }
+    sync
+}
+
+// Function to append a long as a zigzag-encoded varint
+fn write_long(out: &mut Vec<u8>, n: i64) {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+    while zigzag >= 0x80 {
+        out.push(zigzag as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    out.push(zigzag as u8);
+}
+
+// Function to append bytes (or a string) prefixed with their length
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
+
+// Function to read a zigzag-encoded varint long
+fn read_long<R: Read>(reader: &mut R) -> Result<i64> {
+    let mut first = [0u8; 1];
+    reader.read_exact(&mut first)?;
+    read_long_after(reader, first[0])
+}
+
+// Function to read the rest of a varint long whose first byte was read
+fn read_long_after<R: Read>(reader: &mut R, first: u8) -> Result<i64> {
+    let (mut zigzag, mut shift, mut byte) = (0u64, 0, first);
+    loop {
+        if shift > 63 {
+            return Err(invalid("Varint is too long"));
+        }
+        zigzag |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        let mut next = [0u8; 1];
+        reader.read_exact(&mut next)?;
+        byte = next[0];
+    }
+    Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+}
+
+// Function to read bytes (or a string) prefixed with their length
+fn read_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = read_long(reader)?;
+    let len = usize::try_from(len).map_err(|_| invalid("Negative length"))?;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(Error::from(ErrorKind::UnexpectedEof));
+    }
+    Ok(bytes)
+}
+
+// Function to build the error for a damaged file
+fn invalid(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::PowerFormat;
+    use crate::columns::Column;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Function to give each test its own file in the temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("prime_generator_avro_{}_{}.avro", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn encodes_longs_as_zigzag_varints() {
+        for (n, encoded) in [(0, vec![0]), (-1, vec![1]), (1, vec![2]), (-64, vec![0x7f]), (64, vec![0x80, 1])] {
+            let mut out = Vec::new();
+            write_long(&mut out, n);
+            assert_eq!(out, encoded, "{}", n);
+        }
+        for n in [i64::MIN, -300, 300, i64::MAX] {
+            let mut out = Vec::new();
+            write_long(&mut out, n);
+            assert_eq!(read_long(&mut out.as_slice()).unwrap(), n);
+        }
+        assert_eq!(read_long(&mut [0xff; 11].as_slice()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_back_appended_blocks() {
+        let path = scratch("round_trip");
+        let options = CsvOptions { columns: vec![Column::Legendre(-1)], ..CsvOptions::default() };
+        AvroWriter::append(&path, &options).unwrap().write_powers(&[(2, powers(2).to_vec()), (3, powers(3).to_vec())]).unwrap();
+        let mut writer = AvroWriter::append(&path, &options).unwrap();
+        writer.write_records(&[PrimeRecord::from_prime(u128::MAX)]).unwrap();
+        writer.write_powers(&[]).unwrap();
+        let contents = fs::read(&path).unwrap();
+        let records = read_records(&path).unwrap();
+        // Appending with another schema is refused
+        let error = AvroWriter::append(&path, &CsvOptions::default()).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.starts_with(MAGIC));
+        assert!(String::from_utf8_lossy(&contents).contains(r#"{"name":"legendre__1","type":"string"}"#));
+        assert_eq!(records, [2, 3, u128::MAX].map(PrimeRecord::from_prime));
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn derives_powers_left_out() {
+        let path = scratch("primes_only");
+        let options = CsvOptions { primes_only: true, power_format: PowerFormat::Hex, ..CsvOptions::default() };
+        AvroWriter::append(&path, &options).unwrap().write_powers(&[(5, powers(5).to_vec())]).unwrap();
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records, [PrimeRecord::from_prime(5)]);
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let path = scratch("damaged");
+        AvroWriter::append(&path, &CsvOptions::default()).unwrap().write_powers(&[(7, powers(7).to_vec())]).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        fs::write(&path, &contents).unwrap();
+        let error = read_records(&path).unwrap_err();
+        fs::write(&path, b"prime,squared\n").unwrap();
+        let not_avro = read_records(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((error.kind(), not_avro.kind()), (ErrorKind::InvalidData, ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,292 @@
+// Arbitrary-precision backend for the power computations: num-bigint by
+// default, or GMP through `rug` with the `gmp` feature.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(not(feature = "gmp"))]
+pub use num_bigint::BigInt as Integer;
+#[cfg(feature = "gmp")]
+pub use rug::Integer;
+
+/// Returns `[n^2, n^3, n^4]`.
+#[cfg(not(feature = "gmp"))]
+pub fn powers(n: u128) -> [Integer; 3] {
+    let big_n = Integer::from(n);
+    let squared = &big_n * &big_n;
+    let cubed = &squared * &big_n;
+    let to_fourth_power = &squared * &squared;
+    [squared, cubed, to_fourth_power]
+}
+
+/// Returns `[n^2, n^3, n^4]`.
+#[cfg(feature = "gmp")]
+pub fn powers(n: u128) -> [Integer; 3] {
+    let big_n = Integer::from(n);
+    let squared = Integer::from(big_n.square_ref());
+    let cubed = Integer::from(&squared * &big_n);
+    let to_fourth_power = Integer::from(squared.square_ref());
+    [squared, cubed, to_fourth_power]
+}
+
+// Widest value converted on the stack: 512 bits, enough for the fourth power of any u128
+const MAX_LIMBS: usize = 8;
+
+// Largest power of ten below 2^64, the base used when converting to decimal
+const DECIMAL_CHUNK: u128 = 10_000_000_000_000_000_000;
+const DECIMAL_CHUNK_DIGITS: usize = 19;
+
+/// Appends the decimal digits of `n` to `buf`.
+///
+/// Values up to 512 bits, which covers the powers of every `u128`, are
+/// converted on the stack, so reusing `buf` across calls makes serialization
+/// allocation-free.
+#[cfg(not(feature = "gmp"))]
+pub fn write_decimal(n: &Integer, buf: &mut Vec<u8>) {
+    if n.bits() > (MAX_LIMBS * 64) as u64 {
+        buf.extend_from_slice(n.to_string().as_bytes());
+        return;
+    }
+    if n.sign() == num_bigint::Sign::Minus {
+        buf.push(b'-');
+    }
+    let mut limbs = [0u64; MAX_LIMBS];
+    let mut len = 0;
+    for (limb, digit) in limbs.iter_mut().zip(n.iter_u64_digits()) {
+        *limb = digit;
+        len += 1;
+    }
+    write_limbs(limbs, len, buf);
+}
+
+/// Appends the decimal digits of `n` to `buf`.
+///
+/// Values up to 512 bits, which covers the powers of every `u128`, are
+/// converted on the stack, so reusing `buf` across calls makes serialization
+/// allocation-free.
+#[cfg(feature = "gmp")]
+pub fn write_decimal(n: &Integer, buf: &mut Vec<u8>) {
+    let len = n.significant_digits::<u64>();
+    if len > MAX_LIMBS {
+        buf.extend_from_slice(n.to_string().as_bytes());
+        return;
+    }
+    if n.cmp0() == std::cmp::Ordering::Less {
+        buf.push(b'-');
+    }
+    let mut limbs = [0u64; MAX_LIMBS];
+    n.write_digits(&mut limbs[..len], rug::integer::Order::Lsf);
+    write_limbs(limbs, len, buf);
+}
+
+/// How [`write_power`] writes a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerFormat {
+    /// `decimal`: every digit.
+    #[default]
+    Decimal,
+    /// `hex`: lowercase hexadecimal digits, without a prefix. About 17% shorter.
+    Hex,
+    /// `scientific`: the nearest `f64` in scientific notation, such as
+    /// `1.157920892373162e77`, for when magnitude and leading digits suffice.
+    Scientific,
+    /// `digits-only`: only the number of decimal digits.
+    DigitsOnly,
+}
+
+impl PowerFormat {
+    /// Returns the suffix naming the format in CSV headers, such as `_hex`
+    /// in `squared_hex`; empty for decimal.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            PowerFormat::Decimal => "",
+            PowerFormat::Hex => "_hex",
+            PowerFormat::Scientific => "_scientific",
+            PowerFormat::DigitsOnly => "_digits",
+        }
+    }
+}
+
+impl FromStr for PowerFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<PowerFormat, String> {
+        match s {
+            "decimal" => Ok(PowerFormat::Decimal),
+            "hex" => Ok(PowerFormat::Hex),
+            "scientific" => Ok(PowerFormat::Scientific),
+            "digits-only" => Ok(PowerFormat::DigitsOnly),
+            _ => Err(format!("Unknown power format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PowerFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PowerFormat::Decimal => "decimal",
+            PowerFormat::Hex => "hex",
+            PowerFormat::Scientific => "scientific",
+            PowerFormat::DigitsOnly => "digits-only",
+        })
+    }
+}
+
+/// Appends `n` to `buf` in the given format.
+pub fn write_power(n: &Integer, format: PowerFormat, buf: &mut Vec<u8>) {
+    match format {
+        PowerFormat::Decimal => write_decimal(n, buf),
+        PowerFormat::Hex => buf.extend_from_slice(to_hex(n).as_bytes()),
+        PowerFormat::Scientific => buf.extend_from_slice(format!("{:e}", to_f64(n)).as_bytes()),
+        PowerFormat::DigitsOnly => {
+            // Write the digits, then replace them by their count
+            let start = buf.len();
+            write_decimal(n, buf);
+            let digits = buf[start..].iter().filter(|byte| byte.is_ascii_digit()).count();
+            buf.truncate(start);
+            write_decimal_u128(digits as u128, buf);
+        }
+    }
+}
+
+// Function to format `n` in lowercase hexadecimal
+#[cfg(not(feature = "gmp"))]
+fn to_hex(n: &Integer) -> String {
+    n.to_str_radix(16)
+}
+
+// Function to format `n` in lowercase hexadecimal
+#[cfg(feature = "gmp")]
+fn to_hex(n: &Integer) -> String {
+    n.to_string_radix(16)
+}
+
+// Function to round `n` to the nearest f64
+#[cfg(not(feature = "gmp"))]
+fn to_f64(n: &Integer) -> f64 {
+    num_traits::ToPrimitive::to_f64(n).unwrap_or(f64::NAN)
+}
+
+// Function to round `n` to the nearest f64; rug's own conversion truncates
+#[cfg(feature = "gmp")]
+fn to_f64(n: &Integer) -> f64 {
+    n.to_string().parse().unwrap_or(f64::NAN)
+}
+
+/// Appends the decimal digits of `n` to `buf`.
+pub fn write_decimal_u128(n: u128, buf: &mut Vec<u8>) {
+    let mut limbs = [0u64; MAX_LIMBS];
+    limbs[0] = n as u64;
+    limbs[1] = (n >> 64) as u64;
+    let len = (128 - n.leading_zeros() as usize).div_ceil(64);
+    write_limbs(limbs, len, buf);
+}
+
+// Function to append the decimal digits of the little-endian magnitude
+// `limbs[..len]`, dividing by 10^19 to peel off 19 digits at a time
+fn write_limbs(mut limbs: [u64; MAX_LIMBS], mut len: usize, buf: &mut Vec<u8>) {
+    let mut chunks = [0u64; MAX_LIMBS + 2];
+    let mut count = 0;
+    while len > 0 {
+        let mut remainder = 0u128;
+        for limb in limbs[..len].iter_mut().rev() {
+            let current = (remainder << 64) | *limb as u128;
+            *limb = (current / DECIMAL_CHUNK) as u64;
+            remainder = current % DECIMAL_CHUNK;
+        }
+        chunks[count] = remainder as u64;
+        count += 1;
+        while len > 0 && limbs[len - 1] == 0 {
+            len -= 1;
+        }
+    }
+    match chunks[..count].split_last() {
+        Some((most_significant, rest)) => {
+            push_digits(*most_significant, 0, buf);
+            for &chunk in rest.iter().rev() {
+                push_digits(chunk, DECIMAL_CHUNK_DIGITS, buf);
+            }
+        }
+        None => buf.push(b'0'),
+    }
+}
+
+// Function to append the digits of `n`, zero-padded to `width`
+fn push_digits(mut n: u64, width: usize, buf: &mut Vec<u8>) {
+    let mut digits = [b'0'; 20];
+    let mut start = digits.len();
+    while n > 0 {
+        start -= 1;
+        digits[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    start = start.min(digits.len() - width.max(1));
+    buf.extend_from_slice(&digits[start..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to format through `write_decimal` into a fresh buffer
+    fn decimal(n: &Integer) -> String {
+        let mut buf = Vec::new();
+        write_decimal(n, &mut buf);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn write_decimal_matches_to_string() {
+        let chunk = Integer::from(DECIMAL_CHUNK);
+        let mut values = vec![Integer::from(0), Integer::from(1), Integer::from(-42), Integer::from(u64::MAX), Integer::from(u128::MAX)];
+        // Values on either side of each 10^19 chunk boundary
+        let mut boundary = Integer::from(1);
+        for _ in 0..8 {
+            boundary *= &chunk;
+            values.extend([boundary.clone() - 1, boundary.clone(), boundary.clone() + 1]);
+        }
+        values.extend(powers(u128::MAX));
+        // Beyond 512 bits falls back to the library's formatting
+        values.push(Integer::from(u128::MAX) * Integer::from(u128::MAX) * Integer::from(u128::MAX) * Integer::from(u128::MAX) * 3);
+        for n in &values {
+            assert_eq!(decimal(n), n.to_string());
+        }
+    }
+
+    #[test]
+    fn write_decimal_u128_matches_to_string() {
+        for n in [0, 9, 10, DECIMAL_CHUNK - 1, DECIMAL_CHUNK, DECIMAL_CHUNK * DECIMAL_CHUNK, u128::MAX] {
+            let mut buf = Vec::new();
+            write_decimal_u128(n, &mut buf);
+            assert_eq!(String::from_utf8(buf).unwrap(), n.to_string());
+        }
+    }
+
+    #[test]
+    fn writes_powers_in_each_format() {
+        let [squared, _, to_fourth_power] = powers(u128::MAX);
+        let written = |n: &Integer, format: PowerFormat| {
+            let mut buf = b"x".to_vec();
+            write_power(n, format, &mut buf);
+            String::from_utf8(buf[1..].to_vec()).unwrap()
+        };
+        assert_eq!(written(&squared, PowerFormat::Decimal), squared.to_string());
+        assert_eq!(written(&squared, PowerFormat::Hex), format!("{}e{}1", "f".repeat(31), "0".repeat(31)));
+        assert_eq!(written(&to_fourth_power, PowerFormat::Scientific), "1.3407807929942597e154");
+        assert_eq!(written(&to_fourth_power, PowerFormat::DigitsOnly), "155");
+        assert_eq!(written(&Integer::from(0), PowerFormat::DigitsOnly), "1");
+        for format in [PowerFormat::Decimal, PowerFormat::Hex, PowerFormat::Scientific, PowerFormat::DigitsOnly] {
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+        assert!("octal".parse::<PowerFormat>().is_err());
+    }
+
+    #[test]
+    fn powers_of_the_largest_u128() {
+        let n = Integer::from(u128::MAX);
+        let [squared, cubed, to_fourth_power] = powers(u128::MAX);
+        assert_eq!(squared, n.clone() * &n);
+        assert_eq!(cubed, squared.clone() * &n);
+        assert_eq!(to_fourth_power, squared.clone() * &squared);
+    }
+}
@@ -0,0 +1,451 @@
+// Sources of numbers to test for primality. The specialized searches differ
+// only in which numbers they try, so each is a `CandidateSource` and they
+// share one pipeline: a batch at a time, tested across the thread pool, with
+// the verdicts kept in the order the source gave the candidates.
+
+use std::io::{BufRead, Error, ErrorKind, Lines, Result};
+
+use num_bigint::BigInt;
+use rayon::prelude::*;
+
+use crate::math::is_prime;
+use crate::polynomial::Polynomial;
+use crate::primality::{is_prime_bigint, probable_prime, Primality, PRIME_TEST, PROBABLE_PRIME_TEST};
+use crate::sample::mix;
+use crate::special::{lucas_lehmer, mersenne_factor, mersenne_number, LUCAS_LEHMER_TEST};
+
+/// A number to test, with where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// Which of the source's sequences it belongs to, such as the index of
+    /// its polynomial; 0 for sources with a single sequence.
+    pub sequence: usize,
+    /// Its position in that sequence: the `n` of a polynomial value, the
+    /// exponent `p` of `2^p - 1` or the line of a number file.
+    pub index: u128,
+    pub value: BigInt,
+}
+
+/// Where the numbers of a search come from.
+pub trait CandidateSource {
+    /// The comma-separated CSV columns [`describe`](Self::describe) fills.
+    fn columns(&self) -> &str;
+
+    /// Returns up to `max` further candidates in order, or none once the
+    /// source is exhausted.
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>>;
+
+    /// Returns the fields of `candidate` for [`columns`](Self::columns).
+    fn describe(&self, candidate: &Candidate) -> String;
+
+    /// The primality test for this source's candidates, by default
+    /// [`probable_prime`].
+    fn test(&self) -> fn(&Candidate) -> Primality {
+        |candidate| probable_prime(&candidate.value)
+    }
+
+    /// Names the test behind a verdict of [`test`](Self::test).
+    fn test_name(&self, primality: Primality) -> &'static str {
+        match primality {
+            Primality::ProbablePrime => PROBABLE_PRIME_TEST,
+            _ => PRIME_TEST,
+        }
+    }
+}
+
+/// Takes the next batch of up to `max` candidates from `source` and tests
+/// them across the thread pool, returning them in order with their verdicts.
+/// An empty batch means the source is exhausted.
+pub fn test_batch<S: CandidateSource + ?Sized>(source: &mut S, max: usize) -> Result<Vec<(Candidate, Primality)>> {
+    let test = source.test();
+    let batch = source.next_batch(max)?;
+    Ok(batch
+        .into_par_iter()
+        .map(|candidate| {
+            let primality = test(&candidate);
+            (candidate, primality)
+        })
+        .collect())
+}
+
+/// The numbers of inclusive ranges, in order.
+pub struct NumberRange {
+    values: Values,
+}
+
+impl NumberRange {
+    /// Yields the numbers of `ranges`, as [`parse_ranges`](crate::ranges::parse_ranges) returns them.
+    pub fn new(ranges: Vec<(u128, u128)>) -> NumberRange {
+        NumberRange { values: Values::new(ranges) }
+    }
+}
+
+impl CandidateSource for NumberRange {
+    fn columns(&self) -> &str {
+        "number"
+    }
+
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>> {
+        Ok(self.values.by_ref().take(max).map(|n| Candidate { sequence: 0, index: n, value: BigInt::from(n) }).collect())
+    }
+
+    fn describe(&self, candidate: &Candidate) -> String {
+        candidate.value.to_string()
+    }
+}
+
+/// Numbers read one per line, of any size. Blank lines are skipped and lines
+/// that aren't integers are set aside for [`take_invalid`](Self::take_invalid).
+pub struct NumberFile<R: BufRead> {
+    lines: Lines<R>,
+    line: u128,
+    invalid: Vec<String>,
+}
+
+impl<R: BufRead> NumberFile<R> {
+    /// Reads the numbers from `reader`.
+    pub fn new(reader: R) -> NumberFile<R> {
+        NumberFile { lines: reader.lines(), line: 0, invalid: Vec::new() }
+    }
+
+    /// Returns the lines that weren't integers since the last call.
+    pub fn take_invalid(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.invalid)
+    }
+}
+
+impl<R: BufRead> CandidateSource for NumberFile<R> {
+    fn columns(&self) -> &str {
+        "number"
+    }
+
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>> {
+        let mut batch = Vec::new();
+        while batch.len() < max {
+            let Some(line) = self.lines.next().transpose()? else {
+                break;
+            };
+            self.line += 1;
+            match line.trim().parse::<BigInt>() {
+                Ok(value) => batch.push(Candidate { sequence: 0, index: self.line, value }),
+                // Blank lines separate input without counting as errors
+                Err(_) if line.trim().is_empty() => {}
+                Err(_) => self.invalid.push(line),
+            }
+        }
+        Ok(batch)
+    }
+
+    fn describe(&self, candidate: &Candidate) -> String {
+        candidate.value.to_string()
+    }
+
+    // Numbers beyond u128 are proven by trial division (or tested with GMP)
+    // rather than passed as probable primes
+    fn test(&self) -> fn(&Candidate) -> Primality {
+        |candidate| match is_prime_bigint(&candidate.value) {
+            true => Primality::Prime,
+            false => Primality::Composite,
+        }
+    }
+}
+
+/// The values of polynomials at each `n` of inclusive ranges, every
+/// polynomial at one `n` before the next `n`.
+pub struct PolynomialValues {
+    polynomials: Vec<Polynomial>,
+    values: Values,
+    // The n being evaluated and the next polynomial to evaluate at it
+    n: Option<u128>,
+    next: usize,
+}
+
+impl PolynomialValues {
+    /// Evaluates `polynomials` at the `n` of `ranges`.
+    pub fn new(polynomials: Vec<Polynomial>, ranges: Vec<(u128, u128)>) -> PolynomialValues {
+        PolynomialValues { polynomials, values: Values::new(ranges), n: None, next: 0 }
+    }
+
+    /// Returns the polynomials, indexed by [`Candidate::sequence`].
+    pub fn polynomials(&self) -> &[Polynomial] {
+        &self.polynomials
+    }
+}
+
+impl CandidateSource for PolynomialValues {
+    fn columns(&self) -> &str {
+        "polynomial,n,value"
+    }
+
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>> {
+        let mut batch = Vec::new();
+        while batch.len() < max && !self.polynomials.is_empty() {
+            if self.n.is_none() || self.next == self.polynomials.len() {
+                let Some(n) = self.values.next() else {
+                    break;
+                };
+                self.n = Some(n);
+                self.next = 0;
+            }
+            let n = self.n.unwrap();
+            batch.push(Candidate { sequence: self.next, index: n, value: self.polynomials[self.next].eval(n) });
+            self.next += 1;
+        }
+        Ok(batch)
+    }
+
+    fn describe(&self, candidate: &Candidate) -> String {
+        format!("{},{},{}", self.polynomials[candidate.sequence], candidate.index, candidate.value)
+    }
+}
+
+/// The Mersenne numbers `2^p - 1` for the prime `p` of inclusive ranges,
+/// trial factored up to [`mersenne_factor_limit`] and proven with the
+/// Lucas-Lehmer test when no factor turns up.
+pub struct MersenneExponents {
+    values: Values,
+}
+
+impl MersenneExponents {
+    /// Tries the prime exponents of `ranges`, which must be below 2^32.
+    pub fn new(ranges: Vec<(u128, u128)>) -> Result<MersenneExponents> {
+        if ranges.iter().any(|&(_, end)| end > u32::MAX as u128) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Mersenne exponents must be below 2^32"));
+        }
+        Ok(MersenneExponents { values: Values::new(ranges) })
+    }
+}
+
+impl CandidateSource for MersenneExponents {
+    fn columns(&self) -> &str {
+        "p"
+    }
+
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>> {
+        // Composite p give composite 2^p - 1, so they aren't candidates at all
+        Ok(self
+            .values
+            .by_ref()
+            .filter(|&p| is_prime(p))
+            .take(max)
+            .map(|p| Candidate { sequence: 0, index: p, value: mersenne_number(p as u32) })
+            .collect())
+    }
+
+    fn describe(&self, candidate: &Candidate) -> String {
+        candidate.index.to_string()
+    }
+
+    fn test(&self) -> fn(&Candidate) -> Primality {
+        |candidate| {
+            let p = candidate.index as u32;
+            match mersenne_factor(p, mersenne_factor_limit(p)).is_none() && lucas_lehmer(p) {
+                true => Primality::Prime,
+                false => Primality::Composite,
+            }
+        }
+    }
+
+    fn test_name(&self, _: Primality) -> &'static str {
+        LUCAS_LEHMER_TEST
+    }
+}
+
+/// How far [`MersenneExponents`] trial factors `2^p - 1` before the
+/// Lucas-Lehmer test: to `64·p²`, about `16·p` candidate factors, while the
+/// test takes `p` squarings of `p`-bit numbers, so trial factoring costs
+/// less and less of the test it can spare as `p` grows.
+pub fn mersenne_factor_limit(p: u32) -> u64 {
+    (p as u64).pow(2).saturating_mul(64)
+}
+
+/// Pseudo-random integers of exactly `bits` bits, the same ones for the
+/// same seed.
+pub struct RandomBits {
+    bits: u32,
+    count: u128,
+    drawn: u128,
+    // SplitMix64 counter
+    state: u64,
+}
+
+impl RandomBits {
+    /// Draws `count` integers of `bits` bits, at least 2, picked by `seed`.
+    pub fn new(bits: u32, count: u128, seed: u64) -> Result<RandomBits> {
+        if bits < 2 {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Random candidates need at least 2 bits, not {}", bits)));
+        }
+        Ok(RandomBits { bits, count, drawn: 0, state: seed })
+    }
+
+    // Function to draw the next integer, with its top bit set so it has
+    // exactly `bits` bits
+    fn draw(&mut self) -> BigInt {
+        let words = self.bits.div_ceil(64);
+        let mut value = BigInt::from(0);
+        for _ in 0..words {
+            value = (value << 64) + mix(self.state);
+            self.state = self.state.wrapping_add(1);
+        }
+        let value = value >> (words * 64 - self.bits);
+        value | (BigInt::from(1) << (self.bits - 1))
+    }
+}
+
+impl CandidateSource for RandomBits {
+    fn columns(&self) -> &str {
+        "number"
+    }
+
+    fn next_batch(&mut self, max: usize) -> Result<Vec<Candidate>> {
+        let size = (self.count - self.drawn).min(max as u128) as usize;
+        let mut batch = Vec::with_capacity(size);
+        for _ in 0..size {
+            batch.push(Candidate { sequence: 0, index: self.drawn, value: self.draw() });
+            self.drawn += 1;
+        }
+        Ok(batch)
+    }
+
+    fn describe(&self, candidate: &Candidate) -> String {
+        candidate.value.to_string()
+    }
+}
+
+// The numbers of inclusive ranges, in order
+struct Values {
+    ranges: std::vec::IntoIter<(u128, u128)>,
+    // Next number and the end of the range it is in
+    current: Option<(u128, u128)>,
+}
+
+impl Values {
+    fn new(ranges: Vec<(u128, u128)>) -> Values {
+        Values { ranges: ranges.into_iter(), current: None }
+    }
+}
+
+impl Iterator for Values {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        loop {
+            match self.current {
+                Some((n, end)) => {
+                    self.current = if n < end { Some((n + 1, end)) } else { None };
+                    return Some(n);
+                }
+                None => self.current = Some(self.ranges.next().filter(|(start, end)| start <= end)?),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to test a whole source and keep its primes, described
+    fn primes<S: CandidateSource>(mut source: S, batch: usize) -> Vec<String> {
+        let mut found = Vec::new();
+        loop {
+            let verdicts = test_batch(&mut source, batch).unwrap();
+            if verdicts.is_empty() {
+                return found;
+            }
+            for (candidate, primality) in verdicts {
+                if primality != Primality::Composite {
+                    found.push(source.describe(&candidate));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tests_ranges_and_files_in_order() {
+        assert_eq!(primes(NumberRange::new(vec![(0, 10), (95, 102)]), 3), ["2", "3", "5", "7", "97", "101"]);
+        // The last range ends without overflowing
+        let top = (u128::MAX - 200..=u128::MAX).filter(|&n| is_prime(n)).count();
+        assert_eq!(primes(NumberRange::new(vec![(u128::MAX - 200, u128::MAX)]), 64).len(), top);
+
+        let mut file = NumberFile::new("7\n\nseven\n 10\n170141183460469231731687303715884105727\n".as_bytes());
+        let verdicts = test_batch(&mut file, 10).unwrap();
+        let described: Vec<(String, u128, Primality)> =
+            verdicts.iter().map(|(candidate, primality)| (file.describe(candidate), candidate.index, *primality)).collect();
+        assert_eq!(
+            described,
+            [
+                ("7".to_string(), 1, Primality::Prime),
+                ("10".to_string(), 4, Primality::Composite),
+                ("170141183460469231731687303715884105727".to_string(), 5, Primality::Prime),
+            ]
+        );
+        assert_eq!(file.take_invalid(), ["seven"]);
+        assert!(file.take_invalid().is_empty());
+    }
+
+    #[test]
+    fn checks_numbers_in_order_across_chunks() {
+        let mut input = String::new();
+        for n in 0..200u128 {
+            input.push_str(&format!("{}\n", n));
+            if n % 50 == 0 {
+                input.push_str("x\n");
+            }
+        }
+        let mut file = NumberFile::new(input.as_bytes());
+        let (mut checked, mut invalid) = (Vec::new(), 0);
+        loop {
+            let verdicts = test_batch(&mut file, 16).unwrap();
+            invalid += file.take_invalid().len();
+            if verdicts.is_empty() {
+                break;
+            }
+            checked.extend(verdicts.iter().map(|(candidate, primality)| (file.describe(candidate), *primality != Primality::Composite)));
+        }
+        let expected: Vec<(String, bool)> = (0..200u128).map(|n| (n.to_string(), is_prime(n))).collect();
+        assert_eq!(checked, expected);
+        assert_eq!(invalid, 4);
+    }
+
+    #[test]
+    fn interleaves_polynomials() {
+        let polynomials = vec!["n^2 + n + 41".parse().unwrap(), "n^2 + 1".parse().unwrap()];
+        let mut source = PolynomialValues::new(polynomials, vec![(0, 1), (39, 40)]);
+        let verdicts = test_batch(&mut source, 100).unwrap();
+        let order: Vec<(usize, u128)> = verdicts.iter().map(|(candidate, _)| (candidate.sequence, candidate.index)).collect();
+        assert_eq!(order, [(0, 0), (1, 0), (0, 1), (1, 1), (0, 39), (1, 39), (0, 40), (1, 40)]);
+        // Euler's polynomial gives primes up to n = 39 and 41^2 at n = 40
+        let euler: Vec<bool> =
+            verdicts.iter().filter(|(candidate, _)| candidate.sequence == 0).map(|(_, primality)| *primality == Primality::Prime).collect();
+        assert_eq!(euler, [true, true, true, false]);
+        assert_eq!(source.describe(&verdicts[7].0), "n^2 + 1,40,1601");
+    }
+
+    #[test]
+    fn finds_mersenne_primes() {
+        // Trial factoring alone rules out M_11 = 23 · 89 and M_29 = 233 · ...
+        assert_eq!(mersenne_factor(11, mersenne_factor_limit(11)), Some(23));
+        assert_eq!(mersenne_factor(29, mersenne_factor_limit(29)), Some(233));
+        let source = MersenneExponents::new(vec![(1, 130)]).unwrap();
+        assert_eq!(source.test_name(Primality::Prime), LUCAS_LEHMER_TEST);
+        assert_eq!(primes(source, 4), ["2", "3", "5", "7", "13", "17", "19", "31", "61", "89", "107", "127"]);
+        assert!(MersenneExponents::new(vec![(2, 1 << 32)]).is_err());
+    }
+
+    #[test]
+    fn draws_the_same_random_integers_for_a_seed() {
+        let draw = |seed| {
+            let mut source = RandomBits::new(100, 50, seed).unwrap();
+            let mut values = source.next_batch(30).unwrap();
+            values.extend(source.next_batch(30).unwrap());
+            values.into_iter().map(|candidate| candidate.value).collect::<Vec<_>>()
+        };
+        let values = draw(7);
+        assert_eq!(values.len(), 50);
+        assert!(values.iter().all(|value| value.bits() == 100));
+        assert_eq!(values, draw(7));
+        assert_ne!(values, draw(8));
+        assert!(RandomBits::new(1, 10, 0).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+// Extra per-prime columns that can follow the powers in the CSV output, for
+// studying number-theoretic patterns across the generated primes.
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::number_theory::{legendre, multiplicative_order, perfect_power, primitive_root};
+
+/// A column computed from each prime, written after `to_fourth_power`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    /// `legendre:<a>`: the Legendre symbol `(a/p)`, see [`legendre`].
+    Legendre(i128),
+    /// `primitive-root`: the smallest primitive root modulo `p`, see
+    /// [`primitive_root`]. Factors `p - 1` for every prime.
+    PrimitiveRoot,
+    /// `order:<a>`: the multiplicative order of `a` modulo `p`, see
+    /// [`multiplicative_order`], or 0 where `p` divides `a`. Factors `p - 1`
+    /// for every prime.
+    Order(u128),
+    /// `base`: the prime `p` of a prime power `p^k` written with
+    /// `--include-prime-powers`, and the prime itself on prime rows, see
+    /// [`perfect_power`].
+    Base,
+    /// `exponent`: the `k` of a prime power `p^k`, 1 on prime rows.
+    Exponent,
+}
+
+impl Column {
+    /// Returns the column's name in the CSV header, such as `legendre_3`.
+    pub fn name(&self) -> String {
+        match self {
+            Column::Legendre(a) => format!("legendre_{}", a),
+            Column::PrimitiveRoot => "primitive_root".to_string(),
+            Column::Order(a) => format!("order_{}", a),
+            Column::Base => "base".to_string(),
+            Column::Exponent => "exponent".to_string(),
+        }
+    }
+
+    /// Appends the column's value for the prime `p` to `out`.
+    pub fn write_value(&self, p: u128, out: &mut Vec<u8>) {
+        // Writing to a Vec can't fail
+        let _ = match self {
+            Column::Legendre(a) => write!(out, "{}", legendre(*a, p).unwrap_or(0)),
+            Column::PrimitiveRoot => write!(out, "{}", primitive_root(p).unwrap_or(0)),
+            Column::Order(a) => write!(out, "{}", multiplicative_order(*a, p).unwrap_or(0)),
+            Column::Base => write!(out, "{}", perfect_power(p).0),
+            Column::Exponent => write!(out, "{}", perfect_power(p).1),
+        };
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Column, String> {
+        let (kind, argument) = s.split_once(':').unwrap_or((s, ""));
+        match kind {
+            "legendre" => argument
+                .parse()
+                .map(Column::Legendre)
+                .map_err(|_| format!("Invalid column {}: expected legendre:<a> with an integer a", s)),
+            "primitive-root" if argument.is_empty() => Ok(Column::PrimitiveRoot),
+            "base" if argument.is_empty() => Ok(Column::Base),
+            "exponent" if argument.is_empty() => Ok(Column::Exponent),
+            "order" => argument
+                .parse()
+                .map(Column::Order)
+                .map_err(|_| format!("Invalid column {}: expected order:<a> with a non-negative integer a", s)),
+            _ => Err(format!("Unknown column: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Column::Legendre(a) => write!(f, "legendre:{}", a),
+            Column::PrimitiveRoot => f.write_str("primitive-root"),
+            Column::Order(a) => write!(f, "order:{}", a),
+            Column::Base => f.write_str("base"),
+            Column::Exponent => f.write_str("exponent"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_names_columns() {
+        let column: Column = "legendre:-3".parse().unwrap();
+        assert_eq!(column, Column::Legendre(-3));
+        assert_eq!((column.name(), column.to_string()), ("legendre_-3".to_string(), "legendre:-3".to_string()));
+        for spec in ["legendre", "legendre:x", "order:10:2", "order:-2", "primitive-root:2", "sideways"] {
+            assert!(spec.parse::<Column>().is_err(), "{}", spec);
+        }
+
+        let mut out = Vec::new();
+        for p in [2, 3, 7, 13] {
+            column.write_value(p, &mut out);
+            out.push(b' ');
+        }
+        // -3 is a residue modulo the primes that are 1 mod 3
+        assert_eq!(out, b"-1 0 1 1 ");
+
+        let column: Column = "primitive-root".parse().unwrap();
+        assert_eq!((column.name(), column.to_string()), ("primitive_root".to_string(), "primitive-root".to_string()));
+        out.clear();
+        column.write_value(41, &mut out);
+        assert_eq!(out, b"6");
+
+        let column: Column = "order:10".parse().unwrap();
+        assert_eq!((column.name(), column.to_string()), ("order_10".to_string(), "order:10".to_string()));
+        out.clear();
+        for p in [3, 5, 7] {
+            column.write_value(p, &mut out);
+            out.push(b' ');
+        }
+        assert_eq!(out, b"1 0 6 ");
+
+        out.clear();
+        for column in ["base", "exponent"] {
+            let column: Column = column.parse().unwrap();
+            assert_eq!(column.name(), column.to_string());
+            for n in [13, 169, 2197] {
+                column.write_value(n, &mut out);
+                out.push(b' ');
+            }
+        }
+        assert_eq!(out, b"13 13 13 1 2 3 ");
+    }
+}
@@ -0,0 +1,92 @@
+// Prime constellations: primes with prime neighbours at fixed distances, as
+// named filters over the generated primes.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::factor::factor;
+use crate::math::is_prime;
+
+/// A property a prime can have through its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constellation {
+    /// `twin`: a member of a twin pair `(p, p + 2)`, so `p - 2` or `p + 2` is prime.
+    Twin,
+    /// `cousin`: a member of a cousin pair `(p, p + 4)`.
+    Cousin,
+    /// `sexy`: a member of a sexy pair `(p, p + 6)`.
+    Sexy,
+    /// `chen`: a Chen prime, where `p + 2` is prime or a product of two primes.
+    Chen,
+}
+
+impl Constellation {
+    /// Returns whether the prime `p` has the property. `p` itself isn't tested.
+    pub fn contains(&self, p: u128) -> bool {
+        // Function to check whether p - gap or p + gap is prime
+        let paired = |gap: u128| p.checked_sub(gap).is_some_and(is_prime) || p.checked_add(gap).is_some_and(is_prime);
+        match self {
+            Constellation::Twin => paired(2),
+            Constellation::Cousin => paired(4),
+            Constellation::Sexy => paired(6),
+            Constellation::Chen => p.checked_add(2).is_some_and(|q| is_prime(q) || factor(q).len() == 2),
+        }
+    }
+}
+
+impl FromStr for Constellation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Constellation, String> {
+        match s {
+            "twin" => Ok(Constellation::Twin),
+            "cousin" => Ok(Constellation::Cousin),
+            "sexy" => Ok(Constellation::Sexy),
+            "chen" => Ok(Constellation::Chen),
+            _ => Err(format!("Unknown filter: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Constellation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Constellation::Twin => "twin",
+            Constellation::Cousin => "cousin",
+            Constellation::Sexy => "sexy",
+            Constellation::Chen => "chen",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    // Function to list the primes up to 100 with the property
+    fn members(constellation: Constellation) -> Vec<u64> {
+        primes_in_range(2, 100).into_iter().filter(|&p| constellation.contains(p.into())).collect()
+    }
+
+    #[test]
+    fn finds_constellation_members() {
+        // OEIS A001097, A046132 with A023200, A046117 with A023201, and A109611
+        assert_eq!(members(Constellation::Twin), [3, 5, 7, 11, 13, 17, 19, 29, 31, 41, 43, 59, 61, 71, 73]);
+        assert_eq!(members(Constellation::Cousin), [3, 7, 11, 13, 17, 19, 23, 37, 41, 43, 47, 67, 71, 79, 83, 97]);
+        assert_eq!(
+            members(Constellation::Sexy),
+            [5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 73, 79, 83, 89, 97]
+        );
+        assert_eq!(members(Constellation::Chen), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 47, 53, 59, 67, 71, 83, 89]);
+        assert!(!Constellation::Twin.contains(u128::MAX));
+    }
+
+    #[test]
+    fn parses_filter_names() {
+        for constellation in [Constellation::Twin, Constellation::Cousin, Constellation::Sexy, Constellation::Chen] {
+            assert_eq!(constellation.to_string().parse(), Ok(constellation));
+        }
+        assert!("triplet".parse::<Constellation>().is_err());
+    }
+}
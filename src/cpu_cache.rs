@@ -0,0 +1,89 @@
+// CPU cache detection for sizing sieve segments.
+
+use std::fs;
+use std::path::Path;
+
+// Segment size used when the cache sizes can't be detected
+const FALLBACK_SEGMENT_SIZE: u64 = 1 << 18;
+
+// Bounds on the auto-detected segment size
+const MIN_SEGMENT_SIZE: u64 = 1 << 14;
+const MAX_SEGMENT_SIZE: u64 = 1 << 24;
+
+/// Per-core data cache sizes in bytes, where known.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheSizes {
+    pub l1_data: Option<u64>,
+    pub l2: Option<u64>,
+}
+
+/// Reads the data cache sizes of the first CPU from sysfs. Returns empty sizes
+/// on platforms without `/sys/devices/system/cpu`.
+pub fn detect_cache_sizes() -> CacheSizes {
+    let mut sizes = CacheSizes::default();
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu/cpu0/cache") else {
+        return sizes;
+    };
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let (Some(level), Some(kind), Some(size)) = (
+            read_trimmed(&dir.join("level")),
+            read_trimmed(&dir.join("type")),
+            read_trimmed(&dir.join("size")).and_then(|s| parse_cache_size(&s)),
+        ) else {
+            continue;
+        };
+        match (level.as_str(), kind.as_str()) {
+            ("1", "Data") => sizes.l1_data = Some(size),
+            ("2", "Data") | ("2", "Unified") => sizes.l2 = Some(size),
+            _ => {}
+        }
+    }
+    sizes
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+// Function to parse sysfs sizes such as "48K" or "2M" into bytes
+fn parse_cache_size(s: &str) -> Option<u64> {
+    let (digits, multiplier) = match s.chars().last()? {
+        'K' => (&s[..s.len() - 1], 1 << 10),
+        'M' => (&s[..s.len() - 1], 1 << 20),
+        'G' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Picks a sieve segment size (in numbers, one byte of sieve state each) that
+/// keeps a segment resident in the L2 cache, or 8x the L1 data cache when L2
+/// is unknown.
+pub fn auto_segment_size() -> u64 {
+    let sizes = detect_cache_sizes();
+    sizes
+        .l2
+        .or(sizes.l1_data.map(|l1| l1 * 8))
+        .map(|bytes| bytes.clamp(MIN_SEGMENT_SIZE, MAX_SEGMENT_SIZE))
+        .unwrap_or(FALLBACK_SEGMENT_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sysfs_cache_sizes() {
+        assert_eq!(parse_cache_size("32K"), Some(32 << 10));
+        assert_eq!(parse_cache_size("1M"), Some(1 << 20));
+        assert_eq!(parse_cache_size("2G"), Some(2 << 30));
+        assert_eq!(parse_cache_size("49152"), Some(49152));
+        for bad in ["", "K", "1.5M", "32 K", "-1K", "32k", "32KB"] {
+            assert_eq!(parse_cache_size(bad), None, "{:?}", bad);
+        }
+        // Too large for u64 once multiplied, or even before
+        assert_eq!(parse_cache_size(&format!("{}G", u64::MAX >> 20)), None);
+        assert_eq!(parse_cache_size("99999999999999999999K"), None);
+    }
+}
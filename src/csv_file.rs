@@ -0,0 +1,534 @@
+// Reading and appending `PrimeRecord`s in CSV files.
+//
+// Every header line is preceded by a `#schema_version=N` line, so readers can
+// tell which layout the rows below it use. Files written before versioning have
+// no such line and are read as version 0, which has the same columns as 1.
+// Version 2 allows extra named columns (see `Column`) after the four of 1.
+// Version 3 allows powers in other formats (see `PowerFormat`), whose columns
+// are then named with the format's suffix, such as `squared_hex`.
+// Version 4 allows leaving the powers out, leaving `prime` followed by the
+// extra columns; readers then derive the powers from the prime.
+//
+// `CsvDialect` changes the punctuation rather than the columns, to match what
+// downstream tools ingest. Without headers, rows carry no schema version and
+// are read as the prime followed by fields readers skip.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use csv::{QuoteStyle, ReaderBuilder, StringRecord, Terminator, Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::bignum::{write_decimal_u128, write_power, Integer, PowerFormat};
+use crate::columns::Column;
+use crate::record::PrimeRecord;
+use crate::timing::TimedWriter;
+
+// Column names matching `PrimeRecord`'s fields
+const HEADER: [&str; 4] = ["prime", "squared", "cubed", "to_fourth_power"];
+
+/// Version of the record layout written above every CSV header. Bump it
+/// whenever columns change, and teach [`read_records`] to migrate the old one.
+pub const SCHEMA_VERSION: u32 = 4;
+
+// Start of the line carrying the schema version
+const SCHEMA_PREFIX: &str = "#schema_version=";
+
+/// How [`write_powers_with`] lays out its rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Extra columns after `to_fourth_power`.
+    pub columns: Vec<Column>,
+    /// How the three powers are written.
+    pub power_format: PowerFormat,
+    /// Whether to leave out the powers, which readers derive from the prime.
+    pub primes_only: bool,
+    /// Punctuation of the rows.
+    pub dialect: CsvDialect,
+}
+
+/// How fields and rows are delimited and quoted, and whether batches start
+/// with the schema version and header lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvDialect {
+    /// Byte between fields, `,` by default and `\t` for TSV.
+    pub delimiter: u8,
+    pub quoting: Quoting,
+    pub terminator: LineTerminator,
+    /// Whether each batch starts with the schema version and header lines.
+    pub header: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> CsvDialect {
+        CsvDialect { delimiter: b',', quoting: Quoting::Necessary, terminator: LineTerminator::Lf, header: true }
+    }
+}
+
+impl CsvDialect {
+    /// Returns whether this is the dialect files are written in by default.
+    pub fn is_default(&self) -> bool {
+        *self == CsvDialect::default()
+    }
+
+    // Function to set up a CSV writer in the dialect
+    fn writer<W: Write>(&self, writer: W) -> Writer<W> {
+        let quote_style = match self.quoting {
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+            Quoting::Never => QuoteStyle::Never,
+        };
+        let terminator = match self.terminator {
+            LineTerminator::Lf => Terminator::Any(b'\n'),
+            LineTerminator::Crlf => Terminator::CRLF,
+        };
+        WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote_style(quote_style)
+            .terminator(terminator)
+            .has_headers(self.header)
+            .from_writer(writer)
+    }
+}
+
+impl CsvOptions {
+    /// Returns the names of the fields of each row: `prime`, the powers
+    /// (unless left out) named after their format, then the extra columns.
+    pub fn field_names(&self) -> Vec<String> {
+        let leading = if self.primes_only { 1 } else { HEADER.len() };
+        let powers = HEADER[1..leading].iter().map(|name| format!("{}{}", name, self.power_format.suffix()));
+        let names = self.columns.iter().map(Column::name);
+        [HEADER[0].to_string()].into_iter().chain(powers).chain(names).collect()
+    }
+
+    /// Fills `fields`, one buffer per name of [`field_names`](CsvOptions::field_names),
+    /// with the row of `prime` and its `[squared, cubed, to_fourth_power]` powers.
+    pub fn write_fields(&self, prime: u128, powers: &[Integer], fields: &mut [Vec<u8>]) {
+        for field in fields.iter_mut() {
+            field.clear();
+        }
+        let leading = if self.primes_only { 1 } else { HEADER.len() };
+        write_decimal_u128(prime, &mut fields[0]);
+        for (field, power) in fields[1..leading].iter_mut().zip(powers) {
+            write_power(power, self.power_format, field);
+        }
+        for (field, column) in fields[leading..].iter_mut().zip(&self.columns) {
+            column.write_value(prime, field);
+        }
+    }
+}
+
+/// When fields are quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quoting {
+    /// `necessary`: only fields containing a delimiter, quote or line break.
+    Necessary,
+    /// `always`: every field.
+    Always,
+    /// `non-numeric`: every field that isn't a number, such as the header's.
+    NonNumeric,
+    /// `never`: no field, even where that makes the row ambiguous.
+    Never,
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Quoting, String> {
+        match s {
+            "necessary" => Ok(Quoting::Necessary),
+            "always" => Ok(Quoting::Always),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            "never" => Ok(Quoting::Never),
+            _ => Err(format!("Unknown quoting: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Quoting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Quoting::Necessary => "necessary",
+            Quoting::Always => "always",
+            Quoting::NonNumeric => "non-numeric",
+            Quoting::Never => "never",
+        })
+    }
+}
+
+/// What ends each row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineTerminator {
+    /// `lf`: `\n`.
+    Lf,
+    /// `crlf`: `\r\n`.
+    Crlf,
+}
+
+impl LineTerminator {
+    // Function to get the characters ending a row
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineTerminator::Lf => "\n",
+            LineTerminator::Crlf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineTerminator {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<LineTerminator, String> {
+        match s {
+            "lf" => Ok(LineTerminator::Lf),
+            "crlf" => Ok(LineTerminator::Crlf),
+            _ => Err(format!("Unknown line terminator: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for LineTerminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LineTerminator::Lf => "lf",
+            LineTerminator::Crlf => "crlf",
+        })
+    }
+}
+
+/// Appends `records` to the CSV file at `path`, creating it if needed.
+pub fn append_records<P, I>(path: P, records: I) -> Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = PrimeRecord>,
+{
+    write_records(open_append(path)?, records)
+}
+
+/// Appends `records` to the CSV file at `path` in `dialect`, like
+/// [`append_records`].
+pub fn append_records_with<P, I>(path: P, records: I, dialect: &CsvDialect) -> Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = PrimeRecord>,
+{
+    write_records_with(open_append(path)?, records, dialect)
+}
+
+/// Writes `records` as CSV, with a header line, to `writer`.
+pub fn write_records<W, I>(writer: W, records: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = PrimeRecord>,
+{
+    write_records_with(writer, records, &CsvDialect::default())
+}
+
+/// Writes `records` as CSV in `dialect` to `writer`, like [`write_records`].
+pub fn write_records_with<W, I>(writer: W, records: I, dialect: &CsvDialect) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = PrimeRecord>,
+{
+    let mut records = records.into_iter().peekable();
+    if records.peek().is_none() {
+        return Ok(());
+    }
+    let mut writer = writer;
+    if dialect.header {
+        write_schema_version(&mut writer, dialect)?;
+    }
+    let mut wtr = dialect.writer(writer);
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Appends primes with their `[squared, cubed, to_fourth_power]` powers to the
+/// CSV file at `path` in the same layout as [`append_records`].
+pub fn append_powers<P: AsRef<Path>>(path: P, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    append_powers_with(path, batch, &CsvOptions::default())
+}
+
+/// Appends primes with their powers to the CSV file at `path` laid out as
+/// `options` says, like [`append_powers`].
+pub fn append_powers_with<P: AsRef<Path>>(path: P, batch: &[(u128, Vec<Integer>)], options: &CsvOptions) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    write_powers_with(open_append(path)?, batch, options)
+}
+
+/// Writes primes with their powers as CSV to `writer`, formatting the numbers
+/// straight into reused buffers instead of building a `PrimeRecord` (and its
+/// strings) per prime.
+pub fn write_powers<W: Write>(writer: W, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+    write_powers_with(writer, batch, &CsvOptions::default())
+}
+
+/// Writes primes with their powers as CSV to `writer` like [`write_powers`],
+/// with the powers in the format `options` names (and their columns named
+/// after it) and followed by one field per extra column. With
+/// `options.primes_only`, the powers are left out altogether. The rows are
+/// punctuated as `options.dialect` says.
+pub fn write_powers_with<W: Write>(mut writer: W, batch: &[(u128, Vec<Integer>)], options: &CsvOptions) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let dialect = &options.dialect;
+    if dialect.header {
+        write_schema_version(&mut writer, dialect)?;
+    }
+    let mut wtr = dialect.writer(writer);
+    let names = options.field_names();
+    if dialect.header {
+        wtr.write_record(&names)?;
+    }
+
+    let mut fields = vec![Vec::new(); names.len()];
+    for (prime, powers) in batch {
+        options.write_fields(*prime, powers, &mut fields);
+        wtr.write_record(&fields)?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+// Function to write the schema version line that precedes a header
+fn write_schema_version<W: Write>(writer: &mut W, dialect: &CsvDialect) -> Result<()> {
+    write!(writer, "{}{}{}", SCHEMA_PREFIX, SCHEMA_VERSION, dialect.terminator.as_str())
+}
+
+// Function to open a file for appending, timing the writes as I/O
+fn open_append<P: AsRef<Path>>(path: P) -> Result<TimedWriter<File>> {
+    OpenOptions::new().append(true).create(true).open(path).map(TimedWriter)
+}
+
+/// Reads every record from the CSV file at `path`.
+///
+/// Rows written before schema versioning are accepted as they are. Fails with
+/// [`ErrorKind::InvalidData`] if the file contains rows of a schema version
+/// newer than [`SCHEMA_VERSION`], rather than misreading their columns.
+pub fn read_records<P: AsRef<Path>>(path: P) -> Result<Vec<PrimeRecord>> {
+    let mut records = Vec::new();
+    for_each_record(path, |record| {
+        records.push(record);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+/// Calls `f` with every record of the CSV file at `path` in file order,
+/// without holding them all in memory. Checks schema versions like
+/// [`read_records`].
+pub fn for_each_record<P, F>(path: P, f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(PrimeRecord) -> Result<()>,
+{
+    for_each_record_with(path, &CsvDialect::default(), f)
+}
+
+/// Calls `f` with every record of the CSV file at `path`, written in
+/// `dialect`, like [`for_each_record`]. Rows without a header above them get
+/// their powers derived from the prime in their first field.
+pub fn for_each_record_with<P, F>(path: P, dialect: &CsvDialect, mut f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(PrimeRecord) -> Result<()>,
+{
+    let file = OpenOptions::new().read(true).open(path)?;
+    // Headers repeat with every appended batch, so they are matched by hand.
+    // Each one may name different extra columns, which records skip
+    let mut rdr = ReaderBuilder::new().delimiter(dialect.delimiter).has_headers(false).flexible(true).from_reader(file);
+    let mut header = StringRecord::from(HEADER.to_vec());
+    // Whether the rows under the header leave out the powers
+    let mut primes_only = !dialect.header;
+
+    for result in rdr.records() {
+        let row = result?;
+        if let Some(version) = row.get(0).and_then(|field| field.strip_prefix(SCHEMA_PREFIX)) {
+            check_schema_version(version)?;
+        } else if row.get(0) == Some(HEADER[0]) {
+            primes_only = !row.iter().any(|name| name.starts_with(HEADER[1]));
+            header = row;
+        } else if primes_only {
+            let prime = row.get(0).and_then(|field| field.parse().ok());
+            let prime = prime.ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid prime: {:?}", row.get(0))))?;
+            f(PrimeRecord::from_prime(prime))?;
+        } else {
+            f(row.deserialize(Some(&header))?)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Function to refuse rows of a schema version this build can't read
+pub(crate) fn check_schema_version(version: &str) -> Result<()> {
+    match version.trim().parse::<u32>() {
+        // Version 0 (unversioned files) has the same columns as version 1,
+        // version 2 only adds optional ones after them, version 3 renames
+        // the powers' columns when they aren't decimal and version 4 may
+        // leave them out
+        Ok(0..=SCHEMA_VERSION) => Ok(()),
+        Ok(version) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Records use schema version {}, but this build only reads up to {}", version, SCHEMA_VERSION),
+        )),
+        Err(_) => Err(Error::new(ErrorKind::InvalidData, format!("Invalid schema version: {}", version))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::powers;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Function to give each test its own file in the temp dir
+    fn scratch(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("prime_generator_csv_{}_{}.csv", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_back_appended_batches() {
+        let path = scratch("round_trip", "");
+        append_records(&path, [2, 3].map(PrimeRecord::from_prime)).unwrap();
+        append_powers(&path, &[(5, powers(5).to_vec()), (u128::MAX, powers(u128::MAX).to_vec())]).unwrap();
+        append_records(&path, []).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents.matches(SCHEMA_PREFIX).count(), 2);
+        assert_eq!(records, [2, 3, 5, u128::MAX].map(PrimeRecord::from_prime));
+    }
+
+    #[test]
+    fn reads_files_without_schema_version() {
+        let path = scratch("legacy", "prime,squared,cubed,to_fourth_power\n7,49,343,2401\nprime,squared,cubed,to_fourth_power\n11,121,1331,14641\n");
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records, [7, 11].map(PrimeRecord::from_prime));
+    }
+
+    #[test]
+    fn rejects_newer_schema_versions() {
+        let newer = format!("{}{}\nprime,squared,cubed,to_fourth_power\n7,49,343,2401\n", SCHEMA_PREFIX, SCHEMA_VERSION + 1);
+        let invalid = format!("{}one\n", SCHEMA_PREFIX);
+        for (name, contents) in [("newer", newer), ("invalid", invalid)] {
+            let path = scratch(name, &contents);
+            let error = read_records(&path).unwrap_err();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{}", name);
+        }
+    }
+
+    #[test]
+    fn skips_extra_columns() {
+        let path = scratch("columns", "");
+        let options = CsvOptions { columns: vec![Column::Legendre(2), Column::Legendre(-1)], ..CsvOptions::default() };
+        append_powers_with(&path, &[(7, powers(7).to_vec())], &options).unwrap();
+        append_powers(&path, &[(11, powers(11).to_vec())]).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.contains("to_fourth_power,legendre_2,legendre_-1\n7,49,343,2401,1,-1\n"));
+        assert_eq!(records, [7, 11].map(PrimeRecord::from_prime));
+    }
+
+    #[test]
+    fn names_power_formats_in_the_header() {
+        let path = scratch("power_format", "");
+        let options = CsvOptions { power_format: PowerFormat::Hex, ..CsvOptions::default() };
+        append_powers_with(&path, &[(17, powers(17).to_vec())], &options).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.ends_with("prime,squared_hex,cubed_hex,to_fourth_power_hex\n17,121,1331,14641\n"), "{}", contents);
+        // The rows read back as they were written
+        assert_eq!(records[0].to_fourth_power, "14641");
+    }
+
+    #[test]
+    fn derives_powers_left_out() {
+        let path = scratch("primes_only", "");
+        let options = CsvOptions { columns: vec![Column::Legendre(-1)], primes_only: true, ..CsvOptions::default() };
+        append_powers_with(&path, &[(7, powers(7).to_vec()), (u128::MAX, powers(u128::MAX).to_vec())], &options).unwrap();
+        append_powers(&path, &[(11, powers(11).to_vec())]).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.contains("prime,legendre_-1\n7,-1\n"), "{}", contents);
+        assert_eq!(records, [7, u128::MAX, 11].map(PrimeRecord::from_prime));
+
+        let path = scratch("primes_only_invalid", "prime\nseven\n");
+        let error = read_records(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writes_other_dialects() {
+        let path = scratch("dialect", "");
+        let dialect = CsvDialect { delimiter: b'\t', quoting: Quoting::Always, terminator: LineTerminator::Crlf, header: false };
+        let options = CsvOptions { columns: vec![Column::Legendre(-1)], dialect, ..CsvOptions::default() };
+        append_powers_with(&path, &[(7, powers(7).to_vec()), (11, powers(11).to_vec())], &options).unwrap();
+        append_records_with(&path, [PrimeRecord::from_prime(13)], &dialect).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_with(&path, &dialect);
+        fs::remove_file(&path).unwrap();
+        assert!(contents.starts_with("\"7\"\t\"49\"\t\"343\"\t\"2401\"\t\"-1\"\r\n"), "{:?}", contents);
+        assert!(!contents.contains(SCHEMA_PREFIX) && !contents.contains("prime"));
+        assert_eq!(records, [7, 11, 13].map(PrimeRecord::from_prime));
+
+        // Headers and schema versions keep the dialect's line terminator
+        let path = scratch("dialect_header", "");
+        let dialect = CsvDialect { delimiter: b';', terminator: LineTerminator::Crlf, ..CsvDialect::default() };
+        append_records_with(&path, [PrimeRecord::from_prime(2)], &dialect).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records = read_with(&path, &dialect);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents, format!("{}{}\r\nprime;squared;cubed;to_fourth_power\r\n2;4;8;16\r\n", SCHEMA_PREFIX, SCHEMA_VERSION));
+        assert_eq!(records, [PrimeRecord::from_prime(2)]);
+    }
+
+    #[test]
+    fn parses_dialect_names() {
+        for quoting in [Quoting::Necessary, Quoting::Always, Quoting::NonNumeric, Quoting::Never] {
+            assert_eq!(quoting.to_string().parse(), Ok(quoting));
+        }
+        for terminator in [LineTerminator::Lf, LineTerminator::Crlf] {
+            assert_eq!(terminator.to_string().parse(), Ok(terminator));
+        }
+        assert!("sometimes".parse::<Quoting>().is_err() && "cr".parse::<LineTerminator>().is_err());
+    }
+
+    // Function to read every record of a file in a dialect
+    fn read_with(path: &Path, dialect: &CsvDialect) -> Vec<PrimeRecord> {
+        let mut records = Vec::new();
+        for_each_record_with(path, dialect, |record| {
+            records.push(record);
+            Ok(())
+        })
+        .unwrap();
+        records
+    }
+}
@@ -0,0 +1,246 @@
+// Bindings to DuckDB's C API (https://duckdb.org/docs/api/c/overview), used by
+// `DuckDbSink` to append records to a table of a database file. Rows go
+// through an appender, which buffers them into DuckDB's columnar chunks.
+// Needs libduckdb to be installed where the linker finds it.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::ptr;
+
+use crate::bignum::Integer;
+use crate::csv_file::CsvOptions;
+
+// `DuckDBSuccess` of `duckdb_state`
+const SUCCESS: c_int = 0;
+
+// Opaque handles, each a pointer to a struct wrapping DuckDB's own pointer
+type Database = *mut c_void;
+type Connection = *mut c_void;
+type Appender = *mut c_void;
+
+// `duckdb_result`, which the API fills in by value
+#[repr(C)]
+struct QueryResult {
+    column_count: u64,
+    row_count: u64,
+    rows_changed: u64,
+    columns: *mut c_void,
+    error_message: *mut c_char,
+    internal_data: *mut c_void,
+}
+
+// `duckdb_uhugeint`
+#[repr(C)]
+struct Uhugeint {
+    lower: u64,
+    upper: u64,
+}
+
+#[link(name = "duckdb")]
+extern "C" {
+    fn duckdb_open(path: *const c_char, database: *mut Database) -> c_int;
+    fn duckdb_close(database: *mut Database);
+    fn duckdb_connect(database: Database, connection: *mut Connection) -> c_int;
+    fn duckdb_disconnect(connection: *mut Connection);
+    fn duckdb_query(connection: Connection, query: *const c_char, result: *mut QueryResult) -> c_int;
+    fn duckdb_result_error(result: *mut QueryResult) -> *const c_char;
+    fn duckdb_destroy_result(result: *mut QueryResult);
+    fn duckdb_appender_create(connection: Connection, schema: *const c_char, table: *const c_char, appender: *mut Appender) -> c_int;
+    fn duckdb_appender_error(appender: Appender) -> *const c_char;
+    fn duckdb_appender_flush(appender: Appender) -> c_int;
+    fn duckdb_appender_destroy(appender: *mut Appender) -> c_int;
+    fn duckdb_append_uhugeint(appender: Appender, value: Uhugeint) -> c_int;
+    fn duckdb_append_varchar_length(appender: Appender, value: *const c_char, length: u64) -> c_int;
+    fn duckdb_appender_end_row(appender: Appender) -> c_int;
+}
+
+/// A table of a DuckDB database file that rows are appended to.
+pub struct DuckDbTable {
+    database: Database,
+    connection: Connection,
+    appender: Appender,
+    options: CsvOptions,
+    // Buffers holding each field of the row being appended
+    fields: Vec<Vec<u8>>,
+}
+
+// Safety: DuckDB handles can be used from any thread, and `&mut self` keeps
+// them to one at a time
+unsafe impl Send for DuckDbTable {}
+
+impl DuckDbTable {
+    /// Opens the database file at `path` (creating it if needed) and appends
+    /// to `table`, created with one column per field `options` describes if
+    /// it doesn't exist: `prime` as `UHUGEINT` and the rest as `VARCHAR`, as
+    /// the powers overflow every integer type.
+    pub fn open<P: AsRef<Path>>(path: P, table: &str, options: &CsvOptions) -> Result<DuckDbTable> {
+        let path = c_string(&path.as_ref().to_string_lossy())?;
+        let names = options.field_names();
+        let mut opened = DuckDbTable {
+            database: ptr::null_mut(),
+            connection: ptr::null_mut(),
+            appender: ptr::null_mut(),
+            options: options.clone(),
+            fields: vec![Vec::new(); names.len()],
+        };
+        // Safety: each handle is only used once DuckDB reports it was set up,
+        // and `Drop` releases whichever were
+        unsafe {
+            if duckdb_open(path.as_ptr(), &mut opened.database) != SUCCESS {
+                return Err(Error::other("Failed to open the DuckDB database"));
+            }
+            if duckdb_connect(opened.database, &mut opened.connection) != SUCCESS {
+                return Err(Error::other("Failed to connect to the DuckDB database"));
+            }
+            let columns: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| format!("{} {}", quote_identifier(name), if i == 0 { "UHUGEINT" } else { "VARCHAR" }))
+                .collect();
+            opened.query(&format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_identifier(table), columns.join(", ")))?;
+            let table = c_string(table)?;
+            if duckdb_appender_create(opened.connection, ptr::null(), table.as_ptr(), &mut opened.appender) != SUCCESS {
+                return Err(opened.appender_error("Failed to create the appender"));
+            }
+        }
+        Ok(opened)
+    }
+
+    /// Appends primes with their `[squared, cubed, to_fourth_power]` powers.
+    /// Rows may stay buffered until [`flush`](DuckDbTable::flush).
+    pub fn append_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        for (prime, powers) in batch {
+            self.options.write_fields(*prime, powers, &mut self.fields);
+            // Safety: the appender is open, and the values outlive the calls
+            unsafe {
+                let mut state = duckdb_append_uhugeint(self.appender, Uhugeint { lower: *prime as u64, upper: (*prime >> 64) as u64 });
+                for field in &self.fields[1..] {
+                    if state == SUCCESS {
+                        state = duckdb_append_varchar_length(self.appender, field.as_ptr() as *const c_char, field.len() as u64);
+                    }
+                }
+                if state != SUCCESS || duckdb_appender_end_row(self.appender) != SUCCESS {
+                    return Err(self.appender_error("Failed to append a row"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered rows to the table.
+    pub fn flush(&mut self) -> Result<()> {
+        // Safety: the appender is open
+        match unsafe { duckdb_appender_flush(self.appender) } {
+            SUCCESS => Ok(()),
+            _ => Err(self.appender_error("Failed to flush the appender")),
+        }
+    }
+
+    // Function to run a statement, returning DuckDB's message if it fails
+    fn query(&mut self, sql: &str) -> Result<()> {
+        let sql = c_string(sql)?;
+        let mut result = QueryResult {
+            column_count: 0,
+            row_count: 0,
+            rows_changed: 0,
+            columns: ptr::null_mut(),
+            error_message: ptr::null_mut(),
+            internal_data: ptr::null_mut(),
+        };
+        // Safety: the connection is open, and the result is destroyed even on failure
+        unsafe {
+            let state = duckdb_query(self.connection, sql.as_ptr(), &mut result);
+            let outcome = match state {
+                SUCCESS => Ok(()),
+                _ => Err(Error::other(message(duckdb_result_error(&mut result), "Query failed"))),
+            };
+            duckdb_destroy_result(&mut result);
+            outcome
+        }
+    }
+
+    // Function to build an error from the appender's last message
+    fn appender_error(&self, fallback: &str) -> Error {
+        if self.appender.is_null() {
+            return Error::other(fallback.to_string());
+        }
+        // Safety: the appender is open, and its message is null or NUL-terminated
+        Error::other(unsafe { message(duckdb_appender_error(self.appender), fallback) })
+    }
+}
+
+impl Drop for DuckDbTable {
+    fn drop(&mut self) {
+        // Safety: each handle is released once, after the ones depending on it;
+        // destroying the appender flushes what it still buffers
+        unsafe {
+            if !self.appender.is_null() {
+                duckdb_appender_destroy(&mut self.appender);
+            }
+            if !self.connection.is_null() {
+                duckdb_disconnect(&mut self.connection);
+            }
+            if !self.database.is_null() {
+                duckdb_close(&mut self.database);
+            }
+        }
+    }
+}
+
+// Function to quote a table or column name, doubling any quotes in it
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Function to pass a string to C, refusing ones with NUL bytes
+fn c_string(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|_| Error::new(ErrorKind::InvalidInput, format!("{:?} contains a NUL byte", s)))
+}
+
+// Function to copy an error message DuckDB owns, if it gave one
+//
+// Safety: `message` must be null or point to a NUL-terminated string
+unsafe fn message(message: *const c_char, fallback: &str) -> String {
+    match message.is_null() {
+        true => fallback.to_string(),
+        false => CStr::from_ptr(message).to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::powers;
+    use std::fs;
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(quote_identifier("primes"), "\"primes\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(c_string("a\0b").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn appends_rows_to_a_table() {
+        let path = std::env::temp_dir().join(format!("prime_generator_{}.duckdb", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let batch: Vec<(u128, Vec<Integer>)> = [2, 3, u128::MAX - 158].iter().map(|&p| (p, powers(p).to_vec())).collect();
+        let mut table = DuckDbTable::open(&path, "my \"primes\"", &CsvOptions::default()).unwrap();
+        table.append_powers(&batch[..2]).unwrap();
+        table.flush().unwrap();
+        drop(table);
+
+        // Reopening keeps the rows already there
+        let mut table = DuckDbTable::open(&path, "my \"primes\"", &CsvOptions::default()).unwrap();
+        table.append_powers(&batch[2..]).unwrap();
+        table.flush().unwrap();
+        // The cast fails unless the table holds exactly the rows appended
+        let check = "SELECT CAST(CASE WHEN count(*) = 3 AND max(prime) = 340282366920938463463374607431768211297 \
+                     AND sum(CASE WHEN to_fourth_power = '16' THEN 1 ELSE 0 END) = 1 THEN '1' ELSE 'unexpected rows' END AS INTEGER) FROM \"my \"\"primes\"\"\"";
+        table.query(check).unwrap();
+        assert!(table.query("SELECT CAST('unexpected rows' AS INTEGER)").is_err());
+        drop(table);
+        fs::remove_file(&path).unwrap();
+    }
+}
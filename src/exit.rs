@@ -0,0 +1,90 @@
+// The exit codes of the CLI, a contract for scripts and schedulers: they tell
+// bad arguments from failed I/O, unreachable servers and output that doesn't
+// verify, so automation can retry only what is worth retrying.
+
+use std::error::Error;
+use std::io::{self, ErrorKind};
+
+/// How the process ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// 0: everything asked for was done.
+    Success,
+    /// 1: any other failure, including warnings under `--strict`.
+    Failure,
+    /// 2: invalid arguments or input; running again unchanged won't help.
+    InvalidArguments,
+    /// 3: a file or other local resource couldn't be read or written.
+    Io,
+    /// 4: a server couldn't be reached or refused the request.
+    Network,
+    /// 5: output failed verification against its manifest, signature or a
+    /// known prime list.
+    Mismatch,
+}
+
+impl Exit {
+    /// The process exit code.
+    pub fn code(self) -> i32 {
+        match self {
+            Exit::Success => 0,
+            Exit::Failure => 1,
+            Exit::InvalidArguments => 2,
+            Exit::Io => 3,
+            Exit::Network => 4,
+            Exit::Mismatch => 5,
+        }
+    }
+
+    /// Picks the exit code for an I/O error by its kind: connection and
+    /// timeout errors are network errors, bad input is an invalid argument
+    /// and the rest are local I/O errors.
+    pub fn for_error(error: &io::Error) -> Exit {
+        match error.kind() {
+            ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::AddrNotAvailable
+            | ErrorKind::HostUnreachable
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::NetworkDown
+            | ErrorKind::TimedOut => Exit::Network,
+            ErrorKind::InvalidInput => Exit::InvalidArguments,
+            _ => Exit::Io,
+        }
+    }
+
+    /// Picks the exit code for an error of a network operation: an I/O error
+    /// is classified by its kind (a missing local file is still an I/O
+    /// error) and anything else, such as an HTTP error, is a network error.
+    pub fn for_network_error(error: &(dyn Error + 'static)) -> Exit {
+        match error.downcast_ref::<io::Error>() {
+            Some(error) => Exit::for_error(error),
+            None => Exit::Network,
+        }
+    }
+
+    /// Ends the process with this exit code.
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_errors() {
+        assert_eq!([Exit::Success, Exit::InvalidArguments, Exit::Mismatch].map(Exit::code), [0, 2, 5]);
+        assert_eq!(Exit::for_error(&io::Error::from(ErrorKind::ConnectionRefused)), Exit::Network);
+        assert_eq!(Exit::for_error(&io::Error::from(ErrorKind::TimedOut)), Exit::Network);
+        assert_eq!(Exit::for_error(&io::Error::from(ErrorKind::NotFound)), Exit::Io);
+        assert_eq!(Exit::for_error(&io::Error::from(ErrorKind::InvalidInput)), Exit::InvalidArguments);
+        let missing: Box<dyn Error> = Box::new(io::Error::from(ErrorKind::PermissionDenied));
+        assert_eq!(Exit::for_network_error(&*missing), Exit::Io);
+        let http: Box<dyn Error> = "HTTP 503".into();
+        assert_eq!(Exit::for_network_error(&*http), Exit::Network);
+    }
+}
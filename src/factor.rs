@@ -0,0 +1,154 @@
+// Integer factorization by trial division and Pollard's rho (Brent variant).
+
+use crate::math::is_prime;
+use crate::montgomery::Montgomery128;
+
+// Primes below this are stripped by trial division before running rho
+const TRIAL_DIVISION_BOUND: u128 = 1000;
+
+/// Returns the prime factors of `n` in increasing order, repeated according to
+/// their multiplicity. `factor(0)` and `factor(1)` are empty.
+pub fn factor(mut n: u128) -> Vec<u128> {
+    let mut factors = Vec::new();
+    if n < 2 {
+        return factors;
+    }
+    let mut d = 2;
+    while d < TRIAL_DIVISION_BOUND && d * d <= n {
+        while n.is_multiple_of(d) {
+            factors.push(d);
+            n /= d;
+        }
+        d += if d == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        split(n, &mut factors);
+    }
+    factors.sort_unstable();
+    factors
+}
+
+/// Returns the distinct prime factors of `n` with their exponents, in increasing order.
+pub fn factor_with_multiplicity(n: u128) -> Vec<(u128, u32)> {
+    let mut grouped: Vec<(u128, u32)> = Vec::new();
+    for p in factor(n) {
+        match grouped.last_mut() {
+            Some((last, exponent)) if *last == p => *exponent += 1,
+            _ => grouped.push((p, 1)),
+        }
+    }
+    grouped
+}
+
+// Function to recursively split a number with no small factors into primes
+fn split(n: u128, factors: &mut Vec<u128>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(n) {
+        factors.push(n);
+        return;
+    }
+    let divisor = (1..)
+        .find_map(|c| pollard_brent(n, c))
+        .expect("Pollard's rho always finds a divisor of a composite");
+    split(divisor, factors);
+    split(n / divisor, factors);
+}
+
+// Function to find a nontrivial divisor of composite n with f(x) = x^2 + c,
+// returning None when this choice of c fails
+fn pollard_brent(n: u128, c: u128) -> Option<u128> {
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    // Iterate in Montgomery form: x_m - y_m = (x - y) * R mod n shares the
+    // same gcd with n because R is coprime to n
+    let mont = Montgomery128::new(n);
+    let c = mont.to_montgomery(c);
+    let f = |x: u128| mont.add(mont.mul(x, x), c);
+    let (mut y, mut product) = (2u128, 1u128);
+    let mut power = 1u64;
+    loop {
+        let x = y;
+        for _ in 0..power {
+            y = f(y);
+        }
+        let mut steps = 0;
+        while steps < power {
+            // Batch gcd computations by accumulating |x - y| products
+            let saved_y = y;
+            let batch = (power - steps).min(128);
+            for _ in 0..batch {
+                y = f(y);
+                product = mont.mul(product, x.abs_diff(y));
+            }
+            let g = gcd(product, n);
+            if g == n {
+                // Overshot: retrace the batch one step at a time
+                let mut retrace = saved_y;
+                loop {
+                    retrace = f(retrace);
+                    let g = gcd(x.abs_diff(retrace), n);
+                    if g == n {
+                        return None;
+                    }
+                    if g > 1 {
+                        return Some(g);
+                    }
+                }
+            }
+            if g > 1 {
+                return Some(g);
+            }
+            steps += batch;
+        }
+        power *= 2;
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::next_prime;
+
+    // Function to check that `factors` are sorted primes multiplying to n
+    fn assert_factorization(n: u128, factors: &[u128]) {
+        assert!(factors.windows(2).all(|w| w[0] <= w[1]), "{:?}", factors);
+        assert!(factors.iter().all(|&p| is_prime(p)), "{:?}", factors);
+        assert_eq!(factors.iter().product::<u128>(), n);
+    }
+
+    #[test]
+    fn factors_small_numbers() {
+        assert!(factor(0).is_empty());
+        assert!(factor(1).is_empty());
+        for n in 2..5000 {
+            assert_factorization(n, &factor(n));
+        }
+        assert_eq!(factor_with_multiplicity(360), [(2, 3), (3, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn splits_large_semiprimes_with_pollard_brent() {
+        assert_eq!(factor((1 << 64) + 1), [274177, 67280421310721]);
+        let p = next_prime(1 << 32).unwrap();
+        let q = next_prime(1 << 90).unwrap();
+        assert_eq!(factor(p * q), [p, q]);
+        assert_eq!(factor(p * p * 3), [3, p, p]);
+    }
+
+    #[test]
+    fn factors_numbers_near_the_word_size() {
+        for n in [u64::MAX as u128, u128::MAX, u128::MAX - 1, (1 << 127) + 1] {
+            assert_factorization(n, &factor(n));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// C ABI for embedding the engine from C, C++ and other languages.
+//
+// Numbers are passed as NUL-terminated decimal strings so callers aren't
+// limited to 64-bit integers; strings returned to C must be released with
+// `pg_string_free`.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+
+use num_bigint::{BigInt, Sign};
+
+use crate::primality::{is_prime, next_prime};
+use crate::sieve::for_each_prime;
+
+/// Callback invoked for each prime by `pg_primes_in_range`; return non-zero to continue, zero to stop.
+pub type PgPrimeCallback = extern "C" fn(prime: u64, user_data: *mut c_void) -> c_int;
+
+// Function to parse a caller-provided decimal string
+unsafe fn parse_number(n: *const c_char) -> Option<BigInt> {
+    if n.is_null() {
+        return None;
+    }
+    CStr::from_ptr(n).to_str().ok()?.trim().parse::<BigInt>().ok()
+}
+
+/// Tests a decimal number for primality.
+///
+/// Returns 1 if `n` is prime, 0 if it is not, and -1 if `n` is not a valid
+/// decimal integer or is larger than 2^128 - 1, so every call returns promptly.
+///
+/// # Safety
+///
+/// `n` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pg_is_prime(n: *const c_char) -> c_int {
+    match parse_number(n) {
+        // Negative numbers are simply not prime
+        Some(big_n) if big_n.sign() == Sign::Minus => 0,
+        Some(big_n) => match u128::try_from(big_n) {
+            Ok(n) => is_prime(n) as c_int,
+            Err(_) => -1,
+        },
+        None => -1,
+    }
+}
+
+/// Returns the smallest prime greater than the decimal number `n` as a newly
+/// allocated decimal string, or null if `n` is invalid or the result exceeds 128 bits.
+///
+/// # Safety
+///
+/// `n` must be null or point to a NUL-terminated string. The result must be
+/// released with `pg_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn pg_next_prime(n: *const c_char) -> *mut c_char {
+    let next = parse_number(n)
+        .and_then(|big_n| u128::try_from(big_n.max(BigInt::from(0))).ok())
+        .and_then(next_prime);
+    match next.map(|p| CString::new(p.to_string())) {
+        Some(Ok(s)) => s.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be null or a pointer returned by this library that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pg_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Streams every prime in `[start, end]` to `callback` in increasing order.
+///
+/// Enumeration stops early when the callback returns zero. Returns the number
+/// of primes delivered.
+///
+/// # Safety
+///
+/// `callback` must be safe to call with `user_data`, which is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn pg_primes_in_range(
+    start: u64,
+    end: u64,
+    callback: PgPrimeCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    let mut delivered = 0;
+    for_each_prime(start, end, |prime| {
+        delivered += 1;
+        callback(prime, user_data) != 0
+    });
+    delivered
+}
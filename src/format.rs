@@ -0,0 +1,132 @@
+// Numbers formatted for people: digits grouped the way the user's locale
+// groups them, and rates shortened with SI suffixes. Machine-readable output
+// (CSV, JSON, systemd status) never goes through here.
+
+use std::env;
+
+// SI prefixes for each power of 1000 from 1000^1
+const SI_PREFIXES: [char; 6] = ['k', 'M', 'G', 'T', 'P', 'E'];
+
+/// How numbers are shown in progress lines and summaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    // Separator between groups of three digits, none for plain numbers
+    grouping: Option<char>,
+    decimal: char,
+}
+
+impl NumberFormat {
+    /// Digits without separators and rates in full, for machine parsing.
+    pub const PLAIN: NumberFormat = NumberFormat { grouping: None, decimal: '.' };
+
+    /// Follows the locale in `LC_ALL`, `LC_NUMERIC` or `LANG`, the first one
+    /// set, as `setlocale` does.
+    pub fn from_env() -> NumberFormat {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"].iter().find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()));
+        NumberFormat::for_locale(locale.as_deref().unwrap_or(""))
+    }
+
+    /// Separators for a POSIX locale name such as `de_DE.UTF-8`: `1.234,5` in
+    /// much of continental Europe, a narrow no-break space and a comma in
+    /// French, Slavic and Nordic locales, `1'234.5` in Switzerland and
+    /// `1,234.5` otherwise, including the C locale.
+    pub fn for_locale(locale: &str) -> NumberFormat {
+        let name = locale.split(['.', '@']).next().unwrap_or("");
+        let (language, territory) = name.split_once('_').unwrap_or((name, ""));
+        let (grouping, decimal) = match (language, territory) {
+            (_, "CH") => ('\'', '.'),
+            ("de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl" | "sr" | "vi", _) => ('.', ','),
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu" | "bg" | "lt" | "lv" | "et", _) => ('\u{202f}', ','),
+            _ => (',', '.'),
+        };
+        NumberFormat { grouping: Some(grouping), decimal }
+    }
+
+    /// Formats a count with its digits grouped in threes.
+    pub fn count<N: Into<u128>>(&self, n: N) -> String {
+        let digits = n.into().to_string();
+        let Some(separator) = self.grouping else {
+            return digits;
+        };
+        let mut grouped = String::with_capacity(digits.len() * 4 / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// Formats a rate or other measurement to three significant digits with
+    /// an SI suffix, such as `1.24G`; plain, it is rounded to a whole number
+    /// (two decimals below 100).
+    pub fn si(&self, x: f64) -> String {
+        if self.grouping.is_none() {
+            return match x.abs() < 100.0 {
+                true => format!("{:.2}", x),
+                false => format!("{:.0}", x),
+            };
+        }
+        let mut scaled = x;
+        let mut prefix = None;
+        for &next in &SI_PREFIXES {
+            // Round first, so 999.7 becomes 1.00k rather than 1000
+            if round_significant(scaled.abs()) < 1000.0 {
+                break;
+            }
+            scaled /= 1000.0;
+            prefix = Some(next);
+        }
+        let decimals = match round_significant(scaled.abs()) {
+            v if v < 10.0 => 2,
+            v if v < 100.0 => 1,
+            _ => 0,
+        };
+        let mut formatted = format!("{:.*}", decimals, scaled).replace('.', &self.decimal.to_string());
+        formatted.extend(prefix);
+        formatted
+    }
+}
+
+// Function to round a non-negative number to three significant digits
+fn round_significant(x: f64) -> f64 {
+    if x == 0.0 || !x.is_finite() {
+        return x;
+    }
+    let scale = 10f64.powi(2 - x.log10().floor() as i32);
+    (x * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_digits_by_locale() {
+        let english = NumberFormat::for_locale("en_US.UTF-8");
+        assert_eq!(english.count(1_234_567u64), "1,234,567");
+        assert_eq!(english.count(999u32), "999");
+        assert_eq!(english.count(1000u32), "1,000");
+        assert_eq!(NumberFormat::for_locale("de_DE.UTF-8").count(1_234_567u64), "1.234.567");
+        assert_eq!(NumberFormat::for_locale("fr_FR").count(1_234_567u64), "1\u{202f}234\u{202f}567");
+        assert_eq!(NumberFormat::for_locale("de_CH.UTF-8").count(1_234_567u64), "1'234'567");
+        assert_eq!(NumberFormat::for_locale("C"), english);
+        assert_eq!(NumberFormat::for_locale(""), english);
+        assert_eq!(NumberFormat::PLAIN.count(u128::MAX), u128::MAX.to_string());
+    }
+
+    #[test]
+    fn shortens_with_si_suffixes() {
+        let english = NumberFormat::for_locale("en_GB.UTF-8");
+        assert_eq!(english.si(1_240_000_000.0), "1.24G");
+        assert_eq!(english.si(12_449_999.0), "12.4M");
+        assert_eq!(english.si(999.7), "1.00k");
+        assert_eq!(english.si(999_499.0), "999k");
+        assert_eq!(english.si(42.0), "42.0");
+        assert_eq!(english.si(0.0), "0.00");
+        assert_eq!(NumberFormat::for_locale("de_DE").si(1_240_000.0), "1,24M");
+        assert_eq!(NumberFormat::PLAIN.si(1_240_000_000.4), "1240000000");
+        assert_eq!(NumberFormat::PLAIN.si(2.5), "2.50");
+    }
+}
@@ -0,0 +1,45 @@
+// Parallel generation that hands records straight to user callbacks.
+
+use rayon::prelude::*;
+
+use crate::cpu_cache::auto_segment_size;
+use crate::record::PrimeRecord;
+use crate::sieve::{primes_in_segment, sieving_primes};
+
+/// Calls `callback` with the record of every prime in `[start, end]`.
+///
+/// Segments of the range are sieved in parallel on the current rayon pool and
+/// the callback runs on the worker that found the prime, so calls arrive
+/// concurrently and in no particular order.
+pub fn generate_with<F>(start: u64, end: u64, callback: F)
+where
+    F: Fn(PrimeRecord) + Sync,
+{
+    generate_batches_with(start, end, |batch| batch.into_iter().for_each(&callback));
+}
+
+/// Calls `callback` once per sieved segment with the records of the primes it
+/// contains, in increasing order within the batch. Batches themselves arrive
+/// concurrently and in no particular order; empty batches are skipped.
+pub fn generate_batches_with<F>(start: u64, end: u64, callback: F)
+where
+    F: Fn(Vec<PrimeRecord>) + Sync,
+{
+    if end < start {
+        return;
+    }
+    let segment_size = auto_segment_size();
+    let base_primes = sieving_primes(end);
+    let segments = (end - start) / segment_size + 1;
+    (0..segments).into_par_iter().for_each(|segment| {
+        let segment_start = start + segment * segment_size;
+        let segment_end = segment_start.saturating_add(segment_size - 1).min(end);
+        let batch: Vec<PrimeRecord> = primes_in_segment(segment_start, segment_end, &base_primes)
+            .into_iter()
+            .map(|prime| PrimeRecord::from_prime(prime as u128))
+            .collect();
+        if !batch.is_empty() {
+            callback(batch);
+        }
+    });
+}
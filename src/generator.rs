@@ -0,0 +1,1661 @@
+// Configurable generation runs: range, thread pool, algorithm and sink.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use num_bigint::{BigInt, ToBigInt};
+use num_traits::ToPrimitive;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::aggregate::{Aggregate, AggregateReport, Aggregates};
+use crate::bignum::{powers, Integer};
+use crate::cpu_cache::auto_segment_size;
+use crate::math::{baillie_psw, is_prime};
+use crate::numa::{pin_current_thread, worker_cpus, NumaPolicy};
+use crate::number_theory::{perfect_power, prime_powers};
+use crate::primality::is_prime_bigint;
+use crate::prime_cache::cached_small_primes;
+use crate::ranges::{merge_ranges, subtract_ranges};
+use crate::record_tracker::{RecordFind, RecordKind, RecordTracker};
+use crate::sieve::{isqrt, sieving_primes, BucketSieve};
+use crate::sink::Sink;
+use crate::telemetry::{record_primes, record_segment, Span};
+use crate::throttle::Throttle;
+use crate::timing::{self, Phase};
+
+// Buffered primes and their powers awaiting a flush to the sink
+type PrimeBatch = Vec<(u128, Vec<Integer>)>;
+
+// Which primes a run keeps
+type PrimeFilter = dyn Fn(u128) -> bool + Send + Sync;
+
+// Where record-setting primes are written, on the writer thread
+type RecordOutput = Box<dyn Write + Send>;
+
+// Define a threshold for record count or memory usage
+const FLUSH_THRESHOLD: usize = 10000;
+
+// Full batches that can wait while the writer works on the previous one
+const QUEUE_DEPTH: usize = 1;
+
+// Most consecutive segments one segmented sieve task works through
+const MAX_STRIPE_SEGMENTS: u64 = 64;
+
+// Numbers tested by one worker task when per-candidate output must be sorted
+const PER_CANDIDATE_CHUNK: u64 = 1 << 12;
+
+// Work units per thread in each warmup trial, so every worker is busy
+const WARMUP_CHUNKS_PER_THREAD: u64 = 4;
+
+// Multiples of the configured chunk size and flush threshold tried during warmup
+const WARMUP_CHUNK_FACTORS: [(u64, u64); 5] = [(1, 4), (1, 2), (1, 1), (2, 1), (4, 1)];
+const WARMUP_FLUSH_FACTORS: [(usize, usize); 3] = [(1, 4), (1, 1), (4, 1)];
+
+// Share of the memory limit buffered records may take before batches are flushed early
+const MEMORY_FLUSH_FRACTION: (usize, usize) = (3, 4);
+
+// Chunks per thread in each window of a memory-guarded run, after which the
+// chunk size can shrink
+const GUARDED_CHUNKS_PER_THREAD: u64 = 64;
+
+// Smallest chunk size the memory guard shrinks to
+const MIN_GUARDED_CHUNK: u64 = 1 << 10;
+
+/// How candidates are classified as prime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// Sieves ranges that fit into `u64` and tests larger ones candidate by candidate.
+    #[default]
+    Auto,
+    /// Tests every odd candidate on its own with deterministic Miller-Rabin
+    /// (trial division below 2^20).
+    PerCandidate,
+    /// Sieves only the requested window, in parallel segments, with the primes
+    /// up to the square root of its end. The range must fit into `u64`.
+    SegmentedSieve,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Algorithm, String> {
+        match s {
+            "auto" => Ok(Algorithm::Auto),
+            "per-candidate" => Ok(Algorithm::PerCandidate),
+            "segmented-sieve" => Ok(Algorithm::SegmentedSieve),
+            _ => Err(format!("Unknown algorithm: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Auto => "auto",
+            Algorithm::PerCandidate => "per-candidate",
+            Algorithm::SegmentedSieve => "segmented-sieve",
+        })
+    }
+}
+
+/// Order in which records reach the sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordOrder {
+    /// Whatever order workers finish in, for maximum throughput.
+    #[default]
+    Arrival,
+    /// Increasing order. Work is handed out in order and finished chunks are
+    /// held back until every earlier chunk has been written.
+    Sorted,
+}
+
+impl FromStr for RecordOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<RecordOrder, String> {
+        match s {
+            "arrival" => Ok(RecordOrder::Arrival),
+            "sorted" => Ok(RecordOrder::Sorted),
+            _ => Err(format!("Unknown ordering: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for RecordOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RecordOrder::Arrival => "arrival",
+            RecordOrder::Sorted => "sorted",
+        })
+    }
+}
+
+/// Which implementation enumerates primes for the sieve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// The built-in segmented sieve.
+    #[default]
+    Native,
+    /// The primesieve C library. Requires the `primesieve` feature.
+    Primesieve,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Backend, String> {
+        match s {
+            "native" => Ok(Backend::Native),
+            "primesieve" => Ok(Backend::Primesieve),
+            _ => Err(format!("Unknown backend: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Native => "native",
+            Backend::Primesieve => "primesieve",
+        })
+    }
+}
+
+/// Configures a [`PrimeGenerator`]; created by [`PrimeGenerator::builder`].
+#[derive(Default)]
+pub struct PrimeGeneratorBuilder {
+    ranges: Vec<(u128, u128)>,
+    excluded: Vec<(u128, u128)>,
+    threads: Option<usize>,
+    algorithm: Algorithm,
+    order: RecordOrder,
+    backend: Backend,
+    segment_size: Option<u64>,
+    numa: NumaPolicy,
+    cpu_list: Option<Vec<usize>>,
+    prime_cache: bool,
+    auto_tune: bool,
+    cross_check: bool,
+    memory_limit: Option<usize>,
+    queue_depth: Option<usize>,
+    filter: Option<Arc<PrimeFilter>>,
+    aggregates: Vec<Aggregate>,
+    records: Option<(Vec<RecordKind>, RecordOutput)>,
+    prime_powers: bool,
+    throttle: Option<Arc<Throttle>>,
+    sink: Option<Box<dyn Sink>>,
+}
+
+impl PrimeGeneratorBuilder {
+    /// Sets the inclusive range to search. Required, unless set by
+    /// [`ranges`](Self::ranges).
+    pub fn range(mut self, start: u128, end: u128) -> Self {
+        self.ranges = vec![(start, end)];
+        self
+    }
+
+    /// Sets several inclusive ranges to search in one run, sharing the thread
+    /// pool and sink. They are searched in increasing order, and overlapping
+    /// parts only once.
+    pub fn ranges<I: IntoIterator<Item = (u128, u128)>>(mut self, ranges: I) -> Self {
+        self.ranges = ranges.into_iter().collect();
+        self
+    }
+
+    /// Leaves out inclusive ranges of numbers, such as those covered by
+    /// earlier runs; only the rest of the range is generated. Can be called
+    /// repeatedly.
+    pub fn exclude<I: IntoIterator<Item = (u128, u128)>>(mut self, ranges: I) -> Self {
+        self.excluded.extend(ranges);
+        self
+    }
+
+    /// Sets the number of worker threads. Defaults to rayon's choice.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the classification algorithm. Defaults to [`Algorithm::Auto`].
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the order records reach the sink in. Defaults to arrival order.
+    pub fn ordering(mut self, order: RecordOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets which implementation enumerates primes for the sieve. Defaults to
+    /// the built-in one. Other backends only apply to the segmented sieve.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets how many numbers the segmented sieve processes per task.
+    /// Defaults to a size that fits the detected L2 cache.
+    pub fn segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = Some(segment_size);
+        self
+    }
+
+    /// Sets whether workers are pinned to NUMA nodes. Defaults to automatic
+    /// pinning on multi-node machines.
+    pub fn numa(mut self, numa: NumaPolicy) -> Self {
+        self.numa = numa;
+        self
+    }
+
+    /// Pins workers round-robin to these CPUs instead of spreading them over
+    /// NUMA nodes, keeping the run off the others. Without
+    /// [`threads`](PrimeGeneratorBuilder::threads), runs one worker per CPU.
+    pub fn cpu_list(mut self, cpus: Vec<usize>) -> Self {
+        self.cpu_list = Some(cpus);
+        self
+    }
+
+    /// Sets whether the segmented sieve loads its base primes from the
+    /// persistent cache under the user cache dir, creating or extending it as
+    /// needed. Defaults to off.
+    pub fn prime_cache(mut self, prime_cache: bool) -> Self {
+        self.prime_cache = prime_cache;
+        self
+    }
+
+    /// Sets whether the run starts with a warmup that times a few chunk sizes
+    /// and flush thresholds on the first windows of the range and keeps the
+    /// fastest for the rest. The warmup windows are part of the output, so no
+    /// work is wasted. Defaults to off.
+    pub fn auto_tune(mut self, auto_tune: bool) -> Self {
+        self.auto_tune = auto_tune;
+        self
+    }
+
+    /// Sets whether every candidate is classified a second time by an
+    /// independent algorithm: Miller-Rabin for the sieve backends, Baillie-PSW
+    /// for per-candidate testing. The run fails on the first disagreement, naming
+    /// the number and both verdicts. Defaults to off.
+    pub fn cross_check(mut self, cross_check: bool) -> Self {
+        self.cross_check = cross_check;
+        self
+    }
+
+    /// Sets a ceiling in bytes on the records held in memory: buffered,
+    /// waiting for earlier chunks in sorted mode, or queued for the sink. As
+    /// the estimate nears it, batches are flushed before the flush threshold
+    /// and the chunk size is halved for the rest of the range, trading
+    /// throughput for a bounded footprint on long runs. Defaults to none.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Sets how many full batches may wait for the sink. When the writer
+    /// falls behind (a slow disk, compressed output) and the queue is full,
+    /// the worker flushing blocks, and the others with it as they hand in
+    /// their chunks, so memory stays bounded. Deeper queues absorb bursts at
+    /// the cost of memory. Defaults to 1.
+    pub fn queue_depth(mut self, batches: usize) -> Self {
+        self.queue_depth = Some(batches);
+        self
+    }
+
+    /// Keeps only the primes `filter` accepts, such as members of a
+    /// [`Constellation`](crate::constellation::Constellation). Workers apply
+    /// it before computing powers, so rejected primes cost nothing more.
+    /// Defaults to keeping every prime.
+    pub fn filter<F: Fn(u128) -> bool + Send + Sync + 'static>(mut self, filter: F) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Keeps running aggregates over the written primes, reported in
+    /// [`RunSummary::aggregates`]. [`Aggregate::MaxGap`] needs
+    /// [`RecordOrder::Sorted`]. Defaults to none.
+    pub fn aggregate<I: IntoIterator<Item = Aggregate>>(mut self, aggregates: I) -> Self {
+        self.aggregates = aggregates.into_iter().collect();
+        self
+    }
+
+    /// Writes every record-setting prime of `kinds` to `out` as soon as the
+    /// prime is written, as `kind,prime,value` rows without a header; the
+    /// standing records end up in [`RunSummary::records`]. Like the
+    /// aggregates, only written primes count. [`RecordKind::Twin`] and
+    /// [`RecordKind::Gap`] need [`RecordOrder::Sorted`]. Defaults to none.
+    pub fn track_records<I, W>(mut self, kinds: I, out: W) -> Self
+    where
+        I: IntoIterator<Item = RecordKind>,
+        W: Write + Send + 'static,
+    {
+        let kinds: Vec<RecordKind> = kinds.into_iter().collect();
+        self.records = (!kinds.is_empty()).then(|| (kinds, Box::new(out) as RecordOutput));
+        self
+    }
+
+    /// Sets whether the prime powers `p^k` with `k >= 2` in the range are
+    /// written too, each right after the primes below it in sorted mode. Their
+    /// base `p` must pass the [`filter`](Self::filter), and they don't count
+    /// towards the aggregates. Tell them apart from primes with
+    /// [`perfect_power`](crate::number_theory::perfect_power), or the
+    /// [`Base`](crate::columns::Column::Base) and
+    /// [`Exponent`](crate::columns::Column::Exponent) columns. Defaults to off.
+    pub fn include_prime_powers(mut self, prime_powers: bool) -> Self {
+        self.prime_powers = prime_powers;
+        self
+    }
+
+    /// Lets `throttle` pause the workers, each stopping once it finishes its
+    /// current candidate or segment, e.g. to keep a shared machine usable.
+    pub fn throttle(mut self, throttle: Arc<Throttle>) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Sets where records are written. Required.
+    pub fn sink<S: Sink + 'static>(mut self, sink: S) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Validates the configuration.
+    pub fn build(self) -> Result<PrimeGenerator> {
+        if let Some(&(start, end)) = self.ranges.iter().find(|(start, end)| start > end) {
+            return Err(invalid_input(format!("Start {} is greater than end {}", start, end)));
+        }
+        let ranges = merge_ranges(&self.ranges);
+        let (Some(&(start, _)), Some(&(_, end))) = (ranges.first(), ranges.last()) else {
+            return Err(invalid_input("No range was given"));
+        };
+        if let Some(&(from, to)) = self.excluded.iter().find(|(from, to)| from > to) {
+            return Err(invalid_input(format!("Excluded range start {} is greater than end {}", from, to)));
+        }
+        if self.algorithm == Algorithm::SegmentedSieve && end > u64::MAX as u128 {
+            return Err(invalid_input("The segmented sieve only supports ranges below 2^64"));
+        }
+        if self.segment_size == Some(0) {
+            return Err(invalid_input("The segment size must be positive"));
+        }
+        if self.aggregates.contains(&Aggregate::MaxGap) && self.order != RecordOrder::Sorted {
+            return Err(invalid_input("The max_gap aggregate needs sorted ordering"));
+        }
+        if let Some(kind) = self.records.iter().flat_map(|(kinds, _)| kinds).find(|kind| kind.needs_order()) {
+            if self.order != RecordOrder::Sorted {
+                return Err(invalid_input(format!("Tracking {} records needs sorted ordering", kind)));
+            }
+        }
+        if self.memory_limit == Some(0) {
+            return Err(invalid_input("The memory limit must be positive"));
+        }
+        if self.queue_depth == Some(0) {
+            return Err(invalid_input("The writer queue depth must be positive"));
+        }
+        if self.cpu_list.as_ref().is_some_and(|cpus| cpus.is_empty()) {
+            return Err(invalid_input("The CPU list is empty"));
+        }
+        let algorithm = match self.algorithm {
+            Algorithm::Auto if end <= u64::MAX as u128 => Algorithm::SegmentedSieve,
+            Algorithm::Auto => Algorithm::PerCandidate,
+            algorithm => algorithm,
+        };
+        if self.backend == Backend::Primesieve {
+            if !cfg!(feature = "primesieve") {
+                return Err(invalid_input("Built without the primesieve feature"));
+            }
+            if algorithm != Algorithm::SegmentedSieve {
+                return Err(invalid_input("The primesieve backend needs the segmented sieve, below 2^64"));
+            }
+        }
+        let sink = self.sink.ok_or_else(|| invalid_input("No sink was given"))?;
+        Ok(PrimeGenerator {
+            start,
+            end,
+            uncovered: ranges.iter().flat_map(|&(from, to)| subtract_ranges(from, to, &self.excluded)).collect(),
+            threads: self.threads.or(self.cpu_list.as_ref().map(|cpus| cpus.len())),
+            algorithm,
+            order: self.order,
+            backend: self.backend,
+            segment_size: self.segment_size.unwrap_or_else(auto_segment_size),
+            numa: self.numa,
+            cpu_list: self.cpu_list,
+            prime_cache: self.prime_cache,
+            auto_tune: self.auto_tune,
+            cross_check: self.cross_check,
+            memory_limit: self.memory_limit,
+            queue_depth: self.queue_depth.unwrap_or(QUEUE_DEPTH),
+            filter: self.filter,
+            aggregates: self.aggregates,
+            records: self.records,
+            prime_powers: self.prime_powers,
+            throttle: self.throttle,
+            sink,
+            progress: Arc::new(AtomicUsize::new(0)),
+            backlog: Arc::new(AtomicUsize::new(0)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+}
+
+fn invalid_input<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidInput, error)
+}
+
+/// A configured generation run.
+///
+/// ```no_run
+/// use prime_generator::{Algorithm, CsvSink, PrimeGenerator};
+///
+/// PrimeGenerator::builder()
+///     .range(2, 1_000_000)
+///     .threads(8)
+///     .algorithm(Algorithm::SegmentedSieve)
+///     .sink(CsvSink::new("primes.csv"))
+///     .build()?
+///     .run()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct PrimeGenerator {
+    start: u128,
+    end: u128,
+    // What is left of the range once the excluded ranges are taken out
+    uncovered: Vec<(u128, u128)>,
+    threads: Option<usize>,
+    algorithm: Algorithm,
+    order: RecordOrder,
+    backend: Backend,
+    segment_size: u64,
+    numa: NumaPolicy,
+    cpu_list: Option<Vec<usize>>,
+    prime_cache: bool,
+    auto_tune: bool,
+    cross_check: bool,
+    memory_limit: Option<usize>,
+    queue_depth: usize,
+    filter: Option<Arc<PrimeFilter>>,
+    aggregates: Vec<Aggregate>,
+    records: Option<(Vec<RecordKind>, RecordOutput)>,
+    prime_powers: bool,
+    throttle: Option<Arc<Throttle>>,
+    sink: Box<dyn Sink>,
+    progress: Arc<AtomicUsize>,
+    backlog: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// Settings chosen by the warmup of an auto-tuned run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    /// Numbers per task: the sieve segment size, or the per-candidate chunk
+    /// in sorted mode. Unused by unordered per-candidate testing.
+    pub chunk_size: u64,
+    /// Records buffered before a batch is handed to the writer.
+    pub flush_threshold: usize,
+}
+
+/// What a finished run reports back.
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    /// The settings the warmup locked in, for auto-tuned runs.
+    pub tuning: Option<Tuning>,
+    /// Batches flushed before the flush threshold because buffered records
+    /// neared the memory limit.
+    pub memory_flushes: usize,
+    /// The chunk size the memory guard shrank to, if it had to.
+    pub shrunk_chunk_size: Option<u64>,
+    /// How often a flush found the writer queue full and waited for the
+    /// sink, and for how long in all: the time backpressure held the workers.
+    pub writer_stalls: usize,
+    pub writer_stalled: Duration,
+    /// The most batches flushed but not yet written at any time.
+    pub max_queued_batches: usize,
+    /// The aggregates asked for with
+    /// [`aggregate`](PrimeGeneratorBuilder::aggregate), if any.
+    pub aggregates: Option<AggregateReport>,
+    /// The standing record of each kind asked for with
+    /// [`track_records`](PrimeGeneratorBuilder::track_records) that has one.
+    pub records: Vec<RecordFind>,
+}
+
+// Records waiting for the sink. A full batch is swapped out and handed to the
+// writer thread, so workers fill the next buffer while the last one is written.
+struct Pending {
+    batch: PrimeBatch,
+    writer: SyncSender<PrimeBatch>,
+    flush_threshold: usize,
+    order: RecordOrder,
+    // Sorted mode: the next chunk due, and finished chunks waiting on earlier ones
+    next_chunk: u128,
+    waiting: BTreeMap<u128, PrimeBatch>,
+    // Estimated bytes of records held here, queued for the writer or being
+    // written; the writer subtracts what it has written
+    buffered: Arc<AtomicUsize>,
+    memory_limit: Option<usize>,
+    // Whether the records neared the memory limit since the last check
+    pressure: bool,
+    memory_flushes: usize,
+    // Batches flushed but not yet written; the writer subtracts those it wrote
+    queued: Arc<AtomicUsize>,
+    max_queued: usize,
+    // Flushes that waited on a full queue, and how long they waited
+    stalls: usize,
+    stalled: Duration,
+}
+
+impl Pending {
+    fn new(writer: SyncSender<PrimeBatch>, order: RecordOrder, memory_limit: Option<usize>, buffered: Arc<AtomicUsize>, queued: Arc<AtomicUsize>) -> Pending {
+        Pending {
+            batch: Vec::new(),
+            writer,
+            flush_threshold: FLUSH_THRESHOLD,
+            order,
+            next_chunk: 0,
+            waiting: BTreeMap::new(),
+            buffered,
+            memory_limit,
+            pressure: false,
+            memory_flushes: 0,
+            queued,
+            max_queued: 0,
+            stalls: 0,
+            stalled: Duration::ZERO,
+        }
+    }
+
+    // Function to prepare for a pass over another part of the range, whose chunks are numbered from 0
+    fn start_pass(&mut self, flush_threshold: usize) {
+        debug_assert!(self.waiting.is_empty(), "Previous pass left chunks behind");
+        self.next_chunk = 0;
+        self.flush_threshold = flush_threshold;
+    }
+
+    // Function to add the primes found in one chunk of work, flushing when the threshold is reached
+    fn push_chunk(&mut self, chunk: u128, found: PrimeBatch) -> Result<()> {
+        self.buffered.fetch_add(batch_bytes(&found), Ordering::SeqCst);
+        match self.order {
+            RecordOrder::Arrival => self.batch.extend(found),
+            RecordOrder::Sorted => {
+                self.waiting.insert(chunk, found);
+                while let Some(ready) = self.waiting.remove(&self.next_chunk) {
+                    self.batch.extend(ready);
+                    self.next_chunk += 1;
+                }
+            }
+        }
+        self.flush_if_due()
+    }
+
+    // Function to add one prime and its powers, flushing when the threshold is reached
+    fn push_record(&mut self, prime: u128, powers: Vec<Integer>) -> Result<()> {
+        self.buffered.fetch_add(record_bytes(prime), Ordering::SeqCst);
+        self.batch.push((prime, powers));
+        self.flush_if_due()
+    }
+
+    // Function to flush once the batch reaches the threshold, or earlier when
+    // the records held in memory near the limit
+    fn flush_if_due(&mut self) -> Result<()> {
+        let (num, den) = MEMORY_FLUSH_FRACTION;
+        let near_limit = self
+            .memory_limit
+            .is_some_and(|limit| self.buffered.load(Ordering::SeqCst) >= limit / den * num);
+        self.pressure |= near_limit;
+        if self.batch.len() >= self.flush_threshold {
+            return self.flush();
+        }
+        if near_limit && !self.batch.is_empty() {
+            self.memory_flushes += 1;
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    // Function to report whether the memory limit was neared since the last call
+    fn take_pressure(&mut self) -> bool {
+        mem::take(&mut self.pressure)
+    }
+
+    // Function to hand the batch to the writer thread, leaving an empty buffer
+    // behind; while the queue is full this blocks, holding the lock the other
+    // workers need to hand in their chunks
+    fn flush(&mut self) -> Result<()> {
+        let batch = mem::take(&mut self.batch);
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_queued = self.max_queued.max(queued);
+        // The writer only hangs up after a failed write, whose error `run` reports
+        let stopped = || Error::other("The writer thread stopped");
+        let batch = match self.writer.try_send(batch) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Full(batch)) => batch,
+            Err(TrySendError::Disconnected(_)) => return Err(stopped()),
+        };
+        let waited = Instant::now();
+        let sent = self.writer.send(batch).map_err(|_| stopped());
+        self.stalls += 1;
+        self.stalled += waited.elapsed();
+        sent
+    }
+}
+
+// What every worker shares during a run
+struct Workers<'a> {
+    storage: &'a Mutex<Pending>,
+    progress: &'a AtomicUsize,
+    cross_check: bool,
+    filter: Option<&'a PrimeFilter>,
+    prime_powers: bool,
+    throttle: Option<&'a Throttle>,
+}
+
+impl Workers<'_> {
+    // Function to hold the calling worker while the run is paused
+    fn wait_if_paused(&self) {
+        if let Some(throttle) = self.throttle {
+            throttle.wait();
+        }
+    }
+
+    // Function to check whether a prime passes the run's filter
+    fn keeps(&self, prime: u128) -> bool {
+        self.filter.is_none_or(|filter| filter(prime))
+    }
+
+    // Function to pair each prime with its powers, which are exact for any
+    // u128, so no prime is ever dropped here
+    fn with_powers<I: IntoIterator<Item = u128>>(&self, primes: I) -> PrimeBatch {
+        let _timer = timing::time(Phase::Powers);
+        primes.into_iter().map(|prime| (prime, powers(prime).to_vec())).collect()
+    }
+
+    // Function to add the prime powers p^k (k >= 2) in [start, end] to the
+    // primes found there, if the run includes them, keeping the batch sorted
+    fn add_prime_powers(&self, start: u128, end: u128, found: &mut PrimeBatch) {
+        if !self.prime_powers {
+            return;
+        }
+        let _timer = timing::time(Phase::Powers);
+        let before = found.len();
+        for (power, _, _) in prime_powers(start, end).into_iter().filter(|&(_, base, _)| self.keeps(base)) {
+            found.push((power, powers(power).to_vec()));
+        }
+        if found.len() > before {
+            found.sort_unstable_by_key(|(n, _)| *n);
+        }
+    }
+
+    // Function to confirm a classification with the independent check, if enabled
+    fn confirm(&self, n: u128, found_prime: bool, classifier: &str, check: fn(u128) -> bool, check_name: &str) -> Result<()> {
+        if !self.cross_check {
+            return Ok(());
+        }
+        let checked_prime = check(n);
+        if checked_prime == found_prime {
+            return Ok(());
+        }
+        let verdict = |prime: bool| if prime { "prime" } else { "composite" };
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Cross-check failed for {}: {} says {}, {} says {}",
+                n,
+                classifier,
+                verdict(found_prime),
+                check_name,
+                verdict(checked_prime)
+            ),
+        ))
+    }
+}
+
+impl PrimeGenerator {
+    pub fn builder() -> PrimeGeneratorBuilder {
+        PrimeGeneratorBuilder::default()
+    }
+
+    /// Counter of candidates processed so far, for progress reporting.
+    pub fn progress(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.progress)
+    }
+
+    /// Estimated bytes of records found but not yet written by the sink, for
+    /// spotting a writer that falls behind or stalls.
+    pub fn backlog(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.backlog)
+    }
+
+    /// Batches flushed but not yet written by the sink: up to the
+    /// [`queue_depth`](PrimeGeneratorBuilder::queue_depth), plus the one being
+    /// written and one a worker is blocked handing in.
+    pub fn queued_batches(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.queued)
+    }
+
+    /// The algorithm the run uses, with [`Algorithm::Auto`] resolved.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Numbers per segmented sieve task, after auto-detection.
+    pub fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
+
+    /// Total count of numbers covered by the run's ranges, without excluded ranges.
+    pub fn total_numbers(&self) -> u128 {
+        self.uncovered.iter().map(|(from, to)| to - from + 1).sum()
+    }
+
+    /// The parts of the range the run generates, in increasing order.
+    pub fn uncovered_ranges(&self) -> &[(u128, u128)] {
+        &self.uncovered
+    }
+
+    /// Runs the generation, writing every prime in the range to the sink.
+    pub fn run(self) -> Result<RunSummary> {
+        let _span = Span::start("generate", &[("start", self.start), ("end", self.end)]);
+        let mut pool = ThreadPoolBuilder::new();
+        if let Some(threads) = self.threads {
+            pool = pool.num_threads(threads);
+        }
+        let placement = match &self.cpu_list {
+            Some(cpus) => Some(cpus.clone()),
+            None if self.numa == NumaPolicy::Auto => {
+                let threads = self
+                    .threads
+                    .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+                worker_cpus(threads)
+            }
+            None => None,
+        };
+        if let Some(cpus) = placement {
+            pool = pool.start_handler(move |worker| {
+                pin_current_thread(cpus[worker % cpus.len()]);
+            });
+        }
+        let pool = pool.build().map_err(Error::other)?;
+
+        // Flushing blocks the workers once the queue is full
+        let (sender, receiver) = mpsc::sync_channel(self.queue_depth);
+        let sink = self.sink;
+        let buffered = self.backlog;
+        let written_bytes = Arc::clone(&buffered);
+        let queued = self.queued;
+        let written_batches = Arc::clone(&queued);
+        let aggregates = (!self.aggregates.is_empty()).then(|| Aggregates::new(&self.aggregates, &self.uncovered));
+        let uncovered = &self.uncovered;
+        let records = self.records.map(|(kinds, out)| RecordTracker::new(&kinds, uncovered, out));
+        let prime_powers = self.prime_powers;
+        let writer = thread::spawn(move || {
+            write_batches(receiver, sink, (&written_bytes, &written_batches), aggregates, records, prime_powers)
+        });
+
+        let storage = Mutex::new(Pending::new(sender, self.order, self.memory_limit, buffered, queued));
+        let filter = self.filter;
+        let workers = Workers {
+            storage: &storage,
+            progress: &self.progress,
+            cross_check: self.cross_check,
+            filter: filter.as_deref(),
+            prime_powers,
+            throttle: self.throttle.as_deref(),
+        };
+        let (end, order, uncovered) = (self.end, self.order, self.uncovered);
+        let (algorithm, backend) = (self.algorithm, self.backend);
+        let base_primes = match algorithm {
+            _ if backend == Backend::Primesieve => Vec::new(),
+            Algorithm::SegmentedSieve if self.prime_cache => cached_small_primes(isqrt(end as u64)),
+            Algorithm::SegmentedSieve => sieving_primes(end as u64),
+            _ => Vec::new(),
+        };
+        // Function to generate `[from, to]` with the given settings
+        let pass = |from: u128, to: u128, settings: Tuning| -> Result<()> {
+            storage.lock().unwrap().start_pass(settings.flush_threshold);
+            let chunk_size = settings.chunk_size;
+            match (algorithm, order) {
+                (Algorithm::PerCandidate | Algorithm::Auto, RecordOrder::Arrival) => run_per_candidate(from, to, &workers),
+                (Algorithm::PerCandidate | Algorithm::Auto, RecordOrder::Sorted) => {
+                    run_per_candidate_in_order(from, to, chunk_size, &workers)
+                }
+                #[cfg(feature = "primesieve")]
+                (Algorithm::SegmentedSieve, _) if backend == Backend::Primesieve => {
+                    run_primesieve(from as u64, to as u64, chunk_size, order, &workers)
+                }
+                (Algorithm::SegmentedSieve, _) => {
+                    run_segmented_sieve(from as u64, to as u64, chunk_size, &base_primes, order, &workers)
+                }
+            }
+        };
+        let defaults = Tuning {
+            chunk_size: match algorithm {
+                Algorithm::SegmentedSieve => self.segment_size,
+                _ => PER_CANDIDATE_CHUNK,
+            },
+            flush_threshold: FLUSH_THRESHOLD,
+        };
+        // Unordered per-candidate testing hands out single numbers, so only the flush threshold matters there
+        let tunes_chunks = algorithm == Algorithm::SegmentedSieve || order == RecordOrder::Sorted;
+        let auto_tune = self.auto_tune;
+        // Only chunked work can shrink its chunks; early flushes apply either way
+        let guards_chunks = self.memory_limit.is_some() && tunes_chunks;
+        let generated = pool.install(|| -> Result<RunSummary> {
+            let threads = rayon::current_num_threads() as u64;
+            let (mut settings, mut tuning, mut shrunk_chunk_size) = (defaults, None, None);
+            for &(start, end) in &uncovered {
+                let mut from = Some(start);
+                // The warmup runs once, on the first part of the range
+                if auto_tune && tuning.is_none() {
+                    let tuned = warm_up(start, end, defaults, tunes_chunks, threads, &pass)?;
+                    (from, settings, tuning) = (tuned.0, tuned.1, Some(tuned.1));
+                }
+                match from {
+                    Some(from) if guards_chunks => {
+                        let pressure = || storage.lock().unwrap().take_pressure();
+                        if let Some(chunk_size) = guard_memory(from, end, settings, threads, &pass, &pressure)? {
+                            settings.chunk_size = chunk_size;
+                            shrunk_chunk_size = Some(chunk_size);
+                        }
+                    }
+                    Some(from) => pass(from, end, settings)?,
+                    None => {}
+                }
+            }
+            Ok(RunSummary { tuning, shrunk_chunk_size, ..RunSummary::default() })
+        });
+
+        // Flush any remaining data, then close the channel so the writer finishes
+        let mut pending = storage.into_inner().unwrap();
+        let generated = generated.and_then(|summary| {
+            if !pending.batch.is_empty() {
+                pending.flush()?;
+            }
+            Ok(RunSummary {
+                memory_flushes: pending.memory_flushes,
+                writer_stalls: pending.stalls,
+                writer_stalled: pending.stalled,
+                max_queued_batches: pending.max_queued,
+                ..summary
+            })
+        });
+        drop(pending);
+        let written = writer.join().expect("Writer thread panicked");
+        // A write error also makes the workers fail, so report it first
+        let (aggregates, records) = written?;
+        generated.map(|summary| RunSummary {
+            aggregates: aggregates.map(|aggregates| aggregates.report()),
+            records: records.map(|records| records.records().to_vec()).unwrap_or_default(),
+            ..summary
+        })
+    }
+}
+
+// Function to time candidate settings on consecutive windows at the start of
+// the range and keep the fastest: chunk sizes first, then flush thresholds
+// with the chosen chunk size. Returns where the rest of the range starts
+// (None if the warmup covered all of it) and the chosen settings.
+fn warm_up<F>(start: u128, end: u128, defaults: Tuning, tunes_chunks: bool, threads: u64, pass: &F) -> Result<(Option<u128>, Tuning)>
+where
+    F: Fn(u128, u128, Tuning) -> Result<()>,
+{
+    let chunk_factors: &[(u64, u64)] = if tunes_chunks { &WARMUP_CHUNK_FACTORS } else { &[] };
+
+    let mut from = Some(start);
+    let mut best = (defaults, 0.0);
+    // Function to run one trial window, returning false once the range is used up
+    let mut trial = |settings: Tuning, best: &mut (Tuning, f64)| -> Result<bool> {
+        let Some(trial_start) = from else {
+            return Ok(false);
+        };
+        // Saturate so huge tuned chunks just take the rest of the range
+        let numbers = settings.chunk_size.saturating_mul(WARMUP_CHUNKS_PER_THREAD).saturating_mul(threads).max(1) as u128;
+        let trial_end = trial_start.saturating_add(numbers - 1).min(end);
+        let timer = Instant::now();
+        pass(trial_start, trial_end, settings)?;
+        let rate = (trial_end - trial_start + 1) as f64 / timer.elapsed().as_secs_f64();
+        if rate > best.1 {
+            *best = (settings, rate);
+        }
+        from = trial_end.checked_add(1).filter(|&next| next <= end);
+        Ok(true)
+    };
+    for &(mul, div) in chunk_factors {
+        let settings = Tuning { chunk_size: (defaults.chunk_size.saturating_mul(mul) / div).max(1), ..defaults };
+        if !trial(settings, &mut best)? {
+            break;
+        }
+    }
+    // Rates from different chunk sizes aren't comparable with the flush trials
+    let chunk_size = best.0.chunk_size;
+    best.1 = 0.0;
+    for (mul, div) in WARMUP_FLUSH_FACTORS {
+        let settings = Tuning { chunk_size, flush_threshold: defaults.flush_threshold.saturating_mul(mul) / div };
+        if !trial(settings, &mut best)? {
+            break;
+        }
+    }
+    Ok((from, best.0))
+}
+
+// Function to generate `[from, end]` in windows of a few chunks per thread,
+// halving the chunk size after every window in which buffered records neared
+// the memory limit. Returns the chunk size it shrank to, if it did.
+fn guard_memory<F, P>(from: u128, end: u128, mut settings: Tuning, threads: u64, pass: &F, pressure: &P) -> Result<Option<u64>>
+where
+    F: Fn(u128, u128, Tuning) -> Result<()>,
+    P: Fn() -> bool,
+{
+    let mut shrunk = None;
+    let mut from = Some(from);
+    while let Some(window_start) = from {
+        let numbers = settings.chunk_size.saturating_mul(GUARDED_CHUNKS_PER_THREAD).saturating_mul(threads).max(1) as u128;
+        let window_end = window_start.saturating_add(numbers - 1).min(end);
+        pass(window_start, window_end, settings)?;
+        if pressure() && settings.chunk_size > MIN_GUARDED_CHUNK {
+            settings.chunk_size = (settings.chunk_size / 2).max(MIN_GUARDED_CHUNK);
+            shrunk = Some(settings.chunk_size);
+        }
+        from = window_end.checked_add(1).filter(|&next| next <= end);
+    }
+    Ok(shrunk)
+}
+
+// Function to convert batches into records and write them to the sink, on the
+// writer thread, adding the primes (but not prime powers) to the aggregates
+// and record tracker if any are kept, and counting down the bytes and batches
+// held for it
+fn write_batches(
+    batches: Receiver<PrimeBatch>,
+    mut sink: Box<dyn Sink>,
+    (buffered, queued): (&AtomicUsize, &AtomicUsize),
+    mut aggregates: Option<Aggregates>,
+    mut records: Option<RecordTracker<RecordOutput>>,
+    prime_powers: bool,
+) -> Result<(Option<Aggregates>, Option<RecordTracker<RecordOutput>>)> {
+    for batch in batches {
+        let _span = Span::start("flush", &[("records", batch.len() as u128)]);
+        // Whatever of the write the sink didn't spend on I/O went into formatting
+        let (started, io_before) = (Instant::now(), timing::elapsed(Phase::Io));
+        sink.write_powers(&batch)?;
+        let io = timing::elapsed(Phase::Io).saturating_sub(io_before);
+        timing::add(Phase::Serialization, started.elapsed().saturating_sub(io));
+        if let Some(aggregates) = &mut aggregates {
+            batch
+                .iter()
+                .filter(|(n, _)| !prime_powers || perfect_power(*n).1 == 1)
+                .for_each(|(prime, _)| aggregates.add(*prime));
+        }
+        if let Some(records) = &mut records {
+            for (prime, _) in batch.iter().filter(|(n, _)| !prime_powers || perfect_power(*n).1 == 1) {
+                records.add(*prime)?;
+            }
+            // Records show up in the file along with the batch that set them
+            records.flush()?;
+        }
+        record_primes(batch.len());
+        buffered.fetch_sub(batch_bytes(&batch), Ordering::SeqCst);
+        queued.fetch_sub(1, Ordering::SeqCst);
+    }
+    sink.finish()?;
+    Ok((aggregates, records))
+}
+
+// Function to estimate the memory one buffered record takes: the tuple, the
+// vector of powers and the limbs of n^2, n^3 and n^4
+fn record_bytes(prime: u128) -> usize {
+    let bits = (u128::BITS - prime.leading_zeros()) as usize;
+    let limbs: usize = [2, 3, 4].iter().map(|power| (power * bits).div_ceil(64)).sum();
+    mem::size_of::<(u128, Vec<Integer>)>() + 3 * mem::size_of::<Integer>() + limbs * 8
+}
+
+// Function to estimate the memory a batch of records takes
+fn batch_bytes(batch: &PrimeBatch) -> usize {
+    batch.iter().map(|(prime, _)| record_bytes(*prime)).sum()
+}
+
+// Function to test each odd candidate of the range in parallel
+fn run_per_candidate(start: u128, end: u128, workers: &Workers) -> Result<()> {
+    let mut prime_powers = PrimeBatch::new();
+    workers.add_prime_powers(start, end, &mut prime_powers);
+    for (power, powers) in prime_powers {
+        workers.storage.lock().unwrap().push_record(power, powers)?;
+    }
+    (start..=end)
+        .into_par_iter()
+        .filter_map(|n| {
+            let _timer = timing::time(Phase::Candidates);
+            let big_n = BigInt::from(n);
+            if big_n.clone() % 2.to_bigint().unwrap() == 1.to_bigint().unwrap() || big_n == 2.to_bigint().unwrap() {
+                Some(big_n)
+            } else {
+                None
+            }
+        })
+        .try_for_each(|big_n| {
+            workers.wait_if_paused();
+            let n = big_n.to_u128().expect("Number should fit in u128");
+            let found_prime = {
+                let _timer = timing::time(Phase::Primality);
+                is_prime_bigint(&big_n)
+            };
+            workers.confirm(n, found_prime, "Miller-Rabin", baillie_psw, "Baillie-PSW")?;
+            if found_prime && workers.keeps(n) {
+                for (prime, powers) in workers.with_powers([n]) {
+                    workers.storage.lock().unwrap().push_record(prime, powers)?;
+                }
+            }
+            // Update progress
+            workers.progress.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+}
+
+// Function to test the range in chunks handed out in increasing order, so the
+// sink receives primes sorted
+fn run_per_candidate_in_order(
+    start: u128,
+    end: u128,
+    chunk_size: u64,
+    workers: &Workers,
+) -> Result<()> {
+    let chunk_size = chunk_size as u128;
+    let chunks = (end - start) / chunk_size + 1;
+    for_each_chunk(chunks, RecordOrder::Sorted, |chunk| {
+        let chunk_start = start + chunk * chunk_size;
+        let chunk_end = chunk_start.saturating_add(chunk_size - 1).min(end);
+        workers.wait_if_paused();
+        let mut primes = Vec::new();
+        for n in (chunk_start..=chunk_end).filter(|n| n % 2 == 1 || *n == 2) {
+            let found_prime = {
+                let _timer = timing::time(Phase::Primality);
+                is_prime_bigint(&BigInt::from(n))
+            };
+            workers.confirm(n, found_prime, "Miller-Rabin", baillie_psw, "Baillie-PSW")?;
+            if found_prime && workers.keeps(n) {
+                primes.push(n);
+            }
+            // Update progress
+            workers.progress.fetch_add(1, Ordering::SeqCst);
+        }
+        let mut found = workers.with_powers(primes);
+        workers.add_prime_powers(chunk_start, chunk_end, &mut found);
+        workers.storage.lock().unwrap().push_chunk(chunk, found)
+    })
+}
+
+// Function to sieve the range in parallel segments. Each task takes a stripe
+// of consecutive segments once the sieving primes outgrow a segment, so the
+// bucket sieve's setup is shared across the stripe.
+fn run_segmented_sieve(
+    start: u64,
+    end: u64,
+    segment_size: u64,
+    base_primes: &[u32],
+    order: RecordOrder,
+    workers: &Workers,
+) -> Result<()> {
+    let segments = (end - start) / segment_size + 1;
+    let stripe = (isqrt(end) / segment_size).clamp(1, MAX_STRIPE_SEGMENTS);
+    for_each_chunk(segments.div_ceil(stripe) as u128, order, |chunk| {
+        let first_segment = chunk as u64 * stripe;
+        let stripe_start = start + first_segment * segment_size;
+        let stripe_end = stripe_start.saturating_add(stripe.saturating_mul(segment_size) - 1).min(end);
+        let mut sieve = {
+            let _timer = timing::time(Phase::Primality);
+            BucketSieve::new(stripe_start, stripe_end, segment_size, base_primes)
+        };
+        for segment in first_segment.. {
+            workers.wait_if_paused();
+            let sieved = {
+                let _timer = timing::time(Phase::Primality);
+                sieve.next()
+            };
+            let Some((segment_start, flags)) = sieved else {
+                break;
+            };
+            let _span = Span::start("segment", &[("start", segment_start as u128), ("numbers", flags.len() as u128)]);
+            if workers.cross_check {
+                for (offset, &found_prime) in flags.iter().enumerate() {
+                    let n = segment_start as u128 + offset as u128;
+                    workers.confirm(n, found_prime, "the segmented sieve", is_prime, "Miller-Rabin")?;
+                }
+            }
+            let primes: Vec<u128> = {
+                let _timer = timing::time(Phase::Candidates);
+                flags
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, is_prime)| **is_prime)
+                    .map(|(offset, _)| segment_start as u128 + offset as u128)
+                    .filter(|&prime| workers.keeps(prime))
+                    .collect()
+            };
+            let mut found = workers.with_powers(primes);
+            let segment_end = segment_start as u128 + flags.len() as u128 - 1;
+            workers.add_prime_powers(segment_start as u128, segment_end, &mut found);
+
+            workers.storage.lock().unwrap().push_chunk(segment as u128, found)?;
+            // Update progress
+            workers.progress.fetch_add(flags.len(), Ordering::SeqCst);
+            record_segment();
+        }
+        Ok(())
+    })
+}
+
+// Function to enumerate the range in parallel segments with primesieve
+#[cfg(feature = "primesieve")]
+fn run_primesieve(
+    start: u64,
+    end: u64,
+    segment_size: u64,
+    order: RecordOrder,
+    workers: &Workers,
+) -> Result<()> {
+    let segments = (end - start) / segment_size + 1;
+    for_each_chunk(segments as u128, order, |chunk| {
+        let segment_start = start + chunk as u64 * segment_size;
+        let segment_end = segment_start.saturating_add(segment_size - 1).min(end);
+        workers.wait_if_paused();
+        let _span = Span::start("segment", &[("start", segment_start as u128), ("numbers", (segment_end - segment_start + 1) as u128)]);
+        let primes = {
+            let _timer = timing::time(Phase::Primality);
+            crate::primesieve::primes_in_range(segment_start, segment_end)?
+        };
+        if workers.cross_check {
+            let mut primes = primes.iter().peekable();
+            for n in segment_start..=segment_end {
+                let found_prime = primes.next_if_eq(&&n).is_some();
+                workers.confirm(n as u128, found_prime, "primesieve", is_prime, "Miller-Rabin")?;
+            }
+        }
+        let primes: Vec<u128> = {
+            let _timer = timing::time(Phase::Candidates);
+            primes.into_iter().map(u128::from).filter(|&prime| workers.keeps(prime)).collect()
+        };
+        let mut found = workers.with_powers(primes);
+        workers.add_prime_powers(segment_start as u128, segment_end as u128, &mut found);
+
+        workers.storage.lock().unwrap().push_chunk(chunk, found)?;
+        // Update progress
+        workers.progress.fetch_add((segment_end - segment_start + 1) as usize, Ordering::SeqCst);
+        record_segment();
+        Ok(())
+    })
+}
+
+// Function to run `work` for every chunk index on the current pool. In sorted
+// mode chunks are handed out in increasing order, so a finished chunk only
+// waits on the few still in flight rather than half the range.
+fn for_each_chunk<F>(chunks: u128, order: RecordOrder, work: F) -> Result<()>
+where
+    F: Fn(u128) -> Result<()> + Sync + Send,
+{
+    match order {
+        RecordOrder::Arrival => (0..chunks).into_par_iter().try_for_each(work),
+        RecordOrder::Sorted => (0..chunks).par_bridge().try_for_each(work),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn warm_up_saturates_huge_chunks() {
+        let defaults = Tuning { chunk_size: u64::MAX, flush_threshold: usize::MAX };
+        let windows = RefCell::new(Vec::new());
+        let pass = |from: u128, to: u128, _: Tuning| {
+            windows.borrow_mut().push((from, to));
+            Ok(())
+        };
+        let (from, tuning) = warm_up(10, 1000, defaults, true, u64::MAX, &pass).unwrap();
+        // The first trial swallows the whole range
+        assert_eq!(*windows.borrow(), [(10, 1000)]);
+        assert_eq!(from, None);
+        assert!(tuning.chunk_size > 0);
+    }
+
+    #[test]
+    fn warm_up_covers_the_range_without_overlap() {
+        let defaults = Tuning { chunk_size: 8, flush_threshold: 4 };
+        let windows = RefCell::new(Vec::new());
+        let pass = |from: u128, to: u128, _: Tuning| {
+            windows.borrow_mut().push((from, to));
+            Ok(())
+        };
+        let start = u128::MAX - 10_000;
+        let (from, _) = warm_up(start, u128::MAX, defaults, true, 2, &pass).unwrap();
+        let windows = windows.into_inner();
+        assert_eq!(windows.len(), WARMUP_CHUNK_FACTORS.len() + WARMUP_FLUSH_FACTORS.len());
+        assert_eq!(windows[0].0, start);
+        assert!(windows.windows(2).all(|w| w[1].0 == w[0].1 + 1));
+        assert_eq!(from, windows.last().map(|w| w.1 + 1));
+    }
+
+    // Sink collecting the primes it receives
+    struct Collect(Arc<Mutex<Vec<u128>>>);
+
+    impl Sink for Collect {
+        fn write_batch(&mut self, records: Vec<crate::record::PrimeRecord>) -> Result<()> {
+            self.0.lock().unwrap().extend(records.iter().map(|record| record.prime));
+            Ok(())
+        }
+    }
+
+    // Sink taking its time over every batch, like a slow disk
+    struct Slow(Arc<Mutex<Vec<u128>>>);
+
+    impl Sink for Slow {
+        fn write_batch(&mut self, records: Vec<crate::record::PrimeRecord>) -> Result<()> {
+            thread::sleep(Duration::from_millis(50));
+            self.0.lock().unwrap().extend(records.iter().map(|record| record.prime));
+            Ok(())
+        }
+    }
+
+    // Sink noting the thread of every call, failing after `fail_after` batches
+    struct Threads {
+        seen: Arc<Mutex<Vec<(thread::ThreadId, bool)>>>,
+        fail_after: usize,
+    }
+
+    impl Sink for Threads {
+        fn write_batch(&mut self, _: Vec<crate::record::PrimeRecord>) -> Result<()> {
+            let mut seen = self.seen.lock().unwrap();
+            seen.push((thread::current().id(), rayon::current_thread_index().is_some()));
+            match seen.len() > self.fail_after {
+                true => Err(Error::new(ErrorKind::WriteZero, "Disk full")),
+                false => Ok(()),
+            }
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            self.seen.lock().unwrap().push((thread::current().id(), rayon::current_thread_index().is_some()));
+            Ok(())
+        }
+    }
+
+    // Writer appending to a buffer the test keeps a handle on
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn skips_excluded_ranges() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let generator = PrimeGenerator::builder()
+            .range(2, 10_000)
+            .exclude([(100, 1999), (1500, 3000)])
+            .exclude([(9000, 20_000)])
+            .threads(2)
+            .ordering(RecordOrder::Sorted)
+            .numa(NumaPolicy::Off)
+            .sink(Collect(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        assert_eq!(generator.uncovered_ranges(), [(2, 99), (3001, 8999)]);
+        assert_eq!(generator.total_numbers(), 98 + 5999);
+        generator.run().unwrap();
+        let expected: Vec<u128> = crate::sieve::primes_in_range(2, 8999)
+            .into_iter()
+            .map(u128::from)
+            .filter(|&p| !(100..=3000).contains(&p))
+            .collect();
+        assert_eq!(*primes.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_the_largest_primes() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let generator = PrimeGenerator::builder()
+            .range(u128::MAX - 400, u128::MAX)
+            .threads(2)
+            .ordering(RecordOrder::Sorted)
+            .numa(NumaPolicy::Off)
+            .sink(Collect(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        generator.run().unwrap();
+        let expected: Vec<u128> = (u128::MAX - 400..=u128::MAX).filter(|&n| crate::is_prime(n)).collect();
+        assert!(expected.contains(&(u128::MAX - 158)));
+        assert_eq!(*primes.lock().unwrap(), expected);
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn leaves_an_empty_csv_without_primes() {
+        let path = std::env::temp_dir().join(format!("prime_generator_empty_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let generator = PrimeGenerator::builder()
+            .range(24, 28)
+            .threads(1)
+            .numa(NumaPolicy::Off)
+            .sink(crate::CsvSink::new(&path))
+            .build()
+            .unwrap();
+        generator.run().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generates_several_ranges_once() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let generator = PrimeGenerator::builder()
+            .ranges([(5000, 6000), (2, 100), (50, 200), (201, 300)])
+            .threads(2)
+            .ordering(RecordOrder::Sorted)
+            .numa(NumaPolicy::Off)
+            .sink(Collect(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        assert_eq!(generator.uncovered_ranges(), [(2, 300), (5000, 6000)]);
+        generator.run().unwrap();
+        let expected: Vec<u128> = [(2, 300), (5000, 6000)]
+            .into_iter()
+            .flat_map(|(from, to)| crate::sieve::primes_in_range(from, to))
+            .map(u128::from)
+            .collect();
+        assert_eq!(*primes.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn runs_on_the_cpu_list() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let generator = PrimeGenerator::builder()
+            .range(2, 10_000)
+            .ordering(RecordOrder::Sorted)
+            .cpu_list(vec![0])
+            .sink(Collect(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        assert_eq!(generator.threads, Some(1));
+        generator.run().unwrap();
+        let expected: Vec<u128> = crate::sieve::primes_in_range(2, 10_000).into_iter().map(u128::from).collect();
+        assert_eq!(*primes.lock().unwrap(), expected);
+
+        let empty = PrimeGenerator::builder().range(2, 100).cpu_list(Vec::new()).sink(Collect(Arc::clone(&primes))).build();
+        assert_eq!(empty.err().unwrap().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn waits_while_throttled() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let throttle = Arc::new(Throttle::new());
+        throttle.pause();
+        let generator = PrimeGenerator::builder()
+            .range(2, 10_000)
+            .threads(2)
+            .numa(NumaPolicy::Off)
+            .throttle(Arc::clone(&throttle))
+            .sink(Collect(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        let progress = generator.progress();
+        let run = thread::spawn(move || generator.run());
+        thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(progress.load(Ordering::SeqCst), 0);
+        throttle.resume();
+        run.join().unwrap().unwrap();
+        assert_eq!(primes.lock().unwrap().len(), 1229);
+    }
+
+    #[test]
+    fn keeps_only_filtered_primes() {
+        let twin = crate::constellation::Constellation::Twin;
+        let expected: Vec<u128> = crate::sieve::primes_in_range(2, 20_000).into_iter().map(u128::from).filter(|&p| twin.contains(p)).collect();
+        for algorithm in [Algorithm::SegmentedSieve, Algorithm::PerCandidate] {
+            let primes = Arc::new(Mutex::new(Vec::new()));
+            PrimeGenerator::builder()
+                .range(2, 20_000)
+                .threads(2)
+                .algorithm(algorithm)
+                .ordering(RecordOrder::Sorted)
+                .numa(NumaPolicy::Off)
+                .filter(move |p| twin.contains(p))
+                .sink(Collect(Arc::clone(&primes)))
+                .build()
+                .unwrap()
+                .run()
+                .unwrap();
+            assert_eq!(*primes.lock().unwrap(), expected, "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn includes_prime_powers() {
+        let expected: Vec<u128> = (1000..=100_000).filter(|&n| is_prime(perfect_power(n).0)).collect();
+        // 2^10 through 2^16 and 3^7 through 3^10 are among them
+        assert!(expected.contains(&1024) && expected.contains(&65_536) && expected.contains(&59_049));
+        for (algorithm, order) in [
+            (Algorithm::SegmentedSieve, RecordOrder::Sorted),
+            (Algorithm::PerCandidate, RecordOrder::Sorted),
+            (Algorithm::PerCandidate, RecordOrder::Arrival),
+        ] {
+            let primes = Arc::new(Mutex::new(Vec::new()));
+            let summary = PrimeGenerator::builder()
+                .range(1000, 100_000)
+                .threads(2)
+                .algorithm(algorithm)
+                .ordering(order)
+                .segment_size(4096)
+                .numa(NumaPolicy::Off)
+                .include_prime_powers(true)
+                .aggregate([Aggregate::Count])
+                .sink(Collect(Arc::clone(&primes)))
+                .build()
+                .unwrap()
+                .run()
+                .unwrap();
+            let mut found = primes.lock().unwrap().clone();
+            if order == RecordOrder::Arrival {
+                found.sort_unstable();
+            }
+            assert_eq!(found, expected, "{}", algorithm);
+            // The aggregates only count the primes
+            assert_eq!(summary.aggregates.unwrap().count, Some(9_592 - 168));
+        }
+    }
+
+    #[test]
+    fn aggregates_the_written_primes() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let builder = || {
+            PrimeGenerator::builder()
+                .range(2, 100_000)
+                .exclude([(31_000, 32_000)])
+                .threads(2)
+                .numa(NumaPolicy::Off)
+                .aggregate([Aggregate::Count, Aggregate::MaxGap])
+                .sink(Collect(Arc::clone(&primes)))
+        };
+        assert_eq!(builder().build().err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+        let summary = builder().ordering(RecordOrder::Sorted).build().unwrap().run().unwrap();
+        let aggregates = summary.aggregates.unwrap();
+        assert_eq!(aggregates.count, Some(primes.lock().unwrap().len() as u64));
+        // The largest gap below 10^5, 72 after 31397, is excluded, so is the hole around it
+        let gap = aggregates.max_gap.unwrap();
+        assert_eq!((gap.from, gap.to), (89_689, 89_753));
+    }
+
+    #[test]
+    fn tracks_records_of_the_written_primes() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let builder = || {
+            PrimeGenerator::builder()
+                .range(2, 100_000)
+                .threads(2)
+                .numa(NumaPolicy::Off)
+                .track_records([RecordKind::Palindromic, RecordKind::Gap], Shared(Arc::clone(&out)))
+                .sink(Collect(Arc::new(Mutex::new(Vec::new()))))
+        };
+        assert_eq!(builder().build().err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+        let summary = builder().ordering(RecordOrder::Sorted).build().unwrap().run().unwrap();
+        assert_eq!(
+            summary.records,
+            [
+                RecordFind { kind: RecordKind::Palindromic, prime: 98_689, value: 5 },
+                RecordFind { kind: RecordKind::Gap, prime: 31_397, value: 72 },
+            ]
+        );
+        let out = String::from_utf8(out.lock().unwrap().clone()).unwrap();
+        assert!(out.starts_with("palindromic,2,1\n") && out.ends_with("palindromic,98689,5\n"), "{}", out);
+        assert!(out.contains("\ngap,31397,72\n"));
+    }
+
+    #[test]
+    fn guard_memory_shrinks_chunks_under_pressure() {
+        let defaults = Tuning { chunk_size: MIN_GUARDED_CHUNK * 4, flush_threshold: FLUSH_THRESHOLD };
+        let windows = RefCell::new(Vec::new());
+        let pass = |from: u128, to: u128, settings: Tuning| {
+            windows.borrow_mut().push((from, to, settings.chunk_size));
+            Ok(())
+        };
+        // Pressure after every window: halve down to the floor, then stay there
+        let shrunk = guard_memory(0, 1_000_000, defaults, 1, &pass, &|| true).unwrap();
+        assert_eq!(shrunk, Some(MIN_GUARDED_CHUNK));
+        let windows = windows.into_inner();
+        let chunk_sizes: Vec<u64> = windows.iter().map(|w| w.2).take(4).collect();
+        assert_eq!(chunk_sizes, [MIN_GUARDED_CHUNK * 4, MIN_GUARDED_CHUNK * 2, MIN_GUARDED_CHUNK, MIN_GUARDED_CHUNK]);
+        assert_eq!(windows[0].0, 0);
+        assert!(windows.windows(2).all(|w| w[1].0 == w[0].1 + 1));
+        assert_eq!(windows.last().unwrap().1, 1_000_000);
+
+        let pass = |_: u128, _: u128, settings: Tuning| {
+            assert_eq!(settings.chunk_size, defaults.chunk_size);
+            Ok(())
+        };
+        assert_eq!(guard_memory(0, 1_000_000, defaults, 1, &pass, &|| false).unwrap(), None);
+    }
+
+    #[test]
+    fn checks_the_primesieve_backend() {
+        for backend in [Backend::Native, Backend::Primesieve] {
+            assert_eq!(backend.to_string().parse(), Ok(backend));
+        }
+        assert!("gmp".parse::<Backend>().is_err());
+
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let builder = |algorithm| {
+            PrimeGenerator::builder()
+                .range(2, 100_000)
+                .algorithm(algorithm)
+                .backend(Backend::Primesieve)
+                .ordering(RecordOrder::Sorted)
+                .numa(NumaPolicy::Off)
+                .sink(Collect(Arc::clone(&primes)))
+        };
+        let error = |algorithm| builder(algorithm).build().err().map(|e| (e.kind(), e.to_string()));
+        #[cfg(not(feature = "primesieve"))]
+        assert_eq!(
+            error(Algorithm::SegmentedSieve),
+            Some((ErrorKind::InvalidInput, "Built without the primesieve feature".to_string()))
+        );
+        #[cfg(feature = "primesieve")]
+        {
+            assert_eq!(
+                error(Algorithm::PerCandidate),
+                Some((ErrorKind::InvalidInput, "The primesieve backend needs the segmented sieve, below 2^64".to_string()))
+            );
+            builder(Algorithm::SegmentedSieve).build().unwrap().run().unwrap();
+            let expected: Vec<u128> = crate::sieve::primes_in_range(2, 100_000).into_iter().map(u128::from).collect();
+            assert_eq!(*primes.lock().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn writes_on_a_dedicated_thread() {
+        let run = |fail_after| {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let generator = PrimeGenerator::builder()
+                .range(2, 2_000_000)
+                .threads(2)
+                .algorithm(Algorithm::SegmentedSieve)
+                .segment_size(1 << 14)
+                .numa(NumaPolicy::Off)
+                .sink(Threads { seen: Arc::clone(&seen), fail_after })
+                .build()
+                .unwrap();
+            let result = generator.run();
+            let seen = seen.lock().unwrap().clone();
+            (result, seen)
+        };
+
+        // Every batch and the finish go through one thread that is neither
+        // the caller nor a worker of the pool
+        let (result, seen) = run(usize::MAX);
+        result.unwrap();
+        assert!(seen.len() > 2);
+        assert!(seen.iter().all(|&(id, worker)| id == seen[0].0 && !worker));
+        assert_ne!(seen[0].0, thread::current().id());
+
+        // A failed write stops the run with the sink's own error, not the
+        // workers' complaint about the closed channel
+        let (result, seen) = run(2);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn blocks_workers_on_a_slow_writer() {
+        let primes = Arc::new(Mutex::new(Vec::new()));
+        let generator = PrimeGenerator::builder()
+            .range(2, 2_000_000)
+            .threads(2)
+            .algorithm(Algorithm::SegmentedSieve)
+            .segment_size(1 << 14)
+            .numa(NumaPolicy::Off)
+            .queue_depth(1)
+            .sink(Slow(Arc::clone(&primes)))
+            .build()
+            .unwrap();
+        let queued = generator.queued_batches();
+        let summary = generator.run().unwrap();
+        // 148933 primes make 15 batches, far more than the writer keeps up with
+        assert!(summary.writer_stalls > 0 && !summary.writer_stalled.is_zero());
+        assert!((1..=3).contains(&summary.max_queued_batches), "{}", summary.max_queued_batches);
+        assert_eq!(queued.load(Ordering::SeqCst), 0);
+        assert_eq!(primes.lock().unwrap().len(), 148_933);
+        assert!(PrimeGenerator::builder().range(2, 10).queue_depth(0).sink(Slow(primes)).build().is_err());
+    }
+
+    #[test]
+    fn flushes_early_near_the_memory_limit() {
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let limit = record_bytes(101) * 8;
+        let mut pending = Pending::new(sender, RecordOrder::Sorted, Some(limit), Arc::clone(&buffered), Arc::new(AtomicUsize::new(0)));
+        // Out-of-order chunks wait without flushing, but still count
+        for chunk in 1..=6 {
+            let prime = 100 + chunk;
+            pending.push_chunk(chunk, vec![(prime, powers(prime).to_vec())]).unwrap();
+        }
+        assert!(pending.take_pressure());
+        assert!(receiver.try_recv().is_err());
+        // Once chunk 0 arrives everything is released in one early flush
+        pending.push_chunk(0, vec![(100, powers(100).to_vec())]).unwrap();
+        let batch = receiver.try_recv().unwrap();
+        assert_eq!(batch.len(), 7);
+        assert_eq!(pending.memory_flushes, 1);
+        // Flushed records count until the writer is done with them
+        assert_eq!(buffered.load(Ordering::SeqCst), batch_bytes(&batch));
+    }
+}
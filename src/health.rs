@@ -0,0 +1,228 @@
+// Liveness and readiness probes over HTTP for long runs in containers. An
+// orchestrator such as Kubernetes polls `/healthz` to restart instances whose
+// workers stopped making progress, and `/readyz` to tell when a run is going
+// and its writer keeps up.
+
+use std::io::{BufRead, BufReader, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// What the probes report on: the run's progress and writer backlog counters,
+/// as handed out by the generator.
+pub struct HealthCheck {
+    progress: Arc<AtomicUsize>,
+    backlog: Arc<AtomicUsize>,
+    total: usize,
+    stall_timeout: Duration,
+    max_backlog: Option<usize>,
+    queued_batches: Option<Arc<AtomicUsize>>,
+    ready: AtomicBool,
+    // The progress last seen and when it last changed
+    last_progress: Mutex<(usize, Instant)>,
+}
+
+/// The outcome of one probe: whether it passed, and a JSON body describing
+/// the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub ok: bool,
+    pub body: String,
+}
+
+impl HealthCheck {
+    /// Watches a run over `total` numbers. Workers count as stuck once
+    /// `progress` hasn't moved for `stall_timeout`; as they block on a full
+    /// writer queue, that also catches a stalled writer.
+    pub fn new(progress: Arc<AtomicUsize>, backlog: Arc<AtomicUsize>, total: usize, stall_timeout: Duration) -> HealthCheck {
+        HealthCheck {
+            progress,
+            backlog,
+            total,
+            stall_timeout,
+            max_backlog: None,
+            queued_batches: None,
+            ready: AtomicBool::new(false),
+            last_progress: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    /// Reports not ready while more than `bytes` of records wait for the writer.
+    pub fn max_backlog(mut self, bytes: usize) -> HealthCheck {
+        self.max_backlog = Some(bytes);
+        self
+    }
+
+    /// Reports the batches waiting for the writer, as counted by the
+    /// generator's `queued_batches`, in the probes' bodies.
+    pub fn queued_batches(mut self, queued: Arc<AtomicUsize>) -> HealthCheck {
+        self.queued_batches = Some(queued);
+        self
+    }
+
+    /// Marks the run as started, so `/readyz` can pass.
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    /// Answers `/healthz`: fails once progress stalls before the run finishes.
+    pub fn liveness(&self, now: Instant) -> Probe {
+        let stalled_for = self.stalled_for(now);
+        let ok = self.finished() || stalled_for < self.stall_timeout;
+        self.probe(ok, stalled_for)
+    }
+
+    /// Answers `/readyz`: passes once the run started, while the writer backlog
+    /// stays under the limit.
+    pub fn readiness(&self, now: Instant) -> Probe {
+        let ok = self.ready.load(Ordering::SeqCst)
+            && self.max_backlog.is_none_or(|limit| self.backlog.load(Ordering::SeqCst) <= limit);
+        self.probe(ok, self.stalled_for(now))
+    }
+
+    /// Answers probes on `listener` from a background thread until the process exits.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> JoinHandle<()> {
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A misbehaving client only costs its own request
+                let _ = self.respond(stream);
+            }
+        })
+    }
+
+    // Function to answer one HTTP request
+    fn respond(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Skip the headers, so closing the connection doesn't reset it
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+        let path = request_line.split_whitespace().nth(1).unwrap_or("");
+        let now = Instant::now();
+        let (status, body) = match path.split('?').next() {
+            Some("/healthz") => self.liveness(now).into_response(),
+            Some("/readyz") => self.readiness(now).into_response(),
+            _ => ("404 Not Found", "{\"status\":\"not found\"}".to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    // Function to tell whether every number was processed
+    fn finished(&self) -> bool {
+        self.progress.load(Ordering::SeqCst) >= self.total
+    }
+
+    // Function to measure how long progress has stood still, noting any change
+    fn stalled_for(&self, now: Instant) -> Duration {
+        let progress = self.progress.load(Ordering::SeqCst);
+        let mut last = self.last_progress.lock().unwrap();
+        if progress != last.0 {
+            *last = (progress, now);
+        }
+        now.saturating_duration_since(last.1)
+    }
+
+    // Function to describe the run in a probe's body
+    fn probe(&self, ok: bool, stalled_for: Duration) -> Probe {
+        let queued = match &self.queued_batches {
+            Some(queued) => format!(",\"queued_batches\":{}", queued.load(Ordering::SeqCst)),
+            None => String::new(),
+        };
+        let body = format!(
+            "{{\"status\":\"{}\",\"progress\":{},\"total\":{},\"backlog_bytes\":{}{},\"stalled_seconds\":{}}}",
+            if ok { "ok" } else { "failing" },
+            self.progress.load(Ordering::SeqCst),
+            self.total,
+            self.backlog.load(Ordering::SeqCst),
+            queued,
+            stalled_for.as_secs()
+        );
+        Probe { ok, body }
+    }
+}
+
+impl Probe {
+    // Function to pick the HTTP status for the probe's outcome
+    fn into_response(self) -> (&'static str, String) {
+        match self.ok {
+            true => ("200 OK", self.body),
+            false => ("503 Service Unavailable", self.body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn fails_liveness_once_progress_stalls() {
+        let progress = Arc::new(AtomicUsize::new(0));
+        let backlog = Arc::new(AtomicUsize::new(0));
+        let check = HealthCheck::new(Arc::clone(&progress), Arc::clone(&backlog), 100, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(check.liveness(start).ok);
+        assert!(!check.liveness(start + Duration::from_secs(61)).ok);
+        // Moving again counts from the time it was seen to move
+        progress.store(40, Ordering::SeqCst);
+        assert!(check.liveness(start + Duration::from_secs(62)).ok);
+        assert!(!check.liveness(start + Duration::from_secs(123)).ok);
+        // A finished run stays live while it writes its outputs
+        progress.store(100, Ordering::SeqCst);
+        assert!(check.liveness(start + Duration::from_secs(1000)).ok);
+        assert!(check.liveness(start + Duration::from_secs(2000)).body.contains("\"progress\":100"));
+    }
+
+    #[test]
+    fn reports_readiness_from_start_and_backlog() {
+        let backlog = Arc::new(AtomicUsize::new(0));
+        let queued = Arc::new(AtomicUsize::new(0));
+        let check = HealthCheck::new(Arc::new(AtomicUsize::new(0)), Arc::clone(&backlog), 100, Duration::from_secs(60))
+            .max_backlog(1000)
+            .queued_batches(Arc::clone(&queued));
+        let now = Instant::now();
+        assert!(!check.readiness(now).ok);
+        check.set_ready();
+        assert!(check.readiness(now).ok);
+        backlog.store(1001, Ordering::SeqCst);
+        queued.store(2, Ordering::SeqCst);
+        let probe = check.readiness(now);
+        assert!(!probe.ok);
+        assert!(probe.body.contains("\"backlog_bytes\":1001,\"queued_batches\":2,"), "{}", probe.body);
+    }
+
+    #[test]
+    fn serves_probes_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let check = Arc::new(HealthCheck::new(Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)), 100, Duration::from_secs(60)));
+        Arc::clone(&check).serve(listener);
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        assert!(get("/healthz").starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get("/readyz").starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        check.set_ready();
+        let response = get("/readyz?verbose");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\"stalled_seconds\":0}"), "{}", response);
+        assert!(get("/metrics").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
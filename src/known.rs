@@ -0,0 +1,274 @@
+// Reference prime datasets downloaded from a mirror and kept under the user
+// cache dir, used to validate the local engine against independently computed
+// primes.
+//
+// A mirror serves `index.json`, listing its datasets, next to the dataset
+// files themselves: plain text with one prime per line in increasing order
+// (lines starting with `#` are comments).
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api::{sha256_file, to_hex};
+use crate::sieve::for_each_prime;
+
+/// Mirror used when none is given.
+pub const DEFAULT_MIRROR: &str = "http://primegen.io/known";
+
+/// A dataset holding every prime in `[start, end]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownDataset {
+    /// File name on the mirror and in the local directory.
+    pub file: String,
+    pub start: u64,
+    pub end: u64,
+    /// Lowercase hex SHA-256 of the file.
+    pub sha256: String,
+}
+
+/// Returns the directory datasets are stored in by default, under the user
+/// cache dir (see [`cache_dir`](crate::paths::cache_dir)).
+pub fn default_known_dir() -> Option<PathBuf> {
+    Some(crate::paths::cache_dir()?.join("known"))
+}
+
+/// Downloads every dataset of the mirror's index that starts at or below
+/// `until` into `dir`, skipping files already there with the right checksum,
+/// and returns them. The datasets are recorded in `dir/index.json`.
+pub async fn fetch_known(client: &reqwest::Client, mirror: &str, until: u64, dir: &Path) -> Result<Vec<KnownDataset>, Box<dyn std::error::Error>> {
+    let mirror = mirror.trim_end_matches('/');
+    let index: Vec<KnownDataset> = client
+        .get(format!("{}/index.json", mirror))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    fs::create_dir_all(dir)?;
+
+    let mut fetched = Vec::new();
+    for dataset in index.into_iter().filter(|dataset| dataset.start <= until) {
+        let path = dataset_path(dir, &dataset)?;
+        if sha256_file(&path).ok().as_ref() != Some(&dataset.sha256) {
+            download(client, &format!("{}/{}", mirror, dataset.file), &path, &dataset.sha256).await?;
+        }
+        fetched.push(dataset);
+    }
+
+    // Keep datasets fetched earlier that this run didn't need
+    let mut local = read_index(dir).unwrap_or_default();
+    local.retain(|dataset| !fetched.iter().any(|new| new.file == dataset.file));
+    local.extend(fetched.iter().cloned());
+    local.sort_by_key(|dataset| dataset.start);
+    serde_json::to_writer_pretty(File::create(dir.join("index.json"))?, &local)?;
+    Ok(fetched)
+}
+
+/// Reads the datasets recorded in `dir/index.json` by [`fetch_known`].
+pub fn read_index(dir: &Path) -> io::Result<Vec<KnownDataset>> {
+    let file = File::open(dir.join("index.json"))?;
+    serde_json::from_reader(file).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Compares the dataset stored in `dir` with the primes the local engine
+/// finds in its range. Returns a description of the first difference, or
+/// `None` if they agree.
+pub fn verify_known(dir: &Path, dataset: &KnownDataset) -> io::Result<Option<String>> {
+    let mut known = BufReader::new(File::open(dataset_path(dir, dataset)?)?).lines();
+    // Function to read the next prime of the dataset
+    let mut next_known = || -> io::Result<Option<u64>> {
+        for line in known.by_ref() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return line
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, format!("{}: not a number: {}", dataset.file, line)));
+        }
+        Ok(None)
+    };
+
+    let mut difference = None;
+    let mut error = None;
+    for_each_prime(dataset.start, dataset.end, |prime| match next_known() {
+        Ok(Some(known)) if known == prime => true,
+        Ok(Some(known)) if known < prime => {
+            difference = Some(format!("{} lists {}, which the engine finds composite", dataset.file, known));
+            false
+        }
+        Ok(_) => {
+            difference = Some(format!("The engine finds {}, which {} doesn't list", prime, dataset.file));
+            false
+        }
+        Err(e) => {
+            error = Some(e);
+            false
+        }
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    if difference.is_none() {
+        if let Some(extra) = next_known()? {
+            difference = Some(format!("{} lists {}, which the engine finds composite or out of range", dataset.file, extra));
+        }
+    }
+    Ok(difference)
+}
+
+// Function to resolve a dataset's file in `dir`, refusing names from the
+// index that would point elsewhere
+fn dataset_path(dir: &Path, dataset: &KnownDataset) -> io::Result<PathBuf> {
+    let mut components = Path::new(&dataset.file).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if dataset.file != "index.json" => Ok(dir.join(&dataset.file)),
+        _ => Err(Error::new(ErrorKind::InvalidData, format!("Dataset name {:?} is not a plain file name", dataset.file))),
+    }
+}
+
+// Function to download `url` to `path`, checking its SHA-256 before the file
+// replaces any previous copy
+async fn download(client: &reqwest::Client, url: &str, path: &Path, sha256: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let mut file = File::create(&partial)?;
+    let mut hasher = Sha256::new();
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk)?;
+    }
+    file.flush()?;
+    let actual = to_hex(&hasher.finalize());
+    if actual != sha256 {
+        fs::remove_file(&partial)?;
+        return Err(format!("{} has SHA-256 {}, expected {}", url, actual, sha256).into());
+    }
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Function to serve `files` over HTTP on a local port, returning the base URL
+    async fn mirror(files: HashMap<String, Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let files = Arc::new(files);
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                let name = request.split_whitespace().nth(1).unwrap().trim_start_matches('/');
+                let response = match files.get(name) {
+                    Some(body) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(), body.clone()].concat(),
+                    None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                };
+                socket.write_all(&response).await.unwrap();
+            }
+        });
+        url
+    }
+
+    // Function to write the primes of a range in the dataset format
+    fn dataset_text(start: u64, end: u64) -> Vec<u8> {
+        let mut text = b"# primes\n".to_vec();
+        for prime in primes_in_range(start, end) {
+            text.extend_from_slice(format!("{}\n", prime).as_bytes());
+        }
+        text
+    }
+
+    fn dataset(file: &str, start: u64, end: u64, text: &[u8]) -> KnownDataset {
+        KnownDataset { file: file.to_string(), start, end, sha256: to_hex(&Sha256::digest(text)) }
+    }
+
+    #[tokio::test]
+    async fn fetches_and_verifies_datasets() {
+        let dir = std::env::temp_dir().join(format!("prime_generator_known_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (low, high) = (dataset_text(0, 100_000), dataset_text(100_001, 200_000));
+        let index = vec![dataset("low.txt", 0, 100_000, &low), dataset("high.txt", 100_001, 200_000, &high)];
+        let files = HashMap::from([
+            ("index.json".to_string(), serde_json::to_vec(&index).unwrap()),
+            ("low.txt".to_string(), low),
+            ("high.txt".to_string(), high),
+        ]);
+        let url = mirror(files).await;
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+
+        let fetched = fetch_known(&client, &url, 50_000, &dir).await.unwrap();
+        assert_eq!(fetched, index[..1]);
+        let fetched = fetch_known(&client, &url, 150_000, &dir).await.unwrap();
+        assert_eq!(fetched, index);
+        assert_eq!(read_index(&dir).unwrap(), index);
+        for dataset in &index {
+            assert_eq!(verify_known(&dir, dataset).unwrap(), None);
+        }
+
+        // Drop a prime and slip in a composite
+        let tampered = String::from_utf8(dataset_text(0, 100_000)).unwrap().replace("\n97\n", "\n").replace("\n101\n", "\n100\n101\n");
+        fs::write(dir.join("low.txt"), &tampered).unwrap();
+        assert!(verify_known(&dir, &index[0]).unwrap().unwrap().contains("97"));
+        fs::write(dir.join("low.txt"), tampered.replace("\n89\n", "\n89\n97\n")).unwrap();
+        assert!(verify_known(&dir, &index[0]).unwrap().unwrap().contains("100"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verifies_local_datasets() {
+        let dir = std::env::temp_dir().join(format!("prime_generator_known_local_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let check = |text: &str| {
+            fs::write(dir.join("small.txt"), text).unwrap();
+            verify_known(&dir, &dataset("small.txt", 10, 30, text.as_bytes()))
+        };
+
+        assert_eq!(check("# 10 to 30\n11\n13\n\n17\n19\n# twin\n23\n29\n").unwrap(), None);
+        assert!(check("11\n13\n17\n19\n23\n29\n31\n").unwrap().unwrap().contains("31"));
+        assert!(check("11\n13\n17\n19\n23\n").unwrap().unwrap().contains("29"));
+        assert_eq!(check("11\n13\nseventeen\n").unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(verify_known(&dir, &dataset("index.json", 10, 30, b"")).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(verify_known(&dir, &dataset("missing.txt", 10, 30, b"")).unwrap_err().kind(), ErrorKind::NotFound);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_corrupt_downloads_and_unsafe_names() {
+        let dir = std::env::temp_dir().join(format!("prime_generator_known_corrupt_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let text = dataset_text(0, 1000);
+        let mut index = vec![dataset("primes.txt", 0, 1000, &text)];
+        index[0].sha256 = to_hex(&Sha256::digest(b"something else"));
+        let files = HashMap::from([("index.json".to_string(), serde_json::to_vec(&index).unwrap()), ("primes.txt".to_string(), text.clone())]);
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        assert!(fetch_known(&client, &mirror(files).await, 1000, &dir).await.is_err());
+        assert!(!dir.join("primes.txt").exists());
+
+        let escape = vec![dataset("../primes.txt", 0, 1000, &text)];
+        let files = HashMap::from([("index.json".to_string(), serde_json::to_vec(&escape).unwrap())]);
+        assert!(fetch_known(&client, &mirror(files).await, 1000, &dir).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
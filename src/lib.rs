@@ -0,0 +1,166 @@
+//! Prime number engine behind the `prime_generator` CLI.
+//!
+//! The core (primality tests and sieving) has no dependency on the async
+//! runtime, HTTP client or thread pool, so it also builds for
+//! `wasm32-unknown-unknown` with `--no-default-features --features wasm`,
+//! and can be embedded from C through the `ffi` feature or from Python through
+//! the `python` feature.
+//!
+//! With `default-features = false` only the allocation-free `math` and
+//! `montgomery` modules are built, as a `no_std` crate.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod math;
+pub mod montgomery;
+
+#[cfg(feature = "std")]
+pub mod aggregate;
+#[cfg(feature = "net")]
+pub mod api;
+#[cfg(feature = "std")]
+pub mod arithmetic;
+#[cfg(feature = "avro")]
+pub mod avro_file;
+#[cfg(feature = "std")]
+pub mod bignum;
+#[cfg(feature = "parallel")]
+pub mod candidates;
+#[cfg(feature = "std")]
+pub mod columns;
+#[cfg(feature = "std")]
+pub mod constellation;
+#[cfg(feature = "std")]
+pub mod cpu_cache;
+#[cfg(feature = "csv")]
+pub mod csv_file;
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+#[cfg(feature = "std")]
+pub mod exit;
+#[cfg(feature = "std")]
+pub mod factor;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "parallel")]
+pub mod generate;
+#[cfg(feature = "parallel")]
+pub mod generator;
+#[cfg(feature = "std")]
+pub mod health;
+#[cfg(feature = "net")]
+pub mod known;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+#[cfg(feature = "mmap")]
+pub mod mmap_file;
+#[cfg(feature = "parallel")]
+pub mod numa;
+#[cfg(feature = "std")]
+pub mod number_theory;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod paths;
+#[cfg(feature = "std")]
+pub mod plan;
+#[cfg(feature = "std")]
+pub mod polynomial;
+#[cfg(feature = "std")]
+pub mod primality;
+#[cfg(feature = "primesieve")]
+pub mod primesieve;
+#[cfg(feature = "std")]
+pub mod prime_cache;
+#[cfg(all(feature = "profile", target_os = "linux"))]
+pub mod profile;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod priority;
+#[cfg(feature = "net")]
+pub mod publish;
+#[cfg(feature = "std")]
+pub mod ranges;
+#[cfg(feature = "std")]
+pub mod recreational;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "std")]
+pub mod record_tracker;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "std")]
+pub mod rows;
+#[cfg(feature = "std")]
+pub mod sample;
+#[cfg(feature = "std")]
+pub mod sieve;
+#[cfg(feature = "sign")]
+pub mod signature;
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(feature = "smtp")]
+pub mod smtp;
+#[cfg(feature = "std")]
+pub mod special;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod systemd;
+#[cfg(feature = "std")]
+pub mod table;
+#[cfg(feature = "std")]
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod throttle;
+#[cfg(feature = "std")]
+pub mod timing;
+#[cfg(feature = "std")]
+pub mod validate;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring_file;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use math::{is_prime, next_prime};
+
+#[cfg(feature = "std")]
+pub use factor::factor;
+#[cfg(feature = "parallel")]
+pub use generate::{generate_batches_with, generate_with};
+#[cfg(feature = "parallel")]
+pub use generator::{Algorithm, Backend, PrimeGenerator, PrimeGeneratorBuilder, RecordOrder, RunSummary, Tuning};
+#[cfg(feature = "manifest")]
+pub use manifest::Manifest;
+#[cfg(feature = "parallel")]
+pub use numa::NumaPolicy;
+#[cfg(feature = "std")]
+pub use primality::is_prime_bigint;
+#[cfg(feature = "std")]
+pub use record::PrimeRecord;
+#[cfg(feature = "std")]
+pub use sieve::primes_in_range;
+#[cfg(feature = "sign")]
+pub use signature::ShardSignature;
+#[cfg(feature = "avro")]
+pub use sink::AvroSink;
+#[cfg(feature = "csv")]
+pub use sink::CsvSink;
+#[cfg(feature = "duckdb")]
+pub use sink::DuckDbSink;
+#[cfg(feature = "mmap")]
+pub use sink::MmapCsvSink;
+#[cfg(feature = "redis")]
+pub use sink::RedisSink;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub use sink::UringCsvSink;
+#[cfg(feature = "std")]
+pub use sink::Sink;
+#[cfg(feature = "stream")]
+pub use stream::prime_stream;
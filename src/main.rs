@@ -1,44 +1,92 @@
-extern crate rayon;
-use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 extern crate num_bigint as bigint;
-extern crate num_traits;
-use bigint::{BigInt, ToBigInt};
-use num_traits::Zero;
-use std::fs::OpenOptions;
-use std::io::Result;
-use std::path::Path;
-use std::convert::From;
-use serde::{Serialize, Deserialize};
-use csv::Writer;
+use bigint::BigInt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, ErrorKind, IsTerminal, Result, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
 extern crate clap;
-use clap::{App, Arg};
-extern crate csv;
-use reqwest;
+use clap::{App, Arg, ArgMatches};
+use num_traits::{Bounded, ToPrimitive};
+use rayon::prelude::*;
+use serde::Serialize;
+#[cfg(feature = "net")]
 use tokio::runtime::Runtime;
-use num_traits::ToPrimitive;
+#[cfg(target_os = "linux")]
+use prime_generator::UringCsvSink;
+#[cfg(feature = "duckdb")]
+use prime_generator::DuckDbSink;
+use prime_generator::{is_prime, sieve, Algorithm, AvroSink, Backend, CsvSink, Manifest, MmapCsvSink, NumaPolicy, PrimeGenerator, RecordOrder, RedisSink, Sink};
+#[cfg(feature = "smtp")]
+use prime_generator::RunSummary;
+use prime_generator::csv_file::{append_records, for_each_record, read_records, CsvDialect};
+#[cfg(feature = "net")]
+use prime_generator::api::{fetch_default_range, upload_state_path, HttpConfig, UploadOptions, DEFAULT_RESULTS_URL};
+#[cfg(feature = "net")]
+use prime_generator::known::{default_known_dir, fetch_known, verify_known, DEFAULT_MIRROR};
+#[cfg(feature = "net")]
+use prime_generator::publish::publisher_from_spec;
+use prime_generator::manifest::{manifest_path, GenerationParameters};
+use prime_generator::output::{expand_output_template, DEFAULT_OUTPUT_TEMPLATE};
+use prime_generator::aggregate::{Aggregate, DecadeDensity};
+use prime_generator::arithmetic;
+use prime_generator::bignum::{Integer, PowerFormat};
+use prime_generator::candidates::{self, CandidateSource, MersenneExponents, NumberFile, NumberRange, PolynomialValues, RandomBits};
+use prime_generator::columns::Column;
+use prime_generator::constellation::Constellation;
+use prime_generator::exit::Exit;
+use prime_generator::factor::factor_with_multiplicity;
+use prime_generator::format::NumberFormat;
+use prime_generator::numa::parse_cpu_list;
+use prime_generator::health::HealthCheck;
+use prime_generator::number_theory::{
+    is_wieferich, is_wilson, legendre, multiplicative_order, two_squares, GaussianSplitting,
+};
+use prime_generator::ranges::{parse_ranges, read_ranges};
+use prime_generator::record::PrimeRecord;
+use prime_generator::record_tracker::{self, records_path, RecordKind};
+use prime_generator::rows::Rows;
+use prime_generator::redis::RedisTarget;
+#[cfg(target_os = "linux")]
+use prime_generator::priority::{self, IoPriority};
+#[cfg(feature = "registry")]
+use prime_generator::registry::{self, RangeEntry, Registry};
+#[cfg(target_os = "linux")]
+use prime_generator::profile::{self, Profiler};
+#[cfg(target_os = "linux")]
+use prime_generator::systemd;
+use prime_generator::table::{self, Align, Style, Table};
+#[cfg(target_os = "linux")]
+use prime_generator::throttle::{self, LoadLimit, Throttle};
+use prime_generator::timing;
+use prime_generator::validate::{self, InvalidArgument};
+use prime_generator::recreational::{is_happy, lucky_numbers};
+use prime_generator::sample::Sample;
+use prime_generator::paths;
+use prime_generator::prime_cache;
+use prime_generator::plan;
+use prime_generator::polynomial::Polynomial;
+use prime_generator::primality::{probable_prime, Primality, PRIME_TEST, PROBABLE_PRIME_TEST};
+use prime_generator::special::{
+    factorials, fermat_digits, known_fermat, pepin_test, primorials, proth_test, repunit_test, KnownFermat,
+};
+#[cfg(feature = "smtp")]
+use prime_generator::smtp::{self, Email, SmtpServer};
+use prime_generator::signature::{read_signing_key, read_verifying_key, signature_path, ShardSignature};
 
-#[derive(Serialize, Deserialize)]
-struct PrimeRecord {
-    prime: u128,
-    squared: String,
-    cubed: String,
-    to_fourth_power: String,
-}
+mod visualize;
 
-#[derive(Deserialize)]
-struct Range {
-    start: u128,
-    end: u128,
-}
+// Numbers `check` reads before testing them in parallel
+const CHECK_CHUNK: usize = 1 << 12;
 
-// Define a threshold for record count or memory usage
-const FLUSH_THRESHOLD: usize = 10000;
+// Output modes offered by `--io`; io_uring only exists on Linux
+#[cfg(target_os = "linux")]
+const IO_MODES: [&str; 3] = ["buffered", "mmap", "uring"];
+#[cfg(not(target_os = "linux"))]
+const IO_MODES: [&str; 2] = ["buffered", "mmap"];
 
 /// The entry point for the Prime Factorization program.
 ///
@@ -50,10 +98,10 @@ const FLUSH_THRESHOLD: usize = 10000;
 /// # Arguments
 ///
 /// * `start` - A CLI argument that specifies the start of the range for prime factorization.
-///             It is provided by the user with the `-s` or `--start` flag.
+///   It is provided by the user with the `-s` or `--start` flag.
 ///
 /// * `end` - A CLI argument that specifies the end of the range for prime factorization.
-///           It is provided by the user with the `-e` or `--end` flag.
+///   It is provided by the user with the `-e` or `--end` flag.
 ///
 /// # Panics
 ///
@@ -69,29 +117,25 @@ const FLUSH_THRESHOLD: usize = 10000;
 ///
 /// This will generate prime numbers and their factors between 2 and 1,000,000.
 fn main() {
-    // Create a new Tokio runtime
-    let rt = Runtime::new().unwrap();
-    // Use the runtime to block on the asynchronous function
-    let (default_start, default_end) = match rt.block_on(fetch_default_range()) {
-        Ok(range) => range,
-        Err(e) => {
-            // Handle error, e.g., log it and use a default value or exit
-            eprintln!("Error fetching range: {}", e);
-            (0, 0) // Example default values, or you could exit the program
-        },
-    };
     // Setup CLI using `clap` crate.
-    let matches = App::new("Prime Factorization")
+    let app = App::new("Prime Factorization")
         // Specifies the version, author, and about text for the help output.
         .version("1.0")
         .author("Daniel R Curtis")
         .about("Generates prime numbers and their factors within a given range")
+        .after_help(
+            "EXIT CODES:\n    0  success\n    1  any other failure, including warnings under --strict\n    2  invalid arguments or input\n    \
+             3  I/O error\n    4  network error\n    5  verification mismatch",
+        )
+        // Range arguments only apply when no subcommand is given.
+        .subcommand_negates_reqs(true)
         // Define `start` argument.
         .arg(
             Arg::with_name("start")
                 .short('s')
                 .long("start")
                 .takes_value(true)
+                .required(cfg!(not(feature = "net")))
                 .help("Start of the range"),
         )
         // Define `end` argument.
@@ -100,8 +144,26 @@ fn main() {
                 .short('e')
                 .long("end")
                 .takes_value(true)
+                .required(cfg!(not(feature = "net")))
                 .help("End of the range"),
         )
+        // Define `ranges` argument.
+        .arg(
+            Arg::with_name("ranges")
+                .long("ranges")
+                .takes_value(true)
+                .conflicts_with_all(&["start", "end", "ranges-file"])
+                .help("Several inclusive ranges in one run, e.g. \"2..10^6,10^9..10^9+10^6\""),
+        )
+        // Define `ranges-file` argument.
+        .arg(
+            Arg::with_name("ranges-file")
+                .long("ranges-file")
+                .takes_value(true)
+                .value_name("ranges.json")
+                .conflicts_with_all(&["start", "end"])
+                .help("Read the ranges to search as JSON: [{\"start\": 2, \"end\": 1000000}, ...]"),
+        )
         // Define `cpus` argument.
         .arg(
             Arg::with_name("cpus")
@@ -110,247 +172,3315 @@ fn main() {
                 .takes_value(true)
                 .help("Number of CPUs to use"),
         )
-        .get_matches();
+        // Define `cpu-list` argument.
+        .arg(
+            Arg::with_name("cpu-list")
+                .long("cpu-list")
+                .takes_value(true)
+                .value_name("LIST")
+                .help("Pin workers to these CPUs, e.g. 0-7 or 0-3,8-11, and run one per CPU unless --cpus is given"),
+        )
+        // Define `nice` argument.
+        .arg(
+            Arg::with_name("nice")
+                .long("nice")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Run with this nice value, from -20 to 19 (19 only takes leftover CPU time) (Linux)"),
+        )
+        // Define `ionice` argument.
+        .arg(
+            Arg::with_name("ionice")
+                .long("ionice")
+                .takes_value(true)
+                .value_name("CLASS[:LEVEL]")
+                .help("I/O scheduling class: idle, best-effort:0-7 or realtime:0-7, as with ionice(1) (Linux)"),
+        )
+        // Define `max-load` argument.
+        .arg(
+            Arg::with_name("max-load")
+                .long("max-load")
+                .takes_value(true)
+                .value_name("FRACTION")
+                .help("Pause workers as needed to keep total CPU use under this share of the machine, e.g. 0.8 (Linux)"),
+        )
+        // Define `max-temp` argument.
+        .arg(
+            Arg::with_name("max-temp")
+                .long("max-temp")
+                .takes_value(true)
+                .value_name("CELSIUS")
+                .help("Pause workers while the hottest thermal zone is at or above this temperature (Linux)"),
+        )
+        // Define `only-when-idle` argument.
+        .arg(
+            Arg::with_name("only-when-idle")
+                .long("only-when-idle")
+                .takes_value(false)
+                .help("Pause workers unless nobody typed on a terminal for 3 minutes and other processes use under 25% of the CPU (Linux)"),
+        )
+        // Define `algorithm` argument.
+        .arg(
+            Arg::with_name("algorithm")
+                .short('a')
+                .long("algorithm")
+                .takes_value(true)
+                .possible_values(["auto", "per-candidate", "segmented-sieve"])
+                .default_value("auto")
+                .help("How candidates are tested for primality"),
+        )
+        // Define `backend` argument.
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .takes_value(true)
+                .possible_values(["native", "primesieve"])
+                .default_value("native")
+                .help("Prime enumeration for the sieve: built in, or the primesieve library (needs --features primesieve)"),
+        )
+        // Define `ordering` argument.
+        .arg(
+            Arg::with_name("ordering")
+                .long("ordering")
+                .takes_value(true)
+                .possible_values(["arrival", "sorted"])
+                .default_value("arrival")
+                .help("Write primes as workers find them, or in increasing order"),
+        )
+        // Define `numa` argument.
+        .arg(
+            Arg::with_name("numa")
+                .long("numa")
+                .takes_value(true)
+                .possible_values(["auto", "off"])
+                .default_value("auto")
+                .help("Pin worker threads to NUMA nodes on multi-socket machines"),
+        )
+        // Define `segment-size` argument.
+        .arg(
+            Arg::with_name("segment-size")
+                .long("segment-size")
+                .takes_value(true)
+                .help("Numbers per segmented sieve task (default: sized to the CPU's L2 cache)"),
+        )
+        // Define `io` argument.
+        .arg(
+            Arg::with_name("io")
+                .long("io")
+                .takes_value(true)
+                .possible_values(IO_MODES)
+                .default_value("buffered")
+                .help("Write the output file with buffered writes, through a memory mapping or with io_uring (Linux)"),
+        )
+        // Define `format` argument.
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["csv", "avro"])
+                .default_value("csv")
+                .help("Write the output file as CSV, or as an Avro object container with the schema in its header (--io and the dialect options only apply to CSV; Avro files get no manifest and aren't uploaded)"),
+        )
+        // Define `columns` argument.
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .takes_value(true)
+                .value_name("legendre:<a>,primitive-root,order:<a>,...")
+                .help("Extra comma-separated columns computed for each prime: legendre:<a> for the Legendre symbol (a/p), primitive-root for the smallest primitive root, order:<a> for the multiplicative order of a, base and exponent for prime powers"),
+        )
+        // Define `power-format` argument.
+        .arg(
+            Arg::with_name("power-format")
+                .long("power-format")
+                .takes_value(true)
+                .possible_values(["decimal", "hex", "scientific", "digits-only"])
+                .default_value("decimal")
+                .help("Write the powers in decimal, in hex, as the nearest float in scientific notation, or as just their number of digits"),
+        )
+        // Define `primes-only` argument.
+        .arg(
+            Arg::with_name("primes-only")
+                .long("primes-only")
+                .takes_value(false)
+                .help("Write just the primes, leaving out the powers; readers derive them and `materialize` writes them back"),
+        )
+        // Define `delimiter` argument.
+        .arg(
+            Arg::with_name("delimiter")
+                .long("delimiter")
+                .takes_value(true)
+                .default_value(",")
+                .help("Character between fields, such as `;`, or `tab` for TSV"),
+        )
+        // Define `quoting` argument.
+        .arg(
+            Arg::with_name("quoting")
+                .long("quoting")
+                .takes_value(true)
+                .possible_values(["necessary", "always", "non-numeric", "never"])
+                .default_value("necessary")
+                .help("Which fields to put in double quotes"),
+        )
+        // Define `line-terminator` argument.
+        .arg(
+            Arg::with_name("line-terminator")
+                .long("line-terminator")
+                .takes_value(true)
+                .possible_values(["lf", "crlf"])
+                .default_value("lf")
+                .help("What ends each row"),
+        )
+        // Define `no-header` argument.
+        .arg(
+            Arg::with_name("no-header")
+                .long("no-header")
+                .takes_value(false)
+                .help("Leave out the schema version and header lines, for loaders that take bare rows"),
+        )
+        // Define `include-prime-powers` argument.
+        .arg(
+            Arg::with_name("include-prime-powers")
+                .long("include-prime-powers")
+                .takes_value(false)
+                .help("Also write the prime powers p^2, p^3, ... in the range, with base and exponent columns telling them apart from the primes"),
+        )
+        // Define `filter` argument.
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .value_name("twin,cousin,sexy,chen,happy")
+                .help("Keep only primes with all of these comma-separated properties: members of twin, cousin or sexy pairs, Chen primes, or happy primes"),
+        )
+        // Define `sample` argument.
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .takes_value(true)
+                .value_name("RATE")
+                .help("Keep only a deterministic pseudo-random share of the primes, such as 0.001, picked by --sample-seed"),
+        )
+        // Define `sample-seed` argument.
+        .arg(
+            Arg::with_name("sample-seed")
+                .long("sample-seed")
+                .takes_value(true)
+                .default_value("0")
+                .requires("sample")
+                .help("Seed picking which primes --sample keeps; the same seed keeps the same primes"),
+        )
+        // Define `aggregate` argument.
+        .arg(
+            Arg::with_name("aggregate")
+                .long("aggregate")
+                .takes_value(true)
+                .value_name("sum,count,max_gap,residues:<m>")
+                .help("Keep comma-separated running aggregates over the primes and report them in the summary and manifest (max_gap needs --ordering sorted)"),
+        )
+        // Define `track-records` argument.
+        .arg(
+            Arg::with_name("track-records")
+                .long("track-records")
+                .takes_value(true)
+                .value_name("palindromic,twin,gap")
+                .help("Stream each new largest palindromic prime, twin pair or prime gap to a records file as it is found (twin and gap need --ordering sorted)"),
+        )
+        // Define `records-file` argument.
+        .arg(
+            Arg::with_name("records-file")
+                .long("records-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("track-records")
+                .help("Append the --track-records finds to this CSV file instead of <output>.records.csv"),
+        )
+        // Define `plain-numbers` argument.
+        .arg(
+            Arg::with_name("plain-numbers")
+                .long("plain-numbers")
+                .help("Print progress and summary numbers as plain digits, without locale separators or SI suffixes, for machine parsing"),
+        )
+        // Define `yes` argument.
+        .arg(
+            Arg::with_name("yes")
+                .short('y')
+                .long("yes")
+                .help("Start runs above --confirm-above or --confirm-above-size without asking"),
+        )
+        // Define `confirm-above` argument.
+        .arg(
+            Arg::with_name("confirm-above")
+                .long("confirm-above")
+                .takes_value(true)
+                .default_value("10^12")
+                .help("Ask before testing more numbers than this, showing an estimate of the run"),
+        )
+        // Define `confirm-above-size` argument.
+        .arg(
+            Arg::with_name("confirm-above-size")
+                .long("confirm-above-size")
+                .takes_value(true)
+                .default_value("100G")
+                .help("Ask before writing more output than this, e.g. 500G, showing an estimate of the run"),
+        )
+        // Define `auto-tune` argument.
+        .arg(
+            Arg::with_name("auto-tune")
+                .long("auto-tune")
+                .help("Time a few chunk sizes and flush thresholds at the start of the run and keep the fastest"),
+        )
+        // Define `cross-check` argument.
+        .arg(
+            Arg::with_name("cross-check")
+                .long("cross-check")
+                .help("Classify every candidate with a second, independent algorithm and abort on any disagreement"),
+        )
+        // Define `exclude-file` argument.
+        .arg(
+            Arg::with_name("exclude-file")
+                .long("exclude-file")
+                .takes_value(true)
+                .value_name("ranges.json")
+                .help("Skip ranges already covered, listed as JSON: [{\"start\": 2, \"end\": 1000000}, ...]"),
+        )
+        // Define `output-template` argument.
+        .arg(
+            Arg::with_name("output-template")
+                .long("output-template")
+                .takes_value(true)
+                .default_value(DEFAULT_OUTPUT_TEMPLATE)
+                .help("Output file name; {start}, {end} and {timestamp} (Unix seconds) are replaced, e.g. primes_{start}_{end}_{timestamp}.csv. duckdb://primes.duckdb appends to the `primes` table of a DuckDB database instead (needs --features duckdb), and redis://host:port/key adds the primes to a Redis sorted set"),
+        )
+        // Define `memory-limit` argument.
+        .arg(
+            Arg::with_name("memory-limit")
+                .long("memory-limit")
+                .takes_value(true)
+                .value_name("SIZE")
+                .help("Keep buffered records under this many bytes (K, M, G and T suffixes) by flushing early and shrinking chunks"),
+        )
+        // Define `queue-depth` argument.
+        .arg(
+            Arg::with_name("queue-depth")
+                .long("queue-depth")
+                .takes_value(true)
+                .value_name("BATCHES")
+                .help("Full batches that may wait for a slow writer before the workers block (1 by default)"),
+        )
+        // Define `no-prime-cache` argument.
+        .arg(
+            Arg::with_name("no-prime-cache")
+                .long("no-prime-cache")
+                .help("Recompute the sieving primes instead of using the cache under the user cache dir"),
+        )
+        // Define `sd-notify` argument.
+        .arg(
+            Arg::with_name("sd-notify")
+                .long("sd-notify")
+                .takes_value(false)
+                .help("Report readiness, progress and watchdog keep-alives to systemd for Type=notify services (Linux)"),
+        )
+        // Define `profile` argument.
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .min_values(0)
+                .default_missing_value("flamegraph.svg")
+                .global(true)
+                .value_name("flamegraph.svg")
+                .help("Sample where the CPU time goes and write a flamegraph SVG, with the folded stacks next to it, at exit (Linux)"),
+        )
+        // Define `no-color` argument.
+        .arg(
+            Arg::with_name("no-color")
+                .long("no-color")
+                .global(true)
+                .help("Don't color tables shown on a terminal (also off with NO_COLOR set or when piped)"),
+        )
+        // Define `strict` argument.
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .global(true)
+                .help("Fail on warnings, such as ranges the registry says were generated already or results that couldn't be published or emailed, rather than carry on"),
+        )
+        // Define `json` argument.
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help("Print the results of a subcommand as one JSON document instead of CSV or text"),
+        )
+        // Define `health-listen` argument.
+        .arg(
+            Arg::with_name("health-listen")
+                .long("health-listen")
+                .takes_value(true)
+                .value_name("ADDR")
+                .help("Serve /healthz and /readyz probes on this address (e.g. 0.0.0.0:8080) for container orchestrators"),
+        )
+        // Define `health-stall-timeout` argument.
+        .arg(
+            Arg::with_name("health-stall-timeout")
+                .long("health-stall-timeout")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("300")
+                .help("Fail /healthz once progress hasn't moved for this many seconds"),
+        )
+        // Define `registry` argument.
+        .arg(
+            Arg::with_name("registry")
+                .long("registry")
+                .takes_value(true)
+                .value_name("registry.db")
+                .help("Warn about ranges this SQLite registry says were generated already, and record this run's (needs --features registry)"),
+        )
+        // Define `no-manifest` argument.
+        .arg(
+            Arg::with_name("no-manifest")
+                .long("no-manifest")
+                .help("Don't write a manifest with row count, prime bounds and SHA-256 next to the output file"),
+        )
+        // Define `sign-key` argument.
+        .arg(
+            Arg::with_name("sign-key")
+                .long("sign-key")
+                .takes_value(true)
+                .value_name("key.pem")
+                .help("Sign the output file's SHA-256 with this Ed25519 private key (PKCS#8 PEM)"),
+        )
+        // Define `verify-manifest` subcommand for checking output files.
+        .subcommand(
+            App::new("verify-manifest")
+                .about("Checks an output file against its manifest to detect bit rot or tampering")
+                .arg(
+                    Arg::with_name("manifest")
+                        .takes_value(true)
+                        .default_value("primes_and_powers.csv.manifest.json")
+                        .help("Manifest to check; the output file is looked up next to it"),
+                ),
+        )
+        // Define `materialize` subcommand for writing back left-out powers.
+        .subcommand(
+            App::new("materialize")
+                .about("Rewrites an output file written with --primes-only with the powers of every prime")
+                .arg(
+                    Arg::with_name("input")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Output file to read"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .required(true)
+                        .help("CSV file to append the records with their powers to; extra columns aren't carried over"),
+                ),
+        )
+        // Define `status` subcommand for listing the ranges in a registry.
+        .subcommand(
+            App::new("status")
+                .about("Lists the ranges recorded in a registry, with the coverage and the gaps in it")
+                .arg(
+                    Arg::with_name("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("registry.db")
+                        .help("SQLite registry written by runs given --registry"),
+                ),
+        )
+        // Define `paths` subcommand for showing where files live.
+        .subcommand(
+            App::new("paths")
+                .about("Prints where caches, configuration and default outputs live on this platform")
+                .arg(
+                    Arg::with_name("output-template")
+                        .long("output-template")
+                        .takes_value(true)
+                        .default_value(DEFAULT_OUTPUT_TEMPLATE)
+                        .help("Output template to resolve, as given to a run"),
+                ),
+        )
+        // Define `coverage` subcommand for picturing the ranges in a registry.
+        .subcommand(
+            App::new("coverage")
+                .about("Renders which parts of the number line a registry records as generated")
+                .arg(
+                    Arg::with_name("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("registry.db")
+                        .help("SQLite registry written by runs given --registry"),
+                )
+                .arg(
+                    Arg::with_name("image")
+                        .long("image")
+                        .takes_value(true)
+                        .default_value("coverage.svg")
+                        .help("Output image path (.png or .svg)"),
+                ),
+        )
+        // Define `plan` subcommand for proposing the runs that reach a target.
+        .subcommand(
+            App::new("plan")
+                .about("Times short runs to calibrate throughput, then proposes work units reaching a target within a time budget")
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Number to generate up to, such as 10^12"),
+                )
+                .arg(
+                    Arg::with_name("budget")
+                        .long("budget")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Wall-clock time available, such as 48h, 90m or 2d"),
+                )
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number to start from, such as the end of the ranges already generated plus one"),
+                )
+                .arg(
+                    Arg::with_name("unit")
+                        .long("unit")
+                        .takes_value(true)
+                        .default_value("1h")
+                        .help("Time each work unit should take"),
+                )
+                .arg(
+                    Arg::with_name("sample")
+                        .long("sample")
+                        .takes_value(true)
+                        .default_value("10^6")
+                        .help("Numbers to time at each power of ten between the start and the target"),
+                )
+                .arg(
+                    Arg::with_name("algorithm")
+                        .long("algorithm")
+                        .takes_value(true)
+                        .default_value("auto")
+                        .possible_values(["auto", "per-candidate", "segmented-sieve"])
+                        .help("Algorithm the runs will use"),
+                )
+                .arg(
+                    Arg::with_name("cpus")
+                        .long("cpus")
+                        .takes_value(true)
+                        .help("Number of CPUs the runs will use; defaults to all"),
+                ),
+        )
+        // Define `verify-signature` subcommand for authenticating output files.
+        .subcommand(
+            App::new("verify-signature")
+                .about("Checks an output file's Ed25519 signature against the signer's public key")
+                .arg(
+                    Arg::with_name("signature")
+                        .takes_value(true)
+                        .default_value("primes_and_powers.csv.sig")
+                        .help("Signature to check; the output file is looked up next to it"),
+                )
+                .arg(
+                    Arg::with_name("public-key")
+                        .long("public-key")
+                        .takes_value(true)
+                        .value_name("key.pub.pem")
+                        .required(true)
+                        .help("The signer's Ed25519 public key (PEM)"),
+                ),
+        )
+        // Define `check` subcommand for testing individual numbers.
+        .subcommand(
+            App::new("check")
+                .about("Tests numbers for primality, writing `number,prime` CSV rows in input order")
+                .arg(
+                    Arg::with_name("numbers")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .required_unless_present("stdin")
+                        .help("Numbers to test (beyond 2^128 this is slow without --features gmp)"),
+                )
+                .arg(
+                    Arg::with_name("stdin")
+                        .long("stdin")
+                        .conflicts_with("numbers")
+                        .help("Read the numbers from standard input, one per line"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .help("Write the verdicts to this CSV file instead of standard output"),
+                ),
+        )
+        // Define `proth` subcommand for searching Proth primes.
+        .subcommand(
+            App::new("proth")
+                .about("Searches for Proth primes k·2^n + 1 (odd k < 2^n), proven with Proth's theorem; prints `k,n` CSV rows")
+                .arg(
+                    Arg::with_name("k")
+                        .short('k')
+                        .takes_value(true)
+                        .required(true)
+                        .help("Multipliers to try, as inclusive ranges, e.g. 1..1000"),
+                )
+                .arg(
+                    Arg::with_name("n")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .help("Exponents to try, as inclusive ranges, e.g. 1..2000"),
+                ),
+        )
+        // Define `fermat-number` subcommand for testing Fermat numbers.
+        .subcommand(
+            App::new("fermat-number")
+                .about("Tests the Fermat number F_n = 2^(2^n) + 1 with Pépin's test")
+                .arg(
+                    Arg::with_name("n")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Index of the Fermat number; the test runs 2^n squarings of 2^n-bit numbers"),
+                ),
+        )
+        // Define `repunit` subcommand for searching repunit primes.
+        .subcommand(
+            App::new("repunit")
+                .about("Searches for repunit primes R_n = (b^n - 1)/(b - 1); prints `n,result,test` CSV rows")
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Base the repunits are written in"),
+                )
+                .arg(
+                    Arg::with_name("n")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lengths to try, as inclusive ranges, e.g. 2..1000"),
+                ),
+        )
+        // Define `factorial` and `primorial` subcommands for searching n! ± 1 and p# ± 1 primes.
+        .subcommand(
+            App::new("factorial")
+                .about("Searches for factorial primes n! ± 1; prints `n,form,result,test` CSV rows")
+                .arg(
+                    Arg::with_name("n")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .help("Values of n to try, as inclusive ranges, e.g. 1..1000"),
+                ),
+        )
+        .subcommand(
+            App::new("primorial")
+                .about("Searches for primorial primes p# ± 1; prints `p,form,result,test` CSV rows")
+                .arg(
+                    Arg::with_name("n")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .help("Range to take the primes p from, as inclusive ranges, e.g. 2..5000"),
+                ),
+        )
+        // Define `gaussian` subcommand for classifying primes in Z[i].
+        .subcommand(
+            App::new("gaussian")
+                .about("Reports whether each prime splits, ramifies or stays inert in Z[i], with p = a^2 + b^2 for split primes; prints `p,splitting,a,b` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range"),
+                ),
+        )
+        // Define `wieferich` and `wilson` subcommands for scanning the range for those primes.
+        .subcommand(
+            App::new("wieferich")
+                .about("Scans the range for Wieferich primes, 2^(p-1) ≡ 1 (mod p^2); prints `p` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range, below 2^64"),
+                ),
+        )
+        .subcommand(
+            App::new("wilson")
+                .about("Scans the range for Wilson primes, (p-1)! ≡ -1 (mod p^2); prints `p` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range, below 2^32; each prime p takes p multiplications"),
+                ),
+        )
+        // Define `mertens` subcommand for summing the Möbius function.
+        .subcommand(
+            App::new("mertens")
+                .about("Sieves the Möbius function μ(n) and prints `x,mobius,mertens` CSV rows with the Mertens function M(x) over the range")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range; M(x) sums μ(n) from 1, so everything below the start is sieved too"),
+                )
+                .arg(
+                    Arg::with_name("every")
+                        .long("every")
+                        .takes_value(true)
+                        .help("Only print every k-th x of the range, counting from the start"),
+                ),
+        )
+        // Define `chebyshev` subcommand for summing logarithms of primes.
+        .subcommand(
+            App::new("chebyshev")
+                .about("Computes Chebyshev's functions θ(x) and ψ(x) at checkpoints in the range; prints `x,theta,psi` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("First checkpoint"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Last checkpoint; θ and ψ sum from 2, so everything below the start is sieved too"),
+                )
+                .arg(
+                    Arg::with_name("every")
+                        .long("every")
+                        .takes_value(true)
+                        .help("Distance between checkpoints, counting from the start (defaults to only the start and end)"),
+                )
+                .arg(
+                    Arg::with_name("at")
+                        .long("at")
+                        .takes_value(true)
+                        .help("Extra comma-separated checkpoints such as 10^6,10^7"),
+                ),
+        )
+        // Define `rough` and `smooth` subcommands for sieving numbers by the size of their prime factors.
+        .subcommand(
+            App::new("rough")
+                .about("Sieves the k-rough numbers of the range, with no prime factor below k; prints `n` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range"),
+                )
+                .arg(
+                    Arg::with_name("min-factor")
+                        .long("min-factor")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Smallest prime factor allowed, k, such as 10^6"),
+                ),
+        )
+        .subcommand(
+            App::new("smooth")
+                .about("Sieves the B-smooth numbers of the range, with no prime factor above B; prints `n,factorization` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range"),
+                )
+                .arg(
+                    Arg::with_name("bound")
+                        .long("bound")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Largest prime factor allowed, B, such as 10^5"),
+                ),
+        )
+        // Define `lucky` subcommand for sieving lucky primes.
+        .subcommand(
+            App::new("lucky")
+                .about("Sieves the lucky numbers and prints the lucky primes of the range as `p` CSV rows")
+                .arg(
+                    Arg::with_name("start")
+                        .short('s')
+                        .long("start")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Start of the range"),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .short('e')
+                        .long("end")
+                        .takes_value(true)
+                        .required(true)
+                        .help("End of the range; the sieve holds 2 bytes per number up to it"),
+                ),
+        )
+        // Define `poly` subcommand for scanning polynomial values for primes.
+        .subcommand(
+            App::new("poly")
+                .about("Scans the values of integer polynomials in n for primes; prints `polynomial,n,value,result,test` CSV rows and each polynomial's prime density")
+                .arg(
+                    Arg::with_name("polynomials")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .required(true)
+                        .help("Polynomials such as \"n^2 + n + 41\" or \"2n^2 - 1\""),
+                )
+                .arg(
+                    Arg::with_name("n")
+                        .short('n')
+                        .long("n")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Values of n to try, as inclusive ranges, e.g. 0..10^7"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .help("Write the primes to this CSV file instead of standard output"),
+                ),
+        )
+        // Define `search` subcommand for testing the candidates of any source.
+        .subcommand(
+            App::new("search")
+                .about("Tests the numbers of a candidate source for primality; prints the source's columns and `result,test` CSV rows for the primes")
+                .subcommand_required(true)
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .global(true)
+                        .help("Write the primes to this CSV file instead of standard output"),
+                )
+                .subcommand(
+                    App::new("range").about("Tests every number of inclusive ranges").arg(
+                        Arg::with_name("n")
+                            .short('n')
+                            .takes_value(true)
+                            .required(true)
+                            .help("Numbers to try, as inclusive ranges, e.g. 10^18..10^18+10^4"),
+                    ),
+                )
+                .subcommand(
+                    App::new("file").about("Tests the numbers of a file, one per line, of any size").arg(
+                        Arg::with_name("input")
+                            .short('i')
+                            .long("input")
+                            .takes_value(true)
+                            .help("File to read the numbers from instead of standard input"),
+                    ),
+                )
+                .subcommand(
+                    App::new("poly")
+                        .about("Tests the values of integer polynomials in n")
+                        .arg(
+                            Arg::with_name("polynomials")
+                                .takes_value(true)
+                                .multiple_values(true)
+                                .required(true)
+                                .help("Polynomials such as \"n^2 + n + 41\" or \"2n^2 - 1\""),
+                        )
+                        .arg(
+                            Arg::with_name("n")
+                                .short('n')
+                                .takes_value(true)
+                                .required(true)
+                                .help("Values of n to try, as inclusive ranges, e.g. 0..10^7"),
+                        ),
+                )
+                .subcommand(
+                    App::new("mersenne").about("Tests the Mersenne numbers 2^p - 1, trial factoring them by q = 2kp + 1 before the Lucas-Lehmer test").arg(
+                        Arg::with_name("p")
+                            .short('p')
+                            .takes_value(true)
+                            .required(true)
+                            .help("Exponents to try, as inclusive ranges, e.g. 2..5000; only prime p can give primes"),
+                    ),
+                )
+                .subcommand(
+                    App::new("random")
+                        .about("Tests pseudo-random integers of a given size, the same ones for the same seed")
+                        .arg(
+                            Arg::with_name("bits")
+                                .long("bits")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Size of the integers in bits, at least 2"),
+                        )
+                        .arg(
+                            Arg::with_name("count")
+                                .long("count")
+                                .takes_value(true)
+                                .default_value("1000")
+                                .help("Number of integers to draw"),
+                        )
+                        .arg(
+                            Arg::with_name("seed")
+                                .long("seed")
+                                .takes_value(true)
+                                .default_value("0")
+                                .help("Seed picking the integers"),
+                        ),
+                ),
+        )
+        // Define `legendre` subcommand for computing Legendre symbols.
+        .subcommand(
+            App::new("legendre")
+                .about("Computes the Legendre symbol (a/p), or the Jacobi symbol when the odd p is composite")
+                .arg(
+                    Arg::with_name("a")
+                        .takes_value(true)
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("The integer whose residuosity is tested"),
+                )
+                .arg(
+                    Arg::with_name("p")
+                        .takes_value(true)
+                        .required(true)
+                        .help("An odd prime, or 2"),
+                ),
+        )
+        // Define `order` subcommand for computing multiplicative orders.
+        .subcommand(
+            App::new("order")
+                .about("Computes the multiplicative order of a modulo the prime p")
+                .arg(
+                    Arg::with_name("a")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The base, not divisible by p"),
+                )
+                .arg(
+                    Arg::with_name("p")
+                        .takes_value(true)
+                        .required(true)
+                        .help("A prime"),
+                ),
+        )
+        // Define `visualize` subcommand for image exports.
+        .subcommand(
+            App::new("visualize")
+                .about("Renders images of the distribution of primes")
+                .subcommand_required(true)
+                .subcommand(
+                    App::new("ulam")
+                        .about("Renders primes on an Ulam spiral")
+                        .arg(
+                            Arg::with_name("size")
+                                .long("size")
+                                .takes_value(true)
+                                .default_value("1000")
+                                .help("Width and height of the image in pixels"),
+                        )
+                        .arg(
+                            Arg::with_name("start")
+                                .short('s')
+                                .long("start")
+                                .takes_value(true)
+                                .default_value("1")
+                                .help("Number at the center of the spiral"),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .short('o')
+                                .long("output")
+                                .takes_value(true)
+                                .default_value("ulam.png")
+                                .help("Output image path (.png or .svg)"),
+                        ),
+                )
+                .subcommand(
+                    App::new("gaps")
+                        .about("Renders a histogram of the gaps between consecutive primes")
+                        .arg(
+                            Arg::with_name("bins")
+                                .long("bins")
+                                .takes_value(true)
+                                .default_value("100")
+                                .help("Number of histogram bins"),
+                        )
+                        .arg(
+                            Arg::with_name("start")
+                                .short('s')
+                                .long("start")
+                                .takes_value(true)
+                                .requires("end")
+                                .help("Start of the range to sieve"),
+                        )
+                        .arg(
+                            Arg::with_name("end")
+                                .short('e')
+                                .long("end")
+                                .takes_value(true)
+                                .requires("start")
+                                .help("End of the range to sieve"),
+                        )
+                        .arg(
+                            Arg::with_name("input")
+                                .short('i')
+                                .long("input")
+                                .takes_value(true)
+                                .conflicts_with("start")
+                                .required_unless_present("start")
+                                .help("Read primes from an existing output CSV instead of sieving"),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .short('o')
+                                .long("output")
+                                .takes_value(true)
+                                .default_value("gaps.svg")
+                                .help("Output image path (.png or .svg)"),
+                        ),
+                ),
+        );
+    let matches = with_otel_args(with_smtp_args(with_net_args(app))).get_matches();
 
-    // Retrieve the number of CPUs from arguments, or use default
-    let num_cpus = matches.value_of("cpus")
-        .map(|c| c.parse::<usize>().expect("Invalid number of CPUs"))
-        .unwrap_or_else(|| num_cpus::get() - 1);
+    // Export traces and metrics until the end of the run
+    #[cfg(feature = "otel")]
+    let _telemetry = matches
+        .value_of("otel-endpoint")
+        .map(|endpoint| prime_generator::telemetry::init(endpoint).expect("Failed to set up OpenTelemetry export"));
+    let _profile = matches.value_of("profile").map(start_profile);
 
-    // Ensure at least 1 CPU is used
-    let thread_count = if num_cpus > 1 { num_cpus - 1 } else { 1 };
+    if let Some(("visualize", visualize_matches)) = matches.subcommand() {
+        run_visualize(visualize_matches);
+        return;
+    }
+    if let Some(("check", check_matches)) = matches.subcommand() {
+        run_check(check_matches);
+        return;
+    }
+    if let Some(("proth", proth_matches)) = matches.subcommand() {
+        run_proth(proth_matches);
+        return;
+    }
+    if let Some(("fermat-number", fermat_matches)) = matches.subcommand() {
+        run_fermat_number(fermat_matches);
+        return;
+    }
+    if let Some(("repunit", repunit_matches)) = matches.subcommand() {
+        run_repunit(repunit_matches);
+        return;
+    }
+    if let Some(("factorial", factorial_matches)) = matches.subcommand() {
+        let mut ns: Vec<u32> = values(factorial_matches.value_of("n").unwrap(), "n");
+        ns.sort_unstable();
+        ns.dedup();
+        search_plus_minus_one("n", factorials(ns), "!", factorial_matches.is_present("json"));
+        return;
+    }
+    if let Some(("primorial", primorial_matches)) = matches.subcommand() {
+        let mut ns: Vec<u32> = values(primorial_matches.value_of("n").unwrap(), "n");
+        ns.sort_unstable();
+        ns.dedup();
+        search_plus_minus_one("p", primorials(ns), "#", primorial_matches.is_present("json"));
+        return;
+    }
+    if let Some(("gaussian", gaussian_matches)) = matches.subcommand() {
+        run_gaussian(gaussian_matches);
+        return;
+    }
+    if let Some(("wieferich", wieferich_matches)) = matches.subcommand() {
+        scan_primes(wieferich_matches, u64::MAX, is_wieferich);
+        return;
+    }
+    if let Some(("wilson", wilson_matches)) = matches.subcommand() {
+        scan_primes(wilson_matches, u32::MAX as u64, is_wilson);
+        return;
+    }
+    if let Some(("mertens", mertens_matches)) = matches.subcommand() {
+        run_mertens(mertens_matches);
+        return;
+    }
+    if let Some(("chebyshev", chebyshev_matches)) = matches.subcommand() {
+        run_chebyshev(chebyshev_matches);
+        return;
+    }
+    if let Some(("rough", rough_matches)) = matches.subcommand() {
+        run_rough_or_smooth(rough_matches, false);
+        return;
+    }
+    if let Some(("smooth", smooth_matches)) = matches.subcommand() {
+        run_rough_or_smooth(smooth_matches, true);
+        return;
+    }
+    if let Some(("lucky", lucky_matches)) = matches.subcommand() {
+        run_lucky(lucky_matches);
+        return;
+    }
+    if let Some(("poly", poly_matches)) = matches.subcommand() {
+        run_poly(poly_matches);
+        return;
+    }
+    if let Some(("search", search_matches)) = matches.subcommand() {
+        run_search(search_matches);
+        return;
+    }
+    if let Some(("legendre", legendre_matches)) = matches.subcommand() {
+        run_legendre(legendre_matches);
+        return;
+    }
+    if let Some(("order", order_matches)) = matches.subcommand() {
+        run_order(order_matches);
+        return;
+    }
+    if let Some(("verify-manifest", verify_matches)) = matches.subcommand() {
+        run_verify_manifest(verify_matches);
+        return;
+    }
+    if let Some(("materialize", materialize_matches)) = matches.subcommand() {
+        run_materialize(materialize_matches);
+        return;
+    }
+    if let Some(("status", status_matches)) = matches.subcommand() {
+        run_status(status_matches);
+        return;
+    }
+    if let Some(("paths", paths_matches)) = matches.subcommand() {
+        run_paths(paths_matches);
+        return;
+    }
+    if let Some(("coverage", coverage_matches)) = matches.subcommand() {
+        run_coverage(coverage_matches);
+        return;
+    }
+    if let Some(("plan", plan_matches)) = matches.subcommand() {
+        run_plan(plan_matches);
+        return;
+    }
+    if let Some(("verify-signature", verify_matches)) = matches.subcommand() {
+        run_verify_signature(verify_matches);
+        return;
+    }
 
-    // Build a new thread pool with the specified number of threads
-    ThreadPoolBuilder::new().num_threads(thread_count).build_global().unwrap();
+    #[cfg(feature = "net")]
+    if let Some(("upload", upload_matches)) = matches.subcommand() {
+        run_upload(upload_matches);
+        return;
+    }
+    #[cfg(feature = "net")]
+    if let Some(("fetch-known", fetch_matches)) = matches.subcommand() {
+        run_fetch_known(fetch_matches);
+        return;
+    }
 
-    let start = matches
-    .value_of("start")
-    .map(|s| s.parse::<u128>().expect("Invalid start value"))
-    .unwrap_or(default_start);
+    // Create a new Tokio runtime
+    #[cfg(feature = "net")]
+    let rt = Runtime::new().unwrap();
+    #[cfg(feature = "net")]
+    let http = http_config(&matches).client().expect("Failed to set up the HTTP client");
+    // Use the runtime to block on the asynchronous function
+    #[cfg(feature = "net")]
+    let (default_start, default_end) = match rt.block_on(fetch_default_range(&http)) {
+        Ok(range) => range,
+        Err(e) => {
+            // Handle error, e.g., log it and use a default value or exit
+            eprintln!("Error fetching range: {}", e);
+            (0, 0) // Example default values, or you could exit the program
+        },
+    };
+    // Without networking there is no API to ask, so -s and -e are required
+    #[cfg(not(feature = "net"))]
+    let (default_start, default_end) = (0, 0);
 
-    let end = matches
-        .value_of("end")
-        .map(|e| e.parse::<u128>().expect("Invalid end value"))
-        .unwrap_or(default_end);
+    // Retrieve the number of CPUs from arguments, or use default
+    let num_cpus = number_arg::<usize>(&matches, "cpus").unwrap_or_else(|| num_cpus::get() - 1);
 
-    let primes_and_powers = Arc::new(Mutex::new(HashMap::new()));
+    // Ensure at least 1 CPU is used
+    let thread_count = if num_cpus > 1 { num_cpus - 1 } else { 1 };
 
-    // Clone `primes_and_powers` before moving it into the closure
-    let primes_and_powers_clone = primes_and_powers.clone();
-    let temp_storage: Arc<Mutex<Vec<(u128, Vec<BigInt>)>>> = Arc::new(Mutex::new(Vec::new()));
-    
-    let progress = Arc::new(AtomicUsize::new(0));
-    let total_numbers = end - start + 1; // Total range of numbers
-    
-    // Clone `progress` for the progress reporting thread
-    let progress_clone_for_thread = Arc::clone(&progress);
-    
-    // Clone `progress` for the main computation
-    let progress_clone_for_computation = Arc::clone(&progress);
-    
-    // Start a separate thread to report progress
-    let progress_thread = thread::spawn(move || {
-        while progress_clone_for_thread.load(Ordering::SeqCst) < total_numbers as usize {
-            println!("Progress: {}/{}", progress_clone_for_thread.load(Ordering::SeqCst), total_numbers);
-            thread::sleep(Duration::from_secs(120)); // Report every 2 minutes
-        }
+    // Workers pinned to a CPU list default to one per listed CPU
+    let cpu_list = matches.value_of("cpu-list").map(|list| match parse_cpu_list(list) {
+        Some(cpus) if !cpus.is_empty() => cpus,
+        _ => invalid(InvalidArgument::new("--cpu-list", format!("{:?} is not a CPU list", list)).hint("list CPUs and ranges of them, e.g. 0-7,16")),
     });
-    
-    let start_time = Instant::now();
-    
+    let thread_count = match &cpu_list {
+        Some(cpus) if !matches.is_present("cpus") => cpus.len(),
+        _ => thread_count,
+    };
 
-    // Clone `progress` again for the update after the main computation
-    let progress_clone_for_update = Arc::clone(&progress);
+    // Lower the priorities before any worker starts, so they all inherit them
+    set_priorities(&matches);
 
-    // Parallel iteration
-    let temp_storage_clone = temp_storage.clone();
-    (start..=end)
-        .into_par_iter()
-        .filter_map(|n| {
-            let big_n = BigInt::from(n);
-            if big_n.clone() % 2.to_bigint().unwrap() == 1.to_bigint().unwrap() || big_n == 2.to_bigint().unwrap() {
-                Some(big_n)
-            } else {
-                None
-            }
-        })
-        .for_each(move |big_n| {
-            if is_prime(big_n.clone()) {
-                let n = big_n.to_u128().expect("Number should fit in u128");
-                if let Some((squared, cubed, to_fourth_power)) = calculate_powers(n) {
-                    let mut storage = temp_storage_clone.lock().unwrap();
-                    storage.push((n, vec![squared, cubed, to_fourth_power]));
-    
-                    if storage.len() >= FLUSH_THRESHOLD {
-                        flush_to_csv(&mut *storage).expect("Failed to flush to CSV");
-                    }
-                } else {
-                    println!("Overflow error for {}", n);
-                }
-            }
-            // Update progress
-            progress_clone_for_computation.fetch_add(1, Ordering::SeqCst);
-        });
-    
-    // Flush any remaining data
-    {
-        let mut storage = temp_storage.lock().unwrap();
-        if !storage.is_empty() {
-            flush_to_csv(&mut *storage).expect("Failed to flush to CSV");
-        }
+    let (start, end) = range_args(&matches, (default_start, default_end));
+
+    // Several ranges replace the single one given by -s and -e
+    let ranges = match (matches.value_of("ranges"), matches.value_of("ranges-file")) {
+        (Some(spec), _) => parse_ranges(spec).unwrap_or_else(|e| invalid(InvalidArgument::new("--ranges", e.to_string()))),
+        (_, Some(file)) => read_ranges(file).or_exit("Failed to read the ranges file"),
+        _ => vec![(start, end)],
+    };
+    // The output is named and described by the bounds of all ranges
+    let start = ranges.iter().map(|range| range.0).min().unwrap_or(start);
+    let end = ranges.iter().map(|range| range.1).max().unwrap_or(end);
+    // Valid ranges can still be mistakes: ones without primes or far too large
+    let warnings: Vec<String> = ranges.iter().flat_map(|&(from, to)| validate::range_warnings(from, to)).collect();
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
     }
-    
-    let elapsed_duration = start_time.elapsed();
-    println!("Time taken: {:?}", elapsed_duration);
-    
-    // Ensure all progress is accounted for
-    progress_clone_for_update.store(total_numbers as usize, Ordering::SeqCst);
-    
-    // Join the progress thread
-    if let Err(_) = progress_thread.join() {
-        eprintln!("Failed to join progress reporting thread.");
+    if !warnings.is_empty() && matches.is_present("strict") {
+        eprintln!("Not generating under --strict");
+        Exit::Failure.exit();
     }
 
-    // Write final data to CSV
-    let data = primes_and_powers_clone.lock().unwrap();
-    write_to_csv(&*data).expect("Failed to write to CSV");
+    let algorithm = parsed_arg::<Algorithm>(&matches, "algorithm").unwrap();
 
-    // Post results to API
-    let _api = match rt.block_on(post_results("primes_and_powers.csv"))
-    {
-        Ok(_) => "Success",
-        Err(_) => "Failure",
+    let backend = parsed_arg::<Backend>(&matches, "backend").unwrap();
+
+    let ordering = parsed_arg::<RecordOrder>(&matches, "ordering").unwrap();
+
+    let numa = parsed_arg::<NumaPolicy>(&matches, "numa").unwrap();
+
+    // Name the output file for this run
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    // A `duckdb://` template names a database file to append to instead
+    let template = matches.value_of("output-template").unwrap();
+    let (duckdb, template) = match template.strip_prefix("duckdb://") {
+        Some(path) => (true, path),
+        None => (false, template),
     };
+    if duckdb && !cfg!(feature = "duckdb") {
+        eprintln!("Built without the duckdb feature; rebuild with --features duckdb to write to DuckDB");
+        Exit::InvalidArguments.exit();
     }
-
-// Function to calculate the powers of a number
-fn calculate_powers(n: u128) -> Option<(BigInt, BigInt, BigInt)> {
-    let big_n = n.to_bigint()?;
-    let squared = &big_n * &big_n;
-    let cubed = &squared * &big_n;
-    let to_fourth_power = &squared * &squared;
-    Some((squared, cubed, to_fourth_power))
-}
-
-// Function to check if a number is prime
-fn is_prime(big_n: BigInt) -> bool {
-    if let Some(n) = big_n.to_u128() {
-        // Handle numbers that fit into u128
-        match n {
-            0 | 1 => false,
-            2 | 3 => true,
-            _ if n % 2 == 0 || n % 3 == 0 => false,
-            _ => {
-                let limit = (n as f64).sqrt() as u128 + 1;
-                (5..=limit).step_by(6).all(|i| n % i != 0 && n % (i + 2) != 0)
+    let output = expand_output_template(template, start, end, timestamp)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Exit::for_error(&e).exit();
+        });
+    // A `redis://` template names a sorted set to add the primes to instead
+    let redis = output.to_str().and_then(|url| url.starts_with("redis://").then(|| RedisTarget::parse(url)));
+    let redis = redis.transpose().unwrap_or_else(|e| invalid(InvalidArgument::new("--output-template", e)));
+    println!("Output file: {}", output.display());
+    // Files derived from the output (manifest, signature, records) inherit its long form
+    let output = match redis {
+        Some(_) => output,
+        None => paths::long_path(&output),
+    };
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty() && redis.is_none()) {
+        std::fs::create_dir_all(dir).or_exit("Failed to create the output directory");
+    }
+
+    let mut builder = PrimeGenerator::builder()
+        .ranges(ranges)
+        .threads(thread_count)
+        .algorithm(algorithm)
+        .backend(backend)
+        .ordering(ordering)
+        .numa(numa)
+        .prime_cache(!matches.is_present("no-prime-cache"))
+        .auto_tune(matches.is_present("auto-tune"))
+        .cross_check(matches.is_present("cross-check"));
+    if let Some(cpus) = cpu_list {
+        builder = builder.cpu_list(cpus);
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(limit) = load_limit(&matches) {
+        let throttle = Arc::new(Throttle::new());
+        builder = builder.throttle(Arc::clone(&throttle));
+        throttle::watch_load(throttle, limit);
+    }
+    #[cfg(not(target_os = "linux"))]
+    if matches.is_present("max-load") || matches.is_present("max-temp") || matches.is_present("only-when-idle") {
+        eprintln!("--max-load, --max-temp and --only-when-idle are only supported on Linux");
+        Exit::InvalidArguments.exit();
+    }
+    let mut columns: Vec<Column> = matches
+        .value_of("columns")
+        .map(|spec| spec.split(',').map(|column| column.trim().parse()).collect())
+        .transpose()
+        .unwrap_or_else(|e| invalid(InvalidArgument::new("--columns", e)))
+        .unwrap_or_default();
+    if matches.is_present("include-prime-powers") {
+        builder = builder.include_prime_powers(true);
+        for column in [Column::Base, Column::Exponent] {
+            if !columns.contains(&column) {
+                columns.push(column);
             }
         }
-    } else {
-        // Use BigInt for very large numbers
-        if big_n <= 1.to_bigint().unwrap() || big_n == 2.to_bigint().unwrap() || big_n == 3.to_bigint().unwrap() {
-            return big_n > 1.to_bigint().unwrap();
+    }
+    let power_format: PowerFormat = matches.value_of("power-format").unwrap().parse().expect("Invalid power format");
+    let primes_only = matches.is_present("primes-only");
+    let dialect = CsvDialect {
+        delimiter: parse_delimiter(matches.value_of("delimiter").unwrap()).unwrap_or_else(|e| invalid(InvalidArgument::new("--delimiter", e))),
+        quoting: matches.value_of("quoting").unwrap().parse().expect("Invalid quoting"),
+        terminator: matches.value_of("line-terminator").unwrap().parse().expect("Invalid line terminator"),
+        header: !matches.is_present("no-header"),
+    };
+    let avro = matches.value_of("format") == Some("avro");
+    // Manifests and uploads read the output back as CSV
+    let csv_output = !avro && !duckdb && redis.is_none();
+    builder = match matches.value_of("io") {
+        _ if redis.is_some() => builder.sink(RedisSink::connect(redis.as_ref().unwrap()).or_exit("Failed to connect to Redis")),
+        #[cfg(feature = "duckdb")]
+        _ if duckdb => builder.sink(
+            DuckDbSink::new(&output, "primes")
+                .columns(columns)
+                .power_format(power_format)
+                .primes_only(primes_only),
+        ),
+        _ if avro => builder.sink(
+            AvroSink::new(&output)
+                .columns(columns)
+                .power_format(power_format)
+                .primes_only(primes_only),
+        ),
+        Some("mmap") => builder.sink(
+            MmapCsvSink::new(&output)
+                .or_exit("Failed to open the output file")
+                .columns(columns)
+                .power_format(power_format)
+                .primes_only(primes_only)
+                .dialect(dialect),
+        ),
+        #[cfg(target_os = "linux")]
+        Some("uring") => builder.sink(
+            UringCsvSink::new(&output)
+                .or_exit("Failed to set up io_uring")
+                .columns(columns)
+                .power_format(power_format)
+                .primes_only(primes_only)
+                .dialect(dialect),
+        ),
+        _ => builder.sink(
+            CsvSink::new(&output)
+                .columns(columns)
+                .power_format(power_format)
+                .primes_only(primes_only)
+                .dialect(dialect),
+        ),
+    };
+    let mut filters: Vec<Box<dyn Fn(u128) -> bool + Send + Sync>> = match matches.value_of("filter") {
+        Some(filters) => filters
+            .split(',')
+            .map(|filter| prime_filter(filter.trim()))
+            .collect::<std::result::Result<_, _>>()
+            .unwrap_or_else(|e| invalid(InvalidArgument::new("--filter", e))),
+        None => Vec::new(),
+    };
+    if let Some(rate) = parsed_arg::<f64>(&matches, "sample") {
+        let seed = number_arg::<u64>(&matches, "sample-seed").unwrap();
+        let sample = Sample::new(rate, seed).unwrap_or_else(|e| invalid(InvalidArgument::new("--sample", e.to_string())));
+        filters.push(Box::new(move |p| sample.keeps(p)));
+    }
+    if !filters.is_empty() {
+        builder = builder.filter(move |p| filters.iter().all(|filter| filter(p)));
+    }
+    // The density per decade always goes into the summary
+    let mut aggregates = vec![Aggregate::Decades];
+    if let Some(requested) = matches.value_of("aggregate") {
+        for aggregate in requested.split(',') {
+            let aggregate = validate::parsed("--aggregate", aggregate).unwrap_or_else(|e| invalid(e));
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
         }
-        if &big_n % 2.to_bigint().unwrap() == Zero::zero() || &big_n % 3.to_bigint().unwrap() == Zero::zero() {
-            return false;
+    }
+    builder = builder.aggregate(aggregates);
+    if let Some(requested) = matches.value_of("track-records") {
+        let kinds = requested
+            .split(',')
+            .map(|kind| validate::parsed::<RecordKind>("--track-records", kind))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| invalid(e));
+        let path = match matches.value_of("records-file") {
+            Some(path) => paths::long_path(path),
+            None if redis.is_some() => {
+                eprintln!("Give --records-file to track records of a Redis output");
+                Exit::InvalidArguments.exit();
+            }
+            None => records_path(&output),
+        };
+        let records = open_records(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to open the records file {}: {}", path.display(), e);
+            Exit::for_error(&e).exit();
+        });
+        println!("Records file: {}", path.display());
+        builder = builder.track_records(kinds, records);
+    }
+    if let Some(segment_size) = number_arg(&matches, "segment-size") {
+        builder = builder.segment_size(segment_size);
+    }
+    if let Some(exclude_file) = matches.value_of("exclude-file") {
+        builder = builder.exclude(read_ranges(exclude_file).or_exit("Failed to read the exclude file"));
+    }
+    if let Some(queue_depth) = positive_arg(&matches, "queue-depth") {
+        builder = builder.queue_depth(queue_depth);
+    }
+    if let Some(memory_limit) = matches.value_of("memory-limit") {
+        builder = builder.memory_limit(parse_size(memory_limit).unwrap_or_else(|| {
+            invalid(InvalidArgument::new("--memory-limit", format!("{:?} is not a size", memory_limit)).hint("give bytes with an optional K, M, G or T suffix, e.g. 4G"))
+        }));
+    }
+    let generator = builder.build().or_exit("Invalid generator configuration");
+    confirm_run(&matches, generator.uncovered_ranges(), csv_output.then_some(primes_only), algorithm, thread_count);
+    if generator.algorithm() == Algorithm::SegmentedSieve {
+        println!("Segment size: {}", generator.segment_size());
+    }
+    if matches.is_present("exclude-file") || matches.is_present("ranges") || matches.is_present("ranges-file") {
+        let uncovered = generator.uncovered_ranges();
+        println!("Searching {} numbers in {} ranges", generator.total_numbers(), uncovered.len());
+    }
+    let algorithm_name = generator.algorithm().to_string();
+    // Warn about ranges an earlier run already generated, per the registry
+    #[cfg(feature = "registry")]
+    let (registry, generated) = (matches.value_of("registry").map(open_registry), generator.uncovered_ranges().to_vec());
+    #[cfg(feature = "registry")]
+    if let Some(registry) = &registry {
+        if warn_about_overlaps(registry, &generated) && matches.is_present("strict") {
+            eprintln!("Not generating ranges again under --strict");
+            Exit::Failure.exit();
         }
+    }
+    #[cfg(not(feature = "registry"))]
+    if matches.is_present("registry") {
+        eprintln!("Built without the registry feature; rebuild with --features registry to use a range registry");
+        Exit::InvalidArguments.exit();
+    }
+    let parameters = GenerationParameters {
+        start,
+        end,
+        algorithm: algorithm_name.clone(),
+        backend: backend.to_string(),
+        ordering: ordering.to_string(),
+        threads: thread_count,
+        segment_size: generator.segment_size(),
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
 
-        let mut i = BigInt::from(5);
-        while &i * &i <= big_n {
-            if &big_n % &i == Zero::zero() || &big_n % (&i + 2) == Zero::zero() {
-                return false;
+    // Check the mail settings now rather than after a long run
+    #[cfg(feature = "smtp")]
+    let smtp_server = matches.value_of("smtp-host").map(|host| {
+        SmtpServer::from_env(host).unwrap_or_else(|e| invalid(InvalidArgument::new("--smtp-host", e.to_string())))
+    });
+
+    let progress = generator.progress();
+    let total_numbers = generator.total_numbers(); // Total range of numbers
+    
+    // Group digits the way the locale does, unless the output is for a machine
+    let numbers = match matches.is_present("plain-numbers") {
+        true => NumberFormat::PLAIN,
+        false => NumberFormat::from_env(),
+    };
+
+    // Clone `progress` for the progress reporting thread
+    let progress_clone_for_thread = Arc::clone(&progress);
+    
+    // Start a separate thread to report progress
+    let progress_thread = thread::spawn(move || {
+        let started = Instant::now();
+        while progress_clone_for_thread.load(Ordering::SeqCst) < total_numbers as usize {
+            let done = progress_clone_for_thread.load(Ordering::SeqCst) as u128;
+            let percent = done as f64 / total_numbers.max(1) as f64 * 100.0;
+            match done {
+                0 => println!("Progress: 0/{} (0.0%)", numbers.count(total_numbers)),
+                _ => println!(
+                    "Progress: {}/{} ({:.1}%, {} numbers/s)",
+                    numbers.count(done),
+                    numbers.count(total_numbers),
+                    percent,
+                    numbers.si(done as f64 / started.elapsed().as_secs_f64())
+                ),
+            }
+            thread::sleep(Duration::from_secs(120)); // Report every 2 minutes
+        }
+    });
+    
+    // Tell systemd the run started, then feed its watchdog with the progress
+    #[cfg(target_os = "linux")]
+    let notify_thread = matches.is_present("sd-notify").then(|| {
+        if !sd_notify(&format!("READY=1\nSTATUS=Generating {} numbers", total_numbers)) && matches.is_present("strict") {
+            Exit::Failure.exit();
+        }
+        let period = systemd::watchdog_interval().map_or(Duration::from_secs(10), |interval| interval / 2);
+        let progress = Arc::clone(&progress);
+        thread::spawn(move || {
+            while progress.load(Ordering::SeqCst) < total_numbers as usize {
+                sd_notify(&format!("WATCHDOG=1\nSTATUS=Progress: {}/{}", progress.load(Ordering::SeqCst), total_numbers));
+                thread::sleep(period);
+            }
+        })
+    });
+
+    // Answer liveness and readiness probes for the rest of the run
+    if let Some(address) = matches.value_of("health-listen") {
+        let stall_timeout = number_arg(&matches, "health-stall-timeout").map(Duration::from_secs).unwrap();
+        let mut check = HealthCheck::new(Arc::clone(&progress), generator.backlog(), total_numbers as usize, stall_timeout)
+            .queued_batches(generator.queued_batches());
+        if let Some(memory_limit) = matches.value_of("memory-limit").and_then(parse_size) {
+            check = check.max_backlog(memory_limit);
+        }
+        let check = Arc::new(check);
+        match TcpListener::bind(address) {
+            Ok(listener) => {
+                check.set_ready();
+                check.serve(listener);
+            }
+            Err(e) => {
+                eprintln!("Failed to listen for health probes on {}: {}", address, e);
+                Exit::for_error(&e).exit();
             }
-            i = i + 6;
         }
-        true
     }
+
+    let start_time = Instant::now();
+
+    let summary = match generator.run() {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("{}", e);
+            Exit::for_error(&e).exit();
+        }
+    };
+    
+    let elapsed_duration = start_time.elapsed();
+    print_phase_times(elapsed_duration, total_numbers, numbers);
+    if let Some(tuning) = summary.tuning {
+        println!("Auto-tuned: chunk size {}, flush threshold {}", tuning.chunk_size, tuning.flush_threshold);
+    }
+    if summary.memory_flushes > 0 {
+        println!("Memory limit: {} early flushes", summary.memory_flushes);
+    }
+    if let Some(chunk_size) = summary.shrunk_chunk_size {
+        println!("Memory limit: chunk size shrunk to {}", chunk_size);
+    }
+    if summary.writer_stalls > 0 {
+        println!(
+            "Writer: workers waited {} times for {:.2?} on a full queue, at most {} batches queued",
+            numbers.count(summary.writer_stalls as u128),
+            summary.writer_stalled,
+            summary.max_queued_batches
+        );
+    }
+    if let Some(aggregates) = &summary.aggregates {
+        print_decade_densities(&aggregates.decades, numbers);
+        if matches.is_present("aggregate") {
+            println!("Aggregates: {}", serde_json::to_string(aggregates).expect("Failed to serialize the aggregates"));
+        }
+    }
+    for record in &summary.records {
+        match record.kind {
+            RecordKind::Palindromic => println!("Largest palindromic prime: {} ({} digits)", numbers.count(record.prime), record.value),
+            RecordKind::Twin => println!("Largest twin primes: {}, {}", numbers.count(record.prime), numbers.count(record.value)),
+            RecordKind::Gap => println!("Largest prime gap: {} after {}", numbers.count(record.value), numbers.count(record.prime)),
+        }
+    }
+    #[cfg(feature = "smtp")]
+    let report = completion_report(&matches, &parameters, &output, elapsed_duration, &summary, numbers);
+    
+    // Ensure all progress is accounted for
+    progress.store(total_numbers as usize, Ordering::SeqCst);
+    
+    // Join the progress thread
+    if progress_thread.join().is_err() {
+        eprintln!("Failed to join progress reporting thread.");
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(notify_thread) = notify_thread {
+        sd_notify("STOPPING=1\nSTATUS=Writing the manifest and publishing");
+        let _ = notify_thread.join();
+    }
+
+    // Describe the output file so later copies can be checked against it
+    if !matches.is_present("no-manifest") && csv_output && output.exists() {
+        Manifest::for_file_with(&output, parameters, dialect)
+            .and_then(|manifest| Manifest { aggregates: summary.aggregates, ..manifest }.write(manifest_path(&output)))
+            .or_exit("Failed to write the manifest");
+    }
+
+    // Sign the output file's checksum, so whoever holds the public key can
+    // check it came from this key and hasn't changed since
+    if let Some(key_file) = matches.value_of("sign-key").filter(|_| redis.is_none()) {
+        read_signing_key(key_file)
+            .and_then(|key| ShardSignature::sign(&output, &key))
+            .and_then(|signature| signature.write(signature_path(&output)))
+            .or_exit("Failed to sign the output file");
+    }
+
+    // Record the generated ranges, so later runs and `status` know about them
+    #[cfg(feature = "registry")]
+    if let Some(mut registry) = registry {
+        let output = match duckdb {
+            true => format!("duckdb://{}", output.display()),
+            false => output.display().to_string(),
+        };
+        for &(start, end) in &generated {
+            let entry = RangeEntry {
+                start,
+                end,
+                algorithm: algorithm_name.clone(),
+                engine_version: env!("CARGO_PKG_VERSION").to_string(),
+                output: output.clone(),
+                recorded_at: timestamp,
+            };
+            registry.record(&entry).or_exit("Failed to record the range in the registry");
+        }
+    }
+
+    // How the run fails under --strict, once it has published and emailed
+    // what it could
+    #[cfg_attr(not(feature = "net"), allow(unused_mut))]
+    let mut failure: Option<Exit> = None;
+
+    // Post results to API, signed when a shared secret is configured
+    #[cfg(feature = "net")]
+    if !matches.is_present("no-upload") && csv_output {
+        let publisher = publisher_from_spec(matches.value_of("publisher").unwrap(), &http, &upload_options(&matches))
+            .unwrap_or_else(|e| invalid(InvalidArgument::new("--publisher", e.to_string())));
+        let result = {
+            let _timer = timing::time(timing::Phase::Upload);
+            rt.block_on(publisher.publish(&output, &mut |_, _| {}))
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to publish results to {} ({}); retry with `prime_generator upload`", publisher.describe(), e);
+            if matches.is_present("strict") {
+                failure.get_or_insert(Exit::for_network_error(&*e));
+            }
+        }
+    }
+
+    // Email the report, with the manifest, to whoever asked for it
+    #[cfg(feature = "smtp")]
+    if let (Some(server), Some(to)) = (&smtp_server, matches.value_of("email-to")) {
+        if let Err(e) = send_report(&matches, server, to, &output, report) {
+            eprintln!("Failed to email the report through {}: {}", server.host, e);
+            if matches.is_present("strict") {
+                failure.get_or_insert(Exit::for_error(&e));
+            }
+        }
+    }
+
+    // The phases again, now with the upload, for tooling to pick up
+    let summary = serde_json::json!({ "elapsed_seconds": start_time.elapsed().as_secs_f64(), "phases": timing::phase_times() });
+    println!("Timings: {}", summary);
+    // Under --strict, the first warning decides how the run failed
+    if let Some(failure) = failure {
+        failure.exit();
+    }
+    }
+
+// Function to print the wall-clock time of the generation and the time its
+// phases took, summed across threads
+fn print_phase_times(elapsed: Duration, total_numbers: u128, numbers: NumberFormat) {
+    let times = timing::phase_times();
+    println!("Time taken: {:?}", elapsed);
+    println!("Throughput: {} numbers/s", numbers.si(total_numbers as f64 / elapsed.as_secs_f64()));
+    println!(
+        "Phases (summed across threads): candidates {:.3}s, primality {:.3}s, powers {:.3}s, serialization {:.3}s, I/O {:.3}s",
+        times.candidates, times.primality, times.powers, times.serialization, times.io
+    );
+}
+
+// Function to open the `--track-records` file for appending, starting a new
+// file with the header
+fn open_records(path: &Path) -> Result<BufWriter<File>> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{}", record_tracker::HEADER)?;
+    }
+    Ok(BufWriter::new(file))
+}
+
+// Function to add the arguments configuring the HTTP client
+#[cfg(feature = "net")]
+fn with_net_args(app: App<'static>) -> App<'static> {
+    app
+        // Define `proxy` argument.
+        .arg(
+            Arg::with_name("proxy")
+                .long("proxy")
+                .takes_value(true)
+                .global(true)
+                .value_name("url")
+                .help("Proxy for API requests (default: HTTPS_PROXY, HTTP_PROXY and NO_PROXY)"),
+        )
+        // Define `ca-bundle` argument.
+        .arg(
+            Arg::with_name("ca-bundle")
+                .long("ca-bundle")
+                .takes_value(true)
+                .global(true)
+                .value_name("ca.pem")
+                .help("Also trust the root certificates in this PEM bundle"),
+        )
+        // Define `client-cert` argument.
+        .arg(
+            Arg::with_name("client-cert")
+                .long("client-cert")
+                .takes_value(true)
+                .global(true)
+                .value_name("cert.pem")
+                .requires("client-key")
+                .help("Client certificate chain (PEM) for mutual TLS"),
+        )
+        // Define `client-key` argument.
+        .arg(
+            Arg::with_name("client-key")
+                .long("client-key")
+                .takes_value(true)
+                .global(true)
+                .value_name("key.pem")
+                .requires("client-cert")
+                .help("Private key (PKCS#8 PEM) of the client certificate"),
+        )
+        // Define `upload-batch-size` argument.
+        .arg(
+            Arg::with_name("upload-batch-size")
+                .long("upload-batch-size")
+                .takes_value(true)
+                .global(true)
+                .help("Records per request when posting results (default: 10000)"),
+        )
+        // Define `upload-rate` argument.
+        .arg(
+            Arg::with_name("upload-rate")
+                .long("upload-rate")
+                .takes_value(true)
+                .global(true)
+                .help("Most requests per second when posting results (default: unlimited)"),
+        )
+        // Define `api-url` argument.
+        .arg(
+            Arg::with_name("api-url")
+                .long("api-url")
+                .takes_value(true)
+                .global(true)
+                .default_value(DEFAULT_RESULTS_URL)
+                .help("Endpoint results are posted to"),
+        )
+        // Define `publisher` argument.
+        .arg(
+            Arg::with_name("publisher")
+                .long("publisher")
+                .takes_value(true)
+                .global(true)
+                .default_value("http")
+                .help("Where results are published: http (the API), none, dir:<path>, s3://<bucket>/<prefix> or kafka:<REST proxy url>/<topic>"),
+        )
+        // Define `no-upload` argument.
+        .arg(
+            Arg::with_name("no-upload")
+                .long("no-upload")
+                .help("Keep the results offline; publish them later with the `upload` subcommand"),
+        )
+        // Define `fetch-known` subcommand for reference datasets.
+        .subcommand(
+            App::new("fetch-known")
+                .about("Downloads reference prime datasets from a mirror and checks the engine against them")
+                .arg(
+                    Arg::with_name("until")
+                        .long("until")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Fetch the datasets covering primes up to this bound (e.g. 1000000000, 10^9 or 1e9)"),
+                )
+                .arg(
+                    Arg::with_name("mirror")
+                        .long("mirror")
+                        .takes_value(true)
+                        .default_value(DEFAULT_MIRROR)
+                        .help("Base URL of the mirror serving index.json and the datasets"),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .long("dir")
+                        .takes_value(true)
+                        .help("Where datasets are stored (default: `known` under the user cache dir)"),
+                )
+                .arg(
+                    Arg::with_name("verify")
+                        .long("verify")
+                        .help("Compare every fetched dataset with the primes the engine finds"),
+                ),
+        )
+        // Define `upload` subcommand for publishing existing results.
+        .subcommand(
+            App::new("upload")
+                .about("Publishes a results file; interrupted uploads of the same file to the API resume where they stopped")
+                .arg(
+                    Arg::with_name("file")
+                        .takes_value(true)
+                        .default_value("primes_and_powers.csv")
+                        .help("Results to upload"),
+                )
+                .arg(
+                    Arg::with_name("restart")
+                        .long("restart")
+                        .help("Send every batch (or S3 part) again instead of resuming"),
+                ),
+        )
+}
+
+// Without networking there is no HTTP client to configure
+#[cfg(not(feature = "net"))]
+fn with_net_args(app: App<'static>) -> App<'static> {
+    app
 }
 
-// Function to flush data to CSV and clear the temporary storage
-fn flush_to_csv(temp_storage: &mut Vec<(u128, Vec<BigInt>)>) -> Result<()> {
-    let mut wtr = Writer::from_writer(OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open("primes_and_powers.csv")?);
+// Samples taken per second of CPU time by `--profile`, off the round 100 so
+// sampling doesn't fall into step with periodic work
+#[cfg(target_os = "linux")]
+const PROFILE_FREQUENCY: u32 = 99;
 
-    for (prime, powers) in temp_storage.iter() {
-        let record = PrimeRecord {
-            prime: *prime,
-            squared: powers[0].to_str_radix(10),
-            cubed: powers[1].to_str_radix(10),
-            to_fourth_power: powers[2].to_str_radix(10),
+// A running `--profile`, written out when the program ends
+#[cfg(target_os = "linux")]
+struct FlamegraphOnExit {
+    profiler: Option<Profiler>,
+    path: PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for FlamegraphOnExit {
+    fn drop(&mut self) {
+        let Some(profiler) = self.profiler.take() else {
+            return;
         };
-        wtr.serialize(record)?;
+        let folded_path = self.path.with_extension("folded");
+        let written = profiler.finish().and_then(|profile| {
+            let folded = profile.folded();
+            profile::write_folded(&folded, BufWriter::new(File::create(&folded_path)?))?;
+            let title = format!("prime_generator {} ({} samples)", std::env::args().skip(1).collect::<Vec<_>>().join(" "), profile.samples());
+            profile::write_flamegraph(&folded, &title, BufWriter::new(File::create(&self.path)?))?;
+            Ok(profile.samples())
+        });
+        match written {
+            Ok(samples) => println!("Wrote a flamegraph of {} samples to {} (folded stacks in {})", samples, self.path.display(), folded_path.display()),
+            Err(e) => eprintln!("Failed to write the profile to {}: {}", self.path.display(), e),
+        }
     }
+}
 
-    wtr.flush()?;
-    temp_storage.clear(); // Clear the temporary storage after flushing
+// Function to start profiling for `--profile`, exiting if it can't
+#[cfg(target_os = "linux")]
+fn start_profile(path: &str) -> FlamegraphOnExit {
+    match Profiler::start(PROFILE_FREQUENCY) {
+        Ok(profiler) => FlamegraphOnExit { profiler: Some(profiler), path: PathBuf::from(path) },
+        Err(e) => {
+            eprintln!("Failed to start profiling: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+}
+
+// Function to refuse `--profile` where there is no profiler
+#[cfg(not(target_os = "linux"))]
+fn start_profile(_: &str) {
+    eprintln!("--profile is only supported on Linux");
+    Exit::InvalidArguments.exit();
+}
+
+// Function to add the arguments configuring the completion email
+#[cfg(feature = "smtp")]
+fn with_smtp_args(app: App<'static>) -> App<'static> {
+    app
+        // Define `email-to` argument.
+        .arg(
+            Arg::with_name("email-to")
+                .long("email-to")
+                .takes_value(true)
+                .value_name("addr[,addr]")
+                .requires("smtp-host")
+                .help("Email the run summary, with the manifest attached, to these addresses when the run finishes"),
+        )
+        // Define `smtp-host` argument.
+        .arg(
+            Arg::with_name("smtp-host")
+                .long("smtp-host")
+                .takes_value(true)
+                .value_name("host[:port]")
+                .help("SMTP server to send --email-to through (port 587 with STARTTLS unless given; 465 for TLS); credentials from PRIMEGEN_SMTP_USER and PRIMEGEN_SMTP_PASSWORD"),
+        )
+        // Define `email-from` argument.
+        .arg(
+            Arg::with_name("email-from")
+                .long("email-from")
+                .takes_value(true)
+                .value_name("addr")
+                .help("Sender of --email-to (default: PRIMEGEN_SMTP_USER, or prime_generator@localhost)"),
+        )
+}
+
+// Without the smtp feature there is nothing to send mail with
+#[cfg(not(feature = "smtp"))]
+fn with_smtp_args(app: App<'static>) -> App<'static> {
+    app
+}
+
+// Function to describe a finished run for the completion email
+#[cfg(feature = "smtp")]
+fn completion_report(
+    matches: &ArgMatches,
+    parameters: &GenerationParameters,
+    output: &Path,
+    elapsed: Duration,
+    summary: &RunSummary,
+    numbers: NumberFormat,
+) -> String {
+    let times = timing::phase_times();
+    let mut report = format!(
+        "Generated {} to {} with {} on {} threads in {:?}.\nOutput file: {}\n",
+        numbers.count(parameters.start),
+        numbers.count(parameters.end),
+        parameters.algorithm,
+        parameters.threads,
+        elapsed,
+        output.display()
+    );
+    report.push_str(&format!(
+        "Phases (summed across threads): candidates {:.3}s, primality {:.3}s, powers {:.3}s, serialization {:.3}s, I/O {:.3}s\n",
+        times.candidates, times.primality, times.powers, times.serialization, times.io
+    ));
+    if let Some(aggregates) = &summary.aggregates {
+        let primes: u64 = aggregates.decades.iter().map(|decade| decade.primes).sum();
+        report.push_str(&format!("Primes: {}\n", numbers.count(primes)));
+        if matches.is_present("aggregate") {
+            report.push_str(&format!("Aggregates: {}\n", serde_json::to_string(aggregates).expect("Failed to serialize the aggregates")));
+        }
+    }
+    for record in &summary.records {
+        report.push_str(&format!("Largest {}: {}, {}\n", record.kind, numbers.count(record.prime), numbers.count(record.value)));
+    }
+    report
+}
+
+// Function to send the completion email, attaching the manifest if there is
+// one; a failed email only fails the run under --strict
+#[cfg(feature = "smtp")]
+fn send_report(matches: &ArgMatches, server: &SmtpServer, to: &str, output: &Path, report: String) -> Result<()> {
+    let from = match matches.value_of("email-from") {
+        Some(from) => from.to_string(),
+        None => server.credentials.as_ref().map_or_else(|| "prime_generator@localhost".to_string(), |(user, _)| user.clone()),
+    };
+    let manifest = manifest_path(output);
+    let attachments = match std::fs::read(&manifest) {
+        Ok(content) => vec![(manifest.file_name().unwrap().to_string_lossy().into_owned(), content)],
+        Err(_) => Vec::new(),
+    };
+    let email = Email {
+        from,
+        to: to.split(',').map(|to| to.trim().to_string()).collect(),
+        subject: format!("prime_generator finished {}", output.display()),
+        body: report,
+        attachments,
+    };
+    smtp::send(server, &email)?;
+    println!("Emailed the report to {}", to);
     Ok(())
 }
 
-fn write_to_csv(data: &HashMap<u128, Vec<BigInt>>) -> Result<()> {
-    let path = "primes_and_powers.csv";
-    let file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(path)?;
+// Function to add the arguments configuring telemetry export
+#[cfg(feature = "otel")]
+fn with_otel_args(app: App<'static>) -> App<'static> {
+    // Define `otel-endpoint` argument.
+    app.arg(
+        Arg::with_name("otel-endpoint")
+            .long("otel-endpoint")
+            .takes_value(true)
+            .global(true)
+            .value_name("url")
+            .help("Export traces and metrics to this OTLP/HTTP collector (e.g. http://localhost:4318)"),
+    )
+}
 
-    let mut wtr = Writer::from_writer(file);
+// Without the otel feature there is nothing to export
+#[cfg(not(feature = "otel"))]
+fn with_otel_args(app: App<'static>) -> App<'static> {
+    app
+}
 
-    for (prime, powers) in data {
-        let record = PrimeRecord {
-            prime: *prime,
-            squared: powers[0].to_str_radix(10),
-            cubed: powers[1].to_str_radix(10),
-            to_fourth_power: powers[2].to_str_radix(10),
+// Function to build the HTTP client configuration from the arguments
+#[cfg(feature = "net")]
+fn http_config(matches: &ArgMatches) -> HttpConfig {
+    HttpConfig {
+        proxy: matches.value_of("proxy").map(String::from),
+        ca_bundle: matches.value_of("ca-bundle").map(PathBuf::from),
+        client_cert: matches
+            .value_of("client-cert")
+            .zip(matches.value_of("client-key"))
+            .map(|(cert, key)| (PathBuf::from(cert), PathBuf::from(key))),
+    }
+}
+
+// Function to build the upload options from the arguments and environment
+#[cfg(feature = "net")]
+fn upload_options(matches: &ArgMatches) -> UploadOptions {
+    let mut options = UploadOptions {
+        api_url: matches.value_of("api-url").unwrap().to_string(),
+        secret: std::env::var("PRIMEGEN_API_SECRET").ok().map(String::into_bytes),
+        ..UploadOptions::default()
+    };
+    if let Some(batch_size) = matches.value_of("upload-batch-size") {
+        options.batch_size = validate::number("--upload-batch-size", batch_size).unwrap_or_else(|e| invalid(e));
+    }
+    if let Some(rate) = matches.value_of("upload-rate") {
+        options.rate = Some(validate::parsed("--upload-rate", rate).unwrap_or_else(|e| invalid(e)));
+    }
+    options
+}
+
+// Function to run the `upload` subcommand
+#[cfg(feature = "net")]
+fn run_upload(matches: &ArgMatches) {
+    let file = matches.value_of("file").unwrap();
+    if matches.is_present("restart") {
+        let _ = std::fs::remove_file(upload_state_path(file));
+    }
+    let http = http_config(matches).client().expect("Failed to set up the HTTP client");
+    let publisher = publisher_from_spec(matches.value_of("publisher").unwrap(), &http, &upload_options(matches))
+        .unwrap_or_else(|e| invalid(InvalidArgument::new("--publisher", e.to_string())));
+    let rt = Runtime::new().unwrap();
+    let json = matches.is_present("json");
+    let result = rt.block_on(publisher.publish(Path::new(file), &mut |sent, total| {
+        // Progress isn't part of the JSON document
+        match json {
+            true => eprintln!("Uploaded {}/{} records", sent, total),
+            false => println!("Uploaded {}/{} records", sent, total),
+        }
+    }));
+    match result {
+        Ok(()) if json => print_json(&serde_json::json!({ "file": file, "publisher": publisher.describe() })),
+        Ok(()) => println!("Published {} to {}", file, publisher.describe()),
+        Err(e) => {
+            eprintln!("Upload to {} failed: {}; run the same command again to resume", publisher.describe(), e);
+            Exit::for_network_error(&*e).exit();
+        }
+    }
+}
+
+// Function to run the `fetch-known` subcommand
+#[cfg(feature = "net")]
+fn run_fetch_known(matches: &ArgMatches) {
+    let until = number_arg::<u64>(matches, "until").unwrap();
+    let dir = matches
+        .value_of("dir")
+        .map(PathBuf::from)
+        .or_else(default_known_dir)
+        .unwrap_or_else(|| invalid(InvalidArgument::new("--dir", "there is no cache directory on this platform").hint("pass --dir")));
+    let http = http_config(matches).client().expect("Failed to set up the HTTP client");
+    let rt = Runtime::new().unwrap();
+    let datasets = match rt.block_on(fetch_known(&http, matches.value_of("mirror").unwrap(), until, &dir)) {
+        Ok(datasets) => datasets,
+        Err(e) => {
+            eprintln!("Failed to fetch known datasets: {}", e);
+            Exit::for_network_error(&*e).exit();
+        }
+    };
+    let json = matches.is_present("json");
+    if !json {
+        println!("Fetched datasets up to {} into {} ({} files)", until, dir.display(), datasets.len());
+    }
+    // Each dataset's differences from the primes sieved here, if verified
+    let mut verified = Vec::new();
+    let mut failed = false;
+    for dataset in datasets.iter().filter(|_| matches.is_present("verify")) {
+        let difference = verify_known(&dir, dataset).or_exit("Failed to read a dataset");
+        match &difference {
+            _ if json => {}
+            None => println!("{}: primes in [{}, {}] match", dataset.file, dataset.start, dataset.end),
+            Some(difference) => eprintln!("{}", difference),
+        }
+        failed |= difference.is_some();
+        verified.push(serde_json::json!({ "file": dataset.file, "start": dataset.start, "end": dataset.end, "difference": difference }));
+    }
+    if json {
+        let files: Vec<&str> = datasets.iter().map(|dataset| dataset.file.as_str()).collect();
+        let verified = matches.is_present("verify").then_some(verified);
+        print_json(&serde_json::json!({ "until": until, "dir": dir, "files": files, "verified": verified }));
+    }
+    if failed {
+        Exit::Mismatch.exit();
+    }
+}
+
+// Function to parse a byte count with an optional binary K, M, G or T suffix
+fn parse_size(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let (digits, shift) = match value.char_indices().last()? {
+        (i, 'k' | 'K') => (&value[..i], 10),
+        (i, 'm' | 'M') => (&value[..i], 20),
+        (i, 'g' | 'G') => (&value[..i], 30),
+        (i, 't' | 'T') => (&value[..i], 40),
+        _ => (value, 0),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(1usize.checked_shl(shift)?)
+}
+
+// Function to run the `visualize` subcommands
+fn run_visualize(matches: &ArgMatches) {
+    if let Some(("ulam", ulam_matches)) = matches.subcommand() {
+        let size = number_arg::<u32>(ulam_matches, "size").unwrap();
+        let start = number_arg::<u64>(ulam_matches, "start").unwrap();
+        let output = ulam_matches.value_of("output").unwrap();
+        visualize::render_ulam(start, size, &paths::long_path(output)).or_exit("Failed to render Ulam spiral");
+        match ulam_matches.is_present("json") {
+            true => print_json(&serde_json::json!({ "image": output, "kind": "ulam" })),
+            false => println!("Wrote Ulam spiral to {}", output),
+        }
+    } else if let Some(("gaps", gaps_matches)) = matches.subcommand() {
+        let bins = number_arg::<usize>(gaps_matches, "bins").unwrap();
+        let mut primes: Vec<u128> = match gaps_matches.value_of("input") {
+            Some(input) => read_records(input)
+                .or_exit("Failed to read input CSV")
+                .into_iter()
+                .map(|record| record.prime)
+                .collect(),
+            None => {
+                let (start, end) = range_args::<u64>(gaps_matches, (0, 0));
+                sieve::primes_in_range(start, end).into_iter().map(u128::from).collect()
+            }
         };
-        wtr.serialize(record)?;
+        let output = gaps_matches.value_of("output").unwrap();
+        let histogram = visualize::render_gap_histogram(&mut primes, bins, &paths::long_path(output))
+            .or_exit("Failed to render gap histogram");
+        match gaps_matches.is_present("json") {
+            true => print_json(&serde_json::json!({
+                "image": output,
+                "kind": "gaps",
+                "gaps": histogram.gaps,
+                "min_gap": histogram.min_gap,
+                "max_gap": histogram.max_gap,
+                "bin_width": histogram.bin_width,
+            })),
+            false => {
+                println!(
+                    "Gaps: {} between {} and {} ({} per bin)",
+                    histogram.gaps, histogram.min_gap, histogram.max_gap, histogram.bin_width
+                );
+                println!("Wrote gap histogram to {}", output);
+            }
+        }
     }
+}
 
-    wtr.flush()?;
-    Ok(())
+// Function to run the `check` subcommand, testing the numbers of each chunk of
+// input in parallel and writing the verdicts in input order
+fn run_check(matches: &ArgMatches) {
+    let output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(paths::long_path(path)).or_exit("Failed to create the output file"))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let input: Box<dyn BufRead> = match matches.values_of("numbers") {
+        Some(numbers) => Box::new(io::Cursor::new(numbers.collect::<Vec<_>>().join("\n").into_bytes())),
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut source = NumberFile::new(input);
+
+    // On a terminal, each chunk is shown as a table rather than CSV or JSON
+    let table = !matches.is_present("json") && matches.value_of("output").is_none() && table::stdout_is_terminal();
+    let color = table::color_enabled(matches.is_present("no-color"));
+
+    let mut invalid = 0;
+    let mut rows = match table {
+        true => None,
+        false => Some(Rows::new(output, "number,prime", matches.is_present("json"))),
+    };
+    let result = (|| loop {
+        let verdicts = candidates::test_batch(&mut source, CHECK_CHUNK).or_exit("Failed to read standard input");
+        for line in source.take_invalid() {
+            eprintln!("Not a number: {}", line);
+            invalid += 1;
+        }
+        if verdicts.is_empty() {
+            break rows.as_mut().map_or(Ok(()), Rows::finish);
+        }
+        let mut shown = Table::new([("number", Align::Right), ("verdict", Align::Left)]);
+        for (candidate, primality) in verdicts {
+            let number = source.describe(&candidate);
+            match (primality != Primality::Composite, rows.as_mut()) {
+                (prime, Some(rows)) => rows.row(format_args!("{},{}", number, prime))?,
+                (true, None) => shown.row([(number.as_str(), Style::Plain), ("prime", Style::Green)]),
+                (false, None) => shown.row([(number.as_str(), Style::Plain), ("composite", Style::Red)]),
+            }
+        }
+        if !shown.is_empty() {
+            io::stdout().lock().write_all(shown.render(color).as_bytes())?;
+        }
+    })();
+    match result {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Failed to write the verdicts: {}", e);
+            Exit::for_error(&e).exit();
+        }
+        Ok(()) => {}
+    }
+    if invalid > 0 {
+        Exit::InvalidArguments.exit();
+    }
 }
 
-async fn fetch_default_range() -> std::result::Result<(u128, u128), reqwest::Error> {
-    let api_url = "http://primegen.io/api/default_range";
-    let client = reqwest::Client::new();
+// Function to expand ranges of values that must fit into `T`
+fn values<T: TryFrom<u128> + Bounded + ToPrimitive>(spec: &str, name: &str) -> Vec<T> {
+    let ranges = parse_ranges(spec).unwrap_or_else(|e| invalid(InvalidArgument::new(&flag(name), e.to_string())));
+    ranges
+        .into_iter()
+        .flat_map(|(from, to)| from..=to)
+        .map(|value| validate::number(&flag(name), &value.to_string()).unwrap_or_else(|e| invalid(e)))
+        .collect()
+}
 
-    let response = client.get(api_url)
-        .send()
-        .await?;
+// Function to describe a (probable) prime as the `result,test` CSV columns
+fn verdict_columns(primality: Primality) -> Option<String> {
+    match primality {
+        Primality::Composite => None,
+        Primality::Prime => Some(format!("prime,{}", PRIME_TEST)),
+        Primality::ProbablePrime => Some(format!("probable-prime,{}", PROBABLE_PRIME_TEST)),
+    }
+}
 
-    let Range { start, end } = response.json::<Range>()
-        .await?;
+// Function to name a verdict in the `result` CSV column
+fn primality_name(primality: Primality) -> &'static str {
+    match primality {
+        Primality::Composite => "composite",
+        Primality::Prime => "prime",
+        Primality::ProbablePrime => "probable-prime",
+    }
+}
 
-    Ok((start, end))
+// Function to run the `repunit` subcommand, testing the lengths in parallel
+fn run_repunit(matches: &ArgMatches) {
+    let base = number_arg::<u32>(matches, "base").unwrap();
+    if base < 2 {
+        invalid(InvalidArgument::new("--base", format!("{} is too small", base)).hint("it must be at least 2"));
+    }
+    let lengths: Vec<u32> = values(matches.value_of("n").unwrap(), "n");
+    let found: Vec<(u32, String)> = lengths
+        .into_par_iter()
+        .filter_map(|n| verdict_columns(repunit_test(base, n)).map(|columns| (n, columns)))
+        .collect();
+    let mut rows = Rows::new(io::stdout().lock(), "n,result,test", matches.is_present("json"));
+    for (n, columns) in found {
+        rows.row(format_args!("{},{}", n, columns)).or_exit("Failed to write the output");
+    }
+    rows.finish().or_exit("Failed to write the output");
 }
 
-// Function to read data from CSV file
-fn read_csv_data<P: AsRef<Path>>(path: P) -> Result<Vec<PrimeRecord>> {
-    let file = OpenOptions::new().read(true).open(path)?;
-    let mut rdr = csv::Reader::from_reader(file);
-    let mut records = Vec::new();
+// Function to test m - 1 and m + 1 for each `(n, m)` of a sequence of
+// factorials or primorials, a batch at a time across the thread pool, and
+// print the (probable) primes in order
+fn search_plus_minus_one<I: Iterator<Item = (u32, BigInt)>>(label: &str, mut sequence: I, symbol: &str, json: bool) {
+    let mut rows = Rows::new(io::stdout().lock(), &format!("{},form,result,test", label), json);
+    let batch_size = rayon::current_num_threads() * 4;
+    loop {
+        let batch: Vec<(u32, BigInt)> = sequence.by_ref().take(batch_size).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let found: Vec<String> = batch
+            .par_iter()
+            .flat_map_iter(|(n, m)| [("-", m - 1), ("+", m + 1)].map(|(sign, candidate)| (*n, sign, candidate)))
+            .filter_map(|(n, sign, candidate)| {
+                verdict_columns(probable_prime(&candidate)).map(|columns| format!("{},{}{}{}1,{}", n, n, symbol, sign, columns))
+            })
+            .collect();
+        for row in found {
+            rows.row(format_args!("{}", row)).or_exit("Failed to write the output");
+        }
+    }
+    rows.finish().or_exit("Failed to write the output");
+}
 
-    for result in rdr.deserialize() {
-        let record: PrimeRecord = result?;
-        records.push(record);
+// Function to run the `gaussian` subcommand
+fn run_gaussian(matches: &ArgMatches) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    let mut rows = Rows::new(BufWriter::new(io::stdout().lock()), "p,splitting,a,b", matches.is_present("json"));
+    let mut result = Ok(());
+    sieve::for_each_prime(start, end, |p| {
+        let p = p as u128;
+        let (a, b) = match two_squares(p) {
+            Some((a, b)) => (a.to_string(), b.to_string()),
+            None => (String::new(), String::new()),
+        };
+        result = rows.row(format_args!("{},{},{},{}", p, GaussianSplitting::of(p), a, b));
+        result.is_ok()
+    });
+    if let Err(e) = result.and_then(|_| rows.finish()) {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        if e.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to write the output: {}", e);
+            Exit::for_error(&e).exit();
+        }
     }
+}
 
-    Ok(records)
+// Function to build the filter named by `--filter`
+fn prime_filter(name: &str) -> std::result::Result<Box<dyn Fn(u128) -> bool + Send + Sync>, String> {
+    match name {
+        "happy" => Ok(Box::new(is_happy)),
+        _ => name.parse::<Constellation>().map(|constellation| -> Box<dyn Fn(u128) -> bool + Send + Sync> {
+            Box::new(move |p| constellation.contains(p))
+        }),
+    }
 }
 
-// Function to post results to an API
-async fn post_results(file_path: &str) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    let records = read_csv_data(file_path)?;
-    let client = reqwest::Client::new();
-    let api_url = "http://primegen.io/api/post_results"; // Replace with your actual POST API URL
+// Function to test the primes of the range in parallel, a sieve window at a
+// time, and print those passing `test` in order
+fn scan_primes(matches: &ArgMatches, max_end: u64, test: fn(u64) -> bool) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    if end > max_end {
+        invalid(InvalidArgument::new("-e/--end", format!("{} is too large", end)).hint(format!("it can be at most {}", max_end)));
+    }
+    let window = (CHECK_CHUNK as u64) << 8;
+    let mut rows = Rows::new(io::stdout().lock(), "p", matches.is_present("json"));
+    let mut from = Some(start);
+    while let Some(window_start) = from.filter(|&from| from <= end) {
+        let window_end = window_start.saturating_add(window - 1).min(end);
+        let found: Vec<u64> = sieve::primes_in_range(window_start, window_end).into_par_iter().filter(|&p| test(p)).collect();
+        for p in found {
+            rows.row(format_args!("{}", p)).or_exit("Failed to write the output");
+        }
+        from = window_end.checked_add(1);
+    }
+    rows.finish().or_exit("Failed to write the output");
+}
 
-    client.post(api_url)
-        .json(&records)
-        .send()
-        .await?
-        .error_for_status()?;
+// Function to run the `mertens` subcommand, summing μ(n) from 1 and printing
+// M(x) over the range, then the extremes of M(x)/sqrt(x) to stderr
+fn run_mertens(matches: &ArgMatches) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    let every = positive_arg(matches, "every").unwrap_or(1);
+    let mut rows = Rows::new(BufWriter::new(io::stdout().lock()), "x,mobius,mertens", matches.is_present("json"));
+    let mut result = Ok(());
+    let mut mertens = 0i64;
+    // The x of the range with the largest |M(x)|/sqrt(x)
+    let mut largest: Option<(u64, i64)> = None;
+    arithmetic::for_each_mobius(1, end, |x, mobius| {
+        mertens += mobius as i64;
+        if x < start {
+            return true;
+        }
+        let ratio = |(x, mertens): (u64, i64)| mertens.unsigned_abs() as f64 / (x as f64).sqrt();
+        if largest.is_none_or(|best| ratio((x, mertens)) > ratio(best)) {
+            largest = Some((x, mertens));
+        }
+        if (x - start).is_multiple_of(every) {
+            result = rows.row(format_args!("{},{},{}", x, mobius, mertens));
+        }
+        result.is_ok()
+    });
+    if let Err(e) = result.and_then(|_| rows.finish()) {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        if e.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to write the output: {}", e);
+            Exit::for_error(&e).exit();
+        }
+        return;
+    }
+    if let Some((x, value)) = largest {
+        eprintln!("M({}) = {}; largest |M(x)|/sqrt(x) in the range: {:.6} at M({}) = {}", end, mertens, value.unsigned_abs() as f64 / (x as f64).sqrt(), x, value);
+    }
+}
 
-    Ok(())
+// Function to run the `chebyshev` subcommand
+fn run_chebyshev(matches: &ArgMatches) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    let every = match matches.value_of("every") {
+        Some(_) => positive_arg(matches, "every").unwrap(),
+        None => end.saturating_sub(start).max(1),
+    };
+    let mut checkpoints: Vec<u64> = (0..).map_while(|i: u64| i.checked_mul(every)?.checked_add(start)).take_while(|&x| x <= end).collect();
+    checkpoints.push(end);
+    for at in matches.value_of("at").into_iter().flat_map(|at| at.split(',')) {
+        checkpoints.push(validate::number("--at", at).unwrap_or_else(|e| invalid(e)));
+    }
+    checkpoints.sort_unstable();
+    checkpoints.dedup();
+
+    let mut rows = Rows::new(BufWriter::new(io::stdout().lock()), "x,theta,psi", matches.is_present("json"));
+    let mut result = Ok(());
+    arithmetic::chebyshev(&checkpoints, |x, theta, psi| {
+        result = rows.row(format_args!("{},{:.6},{:.6}", x, theta, psi));
+        result.is_ok()
+    });
+    if let Err(e) = result.and_then(|_| rows.finish()) {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        if e.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to write the output: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+}
+
+// Function to run the `rough` or `smooth` subcommand, sieving a window of the
+// range at a time with the primes below the minimum factor or up to the bound
+// and factoring the smooth numbers in parallel
+fn run_rough_or_smooth(matches: &ArgMatches, smooth: bool) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    let limit_name = if smooth { "bound" } else { "min-factor" };
+    let limit = number_arg::<u64>(matches, limit_name).unwrap();
+    let primes = match smooth {
+        true => sieve::small_primes(limit),
+        false => sieve::small_primes(limit.saturating_sub(1)),
+    };
+    let window = (CHECK_CHUNK as u64) << 8;
+    let mut rows = Rows::new(BufWriter::new(io::stdout().lock()), if smooth { "n,factorization" } else { "n" }, matches.is_present("json"));
+    let mut result = Ok(());
+    // 0 is divisible by every prime
+    let mut from = Some(start.max(1));
+    while let Some(window_start) = from.filter(|&from| from <= end && result.is_ok()) {
+        let window_end = window_start.saturating_add(window - 1).min(end);
+        let (smallest, cofactors) = arithmetic::sieve_factors(window_start, window_end, &primes);
+        let numbers = (window_start..=window_end).zip(smallest.into_iter().zip(cofactors));
+        if smooth {
+            let found: Vec<u64> = numbers.filter(|&(_, (_, cofactor))| cofactor == 1).map(|(n, _)| n).collect();
+            let factorizations: Vec<String> = found.par_iter().map(|&n| factorization(n)).collect();
+            for (n, factors) in found.into_iter().zip(factorizations) {
+                result = result.and_then(|_| rows.row(format_args!("{},{}", n, factors)));
+            }
+        } else {
+            for (n, _) in numbers.filter(|&(_, (smallest, _))| smallest == 0) {
+                result = result.and_then(|_| rows.row(format_args!("{}", n)));
+            }
+        }
+        from = window_end.checked_add(1);
+    }
+    if let Err(e) = result.and_then(|_| rows.finish()) {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        if e.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to write the output: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+}
+
+// Function to write n as a product of prime powers, such as 2^3*3*5, or 1
+fn factorization(n: u64) -> String {
+    let factors: Vec<String> = factor_with_multiplicity(n as u128)
+        .into_iter()
+        .map(|(p, k)| if k == 1 { p.to_string() } else { format!("{}^{}", p, k) })
+        .collect();
+    match factors.is_empty() {
+        true => "1".to_string(),
+        false => factors.join("*"),
+    }
+}
+
+// Function to run the `lucky` subcommand
+fn run_lucky(matches: &ArgMatches) {
+    let (start, end) = range_args::<u64>(matches, (0, 0));
+    let mut rows = Rows::new(BufWriter::new(io::stdout().lock()), "p", matches.is_present("json"));
+    let mut result = Ok(());
+    for lucky in lucky_numbers(end).into_iter().filter(|&n| n >= start && is_prime(n as u128)) {
+        result = result.and_then(|_| rows.row(format_args!("{}", lucky)));
+    }
+    if let Err(e) = result.and_then(|_| rows.finish()) {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        if e.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to write the output: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+}
+
+// Function to run the `poly` subcommand, testing CHECK_CHUNK values of n at a
+// time across the thread pool, and report each polynomial's prime density
+fn run_poly(matches: &ArgMatches) {
+    let polynomials: Vec<Polynomial> = matches
+        .values_of("polynomials")
+        .unwrap()
+        .map(|spec| spec.parse())
+        .collect::<std::result::Result<_, _>>()
+        .unwrap_or_else(|e| invalid(InvalidArgument::new("<polynomials>", e)));
+    let ranges = parse_ranges(matches.value_of("n").unwrap()).unwrap_or_else(|e| invalid(InvalidArgument::new("-n", e.to_string())));
+    let output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(paths::long_path(path)).or_exit("Failed to create the output file"))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let mut rows = Rows::new(output, "polynomial,n,value,result,test", matches.is_present("json"));
+    let mut source = PolynomialValues::new(polynomials, ranges);
+
+    // Values of n tested and primes found per polynomial, with the longest run
+    // of consecutive n giving primes, the current one and the n it ends at
+    let mut tested = 0u128;
+    let mut primes = vec![0u128; source.polynomials().len()];
+    let mut runs = vec![(0u128, 0u128, None::<u128>); source.polynomials().len()];
+    let result = (|| loop {
+        let verdicts = candidates::test_batch(&mut source, CHECK_CHUNK)?;
+        if verdicts.is_empty() {
+            break rows.finish();
+        }
+        for (candidate, primality) in verdicts {
+            let i = candidate.sequence;
+            if i == 0 {
+                tested += 1;
+            }
+            let (longest, current, last) = &mut runs[i];
+            // Runs don't continue across ranges
+            if last.and_then(|n| n.checked_add(1)) != Some(candidate.index) {
+                *current = 0;
+            }
+            *last = Some(candidate.index);
+            if primality == Primality::Composite {
+                *current = 0;
+                continue;
+            }
+            rows.row(format_args!("{},{},{}", source.describe(&candidate), primality_name(primality), source.test_name(primality)))?;
+            primes[i] += 1;
+            *current += 1;
+            *longest = (*longest).max(*current);
+        }
+    })();
+    match result {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => return,
+        Err(e) => {
+            eprintln!("Failed to write the primes: {}", e);
+            Exit::for_error(&e).exit();
+        }
+        Ok(()) => {}
+    }
+    for ((polynomial, primes), (longest, _, _)) in source.polynomials().iter().zip(primes).zip(runs) {
+        let density = if tested == 0 { 0.0 } else { primes as f64 / tested as f64 };
+        eprintln!(
+            "{}: {} primes among {} values ({:.4}%), at most {} consecutive",
+            polynomial,
+            primes,
+            tested,
+            density * 100.0,
+            longest
+        );
+    }
+}
+
+// Function to run the `search` subcommands, testing the candidates of the
+// chosen source and writing its primes in the source's order
+fn run_search(matches: &ArgMatches) {
+    let ranges = |matches: &ArgMatches, name: &str| {
+        parse_ranges(matches.value_of(name).unwrap()).unwrap_or_else(|e| invalid(InvalidArgument::new(&format!("-{}", name), e.to_string())))
+    };
+    let mut source: Box<dyn CandidateSource> = match matches.subcommand() {
+        Some(("range", range_matches)) => Box::new(NumberRange::new(ranges(range_matches, "n"))),
+        Some(("file", file_matches)) => Box::new(NumberFile::new(match file_matches.value_of("input") {
+            Some(path) => Box::new(io::BufReader::new(File::open(paths::long_path(path)).or_exit("Failed to open the input file"))) as Box<dyn BufRead>,
+            None => Box::new(io::stdin().lock()),
+        })),
+        Some(("poly", poly_matches)) => {
+            let polynomials = poly_matches
+                .values_of("polynomials")
+                .unwrap()
+                .map(|spec| spec.parse())
+                .collect::<std::result::Result<_, _>>()
+                .unwrap_or_else(|e| invalid(InvalidArgument::new("<polynomials>", e)));
+            Box::new(PolynomialValues::new(polynomials, ranges(poly_matches, "n")))
+        }
+        Some(("mersenne", mersenne_matches)) => Box::new(
+            MersenneExponents::new(ranges(mersenne_matches, "p")).unwrap_or_else(|e| invalid(InvalidArgument::new("-p", e.to_string()))),
+        ),
+        Some(("random", random_matches)) => {
+            let bits = number_arg::<u32>(random_matches, "bits").unwrap();
+            let count = number_arg::<u128>(random_matches, "count").unwrap();
+            let seed = number_arg::<u64>(random_matches, "seed").unwrap();
+            Box::new(RandomBits::new(bits, count, seed).unwrap_or_else(|e| invalid(InvalidArgument::new("--bits", e.to_string()))))
+        }
+        _ => unreachable!("clap requires a source"),
+    };
+    let output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(paths::long_path(path)).or_exit("Failed to create the output file"))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let mut rows = Rows::new(output, &format!("{},result,test", source.columns()), matches.is_present("json"));
+
+    let (mut tested, mut primes) = (0u128, 0u128);
+    let result = (|| loop {
+        let verdicts = candidates::test_batch(source.as_mut(), CHECK_CHUNK).or_exit("Failed to read the candidates");
+        if verdicts.is_empty() {
+            break rows.finish();
+        }
+        tested += verdicts.len() as u128;
+        for (candidate, primality) in verdicts.iter().filter(|(_, primality)| *primality != Primality::Composite) {
+            rows.row(format_args!("{},{},{}", source.describe(candidate), primality_name(*primality), source.test_name(*primality)))?;
+            primes += 1;
+        }
+    })();
+    match result {
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => return,
+        Err(e) => {
+            eprintln!("Failed to write the primes: {}", e);
+            Exit::for_error(&e).exit();
+        }
+        Ok(()) => {}
+    }
+    eprintln!("{} primes among {} candidates", primes, tested);
+}
+
+// Function to print the observed density of primes per decade next to the one
+// li(x) predicts
+fn print_decade_densities(decades: &[DecadeDensity], numbers: NumberFormat) {
+    if decades.is_empty() {
+        return;
+    }
+    println!("Density by decade (observed vs li(x)):");
+    for decade in decades {
+        println!(
+            "  [10^{}, 10^{}): {} primes of {} numbers, density {:.6} vs {:.6} predicted{}",
+            decade.k,
+            decade.k + 1,
+            numbers.count(decade.primes),
+            numbers.count(decade.numbers),
+            decade.density(),
+            decade.expected_density(),
+            match decade.expected > 0.0 {
+                true => format!(" ({:+.2}%)", (decade.primes as f64 / decade.expected - 1.0) * 100.0),
+                false => String::new(),
+            }
+        );
+    }
+}
+
+// Extension reporting a failed I/O operation and exiting with the code for
+// the kind of error, where `expect` would panic
+trait OrExit<T> {
+    fn or_exit(self, message: &str) -> T;
+}
+
+impl<T> OrExit<T> for Result<T> {
+    fn or_exit(self, message: &str) -> T {
+        self.unwrap_or_else(|e| {
+            eprintln!("{}: {}", message, e);
+            Exit::for_error(&e).exit()
+        })
+    }
+}
+
+// Function to print the result of a subcommand run with --json as one JSON
+// document
+fn print_json<T: Serialize>(document: &T) {
+    println!("{}", serde_json::to_string_pretty(document).expect("Failed to serialize the result"));
+}
+
+// Function to report an invalid argument and exit
+fn invalid(error: InvalidArgument) -> ! {
+    eprintln!("error: {}", error);
+    Exit::InvalidArguments.exit()
+}
+
+// Function to name an argument as it's written on the command line
+fn flag(name: &str) -> String {
+    match name {
+        "start" | "end" | "cpus" | "algorithm" | "input" | "output" => format!("-{}/--{}", &name[..1], name),
+        "k" | "n" => format!("-{}", name),
+        "a" | "p" => format!("<{}>", name),
+        _ => format!("--{}", name),
+    }
+}
+
+// Function to read a numeric argument, exiting if it doesn't fit into `T`
+fn number_arg<T: TryFrom<u128> + Bounded + ToPrimitive>(matches: &ArgMatches, name: &str) -> Option<T> {
+    matches.value_of(name).map(|value| validate::number(&flag(name), value).unwrap_or_else(|e| invalid(e)))
+}
+
+// Function to read a numeric argument that must be at least 1
+fn positive_arg<T: TryFrom<u128> + Bounded + ToPrimitive + PartialEq + Default>(matches: &ArgMatches, name: &str) -> Option<T> {
+    let value = number_arg::<T>(matches, name)?;
+    if value == T::default() {
+        invalid(InvalidArgument::new(&flag(name), "0 is too small").hint("it must be at least 1"));
+    }
+    Some(value)
+}
+
+// Function to read an argument with its `FromStr` implementation, exiting if
+// it doesn't parse
+fn parsed_arg<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    matches.value_of(name).map(|value| validate::parsed(&flag(name), value).unwrap_or_else(|e| invalid(e)))
+}
+
+// Function to read a duration argument such as 48h
+fn duration_arg(matches: &ArgMatches, name: &str) -> Duration {
+    let value = matches.value_of(name).unwrap();
+    plan::parse_duration(value).unwrap_or_else(|| {
+        invalid(InvalidArgument::new(&flag(name), format!("{:?} is not a duration", value)).hint("give a number with an s, m, h or d suffix, e.g. 48h"))
+    })
+}
+
+// Function to read the -s and -e arguments, defaulting those not given, and
+// exit if the start is past the end
+fn range_args<T: TryFrom<u128> + Bounded + ToPrimitive + Into<u128> + Copy>(matches: &ArgMatches, defaults: (T, T)) -> (T, T) {
+    let start = number_arg(matches, "start").unwrap_or(defaults.0);
+    let end = number_arg(matches, "end").unwrap_or(defaults.1);
+    let start_text = matches.value_of("start").map_or_else(|| start.into().to_string(), String::from);
+    let end_text = matches.value_of("end").map_or_else(|| end.into().to_string(), String::from);
+    validate::range("-s/--start", (&start_text, start.into()), "-e/--end", (&end_text, end.into())).unwrap_or_else(|e| invalid(e));
+    (start, end)
+}
+
+// Function to run the `legendre` subcommand
+fn run_legendre(matches: &ArgMatches) {
+    let a = parsed_arg::<i128>(matches, "a").unwrap();
+    let p = number_arg::<u128>(matches, "p").unwrap();
+    let Some(symbol) = legendre(a, p) else {
+        invalid(InvalidArgument::new("<p>", format!("{} is even", p)).hint("it must be odd, or 2"));
+    };
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Symbol {
+            a: i128,
+            p: u128,
+            symbol: i32,
+            // Whether this is the Legendre symbol rather than the Jacobi symbol
+            p_prime: bool,
+        }
+        print_json(&Symbol { a, p, symbol, p_prime: is_prime(p) });
+        return;
+    }
+    println!("({}/{}) = {}", a, p, symbol);
+    if !is_prime(p) {
+        println!("{} is composite, so this is the Jacobi symbol: 1 doesn't mean {} is a square modulo {}", p, a, p);
+    } else if symbol != 0 {
+        let kind = if symbol == 1 { "residue" } else { "non-residue" };
+        println!("{} is a quadratic {} modulo {}", a, kind, p);
+    }
+}
+
+// Function to run the `order` subcommand
+fn run_order(matches: &ArgMatches) {
+    let a = number_arg::<u128>(matches, "a").unwrap();
+    let p = number_arg::<u128>(matches, "p").unwrap();
+    let Some(order) = multiplicative_order(a, p) else {
+        let reason = match is_prime(p) {
+            true => format!("{} divides {}", p, a),
+            false => format!("{} isn't prime", p),
+        };
+        invalid(InvalidArgument::new("<p>", reason).hint("it must be a prime that doesn't divide a"));
+    };
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Order {
+            a: u128,
+            p: u128,
+            order: u128,
+            primitive_root: bool,
+        }
+        print_json(&Order { a, p, order, primitive_root: order == p - 1 });
+        return;
+    }
+    println!("ord_{}({}) = {}", p, a, order);
+    if order == p - 1 {
+        println!("{} is a primitive root modulo {}", a, p);
+    }
+}
+
+// Function to run the `proth` subcommand, testing every Proth number of the
+// k and n ranges in parallel
+fn run_proth(matches: &ArgMatches) {
+    let ks: Vec<u64> = values(matches.value_of("k").unwrap(), "k");
+    let ns: Vec<u32> = values(matches.value_of("n").unwrap(), "n");
+    let candidates: Vec<(u64, u32)> = ns.iter().flat_map(|&n| ks.iter().map(move |&k| (k, n))).collect();
+    let primes: Vec<(u64, u32)> = candidates
+        .into_par_iter()
+        .filter(|&(k, n)| proth_test(k, n) == Some(true))
+        .collect();
+    let mut rows = Rows::new(io::stdout().lock(), "k,n", matches.is_present("json"));
+    for (k, n) in primes {
+        rows.row(format_args!("{},{}", k, n)).or_exit("Failed to write the output");
+    }
+    rows.finish().or_exit("Failed to write the output");
+}
+
+// Function to run the `fermat-number` subcommand
+fn run_fermat_number(matches: &ArgMatches) {
+    let n = validate::number::<u32>("<n>", matches.value_of("n").unwrap()).unwrap_or_else(|e| invalid(e));
+    if n >= usize::BITS {
+        invalid(InvalidArgument::new("<n>", format!("F_{} has more bits than memory can address", n)).hint(format!("it must be below {}", usize::BITS)));
+    }
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct FermatNumber {
+            n: u32,
+            digits: u64,
+            known: Option<&'static str>,
+            known_factor: Option<u128>,
+            pepin: &'static str,
+            seconds: f64,
+        }
+        let (known, known_factor) = match known_fermat(n) {
+            Some(KnownFermat::Prime) => (Some("prime"), None),
+            Some(KnownFermat::Factor(factor)) => (Some("composite"), Some(factor)),
+            Some(KnownFermat::Composite) => (Some("composite"), None),
+            None => (None, None),
+        };
+        let start_time = Instant::now();
+        let pepin = if pepin_test(n) { "prime" } else { "composite" };
+        let seconds = start_time.elapsed().as_secs_f64();
+        print_json(&FermatNumber { n, digits: fermat_digits(n), known, known_factor, pepin, seconds });
+        return;
+    }
+    println!("F_{} = 2^(2^{}) + 1 has {} digits", n, n, fermat_digits(n));
+    match known_fermat(n) {
+        Some(KnownFermat::Prime) => println!("Known: prime"),
+        Some(KnownFermat::Factor(factor)) => println!("Known: composite, divisible by {}", factor),
+        Some(KnownFermat::Composite) => println!("Known: composite, no factor known"),
+        None => println!("Known: nothing recorded"),
+    }
+    let start_time = Instant::now();
+    let prime = pepin_test(n);
+    println!("Pépin's test: {} ({:?})", if prime { "prime" } else { "composite" }, start_time.elapsed());
+}
+
+// Function to run the `verify-manifest` subcommand
+fn run_verify_manifest(matches: &ArgMatches) {
+    let manifest = matches.value_of("manifest").unwrap();
+    let mismatches = Manifest::verify(manifest).or_exit("Failed to read the manifest or its output file");
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Verification<'a> {
+            manifest: &'a str,
+            valid: bool,
+            mismatches: &'a [String],
+        }
+        print_json(&Verification { manifest, valid: mismatches.is_empty(), mismatches: &mismatches });
+        if !mismatches.is_empty() {
+            Exit::Mismatch.exit();
+        }
+        return;
+    }
+    if mismatches.is_empty() {
+        println!("{} matches its output file", manifest);
+        return;
+    }
+    for mismatch in &mismatches {
+        eprintln!("{}", mismatch);
+    }
+    Exit::Mismatch.exit();
+}
+
+// Function to read --max-load, --max-temp and --only-when-idle, if any was given
+#[cfg(target_os = "linux")]
+fn load_limit(matches: &ArgMatches) -> Option<LoadLimit> {
+    let max_load = parsed_arg::<f64>(matches, "max-load").map(|load| match load > 0.0 && load <= 1.0 {
+        true => load,
+        false => invalid(InvalidArgument::new("--max-load", format!("{} is out of range", load)).hint("give a share of the machine above 0 and at most 1, e.g. 0.8")),
+    });
+    let max_temperature = parsed_arg::<f64>(matches, "max-temp");
+    let only_when_idle = matches.is_present("only-when-idle");
+    (max_load.is_some() || max_temperature.is_some() || only_when_idle).then_some(LoadLimit {
+        max_load,
+        max_temperature,
+        only_when_idle,
+    })
+}
+
+// Function to apply --nice and --ionice to the current thread, which the
+// workers spawned later inherit
+#[cfg(target_os = "linux")]
+fn set_priorities(matches: &ArgMatches) {
+    if let Some(nice) = matches.value_of("nice") {
+        let applied = nice
+            .parse::<i32>()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, format!("Invalid nice value: {}", nice)))
+            .and_then(priority::set_nice);
+        if let Err(e) = applied {
+            eprintln!("--nice: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+    if let Some(class) = matches.value_of("ionice") {
+        let applied = class
+            .parse::<IoPriority>()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))
+            .and_then(priority::set_io_priority);
+        if let Err(e) = applied {
+            eprintln!("--ionice: {}", e);
+            Exit::for_error(&e).exit();
+        }
+    }
+}
+
+// Function to refuse --nice and --ionice where they aren't supported
+#[cfg(not(target_os = "linux"))]
+fn set_priorities(matches: &ArgMatches) {
+    if matches.is_present("nice") || matches.is_present("ionice") {
+        eprintln!("--nice and --ionice are only supported on Linux");
+        Exit::InvalidArguments.exit();
+    }
+}
+
+// Function to send a state change to systemd, warning if it can't be sent;
+// tells whether it was
+#[cfg(target_os = "linux")]
+fn sd_notify(state: &str) -> bool {
+    match systemd::notify(state) {
+        Ok(true) => return true,
+        Ok(false) => eprintln!("--sd-notify: NOTIFY_SOCKET isn't set; is this running as a Type=notify service?"),
+        Err(e) => eprintln!("--sd-notify: failed to notify systemd: {}", e),
+    }
+    false
+}
+
+// Function to parse a field delimiter: a single ASCII character, or `tab`
+fn parse_delimiter(s: &str) -> std::result::Result<u8, String> {
+    match s {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() && s != "\"" => Ok(s.as_bytes()[0]),
+        _ => Err(format!("{:?} is not a delimiter; give a single ASCII character other than a quote, or `tab`", s)),
+    }
+}
+
+// Function to run the `materialize` subcommand
+fn run_materialize(matches: &ArgMatches) {
+    let (input, output) = (matches.value_of("input").unwrap(), matches.value_of("output").unwrap());
+    if Path::new(input) == Path::new(output) {
+        eprintln!("The output file must differ from the input file");
+        Exit::InvalidArguments.exit();
+    }
+    // Records are rewritten a batch at a time, so memory use doesn't grow with the file
+    const BATCH: usize = 1 << 16;
+    let mut batch = Vec::with_capacity(BATCH);
+    let mut count = 0u64;
+    let result = for_each_record(input, |record| {
+        batch.push(record);
+        if batch.len() == BATCH {
+            count += batch.len() as u64;
+            append_records(output, batch.drain(..))?;
+        }
+        Ok(())
+    })
+    .and_then(|()| {
+        count += batch.len() as u64;
+        append_records(output, batch.drain(..))
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to materialize {}: {}", input, e);
+        Exit::for_error(&e).exit();
+    }
+    match matches.is_present("json") {
+        true => print_json(&serde_json::json!({ "input": input, "output": output, "records": count })),
+        false => println!("Wrote {} records with their powers to {}", count, output),
+    }
+}
+
+// Function to open the range registry, exiting if it can't be
+#[cfg(feature = "registry")]
+fn open_registry(path: &str) -> Registry {
+    Registry::open(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        Exit::for_error(&e).exit();
+    })
+}
+
+// Function to warn about the parts of a run the registry says were generated
+// already, telling whether there were any
+#[cfg(feature = "registry")]
+fn warn_about_overlaps(registry: &Registry, ranges: &[(u128, u128)]) -> bool {
+    let mut overlaps = false;
+    for &(start, end) in ranges {
+        for entry in registry.overlapping(start, end).or_exit("Failed to query the registry") {
+            eprintln!(
+                "Warning: {}-{} overlaps {}-{}, generated with {} {} into {}",
+                start, end, entry.start, entry.end, entry.algorithm, entry.engine_version, entry.output
+            );
+            overlaps = true;
+        }
+    }
+    overlaps
+}
+
+// Function to run the `paths` subcommand
+fn run_paths(matches: &ArgMatches) {
+    let template = matches.value_of("output-template").unwrap();
+    let output = std::path::absolute(template).unwrap_or_else(|_| PathBuf::from(template));
+    if matches.is_present("json") {
+        // Directories that can't be found (no home directory) are null
+        #[derive(Serialize)]
+        struct Paths {
+            cache: Option<PathBuf>,
+            sieving_primes: Option<PathBuf>,
+            #[cfg(feature = "net")]
+            known_prime_lists: Option<PathBuf>,
+            config: Option<PathBuf>,
+            output: PathBuf,
+            manifest: PathBuf,
+            signature: PathBuf,
+            records: PathBuf,
+            #[cfg(feature = "net")]
+            upload_progress: PathBuf,
+        }
+        print_json(&Paths {
+            cache: paths::cache_dir(),
+            sieving_primes: prime_cache::cache_path(),
+            #[cfg(feature = "net")]
+            known_prime_lists: default_known_dir(),
+            config: paths::config_dir(),
+            output: paths::long_path(&output),
+            manifest: manifest_path(&output),
+            signature: signature_path(&output),
+            records: records_path(&output),
+            #[cfg(feature = "net")]
+            upload_progress: upload_state_path(&output),
+        });
+        return;
+    }
+    let show = |dir: Option<PathBuf>| dir.map_or_else(|| "unknown (no home directory)".to_string(), |dir| dir.display().to_string());
+    println!("Cache: {}", show(paths::cache_dir()));
+    println!("  Sieving primes: {}", show(prime_cache::cache_path()));
+    #[cfg(feature = "net")]
+    println!("  Known prime lists: {}", show(default_known_dir()));
+    println!("Config: {}", show(paths::config_dir()));
+    println!("Default output: {}", paths::long_path(&output).display());
+    println!("  Manifest: {}", manifest_path(&output).display());
+    println!("  Signature: {}", signature_path(&output).display());
+    println!("  Records: {}", records_path(&output).display());
+    #[cfg(feature = "net")]
+    println!("  Upload progress: {}", upload_state_path(&output).display());
+    println!("Checkpoints: none; a stopped run starts its range over");
+}
+
+// Function to run the `status` subcommand
+#[cfg(feature = "registry")]
+fn run_status(matches: &ArgMatches) {
+    let entries = open_registry(matches.value_of("registry").unwrap()).entries().or_exit("Failed to read the registry");
+    let coverage = registry::coverage(&entries);
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Status<'a> {
+            ranges: &'a [RangeEntry],
+            covered: &'a [(u128, u128)],
+            covered_numbers: u128,
+            gaps: Vec<(u128, u128)>,
+        }
+        let gaps = match (coverage.first(), coverage.last()) {
+            (Some(&(first, _)), Some(&(_, last))) => registry::gaps(&entries, first, last),
+            _ => Vec::new(),
+        };
+        let covered_numbers = coverage.iter().map(|(from, to)| to - from + 1).sum();
+        print_json(&Status { ranges: &entries, covered: &coverage, covered_numbers, gaps });
+        return;
+    }
+    let (Some(&(first, _)), Some(&(_, last))) = (coverage.first(), coverage.last()) else {
+        println!("No ranges recorded");
+        return;
+    };
+    for entry in &entries {
+        println!("{}-{}  {} {}  {}", entry.start, entry.end, entry.algorithm, entry.engine_version, entry.output);
+    }
+    let format = |ranges: &[(u128, u128)]| ranges.iter().map(|(from, to)| format!("{}-{}", from, to)).collect::<Vec<_>>().join(", ");
+    let covered: u128 = coverage.iter().map(|(from, to)| to - from + 1).sum();
+    println!("Covered: {} ({} numbers)", format(&coverage), covered);
+    let gaps = registry::gaps(&entries, first, last);
+    match gaps.is_empty() {
+        true => println!("Gaps: none"),
+        false => println!("Gaps: {}", format(&gaps)),
+    }
+}
+
+// Function to refuse the `status` subcommand without a registry to read
+#[cfg(not(feature = "registry"))]
+fn run_status(_: &ArgMatches) {
+    eprintln!("Built without the registry feature; rebuild with --features registry to use a range registry");
+    Exit::InvalidArguments.exit();
+}
+
+// Function to run the `coverage` subcommand
+#[cfg(feature = "registry")]
+fn run_coverage(matches: &ArgMatches) {
+    let entries = open_registry(matches.value_of("registry").unwrap()).entries().or_exit("Failed to read the registry");
+    let coverage = registry::coverage(&entries);
+    let output = matches.value_of("image").unwrap();
+    let decades = visualize::render_coverage(&coverage, &paths::long_path(output)).or_exit("Failed to render coverage");
+    match matches.is_present("json") {
+        true => print_json(&serde_json::json!({ "image": output, "ranges": coverage.len(), "decades": decades })),
+        false => {
+            println!("Number line: 1 to 10^{}, one tick per power of ten", decades);
+            println!("Wrote coverage of {} ranges to {}", coverage.len(), output);
+        }
+    }
+}
+
+// Function to refuse the `coverage` subcommand without a registry to read
+#[cfg(not(feature = "registry"))]
+fn run_coverage(_: &ArgMatches) {
+    eprintln!("Built without the registry feature; rebuild with --features registry to use a range registry");
+    Exit::InvalidArguments.exit();
+}
+
+// Numbers timed to estimate how long a run will take
+const CONFIRM_SAMPLE: u128 = 100_000;
+
+// Function to ask before a run estimated to test more numbers than
+// --confirm-above or write more CSV than --confirm-above-size (given whether
+// it writes only the primes), showing the estimate; without a terminal to
+// ask on, such runs need --yes
+fn confirm_run(matches: &ArgMatches, ranges: &[(u128, u128)], csv_primes_only: Option<bool>, algorithm: Algorithm, threads: usize) {
+    let estimate = plan::estimate_run(ranges, csv_primes_only.unwrap_or(false));
+    let csv_bytes = csv_primes_only.map(|_| estimate.output_bytes);
+    let max_numbers = number_arg::<u128>(matches, "confirm-above").unwrap();
+    let max_size = matches.value_of("confirm-above-size").unwrap();
+    let max_bytes = parse_size(max_size).unwrap_or_else(|| {
+        invalid(InvalidArgument::new("--confirm-above-size", format!("{:?} is not a size", max_size)).hint("give bytes with an optional K, M, G or T suffix, e.g. 500G"))
+    });
+    if matches.is_present("yes") || (estimate.candidates <= max_numbers && csv_bytes.is_none_or(|bytes| bytes <= max_bytes as f64)) {
+        return;
+    }
+
+    // Time a sample from the middle of the largest range
+    let &(from, to) = ranges.iter().max_by_key(|(from, to)| to - from).unwrap();
+    let at = from + (to - from) / 2;
+    let end = at.saturating_add(CONFIRM_SAMPLE - 1).min(to);
+    let begun = Instant::now();
+    PrimeGenerator::builder()
+        .range(at, end)
+        .algorithm(algorithm)
+        .threads(threads)
+        .sink(Discard)
+        .build()
+        .and_then(PrimeGenerator::run)
+        .or_exit("Failed to time a sample");
+    let calibration = plan::Calibration::new([(at, (end - at + 1) as f64 / begun.elapsed().as_secs_f64())]);
+    let duration: Duration = ranges.iter().filter_map(|&(from, to)| calibration.estimate(from, to)).sum();
+
+    let numbers = match matches.is_present("plain-numbers") {
+        true => NumberFormat::PLAIN,
+        false => NumberFormat::from_env(),
+    };
+    eprintln!("This run is above --confirm-above or --confirm-above-size:");
+    eprintln!("  {} numbers to test in {} range(s)", numbers.count(estimate.candidates), ranges.len());
+    eprintln!("  about {} primes", numbers.count(estimate.primes as u128));
+    if let Some(bytes) = csv_bytes {
+        eprintln!("  about {}B of CSV output", numbers.si(bytes));
+    }
+    eprintln!("  about {} on {} threads", plan::format_duration(duration), threads);
+    if !io::stdin().is_terminal() {
+        eprintln!("Not starting without a terminal to confirm on; pass --yes to start it");
+        Exit::InvalidArguments.exit();
+    }
+    eprint!("Start it? [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).or_exit("Failed to read the answer");
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        eprintln!("Not started");
+        Exit::Failure.exit();
+    }
+}
+
+// Function to run the `plan` subcommand
+fn run_plan(matches: &ArgMatches) {
+    let target = number_arg::<u128>(matches, "target").unwrap();
+    let start = number_arg::<u128>(matches, "start").unwrap();
+    let given = |name| matches.value_of(name).unwrap();
+    validate::range("-s/--start", (given("start"), start), "--target", (given("target"), target)).unwrap_or_else(|e| invalid(e));
+    let budget = duration_arg(matches, "budget");
+    let unit = duration_arg(matches, "unit");
+    if unit.is_zero() {
+        invalid(InvalidArgument::new("--unit", "work units can't take no time").hint("give a duration such as 6h"));
+    }
+    let sample = positive_arg::<u128>(matches, "sample").unwrap();
+    let algorithm = parsed_arg::<Algorithm>(matches, "algorithm").unwrap();
+    let threads = number_arg::<usize>(matches, "cpus").unwrap_or_else(num_cpus::get);
+
+    // Time a sample at the start, at each power of ten up to the target, and at the target
+    let mut points = vec![start];
+    points.extend((0..=38).map(|k| 10u128.pow(k)).filter(|&n| n > start && n < target));
+    points.push(target);
+    points.dedup();
+    let mut measured = Vec::new();
+    for at in points {
+        let end = at.saturating_add(sample - 1);
+        let begun = Instant::now();
+        PrimeGenerator::builder()
+            .range(at, end)
+            .algorithm(algorithm)
+            .threads(threads)
+            .sink(Discard)
+            .build()
+            .and_then(PrimeGenerator::run)
+            .or_exit("Failed to time a sample");
+        let rate = (end - at + 1) as f64 / begun.elapsed().as_secs_f64();
+        if !matches.is_present("json") {
+            println!("Calibration: {:.0} numbers/s at {}", rate, at);
+        }
+        measured.push((at, rate));
+    }
+    let calibration = plan::Calibration::new(measured.clone());
+
+    let units = plan::plan(&calibration, start, target, unit, budget);
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Calibration {
+            at: u128,
+            numbers_per_second: f64,
+        }
+        #[derive(Serialize)]
+        struct Unit {
+            start: u128,
+            end: u128,
+            estimated_seconds: f64,
+        }
+        #[derive(Serialize)]
+        struct Plan {
+            target: u128,
+            calibration: Vec<Calibration>,
+            units: Vec<Unit>,
+            // How far the budget gets, and the estimate for the rest to the target
+            reaches: Option<u128>,
+            remaining_seconds: f64,
+        }
+        let reaches = units.last().map(|last| last.end);
+        let remaining = match reaches {
+            Some(end) if end == target => Duration::ZERO,
+            Some(end) => calibration.estimate(end + 1, target).unwrap_or_default(),
+            None => calibration.estimate(start, target).unwrap_or_default(),
+        };
+        print_json(&Plan {
+            target,
+            calibration: measured.into_iter().map(|(at, numbers_per_second)| Calibration { at, numbers_per_second }).collect(),
+            units: units
+                .iter()
+                .map(|unit| Unit { start: unit.start, end: unit.end, estimated_seconds: unit.estimated.as_secs_f64() })
+                .collect(),
+            reaches,
+            remaining_seconds: remaining.as_secs_f64(),
+        });
+        return;
+    }
+    for (i, unit) in units.iter().enumerate() {
+        println!("{:>4}  {}-{}  ~{}", i + 1, unit.start, unit.end, plan::format_duration(unit.estimated));
+    }
+    let planned: Duration = units.iter().map(|unit| unit.estimated).sum();
+    match units.last() {
+        Some(last) if last.end == target => {
+            println!("Reaches {} in ~{} over {} units", target, plan::format_duration(planned), units.len())
+        }
+        Some(last) => {
+            let rest = calibration.estimate(last.end + 1, target).unwrap_or_default();
+            println!("The budget reaches {}; the rest to {} needs ~{} more", last.end, target, plan::format_duration(rest))
+        }
+        None => println!("The budget is too small to plan a unit"),
+    }
+}
+
+// Throws away what a calibration run finds, so only computing it is timed
+struct Discard;
+
+impl Sink for Discard {
+    fn write_batch(&mut self, _: Vec<PrimeRecord>) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_powers(&mut self, _: &[(u128, Vec<Integer>)]) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Function to run the `verify-signature` subcommand
+fn run_verify_signature(matches: &ArgMatches) {
+    let signature = matches.value_of("signature").unwrap();
+    let key = read_verifying_key(matches.value_of("public-key").unwrap()).or_exit("Failed to read the public key");
+    let verified = ShardSignature::verify(signature, &key);
+    // A shard that changed or a signature by another key is a mismatch; not
+    // being able to read them is an I/O error
+    let failed = |e: &io::Error| match e.kind() {
+        ErrorKind::InvalidData => Exit::Mismatch,
+        _ => Exit::for_error(e),
+    };
+    if matches.is_present("json") {
+        #[derive(Serialize)]
+        struct Verification<'a> {
+            signature: &'a str,
+            valid: bool,
+            error: Option<String>,
+        }
+        let error = verified.as_ref().err().map(ToString::to_string);
+        print_json(&Verification { signature, valid: error.is_none(), error });
+        if let Err(e) = &verified {
+            failed(e).exit();
+        }
+        return;
+    }
+    match verified {
+        Ok(()) => println!("{} is a valid signature of its output file", signature),
+        Err(e) => {
+            eprintln!("{}", e);
+            failed(&e).exit();
+        }
+    }
 }
@@ -0,0 +1,244 @@
+// Manifests describing output shards: row count, prime bounds, SHA-256 of the
+// file and the parameters of the run that wrote it, stored as JSON next to the
+// shard so bit rot or tampering can be detected later.
+
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::aggregate::AggregateReport;
+use crate::csv_file::{for_each_record_with, CsvDialect, SCHEMA_VERSION};
+
+/// Settings of the run that produced a shard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationParameters {
+    pub start: u128,
+    pub end: u128,
+    pub algorithm: String,
+    pub backend: String,
+    pub ordering: String,
+    pub threads: usize,
+    pub segment_size: u64,
+    /// Version of `prime_generator` that ran the generation.
+    pub engine_version: String,
+}
+
+/// Summary of an output shard, written next to it by [`Manifest::write`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// File name of the shard, relative to the manifest.
+    pub file: String,
+    pub schema_version: u32,
+    pub rows: u64,
+    /// Smallest and largest prime in the shard, or `None` if it is empty.
+    pub min_prime: Option<u128>,
+    pub max_prime: Option<u128>,
+    /// Lowercase hex SHA-256 of the shard's bytes.
+    pub sha256: String,
+    /// The run that last wrote to the shard.
+    pub parameters: GenerationParameters,
+    /// Aggregates that run kept over the primes it wrote, if asked to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregates: Option<AggregateReport>,
+    /// How the shard's rows are punctuated, if not the default CSV.
+    #[serde(default, skip_serializing_if = "CsvDialect::is_default")]
+    pub dialect: CsvDialect,
+}
+
+/// Returns where the manifest of the shard at `path` is stored:
+/// `primes.csv` gets `primes.csv.manifest.json`.
+pub fn manifest_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".manifest.json");
+    PathBuf::from(name)
+}
+
+impl Manifest {
+    /// Reads the shard at `path` and summarizes it.
+    pub fn for_file<P: AsRef<Path>>(path: P, parameters: GenerationParameters) -> Result<Manifest> {
+        Manifest::for_file_with(path, parameters, CsvDialect::default())
+    }
+
+    /// Reads the shard at `path`, written in `dialect`, and summarizes it like
+    /// [`Manifest::for_file`].
+    pub fn for_file_with<P: AsRef<Path>>(path: P, parameters: GenerationParameters, dialect: CsvDialect) -> Result<Manifest> {
+        let path = path.as_ref();
+        let file = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Shard path has no file name"))?
+            .to_string();
+        let (rows, min_prime, max_prime) = count_records(path, &dialect)?;
+        Ok(Manifest {
+            file,
+            schema_version: SCHEMA_VERSION,
+            rows,
+            min_prime,
+            max_prime,
+            sha256: sha256_file(path)?,
+            parameters,
+            aggregates: None,
+            dialect,
+        })
+    }
+
+    /// Reads a manifest written by [`Manifest::write`].
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Manifest> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the manifest as pretty-printed JSON.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::other)
+    }
+
+    /// Re-reads the shard the manifest at `manifest` describes and returns
+    /// every way it no longer matches; an empty list means the shard is intact.
+    pub fn verify<P: AsRef<Path>>(manifest: P) -> Result<Vec<String>> {
+        let manifest_file = manifest.as_ref();
+        let expected = Manifest::read(manifest_file)?;
+        let shard = shard_next_to(manifest_file, &expected.file)?;
+
+        let mut mismatches = Vec::new();
+        let sha256 = sha256_file(&shard)?;
+        if sha256 != expected.sha256 {
+            mismatches.push(format!("SHA-256 is {}, expected {}", sha256, expected.sha256));
+        }
+        // A damaged file may not parse at all, which the checksum already reports
+        match count_records(&shard, &expected.dialect) {
+            Ok((rows, min_prime, max_prime)) => {
+                if rows != expected.rows {
+                    mismatches.push(format!("{} rows, expected {}", rows, expected.rows));
+                }
+                if (min_prime, max_prime) != (expected.min_prime, expected.max_prime) {
+                    mismatches.push(format!(
+                        "Primes span {:?}..{:?}, expected {:?}..{:?}",
+                        min_prime, max_prime, expected.min_prime, expected.max_prime
+                    ));
+                }
+            }
+            Err(e) => mismatches.push(format!("Records can't be read: {}", e)),
+        }
+        Ok(mismatches)
+    }
+}
+
+// Function to resolve the shard a manifest or signature file names, refusing
+// anything but a plain file name so the JSON can't point elsewhere
+pub(crate) fn shard_next_to(described_by: &Path, file: &str) -> Result<PathBuf> {
+    let mut components = Path::new(file).components();
+    if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} names {:?}, which is not a plain file name", described_by.display(), file),
+        ));
+    }
+    Ok(described_by.with_file_name(file))
+}
+
+// Function to count the records of a shard and find its smallest and largest prime
+fn count_records(path: &Path, dialect: &CsvDialect) -> Result<(u64, Option<u128>, Option<u128>)> {
+    let (mut rows, mut min_prime, mut max_prime) = (0, None, None);
+    for_each_record_with(path, dialect, |record| {
+        rows += 1;
+        min_prime = Some(min_prime.map_or(record.prime, |min: u128| min.min(record.prime)));
+        max_prime = Some(max_prime.map_or(record.prime, |max: u128| max.max(record.prime)));
+        Ok(())
+    })?;
+    Ok((rows, min_prime, max_prime))
+}
+
+// Function to hash a file with SHA-256, returned as lowercase hex
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_file::{append_records, append_records_with};
+    use crate::record::PrimeRecord;
+    use std::fs;
+
+    fn parameters() -> GenerationParameters {
+        GenerationParameters {
+            start: 2,
+            end: 20,
+            algorithm: "segmented-sieve".to_string(),
+            backend: "native".to_string(),
+            ordering: "sorted".to_string(),
+            threads: 1,
+            segment_size: 1024,
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    // Function to write a shard and its manifest into a fresh directory
+    fn shard(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("prime_generator_manifest_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("primes.csv");
+        append_records(&path, [2, 3, 5, 7, 11, 13, 17, 19].map(PrimeRecord::from_prime)).unwrap();
+        let manifest = manifest_path(&path);
+        Manifest::for_file(&path, parameters()).unwrap().write(&manifest).unwrap();
+        (path, manifest)
+    }
+
+    #[test]
+    fn summarizes_and_verifies_a_shard() {
+        let (path, manifest) = shard("intact");
+        let read = Manifest::read(&manifest).unwrap();
+        assert_eq!((read.rows, read.min_prime, read.max_prime), (8, Some(2), Some(19)));
+        assert_eq!(read.sha256.len(), 64);
+        assert_eq!(read.parameters, parameters());
+        assert!(Manifest::verify(&manifest).unwrap().is_empty());
+        assert!(!fs::read_to_string(&manifest).unwrap().contains("dialect"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn detects_changed_rows() {
+        let (path, manifest) = shard("tampered");
+        append_records(&path, [PrimeRecord::from_prime(23)]).unwrap();
+        let mismatches = Manifest::verify(&manifest).unwrap();
+        assert_eq!(mismatches.len(), 3, "{:?}", mismatches);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn verifies_shards_in_other_dialects() {
+        let dir = std::env::temp_dir().join(format!("prime_generator_manifest_dialect_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("primes.tsv");
+        let dialect = CsvDialect { delimiter: b'\t', header: false, ..CsvDialect::default() };
+        append_records_with(&path, [2, 3, 5, 7].map(PrimeRecord::from_prime), &dialect).unwrap();
+        let manifest = manifest_path(&path);
+        Manifest::for_file_with(&path, parameters(), dialect).unwrap().write(&manifest).unwrap();
+        let read = Manifest::read(&manifest).unwrap();
+        assert_eq!((read.rows, read.max_prime, read.dialect), (4, Some(7), dialect));
+        assert!(Manifest::verify(&manifest).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_manifest_directory() {
+        let (path, manifest) = shard("escape");
+        for file in ["../primes.csv", "/etc/passwd", "sub/primes.csv"] {
+            let mut crafted = Manifest::read(&manifest).unwrap();
+            crafted.file = file.to_string();
+            crafted.write(&manifest).unwrap();
+            assert_eq!(Manifest::verify(&manifest).unwrap_err().kind(), ErrorKind::InvalidData, "{}", file);
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
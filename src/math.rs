@@ -0,0 +1,426 @@
+// Allocation-free number theory core: modular arithmetic, deterministic
+// Miller-Rabin, Baillie-PSW, wheel iteration and next_prime.
+//
+// Nothing in here uses `std` or `alloc`, so it is available to `no_std`
+// builds (`default-features = false`).
+
+use crate::montgomery::{Montgomery128, Montgomery64};
+
+// Numbers below this are tested by trial division, above it by Miller-Rabin
+const TRIAL_DIVISION_LIMIT: u64 = 1 << 20;
+
+// Witnesses making Miller-Rabin deterministic for every 64-bit n
+// (Jim Sinclair's set)
+const WITNESSES_U64: [u64; 7] = [2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+// Miller-Rabin witnesses: the first 13 primes make the test deterministic for
+// every n < 3.3 * 10^24, and a strong probable-prime test beyond that
+const WITNESSES: [u128; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+// Residues modulo 30 that are coprime to 2, 3 and 5
+const WHEEL_OFFSETS: [u128; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+/// Returns `true` if `n` is prime.
+///
+/// The answer is exact for every `n` below 3.3 * 10^24; larger inputs are
+/// strong probable primes to 13 bases, with no known counterexamples.
+pub fn is_prime(n: u128) -> bool {
+    if let Ok(n) = u64::try_from(n) {
+        return is_prime_u64(n);
+    }
+    if n.is_multiple_of(2) || n.is_multiple_of(3) {
+        return false;
+    }
+    miller_rabin(n)
+}
+
+/// Returns `true` if `n` is prime. Deterministic for every 64-bit input.
+pub fn is_prime_u64(n: u64) -> bool {
+    match n {
+        0 | 1 => false,
+        2 | 3 => true,
+        _ if n.is_multiple_of(2) || n.is_multiple_of(3) => false,
+        _ if n < TRIAL_DIVISION_LIMIT => {
+            let mut i = 5;
+            while i * i <= n {
+                if n.is_multiple_of(i) || n.is_multiple_of(i + 2) {
+                    return false;
+                }
+                i += 6;
+            }
+            true
+        }
+        _ => miller_rabin_u64(n),
+    }
+}
+
+/// Returns `true` if `n` passes the Baillie-PSW test: a strong probable-prime
+/// test to base 2 followed by a strong Lucas test with Selfridge's parameters.
+///
+/// Shares nothing with [`is_prime`] beyond the base-2 round, so the two make
+/// independent checks on each other. Exact for every 64-bit `n`; no
+/// counterexamples are known beyond that.
+pub fn baillie_psw(n: u128) -> bool {
+    match n {
+        0 | 1 => false,
+        2 | 3 => true,
+        _ if n.is_multiple_of(2) || n.is_multiple_of(3) => false,
+        _ => {
+            let mont = Montgomery128::new(n);
+            strong_probable_prime_base_2(&mont) && strong_lucas(&mont)
+        }
+    }
+}
+
+/// Returns the smallest prime strictly greater than `n`, or `None` if it
+/// doesn't fit into a `u128`.
+pub fn next_prime(n: u128) -> Option<u128> {
+    Wheel::new(n.checked_add(1)?).find(|&candidate| is_prime(candidate))
+}
+
+/// Iterator over the integers from `start` upwards that could be prime with
+/// respect to the wheel 2 * 3 * 5: the primes 2, 3 and 5 themselves, then
+/// only numbers coprime to 30 (8 of every 30 integers).
+pub struct Wheel {
+    next: Option<u128>,
+}
+
+impl Wheel {
+    pub fn new(start: u128) -> Wheel {
+        Wheel { next: Some(start) }
+    }
+}
+
+impl Iterator for Wheel {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        let candidate = wheel_candidate_at_or_after(self.next?)?;
+        self.next = candidate.checked_add(1);
+        Some(candidate)
+    }
+}
+
+// Function to find the smallest wheel candidate that is >= n
+fn wheel_candidate_at_or_after(n: u128) -> Option<u128> {
+    match n {
+        0..=2 => Some(2),
+        3 => Some(3),
+        4 | 5 => Some(5),
+        6 | 7 => Some(7),
+        _ => {
+            let residue = n % 30;
+            let offset = WHEEL_OFFSETS.iter().find(|&&offset| offset >= residue)?;
+            (n - residue).checked_add(*offset)
+        }
+    }
+}
+
+/// Computes `a * b mod m` without overflowing.
+pub fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    if m <= u64::MAX as u128 {
+        return (a % m) * (b % m) % m;
+    }
+    // Double-and-add so intermediate values stay below 2 * m
+    let (mut a, mut b) = (a % m, b % m);
+    let mut result = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a, m);
+        }
+        a = add_mod(a, a, m);
+        b >>= 1;
+    }
+    result
+}
+
+pub(crate) fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+    let (sum, overflowed) = a.overflowing_add(b);
+    if overflowed || sum >= m {
+        sum.wrapping_sub(m)
+    } else {
+        sum
+    }
+}
+
+/// Computes `base^exponent mod m` by square-and-multiply.
+pub fn pow_mod(base: u128, mut exponent: u128, m: u128) -> u128 {
+    let mut base = base % m;
+    let mut result = 1 % m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+// Function to run Miller-Rabin on an odd 64-bit n > 3 with the deterministic witness set
+fn miller_rabin_u64(n: u64) -> bool {
+    let mont = Montgomery64::new(n);
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let minus_one = mont.to_montgomery(n - 1);
+    WITNESSES_U64.iter().all(|&a| {
+        // A witness that is a multiple of n says nothing
+        if a.is_multiple_of(n) {
+            return true;
+        }
+        let mut x = mont.pow(mont.to_montgomery(a), d);
+        if x == mont.one() || x == minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = mont.mul(x, x);
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// Function to run Miller-Rabin on an odd n > 3 with the fixed witness set
+fn miller_rabin(n: u128) -> bool {
+    let mont = Montgomery128::new(n);
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let minus_one = mont.to_montgomery(n - 1);
+    WITNESSES.iter().all(|&a| {
+        if a.is_multiple_of(n) {
+            return true;
+        }
+        let mut x = mont.pow(mont.to_montgomery(a), d);
+        if x == mont.one() || x == minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = mont.mul(x, x);
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// Function to run a single Miller-Rabin round to base 2 on an odd n > 3
+fn strong_probable_prime_base_2(mont: &Montgomery128) -> bool {
+    let n = mont.modulus();
+    let s = (n - 1).trailing_zeros();
+    let minus_one = mont.to_montgomery(n - 1);
+    let mut x = mont.pow(mont.to_montgomery(2), (n - 1) >> s);
+    if x == mont.one() || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = mont.mul(x, x);
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+// Function to run the strong Lucas test on an odd n > 3 with P = 1 and
+// Selfridge's choice of D (the first of 5, -7, 9, -11, ... with (D/n) = -1)
+fn strong_lucas(mont: &Montgomery128) -> bool {
+    let n = mont.modulus();
+    // No suitable D exists for squares
+    if is_square(n) {
+        return false;
+    }
+    let mut d: i128 = 5;
+    loop {
+        match jacobi(d, n) {
+            -1 => break,
+            // D shares a factor with n
+            0 if d.unsigned_abs() != n => return false,
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    }
+    let to_mont = |v: i128| {
+        let residue = mont.to_montgomery(v.unsigned_abs());
+        if v < 0 {
+            mont.sub(0, residue)
+        } else {
+            residue
+        }
+    };
+    let (d_m, q_m) = (to_mont(d), to_mont((1 - d) / 4));
+
+    // n + 1 = k * 2^s with k odd; n is odd and below u128::MAX (divisible by 3)
+    let n_plus_one = n + 1;
+    let s = n_plus_one.trailing_zeros();
+    let k = n_plus_one >> s;
+
+    // Walk the bits of k from the top, keeping U_j, V_j and Q^j (starting at j = 1)
+    let (mut u, mut v, mut q_k) = (mont.one(), mont.one(), q_m);
+    for bit in (0..127 - k.leading_zeros()).rev() {
+        // Double: U_2j = U_j V_j, V_2j = V_j^2 - 2 Q^j
+        u = mont.mul(u, v);
+        v = mont.sub(mont.mul(v, v), mont.add(q_k, q_k));
+        q_k = mont.mul(q_k, q_k);
+        if (k >> bit) & 1 == 1 {
+            // Increment: U_j+1 = (U_j + V_j) / 2, V_j+1 = (D U_j + V_j) / 2
+            (u, v) = (mont.half(mont.add(u, v)), mont.half(mont.add(mont.mul(d_m, u), v)));
+            q_k = mont.mul(q_k, q_m);
+        }
+    }
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = mont.sub(mont.mul(v, v), mont.add(q_k, q_k));
+        if v == 0 {
+            return true;
+        }
+        q_k = mont.mul(q_k, q_k);
+    }
+    false
+}
+
+// Function to compute the Jacobi symbol (a/n) for odd n > 0
+pub(crate) fn jacobi(a: i128, n: u128) -> i32 {
+    let mut a = if a < 0 { (n - a.unsigned_abs() % n) % n } else { a as u128 % n };
+    let mut n = n;
+    let mut result = 1;
+    while a != 0 {
+        while a.is_multiple_of(2) {
+            a >>= 1;
+            if n % 8 == 3 || n % 8 == 5 {
+                result = -result;
+            }
+        }
+        (a, n) = (n, a);
+        if a % 4 == 3 && n % 4 == 3 {
+            result = -result;
+        }
+        a %= n;
+    }
+    if n == 1 {
+        result
+    } else {
+        0
+    }
+}
+
+// Function to check whether n is a perfect square
+fn is_square(n: u128) -> bool {
+    // Newton's iteration from above converges to floor(sqrt(n))
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (x + n / x) / 2;
+        if next >= x {
+            return x * x == n;
+        }
+        x = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to test primality the slow, obvious way
+    fn naive_is_prime(n: u128) -> bool {
+        n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+    }
+
+    #[test]
+    fn is_prime_matches_naive_test() {
+        for n in 0..100_000 {
+            assert_eq!(is_prime(n), naive_is_prime(n), "{}", n);
+        }
+        // Around the switch from trial division to Miller-Rabin
+        for n in (TRIAL_DIVISION_LIMIT as u128 - 1000)..(TRIAL_DIVISION_LIMIT as u128 + 1000) {
+            assert_eq!(is_prime(n), naive_is_prime(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn rejects_strong_pseudoprimes() {
+        // Strong pseudoprimes to base 2, to bases 2-11, to bases 2-23 and to bases 2-37
+        for n in [2047u128, 3215031751, 2152302898747, 3825123056546413051, 318665857834031151167461] {
+            assert!(!is_prime(n), "{}", n);
+        }
+        // Carmichael numbers
+        for n in [561u128, 41041, 825265, 321197185] {
+            assert!(!is_prime(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn recognizes_large_primes() {
+        for exponent in [61, 89, 107, 127] {
+            assert!(is_prime((1u128 << exponent) - 1), "2^{} - 1", exponent);
+        }
+        assert!(is_prime(u64::MAX as u128 - 58));
+        assert!(is_prime(u128::MAX - 158));
+        assert!(!is_prime(((1u128 << 61) - 1) * ((1 << 31) - 1)));
+        assert!(!is_prime(u128::MAX));
+    }
+
+    #[test]
+    fn next_prime_steps_over_composites() {
+        assert_eq!(next_prime(0), Some(2));
+        assert_eq!(next_prime(7), Some(11));
+        assert_eq!(next_prime(u64::MAX as u128), Some((1 << 64) + 13));
+        assert_eq!(next_prime(u128::MAX - 158), None);
+    }
+
+    #[test]
+    fn baillie_psw_matches_naive_test() {
+        for n in 0..100_000 {
+            assert_eq!(baillie_psw(n), naive_is_prime(n), "{}", n);
+        }
+        for n in (u64::MAX as u128 - 2000)..=(u64::MAX as u128 + 2000) {
+            assert_eq!(baillie_psw(n), is_prime(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn baillie_psw_rejects_pseudoprimes_of_either_half() {
+        // Strong Lucas pseudoprimes with Selfridge's parameters, caught by the base-2 round
+        for n in [5459u128, 5777, 10877, 16109, 18971] {
+            assert!(strong_lucas(&Montgomery128::new(n)), "{}", n);
+            assert!(!baillie_psw(n), "{}", n);
+        }
+        // Strong pseudoprimes to base 2, caught by the Lucas round
+        for n in [2047u128, 3277, 4033, 4681, 8321, 3215031751, 3825123056546413051] {
+            assert!(strong_probable_prime_base_2(&Montgomery128::new(n)), "{}", n);
+            assert!(!baillie_psw(n), "{}", n);
+        }
+        // Squares have no Selfridge parameter
+        assert!(!baillie_psw(1093 * 1093));
+    }
+
+    #[test]
+    fn baillie_psw_recognizes_large_primes() {
+        for exponent in [61, 89, 107, 127] {
+            assert!(baillie_psw((1u128 << exponent) - 1), "2^{} - 1", exponent);
+        }
+        assert!(baillie_psw(u128::MAX - 158));
+        assert!(!baillie_psw(u128::MAX));
+    }
+
+    #[test]
+    fn jacobi_matches_eulers_criterion() {
+        for p in (3..200u128).filter(|&p| naive_is_prime(p)) {
+            for a in -300i128..300 {
+                let residue = a.rem_euclid(p as i128) as u128;
+                let expected = match (1..p).find(|x| x * x % p == residue) {
+                    _ if residue == 0 => 0,
+                    Some(_) => 1,
+                    None => -1,
+                };
+                assert_eq!(jacobi(a, p), expected, "({}/{})", a, p);
+            }
+        }
+        // Composite n multiplies the symbols of its factors
+        assert_eq!(jacobi(2, 15), jacobi(2, 3) * jacobi(2, 5));
+        assert_eq!(jacobi(-6, 45), 0);
+    }
+}
@@ -0,0 +1,124 @@
+// Append-only file writer backed by a memory mapping.
+//
+// The file is grown a window at a time and the window mapped into memory, so
+// appending a batch is a memcpy rather than a write syscall, and the page
+// cache absorbs bursts until the kernel writes the pages back. `finish` (or
+// dropping the writer) trims the unused tail of the last window.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Result, Write};
+use std::path::Path;
+
+use memmap2::{MmapMut, MmapOptions};
+
+// Bytes mapped at a time
+const MAP_WINDOW: u64 = 64 << 20;
+
+// Mapping offsets must be aligned to the allocation granularity, which is
+// 64 KiB on Windows and a page elsewhere
+const MAP_ALIGNMENT: u64 = 1 << 16;
+
+/// Appends bytes to a file through a sliding memory-mapped window.
+pub struct MmapWriter {
+    file: File,
+    map: Option<MmapMut>,
+    // File offset of the start of `map`
+    map_start: u64,
+    // Bytes of `map` holding data
+    used: usize,
+}
+
+impl MmapWriter {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<MmapWriter> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let len = file.metadata()?.len();
+        Ok(MmapWriter { file, map: None, map_start: len, used: 0 })
+    }
+
+    // Function to map the next window, starting at the end of the data
+    fn remap(&mut self) -> Result<()> {
+        let end = self.map_start + self.used as u64;
+        if let Some(map) = self.map.take() {
+            map.flush_async()?;
+        }
+        let start = end - end % MAP_ALIGNMENT;
+        self.file.set_len(start + MAP_WINDOW)?;
+        // Safety: the file is only written through this mapping while it exists
+        let map = unsafe { MmapOptions::new().offset(start).len(MAP_WINDOW as usize).map_mut(&self.file)? };
+        self.map = Some(map);
+        self.map_start = start;
+        self.used = (end - start) as usize;
+        Ok(())
+    }
+
+    /// Flushes the mapping and truncates the file to the data written.
+    pub fn finish(&mut self) -> Result<()> {
+        let end = self.map_start + self.used as u64;
+        if let Some(map) = self.map.take() {
+            map.flush()?;
+            self.file.set_len(end)?;
+        }
+        self.map_start = end;
+        self.used = 0;
+        Ok(())
+    }
+}
+
+impl Write for MmapWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+        if self.map.as_ref().is_none_or(|map| self.used == map.len()) {
+            self.remap()?;
+        }
+        let map = self.map.as_mut().expect("Window was just mapped");
+        let n = bytes.len().min(map.len() - self.used);
+        map[self.used..self.used + n].copy_from_slice(&bytes[..n]);
+        self.used += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &self.map {
+            Some(map) => map.flush_async(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MmapWriter {
+    fn drop(&mut self) {
+        // Errors can't be reported here; `finish` reports them
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_and_trims_the_window() {
+        let path = std::env::temp_dir().join(format!("prime_generator_mmap_{}.csv", std::process::id()));
+        std::fs::write(&path, b"prime\n").unwrap();
+
+        // Existing data stays, at an offset that isn't window-aligned
+        let mut writer = MmapWriter::append(&path).unwrap();
+        writer.write_all(b"2\n3\n").unwrap();
+        writer.write_all(b"5\n").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"prime\n2\n3\n5\n");
+
+        // Writing after `finish` maps a new window; dropping trims it too
+        writer.write_all(b"7\n").unwrap();
+        drop(writer);
+        let mut writer = MmapWriter::append(&path).unwrap();
+        writer.write_all(b"11\n").unwrap();
+        drop(writer);
+        assert_eq!(std::fs::read(&path).unwrap(), b"prime\n2\n3\n5\n7\n11\n");
+
+        // A writer that writes nothing leaves the file alone
+        MmapWriter::append(&path).unwrap().finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 17);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+// Montgomery modular arithmetic for odd 64- and 128-bit moduli.
+//
+// Values are kept in Montgomery form (a * R mod n, with R = 2^64 or 2^128) so
+// each modular multiplication costs a couple of wide multiplies and no
+// division. Like `math`, this is allocation-free and `no_std`.
+
+use crate::math::add_mod;
+
+/// Montgomery arithmetic modulo an odd `n < 2^64`, with R = 2^64.
+#[derive(Debug, Clone, Copy)]
+pub struct Montgomery64 {
+    n: u64,
+    // n^-1 mod 2^64
+    n_inv: u64,
+    // R^2 mod n, used to convert into Montgomery form
+    r2: u64,
+    // R mod n, the Montgomery form of 1
+    one: u64,
+}
+
+impl Montgomery64 {
+    /// Panics if `n` is even or less than 3.
+    pub fn new(n: u64) -> Montgomery64 {
+        assert!(!n.is_multiple_of(2) && n > 1, "Montgomery modulus must be odd and greater than 1");
+        // Newton's iteration doubles the correct low bits each step, starting
+        // from 3 (every odd n is its own inverse mod 8)
+        let mut n_inv = n;
+        for _ in 0..5 {
+            n_inv = n_inv.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(n_inv)));
+        }
+        let one = ((u64::MAX % n) + 1) % n;
+        let r2 = ((one as u128 * one as u128) % n as u128) as u64;
+        Montgomery64 { n, n_inv, r2, one }
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.n
+    }
+
+    /// Montgomery form of 1.
+    pub fn one(&self) -> u64 {
+        self.one
+    }
+
+    // Function to compute t * R^-1 mod n for t < n * R
+    fn reduce(&self, t: u128) -> u64 {
+        let m = (t as u64).wrapping_mul(self.n_inv);
+        let mn = m as u128 * self.n as u128;
+        let (t_hi, mn_hi) = ((t >> 64) as u64, (mn >> 64) as u64);
+        // The low halves cancel, so (t - mn) / R is the difference of the high halves
+        if t_hi < mn_hi {
+            t_hi.wrapping_sub(mn_hi).wrapping_add(self.n)
+        } else {
+            t_hi - mn_hi
+        }
+    }
+
+    /// Converts `a` into Montgomery form.
+    pub fn to_montgomery(&self, a: u64) -> u64 {
+        self.reduce((a % self.n) as u128 * self.r2 as u128)
+    }
+
+    /// Converts `a` out of Montgomery form.
+    pub fn from_montgomery(&self, a: u64) -> u64 {
+        self.reduce(a as u128)
+    }
+
+    /// Multiplies two values in Montgomery form.
+    pub fn mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    /// Adds two values in Montgomery form.
+    pub fn add(&self, a: u64, b: u64) -> u64 {
+        let (sum, overflowed) = a.overflowing_add(b);
+        if overflowed || sum >= self.n {
+            sum.wrapping_sub(self.n)
+        } else {
+            sum
+        }
+    }
+
+    /// Raises a value in Montgomery form to a plain `exponent`.
+    pub fn pow(&self, mut base: u64, mut exponent: u64) -> u64 {
+        let mut result = self.one;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+/// Montgomery arithmetic modulo an odd `n < 2^128`, with R = 2^128.
+#[derive(Debug, Clone, Copy)]
+pub struct Montgomery128 {
+    n: u128,
+    // n^-1 mod 2^128
+    n_inv: u128,
+    // R^2 mod n, used to convert into Montgomery form
+    r2: u128,
+    // R mod n, the Montgomery form of 1
+    one: u128,
+}
+
+impl Montgomery128 {
+    /// Panics if `n` is even or less than 3.
+    pub fn new(n: u128) -> Montgomery128 {
+        assert!(!n.is_multiple_of(2) && n > 1, "Montgomery modulus must be odd and greater than 1");
+        let mut n_inv = n;
+        for _ in 0..6 {
+            n_inv = n_inv.wrapping_mul(2u128.wrapping_sub(n.wrapping_mul(n_inv)));
+        }
+        let one = ((u128::MAX % n) + 1) % n;
+        // Doubling R mod n another 128 times gives R^2 mod n
+        let mut r2 = one;
+        for _ in 0..128 {
+            r2 = add_mod(r2, r2, n);
+        }
+        Montgomery128 { n, n_inv, r2, one }
+    }
+
+    pub fn modulus(&self) -> u128 {
+        self.n
+    }
+
+    /// Montgomery form of 1.
+    pub fn one(&self) -> u128 {
+        self.one
+    }
+
+    // Function to compute (hi * 2^128 + lo) * R^-1 mod n for hi < n
+    fn reduce(&self, lo: u128, hi: u128) -> u128 {
+        let m = lo.wrapping_mul(self.n_inv);
+        let (_, mn_hi) = mul_wide(m, self.n);
+        if hi < mn_hi {
+            hi.wrapping_sub(mn_hi).wrapping_add(self.n)
+        } else {
+            hi - mn_hi
+        }
+    }
+
+    /// Converts `a` into Montgomery form.
+    pub fn to_montgomery(&self, a: u128) -> u128 {
+        let (lo, hi) = mul_wide(a % self.n, self.r2);
+        self.reduce(lo, hi)
+    }
+
+    /// Converts `a` out of Montgomery form.
+    pub fn from_montgomery(&self, a: u128) -> u128 {
+        self.reduce(a, 0)
+    }
+
+    /// Multiplies two values in Montgomery form.
+    pub fn mul(&self, a: u128, b: u128) -> u128 {
+        let (lo, hi) = mul_wide(a, b);
+        self.reduce(lo, hi)
+    }
+
+    /// Adds two values in Montgomery form.
+    pub fn add(&self, a: u128, b: u128) -> u128 {
+        add_mod(a, b, self.n)
+    }
+
+    /// Subtracts two values in Montgomery form.
+    pub fn sub(&self, a: u128, b: u128) -> u128 {
+        if a >= b {
+            a - b
+        } else {
+            a.wrapping_sub(b).wrapping_add(self.n)
+        }
+    }
+
+    /// Halves a value in Montgomery form.
+    pub fn half(&self, a: u128) -> u128 {
+        // For odd a, (a + n) / 2 without overflowing
+        if a.is_multiple_of(2) {
+            a >> 1
+        } else {
+            (a >> 1) + (self.n >> 1) + 1
+        }
+    }
+
+    /// Raises a value in Montgomery form to a plain `exponent`.
+    pub fn pow(&self, mut base: u128, mut exponent: u128) -> u128 {
+        let mut result = self.one;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+// Function to compute the full 256-bit product of a and b as (low, high) halves
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_lo, a_hi) = (a & MASK, a >> 64);
+    let (b_lo, b_hi) = (b & MASK, b >> 64);
+
+    let lo_lo = a_lo * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_lo = a_hi * b_lo;
+    let hi_hi = a_hi * b_hi;
+
+    // Sum the middle 64-bit column, keeping its carry for the high half
+    let middle = (lo_lo >> 64) + (lo_hi & MASK) + (hi_lo & MASK);
+    let lo = (lo_lo & MASK) | (middle << 64);
+    let hi = hi_hi + (lo_hi >> 64) + (hi_lo >> 64) + (middle >> 64);
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{mul_mod, pow_mod};
+
+    // Odd moduli of every size, including ones right below the word size
+    const MODULI_64: [u64; 6] = [3, 5, 1_000_003, (1 << 61) - 1, u64::MAX - 58, u64::MAX];
+    const MODULI_128: [u128; 6] = [3, 1_000_003, u64::MAX as u128 + 2, (1 << 127) - 1, u128::MAX - 158, u128::MAX];
+
+    // Function to produce spread-out test values from a simple LCG
+    fn values() -> impl Iterator<Item = u128> {
+        let mut x: u128 = 0x9e37_79b9_7f4a_7c15;
+        (0..200).map(move |_| {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            x ^ (x >> 67)
+        })
+    }
+
+    #[test]
+    fn montgomery64_matches_plain_arithmetic() {
+        for n in MODULI_64 {
+            let mont = Montgomery64::new(n);
+            assert_eq!(mont.from_montgomery(mont.one()), 1 % n);
+            for (a, b) in values().map(|v| v as u64).zip(values().skip(1).map(|v| (v >> 64) as u64)) {
+                let (am, bm) = (mont.to_montgomery(a), mont.to_montgomery(b));
+                assert_eq!(mont.from_montgomery(am), a % n);
+                assert_eq!(mont.from_montgomery(mont.mul(am, bm)) as u128, mul_mod(a as u128, b as u128, n as u128));
+                assert_eq!(mont.from_montgomery(mont.add(am, bm)) as u128, (a as u128 % n as u128 + b as u128 % n as u128) % n as u128);
+                assert_eq!(mont.from_montgomery(mont.pow(am, b)) as u128, pow_mod(a as u128, b as u128, n as u128));
+            }
+        }
+    }
+
+    #[test]
+    fn montgomery128_matches_plain_arithmetic() {
+        for n in MODULI_128 {
+            let mont = Montgomery128::new(n);
+            assert_eq!(mont.from_montgomery(mont.one()), 1 % n);
+            for (a, b) in values().zip(values().skip(1)) {
+                let (am, bm) = (mont.to_montgomery(a), mont.to_montgomery(b));
+                assert_eq!(mont.from_montgomery(am), a % n);
+                assert_eq!(mont.from_montgomery(mont.mul(am, bm)), mul_mod(a, b, n));
+                assert_eq!(mont.from_montgomery(mont.add(am, bm)), crate::math::add_mod(a % n, b % n, n));
+                assert_eq!(mont.from_montgomery(mont.pow(am, b >> 100)), pow_mod(a, b >> 100, n));
+            }
+        }
+    }
+
+    #[test]
+    fn montgomery128_sub_and_half_invert_add() {
+        for n in MODULI_128 {
+            let mont = Montgomery128::new(n);
+            for (a, b) in values().zip(values().skip(1)) {
+                let (am, bm) = (mont.to_montgomery(a), mont.to_montgomery(b));
+                assert_eq!(mont.add(mont.sub(am, bm), bm), am);
+                assert_eq!(mont.from_montgomery(mont.sub(am, bm)), crate::math::add_mod(a % n, n - b % n, n));
+                let half = mont.half(am);
+                assert_eq!(mont.add(half, half), am);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_even_moduli() {
+        Montgomery128::new(1 << 100);
+    }
+}
@@ -0,0 +1,116 @@
+// NUMA topology detection and worker placement.
+//
+// Workers are pinned to a core of a NUMA node before they start, and Linux
+// places memory on the node of the thread that first touches it, so the sieve
+// segments each worker allocates stay local to it.
+
+use std::fs;
+use std::str::FromStr;
+
+/// Whether worker threads are pinned to NUMA nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumaPolicy {
+    /// Pin workers round-robin across nodes on multi-node machines.
+    #[default]
+    Auto,
+    /// Leave thread placement to the OS scheduler.
+    Off,
+}
+
+impl FromStr for NumaPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<NumaPolicy, String> {
+        match s {
+            "auto" => Ok(NumaPolicy::Auto),
+            "off" => Ok(NumaPolicy::Off),
+            _ => Err(format!("Unknown NUMA policy: {}", s)),
+        }
+    }
+}
+
+/// Returns the CPUs of each NUMA node, from sysfs. Empty when the topology is unknown.
+pub fn numa_nodes() -> Vec<Vec<usize>> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/node") else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<(usize, Vec<usize>)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let node = name.strip_prefix("node")?.parse::<usize>().ok()?;
+            let cpus = parse_cpu_list(fs::read_to_string(entry.path().join("cpulist")).ok()?.trim())?;
+            Some((node, cpus))
+        })
+        .filter(|(_, cpus)| !cpus.is_empty())
+        .collect();
+    nodes.sort();
+    nodes.into_iter().map(|(_, cpus)| cpus).collect()
+}
+
+/// Parses a Linux CPU list such as `0-7,16-23`.
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse::<usize>().ok()?),
+            None => cpus.push(part.parse::<usize>().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Chooses the CPU for each of `threads` workers, spreading them round-robin
+/// across NUMA nodes. Returns `None` on single-node machines, where pinning
+/// buys nothing.
+pub fn worker_cpus(threads: usize) -> Option<Vec<usize>> {
+    let nodes = numa_nodes();
+    if nodes.len() < 2 {
+        return None;
+    }
+    Some(spread(&nodes, threads))
+}
+
+// Function to deal `threads` workers out to the CPUs of non-empty `nodes`,
+// one node after the other, cycling through each node's CPUs
+fn spread(nodes: &[Vec<usize>], threads: usize) -> Vec<usize> {
+    let mut next_in_node = vec![0; nodes.len()];
+    (0..threads)
+        .map(|worker| {
+            let node = worker % nodes.len();
+            let cpu = nodes[node][next_in_node[node] % nodes[node].len()];
+            next_in_node[node] += 1;
+            cpu
+        })
+        .collect()
+}
+
+/// Pins the calling thread to `cpu`. Returns `false` if the OS refused.
+pub fn pin_current_thread(cpu: usize) -> bool {
+    core_affinity::set_for_current(core_affinity::CoreId { id: cpu })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+        assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+        assert_eq!(parse_cpu_list(" 0-1, 4 "), Some(vec![0, 1, 4]));
+        // A node without CPUs has an empty list
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        for bad in ["a", "0-", "-3", "1-2-3", "0;1", "0x1"] {
+            assert_eq!(parse_cpu_list(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn spreads_workers_across_nodes() {
+        let nodes = [vec![0, 1], vec![8, 9, 10]];
+        assert_eq!(spread(&nodes, 5), [0, 8, 1, 9, 0]);
+        assert_eq!(spread(&nodes, 8), [0, 8, 1, 9, 0, 10, 1, 8]);
+        assert!(spread(&nodes, 0).is_empty());
+    }
+}
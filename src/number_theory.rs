@@ -0,0 +1,343 @@
+// Number-theoretic properties of individual primes, for studying patterns
+// across the generated lists.
+
+use crate::factor::factor_with_multiplicity;
+use crate::math::{is_prime, jacobi, pow_mod};
+use crate::montgomery::{Montgomery128, Montgomery64};
+
+/// How a rational prime factors in the Gaussian integers Z[i].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaussianSplitting {
+    /// `p ≡ 1 (mod 4)`: `p = (a + bi)(a - bi)`, two non-associate primes.
+    Split,
+    /// `p = 2 = -i(1 + i)^2`.
+    Ramified,
+    /// `p ≡ 3 (mod 4)`: `p` stays prime in Z[i].
+    Inert,
+}
+
+impl GaussianSplitting {
+    /// Returns how the prime `p` factors in Z[i].
+    pub fn of(p: u128) -> GaussianSplitting {
+        match p % 4 {
+            1 => GaussianSplitting::Split,
+            3 => GaussianSplitting::Inert,
+            _ => GaussianSplitting::Ramified,
+        }
+    }
+}
+
+impl std::fmt::Display for GaussianSplitting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            GaussianSplitting::Split => "split",
+            GaussianSplitting::Ramified => "ramified",
+            GaussianSplitting::Inert => "inert",
+        })
+    }
+}
+
+/// Writes the prime `p` as a sum of two squares `a^2 + b^2` with Cornacchia's
+/// algorithm, returning `(a, b)` with `a` odd and `b` even, or `(1, 1)` for
+/// 2. Returns `None` for primes `p ≡ 3 (mod 4)`, which are no such sum.
+pub fn two_squares(p: u128) -> Option<(u128, u128)> {
+    match GaussianSplitting::of(p) {
+        GaussianSplitting::Ramified => return Some((1, 1)),
+        GaussianSplitting::Inert => return None,
+        GaussianSplitting::Split => {}
+    }
+    // A square root of -1: c^((p-1)/4) for any quadratic non-residue c
+    let non_residue = (2..p).find(|&c| jacobi(c as i128, p) == -1)?;
+    let root = pow_mod(non_residue, (p - 1) / 4, p);
+    // Euclid's algorithm on (p, root) until the remainder drops below sqrt(p)
+    let (mut a, mut b) = (p, root);
+    while b > p / b {
+        (a, b) = (b, a % b);
+    }
+    let other = isqrt(p - b * b);
+    debug_assert_eq!(b * b + other * other, p, "Cornacchia's algorithm failed for {}", p);
+    Some(if b % 2 == 1 { (b, other) } else { (other, b) })
+}
+
+/// Returns the Legendre symbol `(a/p)` of an odd prime `p`: 1 if `a` is a
+/// quadratic residue modulo `p`, -1 if it is a non-residue, and 0 if `p`
+/// divides `a`. For odd composite `p` this is the Jacobi symbol, and for
+/// `p = 2` the Kronecker symbol, so every prime has a value. Returns `None`
+/// for 0 and other even `p`.
+pub fn legendre(a: i128, p: u128) -> Option<i32> {
+    match p {
+        2 => Some(match a.rem_euclid(8) {
+            1 | 7 => 1,
+            3 | 5 => -1,
+            _ => 0,
+        }),
+        _ if p % 2 == 1 => Some(jacobi(a, p)),
+        _ => None,
+    }
+}
+
+/// Returns the smallest primitive root modulo the prime `p`: the smallest `g`
+/// whose powers run through every nonzero residue, found by checking
+/// `g^((p-1)/q) ≠ 1` for each prime factor `q` of `p - 1`. Returns `None`
+/// if `p` isn't prime.
+pub fn primitive_root(p: u128) -> Option<u128> {
+    if !is_prime(p) {
+        return None;
+    }
+    if p == 2 {
+        return Some(1);
+    }
+    let factors = factor_with_multiplicity(p - 1);
+    (2..p).find(|&g| factors.iter().all(|&(q, _)| pow_mod(g, (p - 1) / q, p) != 1))
+}
+
+/// Returns the multiplicative order of `a` modulo the prime `p`: the smallest
+/// `k > 0` with `a^k ≡ 1 (mod p)`, a divisor of `p - 1`. When it is `p - 1`,
+/// `a` is a primitive root, and for `a = 10` `p` is a full-reptend prime
+/// (`1/p` repeats with period `p - 1` in decimal). Returns `None` if `p`
+/// isn't prime or divides `a`.
+pub fn multiplicative_order(a: u128, p: u128) -> Option<u128> {
+    if !is_prime(p) || a.is_multiple_of(p) {
+        return None;
+    }
+    // Strip each prime factor of p - 1 from the order while a's power stays 1
+    let mut order = p - 1;
+    for (q, _) in factor_with_multiplicity(p - 1) {
+        while order.is_multiple_of(q) && pow_mod(a, order / q, p) == 1 {
+            order /= q;
+        }
+    }
+    Some(order)
+}
+
+/// Returns whether the prime `p` is a Wieferich prime, with
+/// `2^(p-1) ≡ 1 (mod p^2)`. Only 1093 and 3511 are known. Works modulo `p^2`
+/// in Montgomery form, so it costs one modular exponentiation.
+pub fn is_wieferich(p: u64) -> bool {
+    if p < 3 {
+        return false;
+    }
+    let modulus = Montgomery128::new(p as u128 * p as u128);
+    let two = modulus.to_montgomery(2);
+    modulus.pow(two, p as u128 - 1) == modulus.one()
+}
+
+/// Returns whether the prime `p` is a Wilson prime, with
+/// `(p-1)! ≡ -1 (mod p^2)`. Only 5, 13 and 563 are known. Multiplies out
+/// `(p-1)!` modulo `p^2` in Montgomery form, so it takes `p` multiplications
+/// and is only feasible for small primes; `p` must be below 2^32.
+pub fn is_wilson(p: u64) -> bool {
+    assert!(p < 1 << 32, "Wilson prime tests only support primes below 2^32");
+    if p < 3 {
+        return false;
+    }
+    let modulus = Montgomery64::new(p * p);
+    let one = modulus.one();
+    // k and (k-1)! in Montgomery form, stepping k by adding one
+    let (mut k, mut factorial) = (one, one);
+    for _ in 2..p {
+        k = modulus.add(k, one);
+        factorial = modulus.mul(factorial, k);
+    }
+    modulus.from_montgomery(factorial) == p * p - 1
+}
+
+/// Returns the integer `k`-th root of `n`, the largest `r` with `r^k <= n`.
+pub fn iroot(n: u128, k: u32) -> u128 {
+    assert!(k > 0, "The 0th root is undefined");
+    if k == 1 || n < 2 {
+        return n;
+    }
+    // Newton's iteration from above converges to the floor of the root; a
+    // power that overflows means x is still far too large
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(k);
+    loop {
+        let next = ((k - 1) as u128 * x + n / x.checked_pow(k - 1).unwrap_or(u128::MAX)) / k as u128;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// Writes `n` as `b^k` with the largest possible exponent `k`, returning
+/// `(b, k)`, or `(n, 1)` if `n` is no perfect power. For a prime power
+/// `p^k` this is `(p, k)`.
+pub fn perfect_power(n: u128) -> (u128, u32) {
+    // Prime exponents suffice, as b^(jk) = (b^j)^k; 2^127 is the largest power
+    const PRIME_EXPONENTS: [u32; 31] = [
+        2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107,
+        109, 113, 127,
+    ];
+    if n < 4 {
+        return (n, 1);
+    }
+    let bits = 128 - n.leading_zeros();
+    for k in PRIME_EXPONENTS.into_iter().take_while(|&k| k < bits) {
+        let root = iroot(n, k);
+        if root.pow(k) == n {
+            let (base, exponent) = perfect_power(root);
+            return (base, exponent * k);
+        }
+    }
+    (n, 1)
+}
+
+/// Returns the prime powers `p^k` with `k >= 2` in `[start, end]` as
+/// `(p^k, p, k)`, in increasing order. The bases of the `k`-th powers lie
+/// between the `k`-th roots of the ends, so only those are tested.
+pub fn prime_powers(start: u128, end: u128) -> Vec<(u128, u128, u32)> {
+    let mut powers = Vec::new();
+    for k in 2..u128::BITS {
+        let last_base = iroot(end, k);
+        if last_base < 2 {
+            break;
+        }
+        let first_base = match start {
+            0 => 2,
+            _ => iroot(start - 1, k) + 1,
+        };
+        for base in (first_base.max(2)..=last_base).filter(|&base| is_prime(base)) {
+            powers.push((base.pow(k), base, k));
+        }
+    }
+    powers.sort_unstable();
+    powers
+}
+
+// Function to compute floor(sqrt(n))
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's iteration from above converges to floor(sqrt(n))
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (x + n / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn decomposes_split_primes() {
+        assert_eq!(two_squares(2), Some((1, 1)));
+        assert_eq!(two_squares(5), Some((1, 2)));
+        assert_eq!(two_squares(13), Some((3, 2)));
+        assert_eq!(two_squares(7), None);
+        for p in primes_in_range(3, 100_000).into_iter().map(u128::from) {
+            match two_squares(p) {
+                Some((a, b)) => {
+                    assert_eq!(GaussianSplitting::of(p), GaussianSplitting::Split);
+                    assert_eq!((a * a + b * b, a % 2, b % 2), (p, 1, 0), "{}", p);
+                }
+                None => assert_eq!(GaussianSplitting::of(p), GaussianSplitting::Inert),
+            }
+        }
+        // The largest primes below 2^64 that are 1 and 3 mod 4, and one near 2^100
+        for p in [(1u128 << 64) - 59, (1u128 << 100) + 277] {
+            let (a, b) = two_squares(p).unwrap();
+            assert_eq!(a * a + b * b, p);
+        }
+        assert_eq!(two_squares((1u128 << 64) - 189), None);
+    }
+
+    #[test]
+    fn legendre_follows_the_supplementary_laws() {
+        // (-1/p) = 1 exactly when p splits in Z[i], and (2/p) = 1 when p ≡ ±1 (mod 8)
+        for p in primes_in_range(3, 10_000).into_iter().map(u128::from) {
+            let minus_one = if GaussianSplitting::of(p) == GaussianSplitting::Split { 1 } else { -1 };
+            let two = if p % 8 == 1 || p % 8 == 7 { 1 } else { -1 };
+            assert_eq!((legendre(-1, p), legendre(2, p)), (Some(minus_one), Some(two)), "{}", p);
+            assert_eq!(legendre(p as i128 * 3, p), Some(0));
+        }
+        assert_eq!([legendre(1, 2), legendre(3, 2), legendre(4, 2), legendre(-1, 2)], [Some(1), Some(-1), Some(0), Some(1)]);
+        assert_eq!(legendre(3, 0), None);
+        assert_eq!(legendre(3, 10), None);
+    }
+
+    #[test]
+    fn finds_smallest_primitive_roots() {
+        // OEIS A001918
+        let roots: Vec<u128> = primes_in_range(2, 50).into_iter().filter_map(|p| primitive_root(p.into())).collect();
+        assert_eq!(roots, [1, 2, 2, 3, 2, 2, 3, 2, 5, 2, 3, 2, 6, 3, 5]);
+        // The powers of a primitive root cover every nonzero residue, and no smaller number's do
+        for p in primes_in_range(3, 2000).into_iter().map(u128::from) {
+            let order = |g: u128| (1..p).find(|&k| pow_mod(g, k, p) == 1).unwrap();
+            let root = primitive_root(p).unwrap();
+            assert_eq!(order(root), p - 1, "{}", p);
+            assert!((2..root).all(|g| order(g) < p - 1), "{}", p);
+        }
+        // Large primes, where p - 1 needs Pollard rho: 2^64 - 60 = 2^2 · 11 · 137 · 547 · 5594472617641
+        assert_eq!(primitive_root((1u128 << 64) - 59), Some(2));
+        assert_eq!(primitive_root((1u128 << 127) - 1), Some(43));
+        assert_eq!(primitive_root(91), None);
+    }
+
+    #[test]
+    fn computes_multiplicative_orders() {
+        // OEIS A001913: the full-reptend primes in base 10
+        let full_reptend: Vec<u128> =
+            primes_in_range(2, 200).into_iter().map(u128::from).filter(|&p| multiplicative_order(10, p) == Some(p - 1)).collect();
+        assert_eq!(full_reptend, [7, 17, 19, 23, 29, 47, 59, 61, 97, 109, 113, 131, 149, 167, 179, 181, 193]);
+        for p in primes_in_range(3, 2000).into_iter().map(u128::from) {
+            for a in [2, 3, 10, p - 1] {
+                let expected = (1..p).find(|&k| pow_mod(a, k, p) == 1);
+                assert_eq!(multiplicative_order(a, p), expected, "ord_{}({})", p, a);
+            }
+        }
+        // 2 has order 127 modulo the Mersenne prime 2^127 - 1
+        assert_eq!(multiplicative_order(2, (1u128 << 127) - 1), Some(127));
+        assert_eq!(multiplicative_order(3, 2), Some(1));
+        assert_eq!(multiplicative_order(14, 7), None);
+        assert_eq!(multiplicative_order(2, 91), None);
+    }
+
+    #[test]
+    fn finds_roots_and_perfect_powers() {
+        for k in 1..=127 {
+            for n in [0, 1, 2, 3, 1000, 1 << 64, u128::MAX] {
+                let root = iroot(n, k);
+                assert!(root.pow(k) <= n && root.checked_add(1).and_then(|next| next.checked_pow(k)).is_none_or(|next| next > n), "{} {}", n, k);
+            }
+        }
+        assert_eq!(iroot(u64::MAX as u128 * u64::MAX as u128, 2), u64::MAX as u128);
+
+        assert_eq!(perfect_power(1024), (2, 10));
+        assert_eq!(perfect_power(1296), (6, 4));
+        assert_eq!(perfect_power(3u128.pow(80)), (3, 80));
+        assert_eq!(perfect_power(1 << 127), (2, 127));
+        for n in [0, 1, 2, 3, 12, 1_000_003, u128::MAX] {
+            assert_eq!(perfect_power(n), (n, 1));
+        }
+    }
+
+    #[test]
+    fn enumerates_prime_powers() {
+        // OEIS A246547
+        let powers: Vec<u128> = prime_powers(0, 100).into_iter().map(|(power, _, _)| power).collect();
+        assert_eq!(powers, [4, 8, 9, 16, 25, 27, 32, 49, 64, 81]);
+        assert_eq!(prime_powers(26, 27), [(27, 3, 3)]);
+        assert!(prime_powers(10, 15).is_empty());
+        assert!(prime_powers(u128::MAX - 1000, u128::MAX).is_empty());
+    }
+
+    #[test]
+    fn finds_wieferich_and_wilson_primes() {
+        let wieferich: Vec<u64> = primes_in_range(2, 100_000).into_iter().filter(|&p| is_wieferich(p)).collect();
+        assert_eq!(wieferich, [1093, 3511]);
+        let wilson: Vec<u64> = primes_in_range(2, 5000).into_iter().filter(|&p| is_wilson(p)).collect();
+        assert_eq!(wilson, [5, 13, 563]);
+        // Near the top of the supported range, checked against 2^(p-1) mod p^2 computed directly
+        let p = u64::MAX - 58;
+        let square = p as u128 * p as u128;
+        assert_eq!(is_wieferich(p), pow_mod(2, p as u128 - 1, square) == 1);
+    }
+}
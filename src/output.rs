@@ -0,0 +1,76 @@
+// Output file names built from a template, so separate runs can write to
+// separate files instead of appending to one.
+
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+/// Template that reproduces the historical fixed output file name.
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "primes_and_powers.csv";
+
+/// Expands an output file name template. `{start}` and `{end}` are replaced
+/// with the bounds of the run and `{timestamp}` with `timestamp`, in seconds
+/// since the Unix epoch; `{{` and `}}` stand for literal braces.
+///
+/// ```
+/// use prime_generator::output::expand_output_template;
+///
+/// let path = expand_output_template("primes_{start}_{end}_{timestamp}.csv", 2, 1000, 1_700_000_000).unwrap();
+/// assert_eq!(path.to_str(), Some("primes_2_1000_1700000000.csv"));
+/// ```
+pub fn expand_output_template(template: &str, start: u128, end: u128, timestamp: u64) -> Result<PathBuf> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("{{") {
+            expanded.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            expanded.push('}');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let (name, after) = after
+                .split_once('}')
+                .ok_or_else(|| invalid_template(format!("Unclosed `{{` in output template {:?}", template)))?;
+            match name {
+                "start" => expanded.push_str(&start.to_string()),
+                "end" => expanded.push_str(&end.to_string()),
+                "timestamp" => expanded.push_str(&timestamp.to_string()),
+                _ => return Err(invalid_template(format!("Unknown variable {{{}}} in output template; use {{start}}, {{end}} or {{timestamp}}", name))),
+            }
+            rest = after;
+        } else {
+            return Err(invalid_template(format!("Unmatched `}}` in output template {:?}", template)));
+        }
+    }
+    expanded.push_str(rest);
+    if expanded.is_empty() {
+        return Err(invalid_template("The output template is empty"));
+    }
+    Ok(PathBuf::from(expanded))
+}
+
+fn invalid_template<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidInput, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_variables_and_escapes() {
+        let expand = |template| expand_output_template(template, 10, 20, 30).map(|path| path.to_string_lossy().into_owned());
+        assert_eq!(expand(DEFAULT_OUTPUT_TEMPLATE).unwrap(), "primes_and_powers.csv");
+        assert_eq!(expand("out/{start}-{end}/{timestamp}_{start}.csv").unwrap(), "out/10-20/30_10.csv");
+        assert_eq!(expand("{{start}}_{end}}}.csv").unwrap(), "{start}_20}.csv");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in ["primes_{stop}.csv", "primes_{start.csv", "primes_}.csv", ""] {
+            assert_eq!(expand_output_template(template, 1, 2, 3).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", template);
+        }
+    }
+}
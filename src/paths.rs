@@ -0,0 +1,111 @@
+// Where files live on each platform: the per-user cache and config
+// directories, and output paths extended past the 260-character limit of
+// classic Windows paths.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Returns the directory `prime_generator` caches files in, or `None` if no
+/// cache directory is known.
+///
+/// Uses `$XDG_CACHE_HOME` or `~/.cache` on Unix, `~/Library/Caches` on macOS and
+/// `%LOCALAPPDATA%` on Windows.
+pub fn cache_dir() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("LOCALAPPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Caches")
+    } else {
+        xdg_dir("XDG_CACHE_HOME", ".cache")?
+    };
+    Some(dir.join("prime_generator"))
+}
+
+/// Returns the directory `prime_generator` reads configuration from, or
+/// `None` if no config directory is known.
+///
+/// Uses `$XDG_CONFIG_HOME` or `~/.config` on Unix, `~/Library/Application
+/// Support` on macOS and `%APPDATA%` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        xdg_dir("XDG_CONFIG_HOME", ".config")?
+    };
+    Some(dir.join("prime_generator"))
+}
+
+/// Returns `path` in a form every file API of the platform accepts whatever
+/// its length: on Windows, made absolute and given the `\\?\` prefix
+/// (`\\?\UNC\` for network shares); elsewhere unchanged.
+pub fn long_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    // `absolute` also turns `/` into `\`, which `\\?\` paths need
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    match absolute.to_str().and_then(extended_windows_path) {
+        Some(extended) => PathBuf::from(extended),
+        None => absolute,
+    }
+}
+
+// Function to give an absolute Windows path the extended-length prefix;
+// `None` if it has one already or isn't a drive or UNC path
+fn extended_windows_path(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{}", share));
+    }
+    let drive = path.as_bytes();
+    match drive.len() >= 3 && drive[0].is_ascii_alphabetic() && drive[1] == b':' && drive[2] == b'\\' {
+        true => Some(format!(r"\\?\{}", path)),
+        false => None,
+    }
+}
+
+// Function to read an XDG base directory, falling back to its default under
+// the home directory when unset, empty or relative (as the spec asks)
+fn xdg_dir(variable: &str, default: &str) -> Option<PathBuf> {
+    match env::var_os(variable).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(default)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extends_windows_paths() {
+        assert_eq!(extended_windows_path(r"C:\data\primes.csv").as_deref(), Some(r"\\?\C:\data\primes.csv"));
+        assert_eq!(extended_windows_path(r"\\server\share\primes.csv").as_deref(), Some(r"\\?\UNC\server\share\primes.csv"));
+        assert_eq!(extended_windows_path(r"\\?\C:\data\primes.csv"), None);
+        assert_eq!(extended_windows_path(r"\\.\pipe\primes"), None);
+        assert_eq!(extended_windows_path("primes.csv"), None);
+        if !cfg!(windows) {
+            assert_eq!(long_path("out/primes.csv"), PathBuf::from("out/primes.csv"));
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn follows_xdg_base_directories() {
+        // Only this test reads or sets XDG_CONFIG_HOME
+        env::set_var("XDG_CONFIG_HOME", "/etc/xdg-test");
+        assert_eq!(config_dir(), Some(PathBuf::from("/etc/xdg-test/prime_generator")));
+        let default = env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/prime_generator"));
+        env::set_var("XDG_CONFIG_HOME", "relative");
+        assert_eq!(config_dir(), default);
+        env::remove_var("XDG_CONFIG_HOME");
+        assert_eq!(config_dir(), default);
+    }
+}
@@ -0,0 +1,211 @@
+// Planning the work units that take a project up to a target within a time
+// budget. Throughput falls slowly as numbers grow, so it is measured at a few
+// magnitudes and interpolated, and each unit is sized to take about the same
+// time wherever it lands on the number line.
+
+use std::time::Duration;
+
+use crate::aggregate::logarithmic_integral;
+
+// Shortest unit worth proposing
+const MIN_UNIT: Duration = Duration::from_secs(1);
+
+/// Numbers per second measured at a few points of the number line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    points: Vec<(u128, f64)>,
+}
+
+/// A range proposed for one run, with how long it should take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkUnit {
+    pub start: u128,
+    pub end: u128,
+    pub estimated: Duration,
+}
+
+impl Calibration {
+    /// Takes `(number, numbers per second)` pairs measured around each number.
+    /// Rates that aren't positive are left out.
+    pub fn new<I: IntoIterator<Item = (u128, f64)>>(points: I) -> Calibration {
+        let mut points: Vec<(u128, f64)> = points.into_iter().filter(|&(_, rate)| rate > 0.0).collect();
+        points.sort_by_key(|&(at, _)| at);
+        Calibration { points }
+    }
+
+    /// Estimates the numbers per second around `n`, interpolating linearly in
+    /// the number's order of magnitude between measured points and holding
+    /// the nearest measurement beyond them. `None` without measurements.
+    pub fn rate_at(&self, n: u128) -> Option<f64> {
+        let magnitude = |n: u128| (n.max(1) as f64).log10();
+        let after = self.points.partition_point(|&(at, _)| at <= n);
+        match (after.checked_sub(1).map(|i| self.points[i]), self.points.get(after)) {
+            (Some((from, from_rate)), Some(&(to, to_rate))) => {
+                let t = (magnitude(n) - magnitude(from)) / (magnitude(to) - magnitude(from));
+                Some(from_rate + (to_rate - from_rate) * t)
+            }
+            (Some((_, rate)), None) | (None, Some(&(_, rate))) => Some(rate),
+            (None, None) => None,
+        }
+    }
+
+    /// Estimates how long `[start, end]` takes.
+    pub fn estimate(&self, start: u128, end: u128) -> Option<Duration> {
+        let rate = self.rate_at(start + (end - start) / 2)?;
+        Some(Duration::from_secs_f64((end - start + 1) as f64 / rate))
+    }
+}
+
+/// What a run is expected to produce, estimated before it starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunEstimate {
+    /// Numbers the run tests.
+    pub candidates: u128,
+    /// Primes among them, by the logarithmic integral.
+    pub primes: f64,
+    /// Bytes of CSV output: a row per prime, with its square, cube and
+    /// fourth power unless only the primes are written.
+    pub output_bytes: f64,
+}
+
+/// Estimates a run over `ranges`, decade by decade, since both the share of
+/// primes and the length of their rows change with the number of digits.
+pub fn estimate_run(ranges: &[(u128, u128)], primes_only: bool) -> RunEstimate {
+    let mut estimate = RunEstimate { candidates: 0, primes: 0.0, output_bytes: 0.0 };
+    for &(start, end) in ranges.iter().filter(|(start, end)| start <= end) {
+        estimate.candidates = estimate.candidates.saturating_add(end - start).saturating_add(1);
+        let mut from = start.max(2);
+        while from <= end {
+            let digits = from.ilog10() + 1;
+            let to = 10u128.checked_pow(digits).map_or(end, |next| end.min(next - 1));
+            let primes = (logarithmic_integral(to as f64 + 1.0) - logarithmic_integral(from as f64)).max(0.0);
+            // The powers have 2, 3 and 4 times the digits, and the row 3 commas and a newline
+            let row = match primes_only {
+                true => digits + 1,
+                false => 10 * digits + 4,
+            };
+            estimate.primes += primes;
+            estimate.output_bytes += primes * row as f64;
+            match to.checked_add(1) {
+                Some(next) => from = next,
+                None => break,
+            }
+        }
+    }
+    estimate
+}
+
+/// Splits `[start, target]` into consecutive units taking about `unit` each,
+/// from the smallest numbers up, for as much of it as fits into `budget`. The
+/// last unit is cut short to end at the target or to use up the budget; the
+/// units reach the target only if the budget allows.
+pub fn plan(calibration: &Calibration, start: u128, target: u128, unit: Duration, budget: Duration) -> Vec<WorkUnit> {
+    let mut units = Vec::new();
+    let (mut next, mut left) = (start, budget);
+    while next <= target && !left.is_zero() {
+        let seconds = unit.min(left).as_secs_f64();
+        let Some(rate) = calibration.rate_at(next) else {
+            break;
+        };
+        // Size by the rate at the start, then again by the rate halfway through
+        let guess = (rate * seconds).max(1.0) as u128;
+        let rate = calibration.rate_at(next.saturating_add(guess / 2)).unwrap_or(rate);
+        let size = (rate * seconds).max(1.0) as u128;
+        let end = next.saturating_add(size - 1).min(target);
+        let estimated = Duration::from_secs_f64((end - next + 1) as f64 / rate);
+        units.push(WorkUnit { start: next, end, estimated });
+        // Leave out the sliver of budget rounding the sizes down leaves
+        left = left.saturating_sub(estimated);
+        if left < MIN_UNIT {
+            break;
+        }
+        if end == u128::MAX {
+            break;
+        }
+        next = end + 1;
+    }
+    units
+}
+
+/// Parses a duration written as a number with a unit: `90s`, `30m`, `48h` or
+/// `2d`; a plain number counts seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (number, seconds) = match value.char_indices().last()? {
+        (i, 's') => (&value[..i], 1.0),
+        (i, 'm') => (&value[..i], 60.0),
+        (i, 'h') => (&value[..i], 3600.0),
+        (i, 'd') => (&value[..i], 86400.0),
+        _ => (value, 1.0),
+    };
+    let number = number.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)?;
+    Duration::try_from_secs_f64(number * seconds).ok()
+}
+
+/// Formats a duration to the minute, or to the second under an hour, such as
+/// `2d 03h 10m` or `4m 05s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64().round() as u64;
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m {:02}s", minutes, seconds % 60),
+        (0, _) => format!("{}h {:02}m", hours, minutes),
+        _ => format!("{}d {:02}h {:02}m", days, hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_throughput_by_magnitude() {
+        let calibration = Calibration::new([(1_000_000, 2e6), (10, 0.0), (1_000, 4e6)]);
+        assert_eq!(calibration.rate_at(1), Some(4e6));
+        assert_eq!(calibration.rate_at(1_000), Some(4e6));
+        assert_eq!(calibration.rate_at(1_000_000_000), Some(2e6));
+        assert!((calibration.rate_at(31_623).unwrap() - 3e6).abs() < 10.0);
+        assert_eq!(calibration.estimate(1, 4_000_000), Some(Duration::from_secs(2)));
+        assert_eq!(Calibration::new([]).rate_at(5), None);
+    }
+
+    #[test]
+    fn plans_units_within_the_budget() {
+        let calibration = Calibration::new([(1, 100.0)]);
+        let hour = Duration::from_secs(3600);
+        let units = plan(&calibration, 1, 1_000_000, hour, 2 * hour + hour / 2);
+        let ranges: Vec<(u128, u128)> = units.iter().map(|unit| (unit.start, unit.end)).collect();
+        assert_eq!(ranges, [(1, 360_000), (360_001, 720_000), (720_001, 900_000)]);
+        assert_eq!(units[2].estimated, hour / 2);
+
+        let units = plan(&calibration, 1, 500_000, hour, 10 * hour);
+        assert_eq!(units.last().map(|unit| unit.end), Some(500_000));
+        assert_eq!(units.len(), 2);
+    }
+
+    #[test]
+    fn estimates_runs() {
+        // pi(10^6) = 78498: 4, 21, 143, 1061, 8363 and 68906 primes of 1 to 6 digits
+        let estimate = estimate_run(&[(0, 999_999)], true);
+        assert_eq!(estimate.candidates, 1_000_000);
+        assert!((estimate.primes - 78_498.0).abs() < 200.0, "{}", estimate.primes);
+        let bytes = 2 * 4 + 3 * 21 + 4 * 143 + 5 * 1061 + 6 * 8363 + 7 * 68906;
+        assert!((estimate.output_bytes - bytes as f64).abs() < 1_500.0, "{}", estimate.output_bytes);
+        let estimate = estimate_run(&[(10, 19), (100, 10)], false);
+        assert_eq!(estimate.candidates, 10);
+        assert_eq!(estimate_run(&[(0, u128::MAX)], true).candidates, u128::MAX);
+    }
+
+    #[test]
+    fn parses_and_formats_durations() {
+        assert_eq!(parse_duration("48h"), Some(Duration::from_secs(48 * 3600)));
+        assert_eq!(parse_duration("1.5d"), Some(Duration::from_secs(36 * 3600)));
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("-1h"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(format_duration(Duration::from_secs(245)), "4m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 600)), "3h 10m");
+        assert_eq!(format_duration(Duration::from_secs(2 * 86400 + 3 * 3600 + 600)), "2d 03h 10m");
+    }
+}
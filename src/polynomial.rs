@@ -0,0 +1,127 @@
+// Integer polynomials in one variable `n`, such as Euler's prime-generating
+// `n^2 + n + 41`, for scanning their values for primes.
+
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+
+/// A polynomial in `n` with integer coefficients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    // Coefficient of n^i at index i, without trailing zeros
+    coefficients: Vec<BigInt>,
+}
+
+impl Polynomial {
+    /// Returns the polynomial's degree, 0 for constants (including zero).
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Returns the polynomial's value at `n`, by Horner's rule.
+    pub fn eval(&self, n: u128) -> BigInt {
+        let n = BigInt::from(n);
+        self.coefficients.iter().rev().fold(BigInt::zero(), |value, coefficient| value * &n + coefficient)
+    }
+}
+
+impl FromStr for Polynomial {
+    type Err = String;
+
+    /// Parses sums and differences of terms such as `3n^2`, `3*n^2`, `-n` and
+    /// `41`, in any order and with repeated powers allowed.
+    fn from_str(s: &str) -> std::result::Result<Polynomial, String> {
+        let invalid = |reason: &str| format!("Invalid polynomial {:?}: {}", s, reason);
+        let expression: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if expression.is_empty() {
+            return Err(invalid("it is empty"));
+        }
+        let mut coefficients: Vec<BigInt> = Vec::new();
+        let mut rest = expression.as_str();
+        while !rest.is_empty() {
+            let negative = rest.starts_with('-');
+            rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let (term, next) = rest.split_at(end);
+            rest = next;
+
+            let (coefficient, power) = match term.split_once('n') {
+                Some((coefficient, power)) => {
+                    let power = match power {
+                        "" => 1,
+                        _ => power.strip_prefix('^').and_then(|p| p.parse().ok()).ok_or_else(|| invalid("bad exponent"))?,
+                    };
+                    (coefficient.strip_suffix('*').unwrap_or(coefficient), power)
+                }
+                None => (term, 0),
+            };
+            let mut coefficient = match coefficient {
+                "" if power > 0 => BigInt::one(),
+                _ => coefficient.parse::<BigInt>().ok().filter(|c| !c.is_negative()).ok_or_else(|| invalid("bad term"))?,
+            };
+            if negative {
+                coefficient = -coefficient;
+            }
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, BigInt::zero());
+            }
+            coefficients[power] += coefficient;
+        }
+        while coefficients.last().is_some_and(Zero::is_zero) {
+            coefficients.pop();
+        }
+        Ok(Polynomial { coefficients })
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<(usize, &BigInt)> = self.coefficients.iter().enumerate().rev().filter(|(_, c)| !c.is_zero()).collect();
+        if terms.is_empty() {
+            return f.write_str("0");
+        }
+        for (i, (power, coefficient)) in terms.into_iter().enumerate() {
+            match (i, coefficient.is_negative()) {
+                (0, true) => f.write_str("-")?,
+                (0, false) => {}
+                (_, true) => f.write_str(" - ")?,
+                (_, false) => f.write_str(" + ")?,
+            }
+            let magnitude = coefficient.abs();
+            if power == 0 || !magnitude.is_one() {
+                write!(f, "{}", magnitude)?;
+            }
+            match power {
+                0 => {}
+                1 => f.write_str("n")?,
+                _ => write!(f, "n^{}", power)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_evaluates_polynomials() {
+        let euler: Polynomial = "n^2 + n + 41".parse().unwrap();
+        assert_eq!((euler.degree(), euler.to_string()), (2, "n^2 + n + 41".to_string()));
+        assert_eq!(euler.eval(40), BigInt::from(1681));
+
+        let messy: Polynomial = "7 - 2*n + 3n^3 - n^3 -10".parse().unwrap();
+        assert_eq!(messy.to_string(), "2n^3 - 2n - 3");
+        assert_eq!(messy.eval(0), BigInt::from(-3));
+        assert_eq!(messy.eval(u128::MAX), BigInt::from(u128::MAX).pow(3) * 2 - BigInt::from(u128::MAX) * 2 - 3);
+
+        assert_eq!("n - n".parse::<Polynomial>().unwrap().to_string(), "0");
+        assert_eq!("-n^2".parse::<Polynomial>().unwrap().to_string(), "-n^2");
+        for spec in ["", "n^", "x + 1", "2n^-1", "n^2 + + 1", "3 * 4"] {
+            assert!(spec.parse::<Polynomial>().is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Primality testing for arbitrary-precision integers, on top of the
+// machine-sized tests in `math`.
+
+#[cfg(feature = "gmp")]
+use num_bigint::Sign;
+use num_bigint::BigInt;
+#[cfg(not(feature = "gmp"))]
+use num_bigint::ToBigInt;
+use num_traits::ToPrimitive;
+#[cfg(not(feature = "gmp"))]
+use num_traits::Zero;
+#[cfg(feature = "gmp")]
+use rug::{integer::IsPrime, integer::Order, Integer};
+
+pub use crate::math::{is_prime, next_prime};
+
+// Miller-Rabin rounds GMP runs after its Baillie-PSW test
+#[cfg(feature = "gmp")]
+const GMP_REPS: u32 = 25;
+
+// Bases of the Miller-Rabin rounds `probable_prime` runs beyond u128: the first 20 primes
+#[cfg(not(feature = "gmp"))]
+const PRP_BASES: [u32; 20] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71];
+
+/// The test [`probable_prime`] runs on numbers up to `u128`, through [`is_prime`].
+pub const PRIME_TEST: &str = "Miller-Rabin (deterministic below 3.3e24)";
+
+/// The test [`probable_prime`] runs on numbers beyond `u128`.
+#[cfg(not(feature = "gmp"))]
+pub const PROBABLE_PRIME_TEST: &str = "Miller-Rabin with 20 bases";
+/// The test [`probable_prime`] runs on numbers beyond `u128`.
+#[cfg(feature = "gmp")]
+pub const PROBABLE_PRIME_TEST: &str = "GMP Baillie-PSW and 25 Miller-Rabin rounds";
+
+/// Outcome of [`probable_prime`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primality {
+    Composite,
+    /// Passed [`PRIME_TEST`]: prime for certain below 3.3 * 10^24.
+    Prime,
+    /// Passed [`PROBABLE_PRIME_TEST`].
+    ProbablePrime,
+}
+
+/// Returns `true` if `big_n` is prime, falling back to BigInt trial division
+/// for numbers that don't fit into a `u128`.
+///
+/// With the `gmp` feature, numbers beyond `u128` are instead tested with GMP's
+/// Baillie-PSW and Miller-Rabin rounds, which is fast but probabilistic.
+pub fn is_prime_bigint(big_n: &BigInt) -> bool {
+    if let Some(n) = big_n.to_u128() {
+        return is_prime(n);
+    }
+    is_prime_large(big_n)
+}
+
+/// Classifies `big_n` quickly at any size: numbers that fit into a `u128` are
+/// tested with [`is_prime`], larger ones are only shown to be probable
+/// primes, by [`PROBABLE_PRIME_TEST`].
+pub fn probable_prime(big_n: &BigInt) -> Primality {
+    if let Some(n) = big_n.to_u128() {
+        return if is_prime(n) { Primality::Prime } else { Primality::Composite };
+    }
+    if probable_prime_large(big_n) {
+        Primality::ProbablePrime
+    } else {
+        Primality::Composite
+    }
+}
+
+// Function to run GMP's probable-prime test on a number beyond u128
+#[cfg(feature = "gmp")]
+fn probable_prime_large(big_n: &BigInt) -> bool {
+    is_prime_large(big_n)
+}
+
+// Function to run Miller-Rabin rounds on an odd number beyond u128
+#[cfg(not(feature = "gmp"))]
+fn probable_prime_large(big_n: &BigInt) -> bool {
+    if big_n.sign() == num_bigint::Sign::Minus || (big_n % 2u32).is_zero() {
+        return false;
+    }
+    let minus_one = big_n - 1u32;
+    let s = minus_one.trailing_zeros().unwrap_or(0);
+    let d = &minus_one >> s;
+    PRP_BASES.iter().all(|&base| {
+        let mut x = BigInt::from(base).modpow(&d, big_n);
+        if x == BigInt::from(1) || x == minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = &x * &x % big_n;
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// Function to test a number beyond u128 with GMP
+#[cfg(feature = "gmp")]
+fn is_prime_large(big_n: &BigInt) -> bool {
+    let (sign, digits) = big_n.to_u64_digits();
+    if sign == Sign::Minus {
+        return false;
+    }
+    Integer::from_digits(&digits, Order::Lsf).is_probably_prime(GMP_REPS) != IsPrime::No
+}
+
+// Function to test a number beyond u128 by BigInt trial division
+#[cfg(not(feature = "gmp"))]
+fn is_prime_large(big_n: &BigInt) -> bool {
+    if *big_n <= 1.to_bigint().unwrap() || *big_n == 2.to_bigint().unwrap() || *big_n == 3.to_bigint().unwrap() {
+        return *big_n > 1.to_bigint().unwrap();
+    }
+    if big_n % 2.to_bigint().unwrap() == Zero::zero() || big_n % 3.to_bigint().unwrap() == Zero::zero() {
+        return false;
+    }
+
+    let mut i = BigInt::from(5);
+    while &i * &i <= *big_n {
+        if big_n % &i == Zero::zero() || big_n % (&i + 2) == Zero::zero() {
+            return false;
+        }
+        i += 6;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probable_prime_classifies_large_numbers() {
+        let two = BigInt::from(2);
+        // Mersenne primes 2^127 - 1 and 2^521 - 1, and the composite 2^523 - 1
+        assert_eq!(probable_prime(&(two.pow(127) - 1)), Primality::Prime);
+        assert_eq!(probable_prime(&(two.pow(521) - 1)), Primality::ProbablePrime);
+        assert_eq!(probable_prime(&(two.pow(523) - 1)), Primality::Composite);
+        // A product of two primes beyond u64, and a Carmichael number
+        let p = BigInt::from(u64::MAX - 58);
+        assert_eq!(probable_prime(&(&p * &p)), Primality::Composite);
+        assert_eq!(probable_prime(&BigInt::from(561)), Primality::Composite);
+        assert_eq!(probable_prime(&(two.pow(200) + 2)), Primality::Composite);
+    }
+}
@@ -0,0 +1,166 @@
+// Persistent cache of the sieving primes below 2^32 under the user cache dir.
+//
+// Primes are stored as a mod-30 wheel bitmap: one byte per 30 numbers with a
+// bit for each residue coprime to 30, so the whole 2^32 range takes about
+// 143 MB and any prefix of the file can be read on its own. The file grows on
+// demand: a run reads the prefix it needs and only sieves (and rewrites the
+// file) when the cache doesn't reach far enough yet.
+
+use std::fs::{self, File};
+use std::io::{Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::paths::cache_dir;
+use crate::sieve::{sieve_segment, sieving_primes, small_primes};
+
+// File signature, bumped whenever the layout changes
+const MAGIC: &[u8; 8] = b"PGPRIME1";
+
+// Residues modulo 30 that are coprime to 2, 3 and 5, one bit each
+const RESIDUES: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+/// Primes up to this limit are cached; that covers the sieving primes of
+/// every `u64` range.
+pub const CACHE_LIMIT: u64 = 1 << 32;
+
+// Smallest extent written, so short runs don't rewrite the file each time
+const MIN_CACHED: u64 = 1 << 16;
+
+// Numbers sieved at a time while building the bitmap (a multiple of 30)
+const BUILD_SEGMENT_SIZE: u64 = 30 << 15;
+
+/// Returns the path of the cache file, or `None` if no cache directory is known.
+pub fn cache_path() -> Option<PathBuf> {
+    Some(cache_dir()?.join("small_primes.bin"))
+}
+
+/// Returns all primes up to and including `limit` (below [`CACHE_LIMIT`], so
+/// they fit into a `u32`), like [`sieving_primes`](crate::sieve::sieving_primes),
+/// reading them from the cache file when it reaches far enough and extending
+/// the file otherwise.
+///
+/// Cache errors are not fatal: the primes are computed in memory instead.
+pub fn cached_small_primes(limit: u64) -> Vec<u32> {
+    assert!(limit < CACHE_LIMIT, "Cached primes must fit into a u32");
+    let Some(path) = cache_path() else {
+        return small_primes(limit).into_iter().map(|p| p as u32).collect();
+    };
+    if let Ok(Some(primes)) = load(&path, limit) {
+        return primes;
+    }
+    let bitmap = build_bitmap(cache_extent(limit));
+    // A failed write only costs the next run a recomputation
+    let _ = store(&path, &bitmap);
+    decode(&bitmap, limit)
+}
+
+// Function to pick how far to build the cache for `limit`, rounded up so
+// nearby limits hit the cache next time; up to CACHE_LIMIT + 30 so the last
+// byte covers every number below CACHE_LIMIT
+fn cache_extent(limit: u64) -> u64 {
+    (limit + 30).next_power_of_two().clamp(MIN_CACHED, CACHE_LIMIT + 30)
+}
+
+// Function to read the primes up to `limit` from the cache, if it covers them
+fn load(path: &Path, limit: u64) -> Result<Option<Vec<u32>>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic)?;
+    let needed = limit / 30 + 1;
+    if &magic != MAGIC || file.metadata()?.len() < MAGIC.len() as u64 + needed {
+        return Ok(None);
+    }
+    let mut bitmap = vec![0; needed as usize];
+    file.read_exact(&mut bitmap)?;
+    Ok(Some(decode(&bitmap, limit)))
+}
+
+// Function to write the bitmap through a temporary file, so concurrent runs
+// never read a partial cache
+fn store(path: &Path, bitmap: &[u8]) -> Result<()> {
+    let dir = path.parent().expect("Cache path has a parent directory");
+    fs::create_dir_all(dir)?;
+    let temp = dir.join(format!("small_primes.{}.tmp", std::process::id()));
+    let mut file = File::create(&temp)?;
+    file.write_all(MAGIC)?;
+    file.write_all(bitmap)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+// Function to sieve [0, extent) into a wheel bitmap, one segment at a time
+fn build_bitmap(extent: u64) -> Vec<u8> {
+    let base_primes = sieving_primes(extent);
+    let mut bitmap = vec![0u8; (extent / 30) as usize];
+    let mut segment_start = 0;
+    while segment_start < extent {
+        let segment_end = (segment_start + BUILD_SEGMENT_SIZE).min(extent) - 1;
+        let flags = sieve_segment(segment_start, segment_end, &base_primes);
+        for (byte, chunk) in flags.chunks_exact(30).enumerate() {
+            let index = (segment_start / 30) as usize + byte;
+            for (bit, &residue) in RESIDUES.iter().enumerate() {
+                if chunk[residue as usize] {
+                    bitmap[index] |= 1 << bit;
+                }
+            }
+        }
+        segment_start += BUILD_SEGMENT_SIZE;
+    }
+    bitmap
+}
+
+// Function to list the primes up to `limit` held in a wheel bitmap
+fn decode(bitmap: &[u8], limit: u64) -> Vec<u32> {
+    let mut primes: Vec<u32> = [2, 3, 5].into_iter().filter(|&p| p as u64 <= limit).collect();
+    for (index, &byte) in bitmap.iter().enumerate() {
+        for (bit, &residue) in RESIDUES.iter().enumerate() {
+            let n = index as u64 * 30 + residue;
+            if n > limit {
+                return primes;
+            }
+            if byte & (1 << bit) != 0 {
+                primes.push(n as u32);
+            }
+        }
+    }
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn stores_and_loads_the_wheel_bitmap() {
+        let extent = cache_extent(10_000);
+        assert_eq!(extent, MIN_CACHED);
+        let bitmap = build_bitmap(extent);
+        let dir = std::env::temp_dir().join(format!("prime_generator_cache_{}", std::process::id()));
+        let path = dir.join("small_primes.bin");
+        store(&path, &bitmap).unwrap();
+
+        for limit in [0, 1, 2, 5, 29, 30, 31, 9_973, extent - 30] {
+            let expected: Vec<u32> = primes_in_range(0, limit).into_iter().map(|p| p as u32).collect();
+            assert_eq!(load(&path, limit).unwrap(), Some(expected.clone()), "limit {}", limit);
+            assert_eq!(decode(&bitmap, limit), expected);
+        }
+        // A cache that doesn't reach the limit is a miss, not a short list
+        assert_eq!(load(&path, extent + 30).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn covers_every_limit_below_the_cache_limit() {
+        assert_eq!(cache_extent(MIN_CACHED + 1), 2 * MIN_CACHED);
+        // The largest limit allowed gets a bitmap reaching past it, so it
+        // loads from the cache rather than missing it every time
+        let extent = cache_extent(CACHE_LIMIT - 1);
+        assert_eq!(extent, CACHE_LIMIT + 30);
+        assert!(extent / 30 > (CACHE_LIMIT - 1) / 30);
+        // The largest prime below 2^32 has its bit in the last byte
+        let largest = 4_294_967_291u64;
+        assert_eq!(largest / 30, extent / 30 - 1);
+        assert!(RESIDUES.contains(&(largest % 30)));
+    }
+}
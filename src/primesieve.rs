@@ -0,0 +1,31 @@
+// Bindings to the primesieve C library (https://github.com/kimwalisch/primesieve),
+// used by `Backend::Primesieve` for raw prime enumeration. Powers and sinks
+// stay in Rust. Needs libprimesieve to be installed where the linker finds it.
+
+use std::ffi::{c_int, c_void};
+use std::io::{Error, Result};
+use std::slice;
+
+// primesieve's type code for `uint64_t` arrays
+const UINT64_PRIMES: c_int = 13;
+
+#[link(name = "primesieve")]
+extern "C" {
+    fn primesieve_generate_primes(start: u64, stop: u64, size: *mut usize, kind: c_int) -> *mut c_void;
+    fn primesieve_free(primes: *mut c_void);
+}
+
+/// Returns the primes in the inclusive window `[start, end]` using primesieve.
+pub fn primes_in_range(start: u64, end: u64) -> Result<Vec<u64>> {
+    let mut size = 0;
+    // Safety: on success primesieve returns an array of `size` u64s that we copy and free
+    unsafe {
+        let primes = primesieve_generate_primes(start, end, &mut size, UINT64_PRIMES);
+        if primes.is_null() {
+            return Err(Error::last_os_error());
+        }
+        let copied = slice::from_raw_parts(primes as *const u64, size).to_vec();
+        primesieve_free(primes);
+        Ok(copied)
+    }
+}
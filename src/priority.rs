@@ -0,0 +1,136 @@
+// CPU and I/O scheduling priority, so background runs on shared machines
+// yield to interactive work. Linux keeps both per thread and new threads
+// inherit them, so they are set before the worker pool starts.
+
+use std::ffi::{c_int, c_long};
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::str::FromStr;
+
+// `PRIO_PROCESS` of setpriority(2); with `who` 0 it targets the calling thread
+const PRIO_PROCESS: c_int = 0;
+// `IOPRIO_WHO_PROCESS` of ioprio_set(2)
+const IOPRIO_WHO_PROCESS: c_int = 1;
+// The class sits above the 13 bits of per-class data
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+#[cfg(target_arch = "x86_64")]
+const SYS_IOPRIO_SET: c_long = 251;
+#[cfg(target_arch = "x86")]
+const SYS_IOPRIO_SET: c_long = 289;
+#[cfg(target_arch = "arm")]
+const SYS_IOPRIO_SET: c_long = 314;
+#[cfg(any(target_arch = "aarch64", target_arch = "riscv64", target_arch = "loongarch64"))]
+const SYS_IOPRIO_SET: c_long = 30;
+
+extern "C" {
+    fn setpriority(which: c_int, who: c_int, prio: c_int) -> c_int;
+    fn syscall(number: c_long, ...) -> c_long;
+}
+
+/// An I/O scheduling class, as ionice(1) names them, with its level from 0
+/// (highest) to 7 where the class has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Only gets disk time when no one else wants it.
+    Idle,
+    /// The default class, sharing disk time by level.
+    BestEffort(u8),
+    /// Served before everyone else; needs `CAP_SYS_ADMIN`.
+    Realtime(u8),
+}
+
+impl FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<IoPriority, String> {
+        let (class, level) = s.split_once(':').unwrap_or((s, "4"));
+        let level = level.parse::<u8>().ok().filter(|&level| level <= 7);
+        match (class, level) {
+            ("idle", _) if !s.contains(':') => Ok(IoPriority::Idle),
+            ("best-effort", Some(level)) => Ok(IoPriority::BestEffort(level)),
+            ("realtime", Some(level)) => Ok(IoPriority::Realtime(level)),
+            ("best-effort" | "realtime", None) => Err(format!("Invalid I/O priority level in {}, expected 0-7", s)),
+            _ => Err(format!("Unknown I/O priority: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoPriority::Idle => f.write_str("idle"),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+            IoPriority::Realtime(level) => write!(f, "realtime:{}", level),
+        }
+    }
+}
+
+impl IoPriority {
+    // Function to encode the priority the way ioprio_set(2) takes it
+    fn value(self) -> c_int {
+        let (class, level) = match self {
+            IoPriority::Realtime(level) => (1, level),
+            IoPriority::BestEffort(level) => (2, level),
+            IoPriority::Idle => (3, 0),
+        };
+        (class << IOPRIO_CLASS_SHIFT) | c_int::from(level)
+    }
+}
+
+/// Sets the calling thread's nice value, from -20 (favoured) to 19 (only
+/// leftover CPU time). Lowering it below the current value needs privileges.
+pub fn set_nice(nice: i32) -> Result<()> {
+    if !(-20..=19).contains(&nice) {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("Nice value {} is outside -20..19", nice)));
+    }
+    // Safety: setpriority only reads its integer arguments
+    match unsafe { setpriority(PRIO_PROCESS, 0, nice) } {
+        0 => Ok(()),
+        _ => Err(Error::last_os_error()),
+    }
+}
+
+/// Sets the calling thread's I/O scheduling priority.
+pub fn set_io_priority(priority: IoPriority) -> Result<()> {
+    // Safety: ioprio_set only reads its integer arguments
+    match unsafe { syscall(SYS_IOPRIO_SET, IOPRIO_WHO_PROCESS, 0 as c_int, priority.value()) } {
+        0 => Ok(()),
+        _ => Err(Error::last_os_error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_io_priorities() {
+        for (name, priority) in [
+            ("idle", IoPriority::Idle),
+            ("best-effort:7", IoPriority::BestEffort(7)),
+            ("realtime:0", IoPriority::Realtime(0)),
+        ] {
+            assert_eq!(name.parse::<IoPriority>(), Ok(priority));
+            assert_eq!(priority.to_string(), name);
+        }
+        assert_eq!("best-effort".parse::<IoPriority>(), Ok(IoPriority::BestEffort(4)));
+        for name in ["idle:3", "best-effort:8", "realtime:x", "low"] {
+            assert!(name.parse::<IoPriority>().is_err(), "{}", name);
+        }
+        assert_eq!(IoPriority::BestEffort(7).value(), (2 << 13) | 7);
+        assert_eq!(IoPriority::Idle.value(), 3 << 13);
+    }
+
+    #[test]
+    fn lowers_priorities_of_the_calling_thread() {
+        // Run in a thread of its own, so the rest of the tests keep theirs
+        std::thread::spawn(|| {
+            set_nice(19).unwrap();
+            set_io_priority(IoPriority::BestEffort(7)).unwrap();
+            assert_eq!(set_nice(20).unwrap_err().kind(), ErrorKind::InvalidInput);
+        })
+        .join()
+        .unwrap();
+    }
+}
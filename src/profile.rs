@@ -0,0 +1,397 @@
+// Sampling CPU profiler for bug reports. A process-wide CPU timer sends
+// SIGPROF to whichever thread is burning CPU; the handler walks that thread's
+// stack into a preallocated slot without allocating, and a collector thread
+// tallies finished slots. At the end the return addresses are resolved to
+// function names, as folded stacks (the collapsed format flamegraph.pl and
+// Pyroscope read) and as a flamegraph SVG.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_int, c_long, c_void};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Deepest stack kept per sample; deeper frames toward the root are cut
+const MAX_DEPTH: usize = 64;
+// Samples that can wait for the collector at once
+const SLOTS: usize = 4096;
+// How often the collector tallies finished samples
+const COLLECT_EVERY: Duration = Duration::from_millis(100);
+
+const SIGPROF: c_int = 27;
+const ITIMER_PROF: c_int = 2;
+const SIG_ERR: usize = usize::MAX;
+// Names signal handlers return through, above the interrupted frame
+const SIGNAL_TRAMPOLINES: [&str; 3] = ["__restore_rt", "__kernel_rt_sigreturn", "[unknown]"];
+
+// States of a sample slot
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+
+#[repr(C)]
+struct TimeVal {
+    sec: c_long,
+    usec: c_long,
+}
+
+#[repr(C)]
+struct ITimerVal {
+    interval: TimeVal,
+    value: TimeVal,
+}
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    fn setitimer(which: c_int, new: *const ITimerVal, old: *mut ITimerVal) -> c_int;
+}
+
+// One sampled stack, innermost frame first
+struct Slot {
+    state: AtomicU8,
+    depth: AtomicUsize,
+    frames: [AtomicUsize; MAX_DEPTH],
+}
+
+impl Slot {
+    const fn new() -> Slot {
+        Slot { state: AtomicU8::new(EMPTY), depth: AtomicUsize::new(0), frames: [const { AtomicUsize::new(0) }; MAX_DEPTH] }
+    }
+}
+
+static SAMPLES: [Slot; SLOTS] = [const { Slot::new() }; SLOTS];
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// A running profile of the whole process; only one runs at a time.
+pub struct Profiler {
+    frequency: u32,
+    stop: Arc<AtomicBool>,
+    collector: JoinHandle<HashMap<Vec<usize>, u64>>,
+}
+
+/// The stacks sampled by a [`Profiler`], with how often each was seen.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    stacks: HashMap<Vec<usize>, u64>,
+    /// Samples lost because the collector fell behind.
+    pub dropped: u64,
+    /// Samples taken per second of CPU time.
+    pub frequency: u32,
+}
+
+impl Profiler {
+    /// Starts sampling the stacks of running threads `frequency` times per
+    /// second of CPU time they use.
+    pub fn start(frequency: u32) -> Result<Profiler> {
+        if frequency == 0 || frequency > 1_000_000 {
+            return Err(Error::new(ErrorKind::InvalidInput, "The sampling frequency must be 1 to 1000000 Hz"));
+        }
+        if ACTIVE.swap(true, Ordering::SeqCst) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "A profile is already running"));
+        }
+        // The handler stays installed, so a signal still in flight when the
+        // timer stops doesn't kill the process
+        if !HANDLER_INSTALLED.swap(true, Ordering::SeqCst) {
+            // Safety: the handler only touches atomics and walks the stack
+            if unsafe { signal(SIGPROF, on_sigprof) } == SIG_ERR {
+                HANDLER_INSTALLED.store(false, Ordering::SeqCst);
+                ACTIVE.store(false, Ordering::SeqCst);
+                return Err(Error::last_os_error());
+            }
+        }
+        DROPPED.store(0, Ordering::SeqCst);
+        let stop = Arc::new(AtomicBool::new(false));
+        let collector = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut stacks = HashMap::new();
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(COLLECT_EVERY);
+                    collect(&mut stacks);
+                }
+                collect(&mut stacks);
+                stacks
+            })
+        };
+        if let Err(e) = set_timer(1_000_000 / frequency as c_long) {
+            stop.store(true, Ordering::SeqCst);
+            let _ = collector.join();
+            ACTIVE.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+        Ok(Profiler { frequency, stop, collector })
+    }
+
+    /// Stops sampling and returns what was sampled.
+    pub fn finish(self) -> Result<Profile> {
+        let stopped = set_timer(0);
+        ACTIVE.store(false, Ordering::SeqCst);
+        self.stop.store(true, Ordering::SeqCst);
+        let stacks = self.collector.join().map_err(|_| Error::other("The profile collector panicked"))?;
+        stopped?;
+        Ok(Profile { stacks, dropped: DROPPED.load(Ordering::SeqCst), frequency: self.frequency })
+    }
+}
+
+impl Profile {
+    /// Number of samples taken.
+    pub fn samples(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// Resolves the sampled stacks to function names, as `root;...;leaf`
+    /// lines with their sample counts. Frames of the profiler's own signal
+    /// handler are left out.
+    pub fn folded(&self) -> BTreeMap<String, u64> {
+        let mut names: HashMap<usize, Vec<String>> = HashMap::new();
+        let mut folded = BTreeMap::new();
+        for (stack, count) in &self.stacks {
+            let mut frames: Vec<String> = Vec::new();
+            for &ip in stack {
+                // Inlined functions resolve to several names, innermost first
+                frames.extend(names.entry(ip).or_insert_with(|| resolve(ip)).iter().cloned());
+            }
+            if let Some(handler) = frames.iter().position(|name| name.contains("on_sigprof")) {
+                frames.drain(..=handler);
+            }
+            // Then the trampoline the kernel returns from the handler through,
+            // which stripped C libraries leave unnamed
+            if frames.first().is_some_and(|name| SIGNAL_TRAMPOLINES.contains(&name.as_str())) {
+                frames.remove(0);
+            }
+            if frames.is_empty() {
+                continue;
+            }
+            frames.reverse();
+            *folded.entry(frames.join(";")).or_insert(0) += count;
+        }
+        folded
+    }
+}
+
+// Function to name the functions at a return address, innermost first
+fn resolve(ip: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    // Step back into the call instruction, which may end the function
+    backtrace::resolve(ip.saturating_sub(1) as *mut c_void, |symbol| {
+        let name = symbol.name().map_or_else(|| "[unknown]".to_string(), |name| format!("{:#}", name));
+        // Separators of the folded format can't appear inside a frame
+        names.push(name.replace(';', ":"));
+    });
+    if names.is_empty() {
+        names.push("[unknown]".to_string());
+    }
+    names
+}
+
+// Function to arm the process CPU timer, or disarm it with 0 microseconds
+fn set_timer(microseconds: c_long) -> Result<()> {
+    let period = || TimeVal { sec: microseconds / 1_000_000, usec: microseconds % 1_000_000 };
+    let timer = ITimerVal { interval: period(), value: period() };
+    // Safety: setitimer reads the struct and doesn't keep it
+    match unsafe { setitimer(ITIMER_PROF, &timer, std::ptr::null_mut()) } {
+        0 => Ok(()),
+        _ => Err(Error::last_os_error()),
+    }
+}
+
+// Function to record the interrupted thread's stack into a free slot
+extern "C" fn on_sigprof(_: c_int) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let slot = &SAMPLES[NEXT_SLOT.fetch_add(1, Ordering::Relaxed) % SLOTS];
+    if slot.state.compare_exchange(EMPTY, WRITING, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    let mut depth = 0;
+    // Safety: the unwinder doesn't allocate, and only this handler uses the slot
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            slot.frames[depth].store(frame.ip() as usize, Ordering::Relaxed);
+            depth += 1;
+            depth < MAX_DEPTH
+        });
+    }
+    slot.depth.store(depth, Ordering::Relaxed);
+    slot.state.store(READY, Ordering::Release);
+}
+
+// Function to tally the finished samples and free their slots
+fn collect(stacks: &mut HashMap<Vec<usize>, u64>) {
+    for slot in &SAMPLES {
+        if slot.state.load(Ordering::Acquire) != READY {
+            continue;
+        }
+        let depth = slot.depth.load(Ordering::Relaxed);
+        let stack = slot.frames[..depth].iter().map(|frame| frame.load(Ordering::Relaxed)).collect();
+        *stacks.entry(stack).or_insert(0) += 1;
+        slot.state.store(EMPTY, Ordering::Release);
+    }
+}
+
+/// Writes folded stacks as `root;...;leaf count` lines.
+pub fn write_folded<W: Write>(folded: &BTreeMap<String, u64>, mut out: W) -> Result<()> {
+    for (stack, count) in folded {
+        writeln!(out, "{} {}", stack, count)?;
+    }
+    out.flush()
+}
+
+const FLAME_WIDTH: f64 = 1200.0;
+const FRAME_HEIGHT: usize = 16;
+// Space above the frames for the title
+const TITLE_HEIGHT: usize = 32;
+// Average width of a label character, for cutting labels to their frame
+const CHAR_WIDTH: f64 = 7.0;
+
+// A function in the call tree, with the samples in it and its callees
+#[derive(Default)]
+struct Frame {
+    samples: u64,
+    callees: BTreeMap<String, Frame>,
+}
+
+impl Frame {
+    fn depth(&self) -> usize {
+        self.callees.values().map(|callee| callee.depth() + 1).max().unwrap_or(0)
+    }
+}
+
+/// Draws folded stacks as a flamegraph SVG: callers below their callees, each
+/// function as wide as its share of the samples, hovering shows the counts.
+pub fn write_flamegraph<W: Write>(folded: &BTreeMap<String, u64>, title: &str, mut out: W) -> Result<()> {
+    let mut root = Frame::default();
+    for (stack, &count) in folded {
+        root.samples += count;
+        let mut frame = &mut root;
+        for name in stack.split(';') {
+            frame = frame.callees.entry(name.to_string()).or_default();
+            frame.samples += count;
+        }
+    }
+    let height = TITLE_HEIGHT + (root.depth() + 1) * FRAME_HEIGHT;
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Verdana, sans-serif" font-size="12">"#,
+        w = FLAME_WIDTH,
+        h = height
+    )?;
+    writeln!(out, r##"<rect width="100%" height="100%" fill="#f8f8f8"/>"##)?;
+    writeln!(out, r#"<text x="{}" y="20" text-anchor="middle" font-size="16">{}</text>"#, FLAME_WIDTH / 2.0, escape_xml(title))?;
+    if root.samples > 0 {
+        let scale = FLAME_WIDTH / root.samples as f64;
+        write_frame(&mut out, "all", &root, root.samples, 0.0, 0, height, scale)?;
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+// Function to draw a frame and its callees above it
+#[allow(clippy::too_many_arguments)]
+fn write_frame<W: Write>(out: &mut W, name: &str, frame: &Frame, total: u64, x: f64, depth: usize, height: usize, scale: f64) -> Result<()> {
+    let width = frame.samples as f64 * scale;
+    // Frames under a pixel wide can't be seen, and neither can their callees
+    if width < 1.0 {
+        return Ok(());
+    }
+    let y = height - (depth + 1) * FRAME_HEIGHT;
+    let escaped = escape_xml(name);
+    writeln!(
+        out,
+        r#"<g><title>{} ({} samples, {:.2}%)</title><rect x="{:.1}" y="{}" width="{:.1}" height="{}" fill="{}" rx="2"/>"#,
+        escaped,
+        frame.samples,
+        frame.samples as f64 * 100.0 / total as f64,
+        x,
+        y,
+        width,
+        FRAME_HEIGHT - 1,
+        frame_color(name)
+    )?;
+    let fits = ((width - 6.0) / CHAR_WIDTH) as usize;
+    if fits >= 3 {
+        let label = match name.chars().count() > fits {
+            true => format!("{}..", name.chars().take(fits - 2).collect::<String>()),
+            false => name.to_string(),
+        };
+        writeln!(out, r#"<text x="{:.1}" y="{}">{}</text>"#, x + 3.0, y + FRAME_HEIGHT - 4, escape_xml(&label))?;
+    }
+    writeln!(out, "</g>")?;
+    let mut callee_x = x;
+    for (callee_name, callee) in &frame.callees {
+        write_frame(out, callee_name, callee, total, callee_x, depth + 1, height, scale)?;
+        callee_x += callee.samples as f64 * scale;
+    }
+    Ok(())
+}
+
+// Function to pick a warm color from the function's name, so the same
+// function keeps its color across graphs
+fn frame_color(name: &str) -> String {
+    let hash = name.bytes().fold(2166136261u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(16777619));
+    format!("rgb({},{},{})", 205 + hash % 50, (hash >> 8) % 230, (hash >> 16) % 55)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    #[test]
+    fn draws_folded_stacks() {
+        let folded = BTreeMap::from([
+            ("main;run;sieve".to_string(), 60),
+            ("main;run;powers<u128>".to_string(), 30),
+            ("main;write".to_string(), 10),
+        ]);
+        let mut lines = Vec::new();
+        write_folded(&folded, &mut lines).unwrap();
+        assert_eq!(String::from_utf8(lines).unwrap(), "main;run;powers<u128> 30\nmain;run;sieve 60\nmain;write 10\n");
+
+        let mut svg = Vec::new();
+        write_flamegraph(&folded, "prime_generator & co", &mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("prime_generator &amp; co"));
+        assert!(svg.contains("<title>all (100 samples, 100.00%)</title>"));
+        assert!(svg.contains("<title>run (90 samples, 90.00%)</title>"));
+        assert!(svg.contains("<title>powers&lt;u128&gt; (30 samples, 30.00%)</title>"));
+        // Four levels under the title: all, main, run and its callees
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="96""#), "{}", svg);
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[inline(never)]
+    fn spin(until: Instant) -> u64 {
+        let mut x = 0u64;
+        while Instant::now() < until {
+            x = black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+        }
+        x
+    }
+
+    #[test]
+    fn samples_busy_threads() {
+        let profiler = Profiler::start(1000).unwrap();
+        assert_eq!(Profiler::start(1000).err().map(|e| e.kind()), Some(ErrorKind::AlreadyExists));
+        spin(Instant::now() + Duration::from_millis(300));
+        let profile = profiler.finish().unwrap();
+        assert!(profile.samples() > 0);
+        let folded = profile.folded();
+        assert!(folded.keys().any(|stack| stack.contains("spin")), "{:?}", folded);
+        assert!(folded.keys().all(|stack| !stack.contains("on_sigprof")));
+        // Another profile can start once this one finished
+        Profiler::start(100).unwrap().finish().unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+// Python bindings, built as the `prime_generator_py` extension module.
+//
+// All computation runs with the GIL released so other Python threads keep
+// running while the engine works.
+
+use pyo3::prelude::*;
+
+use crate::sieve::{sieve_segment, sieving_primes};
+
+// Numbers sieved per refill of a `primes()` generator
+const GENERATOR_SEGMENT_SIZE: u64 = 1 << 16;
+
+/// Returns True if `n` is prime.
+#[pyfunction]
+fn is_prime(py: Python<'_>, n: u128) -> bool {
+    py.allow_threads(|| crate::primality::is_prime(n))
+}
+
+/// Returns the prime factors of `n` in increasing order, with multiplicity.
+#[pyfunction]
+fn factor(py: Python<'_>, n: u128) -> Vec<u128> {
+    py.allow_threads(|| crate::factor::factor(n))
+}
+
+/// Yields the primes in the inclusive range [start, end] in increasing order.
+#[pyfunction]
+fn primes(py: Python<'_>, start: u64, end: u64) -> PrimeGenerator {
+    let base_primes = py.allow_threads(|| sieving_primes(end));
+    PrimeGenerator { base_primes, next_start: Some(start), end, pending: Vec::new() }
+}
+
+/// Lazy iterator returned by `primes()`, sieving one segment at a time.
+#[pyclass]
+struct PrimeGenerator {
+    base_primes: Vec<u32>,
+    next_start: Option<u64>,
+    end: u64,
+    // Primes of the current segment, in reverse so `pop` yields the smallest
+    pending: Vec<u64>,
+}
+
+#[pymethods]
+impl PrimeGenerator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> Option<u64> {
+        while self.pending.is_empty() {
+            let start = self.next_start.filter(|start| *start <= self.end)?;
+            let end = start.saturating_add(GENERATOR_SEGMENT_SIZE - 1).min(self.end);
+            let base_primes = &self.base_primes;
+            let flags = py.allow_threads(|| sieve_segment(start, end, base_primes));
+            self.pending = flags
+                .into_iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, is_prime)| *is_prime)
+                .map(|(offset, _)| start + offset as u64)
+                .collect();
+            self.next_start = end.checked_add(1);
+        }
+        self.pending.pop()
+    }
+}
+
+#[pymodule]
+fn prime_generator_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(is_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(primes, m)?)?;
+    m.add_function(wrap_pyfunction!(factor, m)?)?;
+    m.add_class::<PrimeGenerator>()?;
+    Ok(())
+}
@@ -0,0 +1,198 @@
+// Sets of inclusive number ranges: the ranges a run is asked for, written as
+// `2..10^6,10^9..10^9+10^6`, and what it covers once the ranges earlier runs
+// already covered are taken out.
+
+#[cfg(feature = "manifest")]
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+#[cfg(feature = "manifest")]
+use std::path::Path;
+
+#[cfg(feature = "manifest")]
+use serde::Deserialize;
+
+/// Parses a number written in decimal, as a power (`10^9`), in scientific
+/// notation (`1e9`) or as a sum or difference of those (`10^9+10^6`, `2^64-1`).
+/// Returns `None` if it is malformed or doesn't fit into `u128`.
+pub fn parse_number(value: &str) -> Option<u128> {
+    // Function to parse one term of the expression
+    let term = |term: &str| -> Option<u128> {
+        let term = term.trim();
+        let power = |mantissa: &str, base: u128, exponent: &str| -> Option<u128> {
+            mantissa.parse::<u128>().ok()?.checked_mul(base.checked_pow(exponent.parse().ok()?)?)
+        };
+        if let Some((base, exponent)) = term.split_once('^') {
+            power("1", base.parse().ok()?, exponent)
+        } else if let Some((mantissa, exponent)) = term.split_once(['e', 'E']) {
+            power(mantissa, 10, exponent)
+        } else {
+            term.parse().ok()
+        }
+    };
+    let first_operator = value.find(['+', '-']).unwrap_or(value.len());
+    let mut total = term(&value[..first_operator])?;
+    let mut rest = &value[first_operator..];
+    while let Some(operator) = rest.chars().next() {
+        let next = rest[1..].find(['+', '-']).map_or(rest.len(), |i| i + 1);
+        let operand = term(&rest[1..next])?;
+        total = match operator {
+            '+' => total.checked_add(operand)?,
+            _ => total.checked_sub(operand)?,
+        };
+        rest = &rest[next..];
+    }
+    Some(total)
+}
+
+/// Parses a comma-separated list of inclusive ranges such as
+/// `2..10^6,10^9..10^9+10^6`, with bounds as accepted by [`parse_number`].
+///
+/// ```
+/// use prime_generator::ranges::parse_ranges;
+///
+/// assert_eq!(parse_ranges("2..10^6, 10^9..10^9+10^6").unwrap(), [(2, 1_000_000), (1_000_000_000, 1_001_000_000)]);
+/// ```
+pub fn parse_ranges(spec: &str) -> Result<Vec<(u128, u128)>> {
+    spec.split(',')
+        .map(|range| {
+            let invalid = |reason: &str| Error::new(ErrorKind::InvalidInput, format!("Invalid range {:?}: {}", range.trim(), reason));
+            let (start, end) = range.split_once("..").ok_or_else(|| invalid("expected start..end"))?;
+            let start = parse_number(start).ok_or_else(|| invalid("bad start"))?;
+            let end = parse_number(end).ok_or_else(|| invalid("bad end"))?;
+            if start > end {
+                return Err(invalid("start is greater than end"));
+            }
+            Ok((start, end))
+        })
+        .collect()
+}
+
+/// Returns the union of inclusive ranges as disjoint ranges in increasing
+/// order, joining ranges that overlap or touch.
+pub fn merge_ranges(ranges: &[(u128, u128)]) -> Vec<(u128, u128)> {
+    let mut ranges: Vec<(u128, u128)> = ranges.iter().copied().filter(|&(from, to)| from <= to).collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+    for (from, to) in ranges {
+        match merged.last_mut() {
+            Some(last) if from <= last.1.saturating_add(1) => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
+        }
+    }
+    merged
+}
+
+/// Returns the parts of `[start, end]` not covered by any of the inclusive
+/// `excluded` ranges, in increasing order. The excluded ranges may overlap
+/// and come in any order.
+///
+/// ```
+/// use prime_generator::ranges::subtract_ranges;
+///
+/// assert_eq!(subtract_ranges(1, 100, &[(10, 19), (50, 200)]), [(1, 9), (20, 49)]);
+/// ```
+pub fn subtract_ranges(start: u128, end: u128, excluded: &[(u128, u128)]) -> Vec<(u128, u128)> {
+    let mut excluded: Vec<(u128, u128)> = excluded.iter().copied().filter(|&(from, to)| from <= to).collect();
+    excluded.sort_unstable();
+
+    let mut uncovered = Vec::new();
+    // Next number not yet accounted for, or None once the whole range is
+    let mut next = Some(start);
+    for (from, to) in excluded {
+        let Some(current) = next.filter(|&current| current <= end) else {
+            break;
+        };
+        if from > current {
+            uncovered.push((current, (from - 1).min(end)));
+        }
+        if to >= current {
+            next = to.checked_add(1);
+        }
+    }
+    if let Some(current) = next.filter(|&current| current <= end) {
+        uncovered.push((current, end));
+    }
+    uncovered
+}
+
+// One covered range in an exclude file
+#[cfg(feature = "manifest")]
+#[derive(Deserialize)]
+struct CoveredRange {
+    start: u128,
+    end: u128,
+}
+
+/// Reads a JSON list of inclusive ranges, such as
+/// `[{"start": 2, "end": 1000000}]`, as written to describe the ranges
+/// earlier runs covered.
+#[cfg(feature = "manifest")]
+pub fn read_ranges<P: AsRef<Path>>(path: P) -> Result<Vec<(u128, u128)>> {
+    let file = File::open(path)?;
+    let ranges: Vec<CoveredRange> = serde_json::from_reader(file).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    ranges
+        .into_iter()
+        .map(|range| match range.start <= range.end {
+            true => Ok((range.start, range.end)),
+            false => Err(Error::new(ErrorKind::InvalidData, format!("Range start {} is greater than end {}", range.start, range.end))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtracts_overlapping_and_unsorted_ranges() {
+        assert_eq!(subtract_ranges(1, 100, &[]), [(1, 100)]);
+        assert_eq!(subtract_ranges(1, 100, &[(50, 60), (1, 10), (55, 70), (100, 100)]), [(11, 49), (71, 99)]);
+        assert_eq!(subtract_ranges(10, 20, &[(0, 5), (25, 30)]), [(10, 20)]);
+        assert_eq!(subtract_ranges(10, 20, &[(0, 30)]), []);
+        assert_eq!(subtract_ranges(10, 20, &[(12, 12), (13, 13)]), [(10, 11), (14, 20)]);
+        // Empty excluded ranges are ignored
+        assert_eq!(subtract_ranges(10, 20, &[(15, 12)]), [(10, 20)]);
+    }
+
+    #[test]
+    fn parses_numbers_and_ranges() {
+        assert_eq!(parse_number("1000"), Some(1000));
+        assert_eq!(parse_number("10^9+10^6"), Some(1_001_000_000));
+        assert_eq!(parse_number("2^64-1"), Some(u64::MAX as u128));
+        assert_eq!(parse_number("3e6 + 1 - 2"), Some(2_999_999));
+        assert_eq!(parse_number("2^128"), None);
+        assert_eq!(parse_number("1-2"), None);
+        assert_eq!(parse_number("10^"), None);
+        assert_eq!(parse_number(""), None);
+
+        assert_eq!(parse_ranges("2..100").unwrap(), [(2, 100)]);
+        assert_eq!(parse_ranges("10^9..10^9+10^6,2..10^6").unwrap(), [(1_000_000_000, 1_001_000_000), (2, 1_000_000)]);
+        for spec in ["2-100", "100..2", "2..x", ""] {
+            assert_eq!(parse_ranges(spec).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn merges_overlapping_and_touching_ranges() {
+        assert_eq!(merge_ranges(&[(50, 60), (1, 10), (11, 20), (55, 70), (100, 100)]), [(1, 20), (50, 70), (100, 100)]);
+        assert_eq!(merge_ranges(&[(0, u128::MAX), (5, 6)]), [(0, u128::MAX)]);
+        assert_eq!(merge_ranges(&[]), []);
+    }
+
+    #[test]
+    fn subtracts_at_the_top_of_u128() {
+        assert_eq!(subtract_ranges(u128::MAX - 10, u128::MAX, &[(u128::MAX - 5, u128::MAX)]), [(u128::MAX - 10, u128::MAX - 6)]);
+        assert_eq!(subtract_ranges(0, u128::MAX, &[(0, u128::MAX - 1)]), [(u128::MAX, u128::MAX)]);
+    }
+
+    #[cfg(feature = "manifest")]
+    #[test]
+    fn reads_range_files() {
+        let path = std::env::temp_dir().join(format!("prime_generator_ranges_{}.json", std::process::id()));
+        std::fs::write(&path, r#"[{"start": 2, "end": 1000}, {"start": 5000, "end": 6000}]"#).unwrap();
+        assert_eq!(read_ranges(&path).unwrap(), [(2, 1000), (5000, 6000)]);
+        std::fs::write(&path, r#"[{"start": 10, "end": 1}]"#).unwrap();
+        assert_eq!(read_ranges(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+// Output record for a prime and its powers.
+
+use std::fmt::Display;
+
+use crate::bignum::powers;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A prime with its square, cube and fourth power as decimal strings (or as
+/// read from a file written in another
+/// [`PowerFormat`](crate::bignum::PowerFormat)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PrimeRecord {
+    pub prime: u128,
+    #[cfg_attr(feature = "serde", serde(alias = "squared_hex", alias = "squared_scientific", alias = "squared_digits"))]
+    pub squared: String,
+    #[cfg_attr(feature = "serde", serde(alias = "cubed_hex", alias = "cubed_scientific", alias = "cubed_digits"))]
+    pub cubed: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(alias = "to_fourth_power_hex", alias = "to_fourth_power_scientific", alias = "to_fourth_power_digits")
+    )]
+    pub to_fourth_power: String,
+}
+
+impl PrimeRecord {
+    /// Builds the record for `prime`, computing its powers.
+    pub fn from_prime(prime: u128) -> PrimeRecord {
+        PrimeRecord::new(prime, &powers(prime))
+    }
+
+    /// Builds a record from a prime and its `[squared, cubed, to_fourth_power]` powers.
+    pub fn new<T: Display>(prime: u128, powers: &[T]) -> PrimeRecord {
+        PrimeRecord {
+            prime,
+            squared: powers[0].to_string(),
+            cubed: powers[1].to_string(),
+            to_fourth_power: powers[2].to_string(),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+// Record-setting primes of a run: the largest palindromic prime so far, the
+// largest twin pair and the largest gap between consecutive primes. Each new
+// record is written out as soon as its prime is, so a long run's records
+// file can be watched while it goes.
+
+use std::fmt;
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The header line of a records file.
+pub const HEADER: &str = "kind,prime,value";
+
+/// Returns where the records of the output at `path` go by default: next to
+/// it, with `.records.csv` appended.
+pub fn records_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".records.csv");
+    PathBuf::from(name)
+}
+
+/// A kind of record a run can track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// `palindromic`: the largest prime that reads the same backwards.
+    Palindromic,
+    /// `twin`: the largest pair of primes (p, p + 2). Needs the records in
+    /// increasing order.
+    Twin,
+    /// `gap`: the largest gap between consecutive primes. Needs the records
+    /// in increasing order.
+    Gap,
+}
+
+impl FromStr for RecordKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<RecordKind, String> {
+        match s {
+            "palindromic" => Ok(RecordKind::Palindromic),
+            "twin" => Ok(RecordKind::Twin),
+            "gap" => Ok(RecordKind::Gap),
+            _ => Err(format!("Unknown record kind: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RecordKind::Palindromic => "palindromic",
+            RecordKind::Twin => "twin",
+            RecordKind::Gap => "gap",
+        })
+    }
+}
+
+impl RecordKind {
+    /// Whether tracking this kind needs the primes in increasing order.
+    pub fn needs_order(self) -> bool {
+        self != RecordKind::Palindromic
+    }
+}
+
+/// A new record: the prime that set it and what it measures, written as a
+/// `kind,prime,value` row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordFind {
+    pub kind: RecordKind,
+    /// The palindromic prime, the smaller prime of the twin pair, or the
+    /// prime before the gap.
+    pub prime: u128,
+    /// The number of digits of the palindromic prime, the larger prime of the
+    /// twin pair, or the length of the gap.
+    pub value: u128,
+}
+
+/// Follows the primes of a run as they are written and writes every new
+/// record to `out`.
+pub struct RecordTracker<W> {
+    kinds: Vec<RecordKind>,
+    out: W,
+    // Inclusive ranges the run covers, in increasing order; neighbours across
+    // the holes between them aren't consecutive primes
+    ranges: Vec<(u128, u128)>,
+    range_index: usize,
+    previous: Option<(usize, u128)>,
+    best: Vec<RecordFind>,
+}
+
+impl<W: Write> RecordTracker<W> {
+    /// Tracks `kinds` for a run covering the disjoint inclusive `ranges`, in
+    /// increasing order. Write [`HEADER`] to `out` first for a new file.
+    pub fn new(kinds: &[RecordKind], ranges: &[(u128, u128)], out: W) -> RecordTracker<W> {
+        RecordTracker { kinds: kinds.to_vec(), out, ranges: ranges.to_vec(), range_index: 0, previous: None, best: Vec::new() }
+    }
+
+    /// Adds the next prime, writing any record it sets. For `twin` and `gap`,
+    /// primes must come in increasing order.
+    pub fn add(&mut self, prime: u128) -> Result<()> {
+        while self.ranges.get(self.range_index).is_some_and(|&(_, end)| end < prime) {
+            self.range_index += 1;
+        }
+        let previous = self.previous.filter(|&(range_index, previous)| range_index == self.range_index && previous < prime);
+        for kind in self.kinds.clone() {
+            let find = match kind {
+                RecordKind::Palindromic => is_palindrome(prime).then(|| RecordFind { kind, prime, value: prime.to_string().len() as u128 }),
+                RecordKind::Twin => previous
+                    .filter(|&(_, previous)| prime - previous == 2)
+                    .map(|(_, previous)| RecordFind { kind, prime: previous, value: prime }),
+                RecordKind::Gap => previous.map(|(_, previous)| RecordFind { kind, prime: previous, value: prime - previous }),
+            };
+            if let Some(find) = find {
+                self.offer(find)?;
+            }
+        }
+        self.previous = Some((self.range_index, prime));
+        Ok(())
+    }
+
+    /// Pushes the records written so far out of any buffer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()
+    }
+
+    /// Returns the standing record of each kind that has one.
+    pub fn records(&self) -> &[RecordFind] {
+        &self.best
+    }
+
+    /// Returns the writer the records went to.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // Function to keep and write a find if it beats the standing record of its kind
+    fn offer(&mut self, find: RecordFind) -> Result<()> {
+        let beats = |best: &RecordFind| match find.kind {
+            RecordKind::Gap => find.value > best.value,
+            _ => find.prime > best.prime,
+        };
+        match self.best.iter_mut().find(|best| best.kind == find.kind) {
+            Some(best) if !beats(best) => return Ok(()),
+            Some(best) => *best = find,
+            None => self.best.push(find),
+        }
+        writeln!(self.out, "{},{},{}", find.kind, find.prime, find.value)
+    }
+}
+
+// Function to tell whether a number's decimal digits read the same backwards
+fn is_palindrome(n: u128) -> bool {
+    let digits = n.to_string().into_bytes();
+    digits.iter().eq(digits.iter().rev())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn parses_record_kinds() {
+        for kind in [RecordKind::Palindromic, RecordKind::Twin, RecordKind::Gap] {
+            assert_eq!(kind.to_string().parse::<RecordKind>(), Ok(kind));
+        }
+        assert!("largest".parse::<RecordKind>().is_err());
+        assert!(!RecordKind::Palindromic.needs_order() && RecordKind::Gap.needs_order());
+    }
+
+    #[test]
+    fn writes_each_new_record() {
+        let kinds = [RecordKind::Palindromic, RecordKind::Twin, RecordKind::Gap];
+        let mut tracker = RecordTracker::new(&kinds, &[(1, 200)], Vec::new());
+        for p in primes_in_range(1, 200) {
+            tracker.add(p as u128).unwrap();
+        }
+        assert_eq!(
+            tracker.records(),
+            [
+                RecordFind { kind: RecordKind::Palindromic, prime: 191, value: 3 },
+                RecordFind { kind: RecordKind::Gap, prime: 113, value: 14 },
+                RecordFind { kind: RecordKind::Twin, prime: 197, value: 199 },
+            ]
+        );
+        let out = String::from_utf8(tracker.into_inner()).unwrap();
+        let gaps: Vec<&str> = out.lines().filter(|line| line.starts_with("gap,")).collect();
+        // The maximal prime gaps below 200
+        assert_eq!(gaps, ["gap,2,1", "gap,3,2", "gap,7,4", "gap,23,6", "gap,89,8", "gap,113,14"]);
+        assert!(out.starts_with("palindromic,2,1\npalindromic,3,1\ngap,2,1\npalindromic,5,1\ntwin,3,5\ngap,3,2\n"), "{}", out);
+        assert!(out.contains("\npalindromic,101,3\n") && !out.contains("twin,197,199\ntwin"));
+    }
+
+    #[test]
+    fn skips_neighbours_across_holes() {
+        let mut tracker = RecordTracker::new(&[RecordKind::Gap, RecordKind::Twin], &[(1, 20), (100, 120)], Vec::new());
+        for p in [2, 3, 5, 7, 11, 13, 17, 19, 101, 103, 107, 109, 113] {
+            tracker.add(p).unwrap();
+        }
+        let out = String::from_utf8(tracker.into_inner()).unwrap();
+        assert!(!out.contains(",19,"), "{}", out);
+        assert!(out.ends_with("twin,107,109\n"), "{}", out);
+    }
+}
@@ -0,0 +1,96 @@
+// Digit and sieve curiosities, for OEIS-style explorations of the primes:
+// happy numbers and Ulam's lucky numbers.
+
+/// Returns whether `n` is happy: repeatedly replacing it by the sum of the
+/// squares of its decimal digits reaches 1. Unhappy numbers end up in the
+/// cycle through 4 instead.
+pub fn is_happy(mut n: u128) -> bool {
+    while n != 1 && n != 4 && n != 0 {
+        let mut sum = 0;
+        while n > 0 {
+            sum += (n % 10) * (n % 10);
+            n /= 10;
+        }
+        n = sum;
+    }
+    n == 1
+}
+
+/// Returns the lucky numbers up to `end` in increasing order. Starting from
+/// the odd numbers, the sieve repeatedly takes the next surviving number `k`
+/// and removes every `k`-th survivor. Survivors are tracked in a Fenwick tree,
+/// so finding the `r`-th one takes `O(log end)` and the whole sieve
+/// `O(end log end)`, with 4 bytes of memory per odd number. `end` must be
+/// below 2^33.
+pub fn lucky_numbers(end: u64) -> Vec<u64> {
+    assert!(end < 1 << 33, "The lucky sieve only supports ends below 2^33");
+    let size = end.div_ceil(2) as usize;
+    // Fenwick tree over the odd numbers, 2i - 1 at position i, all surviving at first
+    let mut tree = vec![0u32; size + 1];
+    for i in 1..=size {
+        tree[i] += 1;
+        let parent = i + (i & i.wrapping_neg());
+        if parent <= size {
+            tree[parent] += tree[i];
+        }
+    }
+    let top_bit = if size == 0 { 0 } else { 1 << size.ilog2() };
+    // Function to find the position of the rank-th survivor
+    let find = |tree: &[u32], mut rank: u32| -> usize {
+        let mut position = 0;
+        let mut step = top_bit;
+        while step > 0 {
+            if position + step <= size && tree[position + step] < rank {
+                position += step;
+                rank -= tree[position];
+            }
+            step >>= 1;
+        }
+        position + 1
+    };
+
+    let mut survivors = size as u32;
+    // Index of the survivor whose value is the next step, from the second on
+    let mut index = 2;
+    while index <= survivors {
+        let step = 2 * find(&tree, index) as u64 - 1;
+        if step > survivors as u64 {
+            break;
+        }
+        let step = step as u32;
+        // Removing from the back keeps the ranks in front of each removal valid
+        for multiple in (1..=survivors / step).rev() {
+            let mut i = find(&tree, multiple * step);
+            while i <= size {
+                tree[i] -= 1;
+                i += i & i.wrapping_neg();
+            }
+        }
+        survivors -= survivors / step;
+        index += 1;
+    }
+    (1..=survivors).map(|rank| 2 * find(&tree, rank) as u64 - 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn finds_happy_primes() {
+        // OEIS A035497
+        let happy: Vec<u64> = primes_in_range(2, 400).into_iter().filter(|&p| is_happy(p.into())).collect();
+        assert_eq!(happy, [7, 13, 19, 23, 31, 79, 97, 103, 109, 139, 167, 193, 239, 263, 293, 313, 331, 367, 379, 383, 397]);
+        assert!(is_happy(1) && !is_happy(0) && !is_happy(u128::MAX));
+    }
+
+    #[test]
+    fn sieves_lucky_numbers() {
+        // OEIS A000959
+        assert_eq!(lucky_numbers(100), [1, 3, 7, 9, 13, 15, 21, 25, 31, 33, 37, 43, 49, 51, 63, 67, 69, 73, 75, 79, 87, 93, 99]);
+        assert!(lucky_numbers(0).is_empty());
+        assert_eq!(lucky_numbers(2), [1]);
+        assert_eq!(lucky_numbers(100_000).len(), 8_772);
+    }
+}
@@ -0,0 +1,126 @@
+// Minimal Redis client speaking RESP over TCP, used by `RedisSink` to add
+// primes to a sorted set. Only the commands the sink needs are implemented.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::net::TcpStream;
+
+/// Where a `redis://host[:port][/key]` URL points: the server's address and
+/// the sorted set's key, `primes` unless given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisTarget {
+    pub address: String,
+    pub key: String,
+}
+
+impl RedisTarget {
+    /// Parses a `redis://host[:port][/key]` URL; the port defaults to 6379.
+    pub fn parse(url: &str) -> std::result::Result<RedisTarget, String> {
+        let rest = url.strip_prefix("redis://").ok_or_else(|| format!("Not a redis:// URL: {}", url))?;
+        let (host, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if host.is_empty() {
+            return Err(format!("No host in {}", url));
+        }
+        let address = match host.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => host.to_string(),
+            Some(_) => return Err(format!("Invalid port in {}", url)),
+            None => format!("{}:6379", host),
+        };
+        let key = if key.is_empty() { "primes" } else { key };
+        Ok(RedisTarget { address, key: key.to_string() })
+    }
+}
+
+/// A connection adding members to one sorted set.
+pub struct RedisSortedSet {
+    stream: BufReader<TcpStream>,
+    key: String,
+}
+
+impl RedisSortedSet {
+    /// Connects to the server of `target`.
+    pub fn connect(target: &RedisTarget) -> Result<RedisSortedSet> {
+        let stream = TcpStream::connect(&target.address)?;
+        Ok(RedisSortedSet { stream: BufReader::new(stream), key: target.key.clone() })
+    }
+
+    /// Adds `primes` with one `ZADD`, each scored by its value and named by
+    /// its decimal digits. Scores are doubles, so above 2^53 they only order
+    /// the primes approximately; the members stay exact. Returns how many
+    /// primes weren't in the set yet.
+    pub fn add_primes(&mut self, primes: &[u128]) -> Result<u64> {
+        if primes.is_empty() {
+            return Ok(0);
+        }
+        let mut args = vec![b"ZADD".to_vec(), self.key.as_bytes().to_vec()];
+        for &p in primes {
+            args.push(format!("{}", p as f64).into_bytes());
+            args.push(p.to_string().into_bytes());
+        }
+        self.command(&args)
+    }
+
+    // Function to send a command and read its integer reply
+    fn command(&mut self, args: &[Vec<u8>]) -> Result<u64> {
+        let mut request = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            request.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            request.extend_from_slice(arg);
+            request.extend_from_slice(b"\r\n");
+        }
+        self.stream.get_mut().write_all(&request)?;
+
+        let mut reply = String::new();
+        if self.stream.read_line(&mut reply)? == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Redis closed the connection"));
+        }
+        let reply = reply.trim_end();
+        match reply.split_at(reply.len().min(1)) {
+            (":", n) => n.parse().map_err(|_| Error::new(ErrorKind::InvalidData, format!("Invalid Redis reply: {}", reply))),
+            ("-", message) => Err(Error::other(format!("Redis error: {}", message))),
+            _ => Err(Error::new(ErrorKind::InvalidData, format!("Unexpected Redis reply: {}", reply))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn parses_redis_urls() {
+        let target = |address: &str, key: &str| Ok(RedisTarget { address: address.to_string(), key: key.to_string() });
+        assert_eq!(RedisTarget::parse("redis://localhost"), target("localhost:6379", "primes"));
+        assert_eq!(RedisTarget::parse("redis://10.0.0.5:6380/shard_7"), target("10.0.0.5:6380", "shard_7"));
+        for url in ["localhost", "redis://", "redis://host:port/key"] {
+            assert!(RedisTarget::parse(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn adds_primes_with_zadd() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        // Fake server answering the first command with a count and the second with an error
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0u8; 4096];
+            for reply in [&b":2\r\n"[..], b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"] {
+                let n = stream.read(&mut buf).unwrap();
+                received.extend_from_slice(&buf[..n]);
+                stream.write_all(reply).unwrap();
+            }
+            String::from_utf8(received).unwrap()
+        });
+
+        let mut set = RedisSortedSet::connect(&RedisTarget { address, key: "primes".to_string() }).unwrap();
+        assert_eq!(set.add_primes(&[]).unwrap(), 0);
+        assert_eq!(set.add_primes(&[2, 3]).unwrap(), 2);
+        assert!(set.add_primes(&[5]).unwrap_err().to_string().contains("WRONGTYPE"));
+        let received = server.join().unwrap();
+        assert!(received.starts_with("*6\r\n$4\r\nZADD\r\n$6\r\nprimes\r\n$1\r\n2\r\n$1\r\n2\r\n$1\r\n3\r\n$1\r\n3\r\n"), "{:?}", received);
+    }
+}
@@ -0,0 +1,328 @@
+// Registry of generated ranges in an SQLite database, through SQLite's C API
+// (https://sqlite.org/cintro.html): which ranges were generated, with which
+// algorithm and engine version, and where the outputs went. Needs libsqlite3
+// to be installed where the linker finds it.
+//
+// SQLite integers stop at 2^63, so range bounds are stored as text padded to
+// the 39 digits of u128::MAX, which sorts and compares like the numbers.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::ptr;
+
+use crate::ranges::{merge_ranges, subtract_ranges};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Result codes and flags of the C API
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x02;
+const SQLITE_OPEN_CREATE: c_int = 0x04;
+// `SQLITE_TRANSIENT`: SQLite copies bound text before the call returns
+const SQLITE_TRANSIENT: isize = -1;
+
+// How long a write waits for another run holding the database
+const BUSY_TIMEOUT_MS: c_int = 10_000;
+
+// Digits of u128::MAX, the width range bounds are padded to
+const BOUND_DIGITS: usize = 39;
+
+// Opaque handles
+type Database = *mut c_void;
+type Statement = *mut c_void;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(filename: *const c_char, database: *mut Database, flags: c_int, vfs: *const c_char) -> c_int;
+    fn sqlite3_close(database: Database) -> c_int;
+    fn sqlite3_errmsg(database: Database) -> *const c_char;
+    fn sqlite3_busy_timeout(database: Database, ms: c_int) -> c_int;
+    fn sqlite3_exec(database: Database, sql: *const c_char, callback: *const c_void, arg: *mut c_void, error: *mut *mut c_char) -> c_int;
+    fn sqlite3_prepare_v2(database: Database, sql: *const c_char, bytes: c_int, statement: *mut Statement, tail: *mut *const c_char) -> c_int;
+    fn sqlite3_bind_text(statement: Statement, index: c_int, text: *const c_char, bytes: c_int, destructor: isize) -> c_int;
+    fn sqlite3_bind_int64(statement: Statement, index: c_int, value: i64) -> c_int;
+    fn sqlite3_step(statement: Statement) -> c_int;
+    fn sqlite3_column_text(statement: Statement, column: c_int) -> *const c_char;
+    fn sqlite3_column_int64(statement: Statement, column: c_int) -> i64;
+    fn sqlite3_finalize(statement: Statement) -> c_int;
+}
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS ranges (
+    id INTEGER PRIMARY KEY,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    algorithm TEXT NOT NULL,
+    engine_version TEXT NOT NULL,
+    output TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ranges_by_start ON ranges (start, end);";
+
+/// One generated range, as recorded in the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RangeEntry {
+    pub start: u128,
+    pub end: u128,
+    pub algorithm: String,
+    pub engine_version: String,
+    /// Where the output went: a file path, or a `duckdb://`/`redis://` target.
+    pub output: String,
+    /// When the range was recorded, in seconds since the Unix epoch.
+    pub recorded_at: u64,
+}
+
+/// An SQLite database recording which ranges have been generated.
+pub struct Registry {
+    database: Database,
+}
+
+// Safety: SQLite's default build is serialized, and `&mut self` keeps writes
+// to one thread at a time
+unsafe impl Send for Registry {}
+
+impl Registry {
+    /// Opens the registry at `path`, creating the database and its table if
+    /// needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Registry> {
+        let path = c_string(&path.as_ref().to_string_lossy())?;
+        let mut registry = Registry { database: ptr::null_mut() };
+        // Safety: the handle is closed by `Drop` even if opening failed
+        unsafe {
+            let opened = sqlite3_open_v2(path.as_ptr(), &mut registry.database, SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE, ptr::null());
+            if opened != SQLITE_OK {
+                return Err(registry.error("Failed to open the registry"));
+            }
+            sqlite3_busy_timeout(registry.database, BUSY_TIMEOUT_MS);
+        }
+        registry.execute(SCHEMA)?;
+        Ok(registry)
+    }
+
+    /// Records a generated range.
+    pub fn record(&mut self, entry: &RangeEntry) -> Result<()> {
+        let mut insert = self.prepare(
+            "INSERT INTO ranges (start, end, algorithm, engine_version, output, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        insert.bind_text(1, &pad_bound(entry.start))?;
+        insert.bind_text(2, &pad_bound(entry.end))?;
+        insert.bind_text(3, &entry.algorithm)?;
+        insert.bind_text(4, &entry.engine_version)?;
+        insert.bind_text(5, &entry.output)?;
+        insert.bind_int(6, entry.recorded_at as i64)?;
+        match insert.step()? {
+            false => Ok(()),
+            true => Err(Error::other("INSERT returned a row")),
+        }
+    }
+
+    /// Returns the recorded ranges overlapping `[start, end]`, by start.
+    pub fn overlapping(&self, start: u128, end: u128) -> Result<Vec<RangeEntry>> {
+        let mut query = self.prepare(
+            "SELECT start, end, algorithm, engine_version, output, recorded_at FROM ranges \
+             WHERE start <= ?2 AND end >= ?1 ORDER BY start, end, id",
+        )?;
+        query.bind_text(1, &pad_bound(start))?;
+        query.bind_text(2, &pad_bound(end))?;
+        query.entries()
+    }
+
+    /// Returns every recorded range, by start.
+    pub fn entries(&self) -> Result<Vec<RangeEntry>> {
+        self.prepare("SELECT start, end, algorithm, engine_version, output, recorded_at FROM ranges ORDER BY start, end, id")?
+            .entries()
+    }
+
+    // Function to run statements that return no rows
+    fn execute(&self, sql: &str) -> Result<()> {
+        let sql = c_string(sql)?;
+        // Safety: the database is open and the SQL is NUL-terminated
+        match unsafe { sqlite3_exec(self.database, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut()) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(self.error("Query failed")),
+        }
+    }
+
+    // Function to compile one statement
+    fn prepare(&self, sql: &str) -> Result<Query<'_>> {
+        let sql = c_string(sql)?;
+        let mut statement = ptr::null_mut();
+        // Safety: the database is open; a failed prepare leaves the statement null
+        match unsafe { sqlite3_prepare_v2(self.database, sql.as_ptr(), -1, &mut statement, ptr::null_mut()) } {
+            SQLITE_OK => Ok(Query { registry: self, statement }),
+            _ => Err(self.error("Failed to prepare a query")),
+        }
+    }
+
+    // Function to build an error from the database's last message
+    fn error(&self, fallback: &str) -> Error {
+        if self.database.is_null() {
+            return Error::other(fallback.to_string());
+        }
+        // Safety: the handle is valid, and its message is null or NUL-terminated
+        let message = unsafe { sqlite3_errmsg(self.database) };
+        match message.is_null() {
+            true => Error::other(fallback.to_string()),
+            false => Error::other(format!("{}: {}", fallback, unsafe { CStr::from_ptr(message) }.to_string_lossy())),
+        }
+    }
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        // Safety: every statement borrows the registry, so all are finalized by now
+        unsafe {
+            sqlite3_close(self.database);
+        }
+    }
+}
+
+// A prepared statement, finalized when dropped
+struct Query<'a> {
+    registry: &'a Registry,
+    statement: Statement,
+}
+
+impl Query<'_> {
+    // Function to bind text to the parameter at `index`, counted from 1
+    fn bind_text(&mut self, index: c_int, text: &str) -> Result<()> {
+        let length = c_int::try_from(text.len()).map_err(|_| Error::new(ErrorKind::InvalidInput, "Text too long for SQLite"))?;
+        // Safety: SQLite copies the text before returning
+        match unsafe { sqlite3_bind_text(self.statement, index, text.as_ptr() as *const c_char, length, SQLITE_TRANSIENT) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(self.registry.error("Failed to bind a parameter")),
+        }
+    }
+
+    // Function to bind an integer to the parameter at `index`, counted from 1
+    fn bind_int(&mut self, index: c_int, value: i64) -> Result<()> {
+        // Safety: the statement is prepared
+        match unsafe { sqlite3_bind_int64(self.statement, index, value) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(self.registry.error("Failed to bind a parameter")),
+        }
+    }
+
+    // Function to run the statement to its next row, returning whether there was one
+    fn step(&mut self) -> Result<bool> {
+        // Safety: the statement is prepared
+        match unsafe { sqlite3_step(self.statement) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(self.registry.error("Query failed")),
+        }
+    }
+
+    // Function to read the text of a column of the current row
+    fn text(&self, column: c_int) -> String {
+        // Safety: there is a current row; SQLite keeps the text valid until the next step
+        let text = unsafe { sqlite3_column_text(self.statement, column) };
+        match text.is_null() {
+            true => String::new(),
+            false => unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned(),
+        }
+    }
+
+    // Function to read every row of a `SELECT start, end, algorithm,
+    // engine_version, output, recorded_at`
+    fn entries(mut self) -> Result<Vec<RangeEntry>> {
+        let mut entries = Vec::new();
+        while self.step()? {
+            let bound = |column| {
+                self.text(column)
+                    .parse::<u128>()
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, format!("Invalid range bound in the registry: {}", self.text(column))))
+            };
+            entries.push(RangeEntry {
+                start: bound(0)?,
+                end: bound(1)?,
+                algorithm: self.text(2),
+                engine_version: self.text(3),
+                output: self.text(4),
+                // Safety: there is a current row
+                recorded_at: unsafe { sqlite3_column_int64(self.statement, 5) }.max(0) as u64,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+impl Drop for Query<'_> {
+    fn drop(&mut self) {
+        // Safety: the statement is finalized once
+        unsafe {
+            sqlite3_finalize(self.statement);
+        }
+    }
+}
+
+/// Returns the union of the recorded ranges as disjoint ranges in increasing
+/// order.
+pub fn coverage(entries: &[RangeEntry]) -> Vec<(u128, u128)> {
+    merge_ranges(&entries.iter().map(|entry| (entry.start, entry.end)).collect::<Vec<_>>())
+}
+
+/// Returns the parts of `[start, end]` no recorded range covers.
+pub fn gaps(entries: &[RangeEntry], start: u128, end: u128) -> Vec<(u128, u128)> {
+    subtract_ranges(start, end, &coverage(entries))
+}
+
+// Function to pad a range bound so text comparison orders it numerically
+fn pad_bound(n: u128) -> String {
+    format!("{:0width$}", n, width = BOUND_DIGITS)
+}
+
+// Function to pass a string to C, refusing ones with NUL bytes
+fn c_string(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|_| Error::new(ErrorKind::InvalidInput, format!("{:?} contains a NUL byte", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entry(start: u128, end: u128, output: &str) -> RangeEntry {
+        RangeEntry {
+            start,
+            end,
+            algorithm: "segmented-sieve".to_string(),
+            engine_version: "0.1.0".to_string(),
+            output: output.to_string(),
+            recorded_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn records_and_finds_overlapping_ranges() {
+        let path = std::env::temp_dir().join(format!("prime_generator_registry_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut registry = Registry::open(&path).unwrap();
+        registry.record(&entry(1, 1000, "low.csv")).unwrap();
+        registry.record(&entry(5000, 9000, "mid.csv")).unwrap();
+        registry.record(&entry(u128::MAX - 10, u128::MAX, "top.csv")).unwrap();
+        drop(registry);
+
+        // A reopened registry keeps what was recorded
+        let registry = Registry::open(&path).unwrap();
+        let outputs = |entries: Vec<RangeEntry>| entries.into_iter().map(|entry| entry.output).collect::<Vec<_>>();
+        assert_eq!(outputs(registry.overlapping(900, 5000).unwrap()), ["low.csv", "mid.csv"]);
+        assert_eq!(outputs(registry.overlapping(1001, 4999).unwrap()), Vec::<String>::new());
+        assert_eq!(outputs(registry.overlapping(u128::MAX, u128::MAX).unwrap()), ["top.csv"]);
+        let entries = registry.entries().unwrap();
+        assert_eq!(entries[2], entry(u128::MAX - 10, u128::MAX, "top.csv"));
+        drop(registry);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reports_coverage_and_gaps() {
+        let entries = [entry(1, 1000, "a"), entry(500, 2000, "b"), entry(2001, 3000, "c"), entry(5000, 6000, "d")];
+        assert_eq!(coverage(&entries), [(1, 3000), (5000, 6000)]);
+        assert_eq!(gaps(&entries, 1, 10_000), [(3001, 4999), (6001, 10_000)]);
+        assert_eq!(pad_bound(42).len(), u128::MAX.to_string().len());
+    }
+}
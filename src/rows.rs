@@ -0,0 +1,129 @@
+// Result rows of the subcommands, written as CSV or, for scripts that would
+// rather not parse CSV, as one JSON document: an array with an object per row
+// keyed by the CSV column names.
+
+use std::fmt;
+use std::io::{Result, Write};
+
+/// Writes the rows of one result table to `out`.
+pub struct Rows<W: Write> {
+    out: W,
+    columns: Vec<String>,
+    json: bool,
+    // Rows written so far; the header (or the opening bracket) goes out
+    // with the first
+    count: u64,
+}
+
+impl<W: Write> Rows<W> {
+    /// Starts a table with the comma-separated `header` as its columns.
+    pub fn new(out: W, header: &str, json: bool) -> Rows<W> {
+        Rows { out, columns: header.split(',').map(String::from).collect(), json, count: 0 }
+    }
+
+    /// Writes a row given as its CSV line, whose fields hold no commas. In
+    /// JSON, integers, decimals and booleans stay unquoted (even past 2^64),
+    /// empty fields are `null` and the rest are strings.
+    pub fn row(&mut self, line: fmt::Arguments) -> Result<()> {
+        if self.count == 0 {
+            self.start()?;
+        }
+        self.count += 1;
+        if !self.json {
+            self.out.write_fmt(line)?;
+            return self.out.write_all(b"\n");
+        }
+        let line = line.to_string();
+        let fields: Vec<String> = self
+            .columns
+            .iter()
+            .zip(line.split(',').map(Some).chain(std::iter::repeat(None)))
+            .map(|(column, field)| format!("{}:{}", json_string(column), json_value(field.unwrap_or(""))))
+            .collect();
+        let separator = if self.count == 1 { "\n" } else { ",\n" };
+        write!(self.out, "{}{{{}}}", separator, fields.join(","))
+    }
+
+    /// Ends the table, closing the JSON array, and flushes; a table without
+    /// rows still gets its header.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.start()?;
+        }
+        if self.json {
+            self.out.write_all(b"\n]\n")?;
+        }
+        self.out.flush()
+    }
+
+    // Function to write the CSV header line or open the JSON array
+    fn start(&mut self) -> Result<()> {
+        match self.json {
+            true => self.out.write_all(b"["),
+            false => writeln!(self.out, "{}", self.columns.join(",")),
+        }
+    }
+}
+
+// Function to write a CSV field as a JSON value
+fn json_value(field: &str) -> String {
+    let digits = field.strip_prefix('-').unwrap_or(field);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    let is_number = !whole.is_empty()
+        && !fraction.is_empty()
+        && whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+        && (whole == "0" || !whole.starts_with('0'));
+    match field {
+        "" => "null".to_string(),
+        "true" | "false" => field.to_string(),
+        _ if is_number => field.to_string(),
+        _ => json_string(field),
+    }
+}
+
+// Function to quote and escape a JSON string
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_csv_or_json() {
+        let mut csv = Rows::new(Vec::new(), "n,form,result", false);
+        csv.row(format_args!("{},{},{}", 3, "3!-1", "prime")).unwrap();
+        csv.finish().unwrap();
+        assert_eq!(String::from_utf8(csv.out).unwrap(), "n,form,result\n3,3!-1,prime\n");
+
+        let mut json = Rows::new(Vec::new(), "p,splitting,a,b,ratio,prime", true);
+        json.row(format_args!("{},inert,,,-0.25,true", u128::MAX)).unwrap();
+        json.row(format_args!("007,\"x\"")).unwrap();
+        json.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(json.out).unwrap(),
+            format!(
+                "[\n{{\"p\":{},\"splitting\":\"inert\",\"a\":null,\"b\":null,\"ratio\":-0.25,\"prime\":true}},\n\
+                 {{\"p\":\"007\",\"splitting\":\"\\\"x\\\"\",\"a\":null,\"b\":null,\"ratio\":null,\"prime\":null}}\n]\n",
+                u128::MAX
+            )
+        );
+
+        let mut empty = Rows::new(Vec::new(), "p", true);
+        empty.finish().unwrap();
+        assert_eq!(empty.out, b"[\n]\n");
+    }
+}
@@ -0,0 +1,68 @@
+// Deterministic pseudo-random sampling of primes. Whether a prime is kept
+// depends only on the prime and the seed, never on which worker found it or
+// when, so reruns, resumed runs and runs split into several ranges keep the
+// same primes.
+
+use std::io::{Error, ErrorKind, Result};
+
+/// Keeps each prime with a fixed probability, decided by hashing it with a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    // Primes whose hash falls below this are kept
+    threshold: u64,
+    seed: u64,
+}
+
+impl Sample {
+    /// Keeps a `rate` share of the primes, from 0 to 1; the seed picks which.
+    pub fn new(rate: f64, seed: u64) -> Result<Sample> {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Sample rate {} is outside 0..1", rate)));
+        }
+        // Rounding 1.0 * 2^64 to u64 saturates at u64::MAX, so also keep that hash
+        let threshold = match rate {
+            1.0 => u64::MAX,
+            _ => (rate * 2f64.powi(64)) as u64,
+        };
+        Ok(Sample { threshold, seed })
+    }
+
+    /// Tells whether `prime` is in the sample.
+    pub fn keeps(&self, prime: u128) -> bool {
+        let hash = mix(mix(prime as u64 ^ self.seed) ^ (prime >> 64) as u64);
+        hash < self.threshold || self.threshold == u64::MAX
+    }
+}
+
+// Function to scramble 64 bits with SplitMix64's finalizer, so nearby primes
+// get unrelated hashes
+pub(crate) fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::primes_in_range;
+
+    #[test]
+    fn keeps_the_same_share_for_the_same_seed() {
+        let primes = primes_in_range(2, 10_000_000);
+        let sample = Sample::new(0.01, 42).unwrap();
+        let kept: Vec<u64> = primes.iter().copied().filter(|&p| sample.keeps(p as u128)).collect();
+        // 664579 primes, so about 6646 with a standard deviation of about 81
+        assert!((6300..7000).contains(&kept.len()), "{}", kept.len());
+        let again: Vec<u64> = primes.iter().copied().filter(|&p| Sample::new(0.01, 42).unwrap().keeps(p as u128)).collect();
+        assert_eq!(kept, again);
+        let other = Sample::new(0.01, 43).unwrap();
+        assert!(kept.iter().filter(|&&p| other.keeps(p as u128)).count() < 200);
+
+        let (all, none) = (Sample::new(1.0, 7).unwrap(), Sample::new(0.0, 7).unwrap());
+        assert!(primes.iter().take(1000).all(|&p| all.keeps(p as u128) && !none.keeps(p as u128)));
+        assert!(Sample::new(1.5, 0).is_err());
+        assert!(Sample::new(f64::NAN, 0).is_err());
+    }
+}
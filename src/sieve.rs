@@ -0,0 +1,369 @@
+// Sieve of Eratosthenes helpers shared by the generators and visualizations.
+
+use std::sync::OnceLock;
+
+// Primes whose multiples are struck out by copying a precomputed pattern
+// instead of being sieved segment by segment
+const PRESIEVE_PRIMES: [u64; 8] = [2, 3, 5, 7, 11, 13, 17, 19];
+
+// Product of the presieving primes, the period of the pattern
+const PRESIEVE_PERIOD: u64 = 2 * 3 * 5 * 7 * 11 * 13 * 17 * 19;
+
+// Limits up to this are sieved in one flat array by `small_primes`
+const DIRECT_SIEVE_LIMIT: u64 = 1 << 16;
+
+// Numbers per segment when `small_primes` sieves larger limits
+const SMALL_PRIMES_SEGMENT_SIZE: u64 = 1 << 18;
+
+/// Integer square root, rounded down.
+pub fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = (n as f64).sqrt() as u64;
+    while (x as u128) * (x as u128) > n as u128 {
+        x -= 1;
+    }
+    while (x as u128 + 1) * (x as u128 + 1) <= n as u128 {
+        x += 1;
+    }
+    x
+}
+
+/// Returns all primes up to and including `limit`.
+pub fn small_primes(limit: u64) -> Vec<u64> {
+    let mut primes = Vec::new();
+    for_each_small_prime(limit, |p| primes.push(p));
+    primes
+}
+
+/// Returns the primes up to `isqrt(end)`, which sieve any window ending at
+/// `end`. They always fit into a `u32`, which halves the list for windows
+/// near 2^64 (about 200 million primes).
+pub fn sieving_primes(end: u64) -> Vec<u32> {
+    let mut primes = Vec::new();
+    for_each_small_prime(isqrt(end), |p| primes.push(p as u32));
+    primes
+}
+
+// Function to call `f` with each prime up to `limit` in increasing order.
+// Beyond `DIRECT_SIEVE_LIMIT` this sieves one segment at a time with the
+// primes up to sqrt(limit), so memory stays at a segment plus those primes.
+fn for_each_small_prime<F: FnMut(u64)>(limit: u64, mut f: F) {
+    if limit < 2 {
+        return;
+    }
+    if limit <= DIRECT_SIEVE_LIMIT {
+        let limit = limit as usize;
+        let mut composite = vec![false; limit + 1];
+        for n in 2..=limit {
+            if composite[n] {
+                continue;
+            }
+            f(n as u64);
+            let mut multiple = n * n;
+            while multiple <= limit {
+                composite[multiple] = true;
+                multiple += n;
+            }
+        }
+        return;
+    }
+    let base_primes = sieving_primes(limit);
+    let mut segment_start = 0u64;
+    loop {
+        let segment_end = segment_start.saturating_add(SMALL_PRIMES_SEGMENT_SIZE - 1).min(limit);
+        let flags = sieve_segment(segment_start, segment_end, &base_primes);
+        for (n, _) in (segment_start..).zip(flags).filter(|(_, is_prime)| *is_prime) {
+            f(n);
+        }
+        if segment_end == limit {
+            return;
+        }
+        segment_start = segment_end + 1;
+    }
+}
+
+/// Sieves the inclusive window `[start, end]`.
+///
+/// Returns one flag per number in the window, `true` where the number is prime.
+pub fn sieve_range(start: u64, end: u64) -> Vec<bool> {
+    sieve_segment(start, end, &sieving_primes(end))
+}
+
+/// Sieves the inclusive window `[start, end]` with precomputed `base_primes`,
+/// which must include every prime up to `isqrt(end)`.
+pub fn sieve_segment(start: u64, end: u64, base_primes: &[u32]) -> Vec<bool> {
+    if end < start {
+        return Vec::new();
+    }
+    let mut flags = presieved_segment(start, end);
+    let limit = isqrt(end);
+    let sieving_primes = base_primes.iter().map(|&p| p as u64).skip_while(|&p| p <= PRESIEVE_PRIMES[7]);
+    for p in sieving_primes.take_while(|&p| p <= limit) {
+        // Start at p^2 or the first multiple of p inside the window, whichever is larger
+        let Some(first) = start.div_ceil(p).checked_mul(p).map(|m| m.max(p * p)) else {
+            continue;
+        };
+        let mut multiple = first;
+        while multiple <= end {
+            flags[(multiple - start) as usize] = false;
+            match multiple.checked_add(p) {
+                Some(next) => multiple = next,
+                None => break,
+            }
+        }
+    }
+    flags
+}
+
+// Function to build the flags for `[start, end]` with the multiples of the
+// presieving primes already cleared
+fn presieved_segment(start: u64, end: u64) -> Vec<bool> {
+    let len = (end - start + 1) as usize;
+    let pattern = presieve_pattern();
+    let mut flags = Vec::with_capacity(len);
+    let mut offset = (start % PRESIEVE_PERIOD) as usize;
+    while flags.len() < len {
+        let take = (len - flags.len()).min(pattern.len() - offset);
+        flags.extend_from_slice(&pattern[offset..offset + take]);
+        offset = 0;
+    }
+    // 0 and 1 are not prime, while the presieving primes struck themselves out
+    for n in start..=end.min(1) {
+        flags[(n - start) as usize] = false;
+    }
+    for &p in PRESIEVE_PRIMES.iter().filter(|&&p| (start..=end).contains(&p)) {
+        flags[(p - start) as usize] = true;
+    }
+    flags
+}
+
+// Function to get one period of the presieve pattern, computed on first use
+fn presieve_pattern() -> &'static [bool] {
+    static PATTERN: OnceLock<Vec<bool>> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        let mut pattern = vec![true; PRESIEVE_PERIOD as usize];
+        for p in PRESIEVE_PRIMES {
+            for multiple in (0..PRESIEVE_PERIOD as usize).step_by(p as usize) {
+                pattern[multiple] = false;
+            }
+        }
+        pattern
+    })
+}
+
+/// Sieves `[start, end]` one segment at a time, in increasing order.
+///
+/// Sieving primes up to the segment size strike every segment and carry their
+/// next multiple over. Larger primes hit a segment at most once, so they are
+/// kept in buckets keyed by the segment of their next multiple and only
+/// touched when that segment comes up. This keeps the per-segment cost
+/// independent of how many sieving primes lie beyond the segment size, which
+/// is what dominates ranges far above 10^12.
+pub struct BucketSieve {
+    start: u64,
+    end: u64,
+    segment_size: u64,
+    next_segment: Option<u64>,
+    // Primes up to the segment size, with their next multiple
+    small: Vec<(u64, u64)>,
+    // Ring of buckets of (prime, next multiple), one per upcoming segment
+    buckets: Vec<Vec<(u64, u64)>>,
+}
+
+impl BucketSieve {
+    /// Prepares to sieve `[start, end]` in segments of `segment_size`
+    /// numbers. `base_primes` must include every prime up to `isqrt(end)`.
+    pub fn new(start: u64, end: u64, segment_size: u64, base_primes: &[u32]) -> BucketSieve {
+        assert!(segment_size > 0, "Segment size must be positive");
+        let limit = isqrt(end);
+        let sieving_primes: Vec<u64> = base_primes
+            .iter()
+            .map(|&p| p as u64)
+            .skip_while(|&p| p <= PRESIEVE_PRIMES[7])
+            .take_while(|&p| p <= limit)
+            .collect();
+        let largest = sieving_primes.last().copied().unwrap_or(0);
+        let mut sieve = BucketSieve {
+            start,
+            end,
+            segment_size,
+            next_segment: (start <= end).then_some(start),
+            small: Vec::new(),
+            buckets: vec![Vec::new(); (largest / segment_size + 2) as usize],
+        };
+        for p in sieving_primes {
+            // Start at p^2 or the first multiple of p inside the range, whichever is larger
+            let Some(first) = start.div_ceil(p).checked_mul(p).map(|m| m.max(p * p)) else {
+                continue;
+            };
+            if p <= segment_size {
+                sieve.small.push((p, first));
+            } else if first <= end {
+                sieve.push_bucket(p, first);
+            }
+        }
+        sieve
+    }
+
+    // Function to file a large prime under the segment holding `multiple`
+    fn push_bucket(&mut self, p: u64, multiple: u64) {
+        let segment = (multiple - self.start) / self.segment_size;
+        let ring = self.buckets.len();
+        self.buckets[segment as usize % ring].push((p, multiple));
+    }
+}
+
+impl Iterator for BucketSieve {
+    /// The segment's first number and one flag per number, `true` where prime.
+    type Item = (u64, Vec<bool>);
+
+    fn next(&mut self) -> Option<(u64, Vec<bool>)> {
+        let segment_start = self.next_segment?;
+        let segment_end = segment_start.saturating_add(self.segment_size - 1).min(self.end);
+        self.next_segment = segment_end.checked_add(1).filter(|&next| next <= self.end);
+
+        let mut flags = presieved_segment(segment_start, segment_end);
+        for (p, next) in self.small.iter_mut() {
+            while *next <= segment_end {
+                flags[(*next - segment_start) as usize] = false;
+                // Past u64::MAX there are no more segments; parking at u64::MAX
+                // is harmless because it is composite
+                let Some(multiple) = next.checked_add(*p) else {
+                    *next = u64::MAX;
+                    break;
+                };
+                *next = multiple;
+            }
+        }
+        let ring = self.buckets.len();
+        let segment = (segment_start - self.start) / self.segment_size;
+        let bucket = std::mem::take(&mut self.buckets[segment as usize % ring]);
+        for (p, multiple) in bucket {
+            // A first multiple (p^2) can lie more than a ring's length ahead
+            if multiple > segment_end {
+                self.push_bucket(p, multiple);
+                continue;
+            }
+            flags[(multiple - segment_start) as usize] = false;
+            if let Some(next) = multiple.checked_add(p).filter(|&next| next <= self.end) {
+                self.push_bucket(p, next);
+            }
+        }
+        Some((segment_start, flags))
+    }
+}
+
+/// Returns the primes in `[start, end]` using precomputed `base_primes`,
+/// which must include every prime up to `isqrt(end)`.
+pub fn primes_in_segment(start: u64, end: u64, base_primes: &[u32]) -> Vec<u64> {
+    sieve_segment(start, end, base_primes)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, is_prime)| *is_prime)
+        .map(|(offset, _)| start + offset as u64)
+        .collect()
+}
+
+/// Returns the primes in the inclusive window `[start, end]`.
+pub fn primes_in_range(start: u64, end: u64) -> Vec<u64> {
+    primes_in_segment(start, end, &sieving_primes(end))
+}
+
+// Numbers sieved at a time when streaming primes
+const STREAM_SEGMENT_SIZE: u64 = 1 << 20;
+
+/// Calls `f` with each prime in `[start, end]` in increasing order, sieving
+/// one segment at a time so memory use doesn't grow with the range.
+///
+/// Enumeration stops early as soon as `f` returns `false`.
+pub fn for_each_prime<F: FnMut(u64) -> bool>(start: u64, end: u64, mut f: F) {
+    let base_primes = sieving_primes(end);
+    for (segment_start, flags) in BucketSieve::new(start, end, STREAM_SEGMENT_SIZE, &base_primes) {
+        for (offset, is_prime) in flags.into_iter().enumerate() {
+            if is_prime && !f(segment_start + offset as u64) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::is_prime;
+
+    // Function to list the primes in [start, end] one Miller-Rabin test at a time
+    fn expected_primes(start: u64, end: u64) -> Vec<u64> {
+        (start..=end).filter(|&n| is_prime(n as u128)).collect()
+    }
+
+    // Function to turn a window's flags back into its primes
+    fn flagged(start: u64, flags: &[bool]) -> Vec<u64> {
+        flags.iter().enumerate().filter(|(_, &is_prime)| is_prime).map(|(offset, _)| start + offset as u64).collect()
+    }
+
+    #[test]
+    fn presieved_windows_match_primality_tests() {
+        // Windows holding the presieving primes themselves
+        for start in 0..40 {
+            assert_eq!(flagged(start, &sieve_range(start, start + 100)), expected_primes(start, start + 100), "{}", start);
+        }
+        // Windows straddling the end of the pattern's period
+        for period in [1, 2, 1000] {
+            let boundary = PRESIEVE_PERIOD * period;
+            let (start, end) = (boundary - 500, boundary + 500);
+            assert_eq!(flagged(start, &sieve_range(start, end)), expected_primes(start, end), "{}", boundary);
+        }
+        // Windows longer than a period
+        let (start, end) = (PRESIEVE_PERIOD / 2, PRESIEVE_PERIOD * 2 + 77);
+        assert_eq!(flagged(start, &sieve_range(start, end)).len(), expected_primes(start, end).len());
+    }
+
+    #[test]
+    fn bucket_sieve_matches_primes_in_range() {
+        for (start, end) in [(0, 1000), (17, 17), (1000, 1000), (10_000, 200_000), (1 << 40, (1 << 40) + 100_000)] {
+            let expected = primes_in_range(start, end);
+            let base_primes = sieving_primes(end);
+            for segment_size in [1, 7, 30, 1000, 1 << 16] {
+                // Tiny segments on long windows only slow the test down
+                if segment_size < 30 && end - start > 10_000 {
+                    continue;
+                }
+                let found: Vec<u64> = BucketSieve::new(start, end, segment_size, &base_primes)
+                    .flat_map(|(segment_start, flags)| flagged(segment_start, &flags))
+                    .collect();
+                assert_eq!(found, expected, "{}..{} in segments of {}", start, end, segment_size);
+            }
+        }
+        assert_eq!(primes_in_range(1 << 40, (1 << 40) + 100_000), expected_primes(1 << 40, (1 << 40) + 100_000));
+    }
+
+    #[test]
+    fn sieves_windows_at_the_top_of_u64() {
+        // Multiples of the base primes must not wrap around past u64::MAX
+        let base_primes = sieving_primes(1 << 24);
+        let flags = sieve_segment(u64::MAX - 1000, u64::MAX, &base_primes);
+        for n in flagged(u64::MAX - 1000, &flags) {
+            assert!(base_primes.iter().all(|&p| n % p as u64 != 0), "{}", n);
+        }
+    }
+
+    #[test]
+    fn segmented_small_primes_match_direct_sieve() {
+        let direct: Vec<u64> = (0..=3_000_000).filter(|&n| is_prime(n as u128)).collect();
+        assert_eq!(small_primes(3_000_000), direct);
+        assert_eq!(sieving_primes(3_000_000u64.pow(2)), direct.iter().map(|&p| p as u32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn small_primes_and_isqrt_agree_with_definitions() {
+        assert!(small_primes(1).is_empty());
+        assert_eq!(small_primes(30), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        for n in [0, 1, 2, 3, 4, 15, 16, 17, u32::MAX as u64, u64::MAX] {
+            let root = isqrt(n) as u128;
+            assert!(root * root <= n as u128 && (root + 1) * (root + 1) > n as u128, "{}", n);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+// Ed25519 signatures over output shards, so anyone holding the public key
+// can check that a file was signed with the matching private key and hasn't
+// changed since.
+//
+// The signed message is the shard's SHA-256 checksum; the signature is stored
+// as JSON next to the shard, like its manifest.
+
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{sha256_file, shard_next_to};
+
+/// Ed25519 signature of an output shard, written next to it by
+/// [`ShardSignature::write`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardSignature {
+    /// File name of the shard, relative to the signature file.
+    pub file: String,
+    /// Lowercase hex SHA-256 of the shard's bytes: the signed message.
+    pub sha256: String,
+    /// Lowercase hex Ed25519 signature of `sha256`.
+    pub signature: String,
+}
+
+/// Returns where the signature of the shard at `path` is stored:
+/// `primes.csv` gets `primes.csv.sig`.
+pub fn signature_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// Reads an Ed25519 private key from a PKCS#8 PEM file, as written by
+/// `openssl genpkey -algorithm ed25519`.
+pub fn read_signing_key<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+    let pem = fs::read_to_string(path)?;
+    SigningKey::from_pkcs8_pem(&pem).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Reads an Ed25519 public key from a PEM file, as written by
+/// `openssl pkey -pubout`.
+pub fn read_verifying_key<P: AsRef<Path>>(path: P) -> Result<VerifyingKey> {
+    let pem = fs::read_to_string(path)?;
+    VerifyingKey::from_public_key_pem(&pem).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+impl ShardSignature {
+    /// Hashes the shard at `path` and signs the checksum with `key`.
+    pub fn sign<P: AsRef<Path>>(path: P, key: &SigningKey) -> Result<ShardSignature> {
+        let path = path.as_ref();
+        let file = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Shard path has no file name"))?
+            .to_string();
+        let sha256 = sha256_file(path)?;
+        let signature = to_hex(&key.sign(sha256.as_bytes()).to_bytes());
+        Ok(ShardSignature { file, sha256, signature })
+    }
+
+    /// Reads a signature written by [`ShardSignature::write`].
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<ShardSignature> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the signature as pretty-printed JSON.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::other)
+    }
+
+    /// Checks the signature file at `signature` against the shard next to it
+    /// and the signer's public `key`. Fails with [`ErrorKind::InvalidData`] if
+    /// the shard changed or the signature wasn't made by `key`.
+    pub fn verify<P: AsRef<Path>>(signature: P, key: &VerifyingKey) -> Result<()> {
+        let signature_file = signature.as_ref();
+        let expected = ShardSignature::read(signature_file)?;
+        let shard = shard_next_to(signature_file, &expected.file)?;
+
+        let sha256 = sha256_file(&shard)?;
+        if sha256 != expected.sha256 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("SHA-256 of {} is {}, but {} was signed", shard.display(), sha256, expected.sha256),
+            ));
+        }
+        let bytes: [u8; 64] = from_hex(&expected.signature)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Signature is not 64 hex-encoded bytes"))?;
+        key.verify(expected.sha256.as_bytes(), &Signature::from_bytes(&bytes))
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Signature was not made by this key"))
+    }
+}
+
+// Function to encode bytes as lowercase hex
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Function to decode hex, or None if it isn't valid
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_file::append_records;
+    use crate::record::PrimeRecord;
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey, EncodePublicKey};
+
+    // Function to write a signed shard into a fresh directory
+    fn signed_shard(name: &str, key: &SigningKey) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("prime_generator_signature_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("primes.csv");
+        append_records(&path, [2, 3, 5, 7].map(PrimeRecord::from_prime)).unwrap();
+        let signature = signature_path(&path);
+        ShardSignature::sign(&path, key).unwrap().write(&signature).unwrap();
+        (path, signature)
+    }
+
+    #[test]
+    fn verifies_with_the_signers_key_only() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let (path, signature) = signed_shard("keys", &key);
+        assert!(ShardSignature::verify(&signature, &key.verifying_key()).is_ok());
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert_eq!(ShardSignature::verify(&signature, &other).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn detects_changed_shards_and_signatures() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let (path, signature) = signed_shard("tampered", &key);
+        // A re-hashed shard no longer matches the signed checksum
+        let mut forged = ShardSignature::read(&signature).unwrap();
+        append_records(&path, [PrimeRecord::from_prime(11)]).unwrap();
+        assert_eq!(ShardSignature::verify(&signature, &key.verifying_key()).unwrap_err().kind(), ErrorKind::InvalidData);
+        forged.sha256 = sha256_file(&path).unwrap();
+        forged.write(&signature).unwrap();
+        assert_eq!(ShardSignature::verify(&signature, &key.verifying_key()).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_pem_keys() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let dir = std::env::temp_dir().join(format!("prime_generator_signature_pem_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (private, public) = (dir.join("key.pem"), dir.join("key.pub.pem"));
+        fs::write(&private, key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+        fs::write(&public, key.verifying_key().to_public_key_pem(LineEnding::LF).unwrap()).unwrap();
+        assert_eq!(read_signing_key(&private).unwrap().to_bytes(), key.to_bytes());
+        assert_eq!(read_verifying_key(&public).unwrap(), key.verifying_key());
+        assert_eq!(read_signing_key(&public).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
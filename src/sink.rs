@@ -0,0 +1,368 @@
+// Destinations for generated records.
+
+use std::io::Result;
+#[cfg(feature = "csv")]
+use std::path::PathBuf;
+
+#[cfg(feature = "avro")]
+use crate::avro_file::AvroWriter;
+use crate::bignum::Integer;
+#[cfg(feature = "duckdb")]
+use crate::bignum::powers;
+#[cfg(feature = "csv")]
+use crate::bignum::PowerFormat;
+#[cfg(feature = "csv")]
+use crate::columns::Column;
+#[cfg(feature = "csv")]
+use crate::csv_file::{CsvDialect, CsvOptions};
+#[cfg(feature = "csv")]
+use crate::csv_file::{append_powers_with, append_records_with};
+#[cfg(any(feature = "mmap", all(feature = "uring", target_os = "linux")))]
+use crate::csv_file::{write_powers_with, write_records_with};
+#[cfg(feature = "duckdb")]
+use crate::duckdb::DuckDbTable;
+#[cfg(feature = "mmap")]
+use crate::mmap_file::MmapWriter;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+use crate::uring_file::UringWriter;
+use crate::record::PrimeRecord;
+#[cfg(any(feature = "mmap", all(feature = "uring", target_os = "linux")))]
+use crate::timing::TimedWriter;
+#[cfg(feature = "redis")]
+use crate::redis::{RedisSortedSet, RedisTarget};
+
+/// Receives batches of records from a [`PrimeGenerator`](crate::PrimeGenerator) run.
+pub trait Sink: Send {
+    /// Writes one batch of records.
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()>;
+
+    /// Writes one batch of primes with their `[squared, cubed, to_fourth_power]`
+    /// powers. The default builds a [`PrimeRecord`] per prime for
+    /// [`write_batch`](Sink::write_batch); sinks that serialize the numbers
+    /// directly can override it to skip those allocations.
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        self.write_batch(batch.iter().map(|(prime, powers)| PrimeRecord::new(*prime, powers)).collect())
+    }
+
+    /// Called once after the last batch of a run.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends records to a CSV file, creating it if needed.
+#[cfg(feature = "csv")]
+pub struct CsvSink {
+    path: PathBuf,
+    options: CsvOptions,
+}
+
+#[cfg(feature = "csv")]
+impl CsvSink {
+    pub fn new<P: Into<PathBuf>>(path: P) -> CsvSink {
+        CsvSink { path: path.into(), options: CsvOptions::default() }
+    }
+
+    /// Adds extra columns after the powers of every prime written through
+    /// [`write_powers`](Sink::write_powers).
+    pub fn columns(mut self, columns: Vec<Column>) -> CsvSink {
+        self.options.columns = columns;
+        self
+    }
+
+    /// Sets how the powers written through [`write_powers`](Sink::write_powers)
+    /// are formatted. Defaults to decimal.
+    pub fn power_format(mut self, power_format: PowerFormat) -> CsvSink {
+        self.options.power_format = power_format;
+        self
+    }
+
+    /// Leaves the powers out of the rows written through
+    /// [`write_powers`](Sink::write_powers), writing just the primes (and any
+    /// extra columns). Readers derive the powers from the primes.
+    pub fn primes_only(mut self, primes_only: bool) -> CsvSink {
+        self.options.primes_only = primes_only;
+        self
+    }
+
+    /// Sets the delimiter, quoting, line terminator and whether batches start
+    /// with headers. Defaults to comma-separated rows under headers.
+    pub fn dialect(mut self, dialect: CsvDialect) -> CsvSink {
+        self.options.dialect = dialect;
+        self
+    }
+}
+
+#[cfg(feature = "csv")]
+impl Sink for CsvSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        append_records_with(&self.path, records, &self.options.dialect)
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        append_powers_with(&self.path, batch, &self.options)
+    }
+
+    // A run without primes still leaves its (empty) output file behind
+    fn finish(&mut self) -> Result<()> {
+        std::fs::OpenOptions::new().append(true).create(true).open(&self.path).map(drop)
+    }
+}
+
+/// Appends records to a CSV file through a memory mapping, creating it if
+/// needed. Writes the same layout as [`CsvSink`].
+#[cfg(feature = "mmap")]
+pub struct MmapCsvSink {
+    writer: MmapWriter,
+    options: CsvOptions,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapCsvSink {
+    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<MmapCsvSink> {
+        Ok(MmapCsvSink { writer: MmapWriter::append(path)?, options: CsvOptions::default() })
+    }
+
+    /// Adds extra columns, like [`CsvSink::columns`].
+    pub fn columns(mut self, columns: Vec<Column>) -> MmapCsvSink {
+        self.options.columns = columns;
+        self
+    }
+
+    /// Sets how the powers are formatted, like [`CsvSink::power_format`].
+    pub fn power_format(mut self, power_format: PowerFormat) -> MmapCsvSink {
+        self.options.power_format = power_format;
+        self
+    }
+
+    /// Leaves the powers out, like [`CsvSink::primes_only`].
+    pub fn primes_only(mut self, primes_only: bool) -> MmapCsvSink {
+        self.options.primes_only = primes_only;
+        self
+    }
+
+    /// Sets how rows are punctuated, like [`CsvSink::dialect`].
+    pub fn dialect(mut self, dialect: CsvDialect) -> MmapCsvSink {
+        self.options.dialect = dialect;
+        self
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Sink for MmapCsvSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        write_records_with(TimedWriter(&mut self.writer), records, &self.options.dialect)
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        write_powers_with(TimedWriter(&mut self.writer), batch, &self.options)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// Appends records to a CSV file with asynchronous io_uring writes, creating
+/// it if needed. Writes the same layout as [`CsvSink`]. Linux only.
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub struct UringCsvSink {
+    writer: UringWriter,
+    options: CsvOptions,
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+impl UringCsvSink {
+    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<UringCsvSink> {
+        Ok(UringCsvSink { writer: UringWriter::append(path)?, options: CsvOptions::default() })
+    }
+
+    /// Adds extra columns, like [`CsvSink::columns`].
+    pub fn columns(mut self, columns: Vec<Column>) -> UringCsvSink {
+        self.options.columns = columns;
+        self
+    }
+
+    /// Sets how the powers are formatted, like [`CsvSink::power_format`].
+    pub fn power_format(mut self, power_format: PowerFormat) -> UringCsvSink {
+        self.options.power_format = power_format;
+        self
+    }
+
+    /// Leaves the powers out, like [`CsvSink::primes_only`].
+    pub fn primes_only(mut self, primes_only: bool) -> UringCsvSink {
+        self.options.primes_only = primes_only;
+        self
+    }
+
+    /// Sets how rows are punctuated, like [`CsvSink::dialect`].
+    pub fn dialect(mut self, dialect: CsvDialect) -> UringCsvSink {
+        self.options.dialect = dialect;
+        self
+    }
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+impl Sink for UringCsvSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        write_records_with(TimedWriter(&mut self.writer), records, &self.options.dialect)
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        write_powers_with(TimedWriter(&mut self.writer), batch, &self.options)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// Appends records to an Avro object container file, creating it with a
+/// header if needed. Takes the same columns, power format and primes-only
+/// options as [`CsvSink`]; the file is opened on the first batch.
+#[cfg(feature = "avro")]
+pub struct AvroSink {
+    path: PathBuf,
+    options: CsvOptions,
+    writer: Option<AvroWriter>,
+}
+
+#[cfg(feature = "avro")]
+impl AvroSink {
+    pub fn new<P: Into<PathBuf>>(path: P) -> AvroSink {
+        AvroSink { path: path.into(), options: CsvOptions::default(), writer: None }
+    }
+
+    /// Adds extra fields, like [`CsvSink::columns`].
+    pub fn columns(mut self, columns: Vec<Column>) -> AvroSink {
+        self.options.columns = columns;
+        self
+    }
+
+    /// Sets how the powers are formatted, like [`CsvSink::power_format`].
+    pub fn power_format(mut self, power_format: PowerFormat) -> AvroSink {
+        self.options.power_format = power_format;
+        self
+    }
+
+    /// Leaves the powers out, like [`CsvSink::primes_only`].
+    pub fn primes_only(mut self, primes_only: bool) -> AvroSink {
+        self.options.primes_only = primes_only;
+        self
+    }
+
+    // Function to open the file on first use
+    fn writer(&mut self) -> Result<&mut AvroWriter> {
+        if self.writer.is_none() {
+            self.writer = Some(AvroWriter::append(&self.path, &self.options)?);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+}
+
+#[cfg(feature = "avro")]
+impl Sink for AvroSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.writer()?.write_records(&records)
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.writer()?.write_powers(batch)
+    }
+}
+
+/// Appends records to a table of a DuckDB database file, creating both if
+/// needed. Takes the same columns, power format and primes-only options as
+/// [`CsvSink`]; the database is opened on the first batch.
+#[cfg(feature = "duckdb")]
+pub struct DuckDbSink {
+    path: PathBuf,
+    table_name: String,
+    options: CsvOptions,
+    table: Option<DuckDbTable>,
+}
+
+#[cfg(feature = "duckdb")]
+impl DuckDbSink {
+    pub fn new<P: Into<PathBuf>>(path: P, table: &str) -> DuckDbSink {
+        DuckDbSink { path: path.into(), table_name: table.to_string(), options: CsvOptions::default(), table: None }
+    }
+
+    /// Adds extra columns, like [`CsvSink::columns`].
+    pub fn columns(mut self, columns: Vec<Column>) -> DuckDbSink {
+        self.options.columns = columns;
+        self
+    }
+
+    /// Sets how the powers are formatted, like [`CsvSink::power_format`].
+    pub fn power_format(mut self, power_format: PowerFormat) -> DuckDbSink {
+        self.options.power_format = power_format;
+        self
+    }
+
+    /// Leaves the powers out, like [`CsvSink::primes_only`].
+    pub fn primes_only(mut self, primes_only: bool) -> DuckDbSink {
+        self.options.primes_only = primes_only;
+        self
+    }
+}
+
+#[cfg(feature = "duckdb")]
+impl Sink for DuckDbSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        let batch: Vec<(u128, Vec<Integer>)> = records.iter().map(|record| (record.prime, powers(record.prime).to_vec())).collect();
+        self.write_powers(&batch)
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        if self.table.is_none() {
+            self.table = Some(DuckDbTable::open(&self.path, &self.table_name, &self.options)?);
+        }
+        self.table.as_mut().unwrap().append_powers(batch)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match &mut self.table {
+            Some(table) => table.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Adds the primes to a Redis sorted set, each scored by its value and named
+/// by its decimal digits. Only the primes are stored, as their powers can be
+/// derived; sets are idempotent, so rerunning a range adds nothing new.
+#[cfg(feature = "redis")]
+pub struct RedisSink {
+    set: RedisSortedSet,
+}
+
+#[cfg(feature = "redis")]
+impl RedisSink {
+    /// Connects to the server `target` points at.
+    pub fn connect(target: &RedisTarget) -> Result<RedisSink> {
+        Ok(RedisSink { set: RedisSortedSet::connect(target)? })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl Sink for RedisSink {
+    fn write_batch(&mut self, records: Vec<PrimeRecord>) -> Result<()> {
+        let primes: Vec<u128> = records.iter().map(|record| record.prime).collect();
+        self.set.add_primes(&primes).map(|_| ())
+    }
+
+    fn write_powers(&mut self, batch: &[(u128, Vec<Integer>)]) -> Result<()> {
+        let primes: Vec<u128> = batch.iter().map(|(prime, _)| *prime).collect();
+        self.set.add_primes(&primes).map(|_| ())
+    }
+}
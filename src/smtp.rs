@@ -0,0 +1,272 @@
+// Minimal SMTP client for emailing a report when a run finishes, for servers
+// without webhook infrastructure. Speaks ESMTP to a submission server over
+// implicit TLS (port 465) or STARTTLS, authenticating with AUTH PLAIN; only
+// what sending one message needs is implemented.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use native_tls::TlsConnector;
+
+use crate::api::to_base64;
+
+// Longest a server may take to answer a command
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+// Separates the parts of multipart messages; never appears in base64 or in
+// the text the tool writes
+const BOUNDARY: &str = "=_prime_generator_part";
+
+/// A submission server and the account to send from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpServer {
+    pub host: String,
+    pub port: u16,
+    /// User name and password for AUTH PLAIN, only ever sent over TLS.
+    pub credentials: Option<(String, String)>,
+}
+
+impl SmtpServer {
+    /// Parses `host[:port]`; the port defaults to 587, submission with
+    /// STARTTLS. Credentials come from `PRIMEGEN_SMTP_USER` and
+    /// `PRIMEGEN_SMTP_PASSWORD` if both are set.
+    pub fn from_env(host: &str) -> std::result::Result<SmtpServer, String> {
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| format!("Invalid SMTP port: {}", port))?),
+            None => (host, 587),
+        };
+        if host.is_empty() {
+            return Err("No SMTP host was given".to_string());
+        }
+        let credentials = match (std::env::var("PRIMEGEN_SMTP_USER"), std::env::var("PRIMEGEN_SMTP_PASSWORD")) {
+            (Ok(user), Ok(password)) => Some((user, password)),
+            _ => None,
+        };
+        Ok(SmtpServer { host: host.to_string(), port, credentials })
+    }
+}
+
+/// A plain-text message with optional attachments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    /// File names and contents, attached as `application/octet-stream`.
+    pub attachments: Vec<(String, Vec<u8>)>,
+}
+
+impl Email {
+    /// Formats the message as RFC 5322 text with CRLF line endings, as a
+    /// MIME multipart message if it has attachments.
+    pub fn to_message(&self) -> String {
+        let mut message = format!("From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n", self.from, self.to.join(", "), self.subject);
+        let text = format!("Content-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n", crlf(&self.body));
+        if self.attachments.is_empty() {
+            message.push_str(&text);
+            return message;
+        }
+        message.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n--{}\r\n{}", BOUNDARY, BOUNDARY, text));
+        for (name, content) in &self.attachments {
+            message.push_str(&format!(
+                "--{}\r\nContent-Type: application/octet-stream; name=\"{}\"\r\nContent-Disposition: attachment; filename=\"{}\"\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+                BOUNDARY, name, name
+            ));
+            for line in to_base64(content).as_bytes().chunks(76) {
+                message.push_str(std::str::from_utf8(line).unwrap());
+                message.push_str("\r\n");
+            }
+        }
+        message.push_str(&format!("--{}--\r\n", BOUNDARY));
+        message
+    }
+}
+
+/// Sends `email` through `server`: over TLS from the start on port 465,
+/// otherwise upgrading with STARTTLS when the server offers it. Credentials
+/// are refused over a connection that stays in the clear.
+pub fn send(server: &SmtpServer, email: &Email) -> Result<()> {
+    let stream = TcpStream::connect((server.host.as_str(), server.port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let tls = || TlsConnector::new().map_err(Error::other);
+    if server.port == 465 {
+        let stream = tls()?.connect(&server.host, stream).map_err(Error::other)?;
+        let mut session = Session::new(stream);
+        session.expect(220)?;
+        session.command("EHLO prime_generator", 250)?;
+        return session.deliver(server.credentials.as_ref(), email);
+    }
+
+    let mut session = Session::new(stream);
+    session.expect(220)?;
+    let extensions = session.command("EHLO prime_generator", 250)?;
+    if extensions.iter().any(|extension| extension.eq_ignore_ascii_case("STARTTLS")) {
+        session.command("STARTTLS", 220)?;
+        let stream = tls()?.connect(&server.host, session.stream.into_inner()).map_err(Error::other)?;
+        let mut session = Session::new(stream);
+        session.command("EHLO prime_generator", 250)?;
+        return session.deliver(server.credentials.as_ref(), email);
+    }
+    if server.credentials.is_some() {
+        return Err(Error::new(ErrorKind::PermissionDenied, "The SMTP server doesn't offer STARTTLS; refusing to send credentials in the clear"));
+    }
+    session.deliver(None, email)
+}
+
+// One conversation with a server, over TCP or TLS
+struct Session<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> Session<S> {
+    fn new(stream: S) -> Session<S> {
+        Session { stream: BufReader::new(stream) }
+    }
+
+    // Function to authenticate if there are credentials, then send the
+    // envelope and the message and say goodbye
+    fn deliver(&mut self, credentials: Option<&(String, String)>, email: &Email) -> Result<()> {
+        if let Some((user, password)) = credentials {
+            let token = to_base64(format!("\0{}\0{}", user, password).as_bytes());
+            self.command(&format!("AUTH PLAIN {}", token), 235)?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", email.from), 250)?;
+        for to in &email.to {
+            self.command(&format!("RCPT TO:<{}>", to), 250)?;
+        }
+        self.command("DATA", 354)?;
+        // Lines starting with a dot get another one, so none ends the data early
+        let mut data = String::new();
+        for line in email.to_message().split_inclusive("\r\n") {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+        }
+        data.push('.');
+        self.command(&data, 250)?;
+        self.command("QUIT", 221)?;
+        Ok(())
+    }
+
+    // Function to send a command line and read its reply, failing unless the
+    // reply has the expected code
+    fn command(&mut self, line: &str, code: u16) -> Result<Vec<String>> {
+        self.stream.get_mut().write_all(format!("{}\r\n", line).as_bytes())?;
+        self.stream.get_mut().flush()?;
+        self.expect(code)
+    }
+
+    // Function to read a possibly multiline reply, returning the text of each
+    // line after the code
+    fn expect(&mut self, code: u16) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "The SMTP server closed the connection"));
+            }
+            let line = line.trim_end();
+            let (reply, rest) = line.split_at(line.len().min(3));
+            if reply.parse::<u16>().ok() != Some(code) {
+                return Err(Error::other(format!("SMTP server replied {} (expected {})", line, code)));
+            }
+            lines.push(rest.get(1..).unwrap_or("").to_string());
+            if !rest.starts_with('-') {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+// Function to make every line break of `text` a CRLF
+fn crlf(text: &str) -> String {
+    text.lines().collect::<Vec<_>>().join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn parses_servers() {
+        let server = SmtpServer::from_env("mail.example.com").unwrap();
+        assert_eq!((server.host.as_str(), server.port), ("mail.example.com", 587));
+        assert_eq!(SmtpServer::from_env("mail.example.com:465").unwrap().port, 465);
+        assert!(SmtpServer::from_env("mail.example.com:smtp").is_err());
+        assert!(SmtpServer::from_env(":25").is_err());
+    }
+
+    #[test]
+    fn sends_a_message_with_an_attachment() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Fake server without STARTTLS accepting everything
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut received = String::new();
+            stream.write_all(b"220 localhost ESMTP\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                received.push_str(&line);
+                let reply: &[u8] = match line.trim_end() {
+                    "." if in_data => {
+                        in_data = false;
+                        b"250 Queued\r\n"
+                    }
+                    _ if in_data => continue,
+                    "EHLO prime_generator" => b"250-localhost\r\n250 8BITMIME\r\n",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 Go ahead\r\n"
+                    }
+                    "QUIT" => b"221 Bye\r\n",
+                    _ => b"250 OK\r\n",
+                };
+                stream.write_all(reply).unwrap();
+            }
+            received
+        });
+
+        let email = Email {
+            from: "primes@example.com".to_string(),
+            to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            subject: "Run finished".to_string(),
+            body: "Done.\n.hidden line".to_string(),
+            attachments: vec![("x.csv.manifest.json".to_string(), b"{}".to_vec())],
+        };
+        let local = SmtpServer { host: "127.0.0.1".to_string(), port, credentials: None };
+        send(&local, &email).unwrap();
+        let received = server.join().unwrap();
+        assert!(received.starts_with("EHLO prime_generator\r\nMAIL FROM:<primes@example.com>\r\nRCPT TO:<a@example.com>\r\nRCPT TO:<b@example.com>\r\nDATA\r\n"), "{}", received);
+        assert!(received.contains("\r\nDone.\r\n..hidden line\r\n"), "{}", received);
+        assert!(received.contains("filename=\"x.csv.manifest.json\"\r\nContent-Transfer-Encoding: base64\r\n\r\ne30=\r\n"), "{}", received);
+        assert!(received.ends_with("--=_prime_generator_part--\r\n.\r\nQUIT\r\n"), "{}", received);
+
+        // Credentials never go out over a plain connection
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"220 localhost ESMTP\r\n250 localhost\r\n").unwrap();
+            let mut rest = String::new();
+            let _ = stream.read_to_string(&mut rest);
+            rest
+        });
+        let credentials = Some(("user".to_string(), "secret".to_string()));
+        let error = send(&SmtpServer { host: "127.0.0.1".to_string(), port, credentials }, &email).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert!(!server.join().unwrap().contains("AUTH"));
+    }
+}
@@ -0,0 +1,338 @@
+// Primality tests for numbers of special forms, too large for the machine-sized
+// tests in `math`, that have fast proofs thanks to their shape.
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::math::{is_prime, jacobi, next_prime, pow_mod};
+use crate::primality::{probable_prime, Primality};
+
+/// Returns `k·2^n + 1`.
+pub fn proth_number(k: u64, n: u32) -> BigInt {
+    (BigInt::from(k) << n) + 1
+}
+
+/// Tests the Proth number `N = k·2^n + 1` with Proth's theorem: `N` is prime
+/// if and only if `a^((N-1)/2) ≡ -1 (mod N)` for a quadratic non-residue `a`.
+/// Returns `None` unless `k` is odd and `k < 2^n`, the conditions under which
+/// the theorem applies.
+pub fn proth_test(k: u64, n: u32) -> Option<bool> {
+    if k.is_multiple_of(2) || n == 0 || (n < 64 && k >= 1 << n) {
+        return None;
+    }
+    let number = proth_number(k, n);
+    let minus_one = &number - 1u32;
+    // A square has no non-residues to use, and isn't prime
+    if number.sqrt().pow(2) == number {
+        return Some(false);
+    }
+    let mut a = 3;
+    loop {
+        if BigInt::from(a) >= number {
+            // Only 3 = 1·2^1 + 1 and 5 = 1·2^2 + 1 get here
+            return Some(true);
+        }
+        match jacobi_small(a, &number) {
+            0 => return Some(false),
+            -1 => return Some(BigInt::from(a).modpow(&(&minus_one >> 1), &number) == minus_one),
+            _ => a = next_prime(a as u128 + 1).and_then(|a| a.to_u64()).expect("Non-residue search overflowed"),
+        }
+    }
+}
+
+/// What is known about a Fermat number `F_n = 2^(2^n) + 1` without testing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownFermat {
+    /// `F_0` to `F_4`, the only known Fermat primes.
+    Prime,
+    /// Composite, with this known prime factor.
+    Factor(u128),
+    /// Proven composite by Pépin's test, with no factor known.
+    Composite,
+}
+
+// A known prime factor of F_5 to F_24, or 0 if none is known
+const FERMAT_FACTORS: [u128; 20] = [
+    641,
+    274_177,
+    59_649_589_127_497_217,
+    1_238_926_361_552_897,
+    2_424_833,
+    45_592_577,
+    319_489,
+    114_689,
+    2_710_954_639_361,
+    0,
+    1_214_251_009,
+    825_753_601,
+    31_065_037_602_817,
+    13_631_489,
+    70_525_124_609,
+    0,
+    4_485_296_422_913,
+    0,
+    167_772_161,
+    0,
+];
+
+/// Returns `F_n = 2^(2^n) + 1`.
+pub fn fermat_number(n: u32) -> BigInt {
+    (BigInt::from(1) << (1usize << n)) + 1
+}
+
+/// Returns the number of decimal digits of `F_n`, which is never a power of
+/// ten. Exact up to `n = 40`.
+pub fn fermat_digits(n: u32) -> u64 {
+    (2f64.powi(n as i32) * std::f64::consts::LOG10_2).floor() as u64 + 1
+}
+
+/// Returns what is known about `F_n` for `n <= 24`, the Fermat numbers whose
+/// status is settled.
+pub fn known_fermat(n: u32) -> Option<KnownFermat> {
+    match n {
+        0..=4 => Some(KnownFermat::Prime),
+        5..=24 => match FERMAT_FACTORS[n as usize - 5] {
+            0 => Some(KnownFermat::Composite),
+            factor => Some(KnownFermat::Factor(factor)),
+        },
+        _ => None,
+    }
+}
+
+/// Tests `F_n` for primality with Pépin's test: for `n >= 1`, `F_n` is prime
+/// if and only if `3^((F_n - 1)/2) ≡ -1 (mod F_n)`. This takes `2^n - 1`
+/// modular squarings of `2^n`-bit numbers, so it is only feasible for small `n`.
+pub fn pepin_test(n: u32) -> bool {
+    if n == 0 {
+        // F_0 = 3, the base of the test itself
+        return true;
+    }
+    let number = fermat_number(n);
+    let minus_one = &number - 1u32;
+    BigInt::from(3).modpow(&(&minus_one >> 1), &number) == minus_one
+}
+
+/// The test [`lucas_lehmer`] runs, which proves Mersenne primes.
+pub const LUCAS_LEHMER_TEST: &str = "Lucas-Lehmer";
+
+/// Returns the Mersenne number `M_p = 2^p - 1`.
+pub fn mersenne_number(p: u32) -> BigInt {
+    (BigInt::from(1) << p) - 1
+}
+
+/// Looks for a factor of `M_p = 2^p - 1`, for a prime `p`, among the
+/// numbers up to `limit` that its prime factors must look like:
+/// `q = 2kp + 1` with `q ≡ ±1 (mod 8)`. Returns the smallest factor found,
+/// which is prime. A factor rules `M_p` out for a few modular powers of
+/// 64-bit numbers, far cheaper than [`lucas_lehmer`].
+pub fn mersenne_factor(p: u32, limit: u64) -> Option<u64> {
+    let step = 2 * p as u64;
+    let mut q = step + 1;
+    while q <= limit {
+        // M_p is no factor of itself
+        if p < 64 && q as u128 >= (1u128 << p) - 1 {
+            return None;
+        }
+        if matches!(q % 8, 1 | 7) && pow_mod(2, p as u128, q as u128) == 1 {
+            return Some(q);
+        }
+        q = q.checked_add(step)?;
+    }
+    None
+}
+
+/// Tests `M_p = 2^p - 1` with the Lucas-Lehmer test: for an odd prime `p`,
+/// `M_p` is prime if and only if `s_(p-2) ≡ 0 (mod M_p)`, where `s_0 = 4`
+/// and `s_(i+1) = s_i^2 - 2`. Since `M_a` divides `M_ab`, composite `p` give
+/// composite `M_p` and are reported without a test.
+pub fn lucas_lehmer(p: u32) -> bool {
+    if !is_prime(p as u128) {
+        return false;
+    }
+    if p == 2 {
+        // M_2 = 3, which the recurrence doesn't cover
+        return true;
+    }
+    let number = mersenne_number(p);
+    let mut s = BigInt::from(4);
+    for _ in 0..p - 2 {
+        s = (&s * &s - 2) % &number;
+    }
+    s.is_zero()
+}
+
+/// Returns the base-`base` repunit `R_n = (base^n - 1) / (base - 1)`, written
+/// as `n` ones in that base.
+pub fn repunit(base: u32, n: u32) -> BigInt {
+    (BigInt::from(base).pow(n) - 1) / (base - 1)
+}
+
+/// Tests the repunit `R_n` in `base` (at least 2) with [`probable_prime`].
+/// Since `R_a` divides `R_ab`, only prime `n` can give a prime, and other
+/// `n` are reported composite without a test.
+pub fn repunit_test(base: u32, n: u32) -> Primality {
+    if !is_prime(n as u128) {
+        return Primality::Composite;
+    }
+    probable_prime(&repunit(base, n))
+}
+
+/// Yields `(n, n!)` for each of the increasing `ns`, computing every
+/// factorial from the previous one.
+pub fn factorials<I: IntoIterator<Item = u32>>(ns: I) -> impl Iterator<Item = (u32, BigInt)> {
+    let (mut last, mut product) = (0, BigInt::from(1));
+    ns.into_iter().map(move |n| {
+        assert!(n >= last, "Factorials must be requested in increasing order");
+        for factor in last + 1..=n {
+            product *= factor;
+        }
+        last = n;
+        (n, product.clone())
+    })
+}
+
+/// Yields `(p, p#)` for the primes `p` among the increasing `ns`, where the
+/// primorial `p#` is the product of the primes up to `p`, computing every
+/// primorial from the previous one.
+pub fn primorials<I: IntoIterator<Item = u32>>(ns: I) -> impl Iterator<Item = (u32, BigInt)> {
+    let (mut last, mut product) = (0, BigInt::from(1));
+    ns.into_iter().filter(|&n| is_prime(n as u128)).map(move |p| {
+        assert!(p >= last, "Primorials must be requested in increasing order");
+        for factor in (last + 1..=p).filter(|&m| is_prime(m as u128)) {
+            product *= factor;
+        }
+        last = p;
+        (p, product.clone())
+    })
+}
+
+// Function to compute the Jacobi symbol (a/n) of a small odd prime a and an
+// odd n > 0, by quadratic reciprocity: (a/n) = (n mod a / a), negated when
+// both a and n are 3 mod 4
+fn jacobi_small(a: u64, n: &BigInt) -> i32 {
+    let residue = (n % a).to_i128().expect("Residue below a fits into i128");
+    let symbol = jacobi(residue, a as u128);
+    let n_mod_4 = (n % 4u32).to_u32().unwrap_or(0);
+    if a % 4 == 3 && n_mod_4 == 3 {
+        -symbol
+    } else {
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proth_test_matches_miller_rabin() {
+        for n in 1..=100 {
+            for k in (1..(1u64 << n.min(63)).min(300)).step_by(2) {
+                let expected = is_prime(proth_number(k, n).to_u128().unwrap());
+                assert_eq!(proth_test(k, n), Some(expected), "{}·2^{}+1", k, n);
+            }
+        }
+    }
+
+    #[test]
+    fn proth_test_proves_known_primes() {
+        // Fermat primes, and Proth primes from OEIS A002253, A002254 and A032353
+        for (k, n) in [(1, 8), (1, 16), (3, 189), (5, 1947), (7, 616)] {
+            assert_eq!(proth_test(k, n), Some(true), "{}·2^{}+1", k, n);
+        }
+        // F_5 = 641 · 6700417
+        assert_eq!(proth_test(1, 32), Some(false));
+        assert_eq!(proth_test(3, 190), Some(false));
+    }
+
+    #[test]
+    fn pepin_test_finds_the_fermat_primes() {
+        for n in 0..=12 {
+            assert_eq!(pepin_test(n), n <= 4, "F_{}", n);
+            assert_eq!(fermat_digits(n), fermat_number(n).to_string().len() as u64, "F_{}", n);
+        }
+        assert_eq!(fermat_digits(14), 4933);
+    }
+
+    #[test]
+    fn known_fermat_factors_divide() {
+        for n in 5..=24 {
+            if let Some(KnownFermat::Factor(q)) = known_fermat(n) {
+                // q divides F_n exactly when 2^(2^n) ≡ -1 (mod q)
+                assert_eq!(crate::math::pow_mod(2, 1 << n, q), q - 1, "F_{}", n);
+                assert!(is_prime(q), "{}", q);
+            }
+        }
+        assert_eq!(known_fermat(14), Some(KnownFermat::Composite));
+        assert_eq!(known_fermat(25), None);
+    }
+
+    #[test]
+    fn trial_factors_mersenne_numbers() {
+        assert_eq!(mersenne_factor(11, 1 << 20), Some(23));
+        assert_eq!(mersenne_factor(23, 1 << 20), Some(47));
+        assert_eq!(mersenne_factor(29, 1 << 20), Some(233));
+        // M_67 = 193707721 · 761838257287, Cole's factorization
+        assert_eq!(mersenne_factor(67, 1 << 28), Some(193_707_721));
+        assert_eq!(mersenne_factor(67, 193_707_720), None);
+        // Primes have no factor to find, however far the search goes
+        assert_eq!(mersenne_factor(3, u64::MAX), None);
+        assert_eq!(mersenne_factor(31, 1 << 26), None);
+        assert_eq!(mersenne_factor(127, 1 << 24), None);
+    }
+
+    #[test]
+    fn lucas_lehmer_finds_the_mersenne_primes() {
+        let exponents: Vec<u32> = (1..=130).filter(|&p| lucas_lehmer(p)).collect();
+        assert_eq!(exponents, [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127]);
+        assert!(lucas_lehmer(521) && !lucas_lehmer(523));
+        assert_eq!(mersenne_number(7), BigInt::from(127));
+    }
+
+    #[test]
+    fn finds_known_repunit_primes() {
+        // OEIS A004023 (base 10), A000043 (base 2: the Mersenne exponents) and A028491 (base 3)
+        let found = |base| (1..=100).filter(|&n| repunit_test(base, n) != Primality::Composite).collect::<Vec<u32>>();
+        assert_eq!(found(10), [2, 19, 23]);
+        assert_eq!(found(2), [2, 3, 5, 7, 13, 17, 19, 31, 61, 89]);
+        assert_eq!(found(3), [3, 7, 13, 71]);
+        assert_eq!(repunit(10, 4), BigInt::from(1111));
+        assert_eq!(repunit_test(10, 317), Primality::ProbablePrime);
+    }
+
+    #[test]
+    fn finds_known_factorial_and_primorial_primes() {
+        // OEIS A002981 and A002982: n such that n! + 1 and n! - 1 are prime
+        let (mut plus, mut minus) = (Vec::new(), Vec::new());
+        for (n, factorial) in factorials(0..100) {
+            if probable_prime(&(&factorial + 1)) != Primality::Composite {
+                plus.push(n);
+            }
+            if probable_prime(&(&factorial - 1)) != Primality::Composite {
+                minus.push(n);
+            }
+        }
+        assert_eq!(plus, [0, 1, 2, 3, 11, 27, 37, 41, 73, 77]);
+        assert_eq!(minus, [3, 4, 6, 7, 12, 14, 30, 32, 33, 38, 94]);
+
+        // OEIS A005234 and A006794: primes p such that p# + 1 and p# - 1 are prime
+        let (mut plus, mut minus) = (Vec::new(), Vec::new());
+        for (p, primorial) in primorials(0..400) {
+            if probable_prime(&(&primorial + 1)) != Primality::Composite {
+                plus.push(p);
+            }
+            if probable_prime(&(&primorial - 1)) != Primality::Composite {
+                minus.push(p);
+            }
+        }
+        assert_eq!(plus, [2, 3, 5, 7, 11, 31, 379]);
+        assert_eq!(minus, [3, 5, 11, 13, 41, 89, 317, 337]);
+    }
+
+    #[test]
+    fn proth_test_rejects_non_proth_numbers() {
+        assert_eq!(proth_test(2, 5), None);
+        assert_eq!(proth_test(33, 5), None);
+        assert_eq!(proth_test(1, 0), None);
+    }
+}
@@ -0,0 +1,42 @@
+// Async `Stream` of prime records for tokio-based services.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::record::PrimeRecord;
+use crate::sieve::for_each_prime;
+
+// Records buffered ahead of the consumer before the producer blocks
+const STREAM_BUFFER: usize = 1024;
+
+/// Stream of the primes in a range, returned by [`prime_stream`].
+pub struct PrimeStream {
+    receiver: mpsc::Receiver<PrimeRecord>,
+}
+
+impl Stream for PrimeStream {
+    type Item = PrimeRecord;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<PrimeRecord>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Streams a record for every prime in `[start, end]` in increasing order.
+///
+/// Primes are sieved on a background thread that stays at most a small buffer
+/// ahead of the consumer, so slow consumers apply backpressure instead of
+/// accumulating results. Dropping the stream stops the producer.
+pub fn prime_stream(start: u64, end: u64) -> impl Stream<Item = PrimeRecord> {
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    thread::spawn(move || {
+        for_each_prime(start, end, |prime| {
+            sender.blocking_send(PrimeRecord::from_prime(prime as u128)).is_ok()
+        });
+    });
+    PrimeStream { receiver }
+}
@@ -0,0 +1,83 @@
+// The systemd notification protocol (sd_notify(3)), for runs under a
+// `Type=notify` service: state changes are datagrams of `KEY=value` lines sent
+// to the socket systemd names in `$NOTIFY_SOCKET`.
+
+use std::env;
+use std::io::{Error, ErrorKind, Result};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Sends `state`, newline-separated assignments such as `READY=1` or
+/// `STATUS=...`, to the service manager. Returns whether it was sent, which
+/// it isn't when the process doesn't run under systemd.
+pub fn notify(state: &str) -> Result<bool> {
+    match env::var("NOTIFY_SOCKET") {
+        Ok(socket) => notify_socket(&socket, state).map(|()| true),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Sends `state` to the notification socket at `socket`, a path or, starting
+/// with `@`, a name in the abstract namespace.
+pub fn notify_socket(socket: &str, state: &str) -> Result<()> {
+    let address = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None if socket.starts_with('/') => SocketAddr::from_pathname(socket)?,
+        None => return Err(Error::new(ErrorKind::InvalidInput, format!("Unsupported notification socket: {}", socket))),
+    };
+    let sent = UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)?;
+    match sent == state.len() {
+        true => Ok(()),
+        false => Err(Error::new(ErrorKind::WriteZero, "The notification was cut short")),
+    }
+}
+
+/// Returns how often systemd expects `WATCHDOG=1` keep-alives from this
+/// process, or `None` if the service has no watchdog.
+pub fn watchdog_interval() -> Option<Duration> {
+    watchdog_interval_from(env::var("WATCHDOG_USEC").ok().as_deref(), env::var("WATCHDOG_PID").ok().as_deref(), std::process::id())
+}
+
+// Function to read the watchdog settings, which only apply to the process
+// `WATCHDOG_PID` names when it is set
+fn watchdog_interval_from(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse() != Ok(own_pid)) {
+        return None;
+    }
+    usec?.parse().ok().filter(|&usec| usec > 0).map(Duration::from_micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_notifications() {
+        let path = env::temp_dir().join(format!("prime_generator_notify_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+        notify_socket(path.to_str().unwrap(), "READY=1\nSTATUS=Sieving").unwrap();
+        let mut buf = [0u8; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=Sieving");
+
+        let name = format!("prime_generator_notify_{}", std::process::id());
+        let listener = UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(&name).unwrap()).unwrap();
+        notify_socket(&format!("@{}", name), "WATCHDOG=1").unwrap();
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"WATCHDOG=1");
+
+        assert_eq!(notify_socket("relative.sock", "READY=1").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn reads_watchdog_settings() {
+        assert_eq!(watchdog_interval_from(Some("30000000"), None, 7), Some(Duration::from_secs(30)));
+        assert_eq!(watchdog_interval_from(Some("30000000"), Some("7"), 7), Some(Duration::from_secs(30)));
+        assert_eq!(watchdog_interval_from(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(watchdog_interval_from(Some("0"), None, 7), None);
+        assert_eq!(watchdog_interval_from(None, None, 7), None);
+    }
+}
@@ -0,0 +1,140 @@
+// Aligned tables for results shown on a terminal, optionally colored with
+// ANSI escapes. Piped or redirected output stays CSV; this is only for people.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// How a cell is highlighted when color is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Bold,
+    Green,
+    Red,
+    Dim,
+}
+
+impl Style {
+    // Function to return the ANSI escape that starts the style
+    fn escape(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Bold => "\x1b[1m",
+            Style::Green => "\x1b[32m",
+            Style::Red => "\x1b[31m",
+            Style::Dim => "\x1b[2m",
+        }
+    }
+}
+
+/// Which side of its column a cell hugs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table of text cells under a header row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    headers: Vec<(String, Align)>,
+    rows: Vec<Vec<(String, Style)>>,
+}
+
+impl Table {
+    /// Starts a table with the given column headers and alignments.
+    pub fn new<I: IntoIterator<Item = (S, Align)>, S: Into<String>>(columns: I) -> Table {
+        Table { headers: columns.into_iter().map(|(header, align)| (header.into(), align)).collect(), rows: Vec::new() }
+    }
+
+    /// Adds a row; missing cells are left blank and extra ones dropped.
+    pub fn row<I: IntoIterator<Item = (S, Style)>, S: Into<String>>(&mut self, cells: I) {
+        let mut row: Vec<(String, Style)> = cells.into_iter().map(|(text, style)| (text.into(), style)).take(self.headers.len()).collect();
+        row.resize(self.headers.len(), (String::new(), Style::Plain));
+        self.rows.push(row);
+    }
+
+    /// Tells whether the table has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Draws the table with box-drawing lines, coloring the cells (and
+    /// bolding the headers) if `color`.
+    pub fn render(&self, color: bool) -> String {
+        let width = |text: &str| text.chars().count();
+        let widths: Vec<usize> = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, (header, _))| self.rows.iter().map(|row| width(&row[i].0)).fold(width(header), usize::max))
+            .collect();
+        let rule = |left: &str, fill: &str, middle: &str, right: &str| {
+            let segments: Vec<String> = widths.iter().map(|&w| fill.repeat(w + 2)).collect();
+            format!("{}{}{}\n", left, segments.join(middle), right)
+        };
+        let line = |cells: &mut dyn Iterator<Item = (&str, Style)>| {
+            let mut line = String::from("│");
+            for (i, (text, style)) in cells.enumerate() {
+                let padding = " ".repeat(widths[i] - width(text));
+                let text = match color && style != Style::Plain {
+                    true => format!("{}{}\x1b[0m", style.escape(), text),
+                    false => text.to_string(),
+                };
+                match self.headers[i].1 {
+                    Align::Left => line.push_str(&format!(" {}{} │", text, padding)),
+                    Align::Right => line.push_str(&format!(" {}{} │", padding, text)),
+                }
+            }
+            line.push('\n');
+            line
+        };
+
+        let mut table = rule("┌", "─", "┬", "┐");
+        table.push_str(&line(&mut self.headers.iter().map(|(header, _)| (header.as_str(), Style::Bold))));
+        table.push_str(&rule("╞", "═", "╪", "╡"));
+        for row in &self.rows {
+            table.push_str(&line(&mut row.iter().map(|(text, style)| (text.as_str(), *style))));
+        }
+        table.push_str(&rule("└", "─", "┴", "┘"));
+        table
+    }
+}
+
+/// Tells whether standard output is a terminal a person is reading.
+pub fn stdout_is_terminal() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// Tells whether to color terminal output: not if `disabled` (`--no-color`),
+/// `NO_COLOR` is set, `TERM` is `dumb` or standard output isn't a terminal.
+pub fn color_enabled(disabled: bool) -> bool {
+    !disabled && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && env::var("TERM").map_or(true, |term| term != "dumb") && stdout_is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_columns() {
+        let mut table = Table::new([("number", Align::Right), ("prime", Align::Left)]);
+        assert!(table.is_empty());
+        table.row([("97", Style::Plain), ("true", Style::Green)]);
+        table.row([("1000000007", Style::Plain), ("false", Style::Red), ("extra", Style::Plain)]);
+        table.row([("4", Style::Plain)]);
+        assert_eq!(
+            table.render(false),
+            "┌────────────┬───────┐\n\
+             │     number │ prime │\n\
+             ╞════════════╪═══════╡\n\
+             │         97 │ true  │\n\
+             │ 1000000007 │ false │\n\
+             │          4 │       │\n\
+             └────────────┴───────┘\n"
+        );
+        let colored = table.render(true);
+        assert!(colored.contains("│ \x1b[32mtrue\x1b[0m  │"), "{}", colored);
+        assert!(colored.contains("\x1b[1mnumber\x1b[0m"), "{}", colored);
+    }
+}
@@ -0,0 +1,122 @@
+// OpenTelemetry instrumentation: spans around segment processing, flushes
+// and uploads, plus counters of segments and primes, exported over OTLP/HTTP
+// once `init` has been called. Without the `otel` feature every hook here is
+// a no-op, so the engine calls them unconditionally.
+
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    global,
+    metrics::Counter,
+    trace::{Span as _, Tracer},
+    KeyValue, Value,
+};
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::SdkTracerProvider, Resource};
+#[cfg(feature = "otel")]
+use std::io::{Error, Result};
+#[cfg(feature = "otel")]
+use std::sync::OnceLock;
+
+// Name of the tracer, meter and service in exported telemetry
+#[cfg(feature = "otel")]
+const SCOPE: &str = "prime_generator";
+
+/// Exports spans and metrics to an OTLP/HTTP collector until dropped, which
+/// flushes whatever is still buffered.
+#[cfg(feature = "otel")]
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+/// Starts exporting to the OTLP/HTTP collector at `endpoint` (such as
+/// `http://localhost:4318`), as the global tracer and meter providers.
+#[cfg(feature = "otel")]
+pub fn init(endpoint: &str) -> Result<Telemetry> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let resource = Resource::builder().with_service_name(SCOPE).build();
+    let spans = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint))
+        .build()
+        .map_err(Error::other)?;
+    let metrics = MetricExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/metrics", endpoint))
+        .build()
+        .map_err(Error::other)?;
+    let tracer_provider = SdkTracerProvider::builder().with_batch_exporter(spans).with_resource(resource.clone()).build();
+    let meter_provider = SdkMeterProvider::builder().with_periodic_exporter(metrics).with_resource(resource).build();
+    global::set_tracer_provider(tracer_provider.clone());
+    global::set_meter_provider(meter_provider.clone());
+    Ok(Telemetry { tracer_provider, meter_provider })
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        // Export errors can't be reported here and shouldn't fail a finished run
+        let _ = self.tracer_provider.shutdown();
+        let _ = self.meter_provider.shutdown();
+    }
+}
+
+/// A trace span covering one phase of work, ended when dropped.
+pub struct Span {
+    #[cfg(feature = "otel")]
+    inner: opentelemetry::global::BoxedSpan,
+}
+
+impl Span {
+    /// Starts a span named `name` with numeric attributes.
+    #[cfg(feature = "otel")]
+    pub fn start(name: &'static str, attributes: &[(&'static str, u128)]) -> Span {
+        let attributes = attributes.iter().map(|&(key, value)| {
+            // Wider values than i64 are exported as strings
+            let value = i64::try_from(value).map(Value::I64).unwrap_or_else(|_| Value::String(value.to_string().into()));
+            KeyValue::new(key, value)
+        });
+        let tracer = global::tracer(SCOPE);
+        Span { inner: tracer.span_builder(name).with_attributes(attributes).start(&tracer) }
+    }
+
+    /// Starts a span named `name` with numeric attributes.
+    #[cfg(not(feature = "otel"))]
+    pub fn start(_name: &'static str, _attributes: &[(&'static str, u128)]) -> Span {
+        Span {}
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.inner.end();
+    }
+}
+
+// Counters, created on first use so they bind to the provider `init` installed
+#[cfg(feature = "otel")]
+fn counters() -> &'static (Counter<u64>, Counter<u64>) {
+    static COUNTERS: OnceLock<(Counter<u64>, Counter<u64>)> = OnceLock::new();
+    COUNTERS.get_or_init(|| {
+        let meter = global::meter(SCOPE);
+        (
+            meter.u64_counter("prime_generator.segments").with_description("Segments sieved").build(),
+            meter.u64_counter("prime_generator.primes").with_description("Primes written").build(),
+        )
+    })
+}
+
+/// Counts a processed segment.
+pub fn record_segment() {
+    #[cfg(feature = "otel")]
+    counters().0.add(1, &[]);
+}
+
+/// Counts primes handed to the output.
+pub fn record_primes(_count: usize) {
+    #[cfg(feature = "otel")]
+    counters().1.add(_count as u64, &[]);
+}
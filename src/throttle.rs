@@ -0,0 +1,290 @@
+// Pausing workers to keep the machine usable during long background runs.
+//
+// A `Throttle` is checked by the workers between units of work. On Linux,
+// `watch_load` drives it from /proc and /sys: each second it measures the CPU
+// time other processes used, and lets the workers run for the share of the
+// next second that keeps the whole machine under the load limit, pausing them
+// outright while the CPU is hotter than allowed or, if asked, while someone
+// uses the machine.
+
+// Only Linux has the load monitor; the helpers stay testable everywhere
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::{fs, sync::Arc, thread, thread::JoinHandle, time::SystemTime};
+
+/// A switch pausing a run's workers, shared with whatever decides when to pause.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    paused: AtomicBool,
+    lock: Mutex<()>,
+    resumed: Condvar,
+}
+
+impl Throttle {
+    pub fn new() -> Throttle {
+        Throttle::default()
+    }
+
+    /// Stops workers as they finish their current unit of work.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Lets paused workers carry on.
+    pub fn resume(&self) {
+        let _lock = self.lock.lock().unwrap();
+        self.paused.store(false, Ordering::SeqCst);
+        self.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Blocks the calling worker while the throttle is paused.
+    pub fn wait(&self) {
+        if !self.is_paused() {
+            return;
+        }
+        let mut lock = self.lock.lock().unwrap();
+        while self.is_paused() {
+            lock = self.resumed.wait(lock).unwrap();
+        }
+    }
+}
+
+// How often the load is measured and the workers' share of time decided
+#[cfg(target_os = "linux")]
+const PERIOD: Duration = Duration::from_secs(1);
+
+// Degrees the CPU must cool below the limit before paused workers resume
+const COOLDOWN_DEGREES: f64 = 5.0;
+
+// Time without terminal input before the machine counts as idle, as BOINC's default
+const IDLE_AFTER: Duration = Duration::from_secs(180);
+
+// Share of the machine other processes may use while it counts as idle
+const IDLE_MAX_LOAD: f64 = 0.25;
+
+/// How busy and hot the machine may get before workers are paused.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadLimit {
+    /// Share of the machine's total CPU time, from 0 to 1, that everything
+    /// including the run may use.
+    pub max_load: Option<f64>,
+    /// Hottest thermal zone reading, in degrees Celsius, before pausing.
+    pub max_temperature: Option<f64>,
+    /// Whether to run only while the machine is idle: no terminal input for
+    /// a few minutes, and little CPU use by other processes.
+    pub only_when_idle: bool,
+}
+
+/// Keeps the machine within `limit` by pausing and resuming `throttle` from
+/// a background thread until the process exits.
+#[cfg(target_os = "linux")]
+pub fn watch_load(throttle: Arc<Throttle>, limit: LoadLimit) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last = read_cpu_times();
+        // The run's share of the machine while running, until it is measured
+        let mut full_share = 1.0;
+        let (mut duty, mut hot) = (1.0, false);
+        loop {
+            if duty > 0.0 {
+                throttle.resume();
+                thread::sleep(PERIOD.mul_f64(duty));
+            }
+            if duty < 1.0 {
+                throttle.pause();
+                thread::sleep(PERIOD.mul_f64(1.0 - duty));
+            }
+            let now = read_cpu_times();
+            let mut others = 0.0;
+            if let (Some(last), Some(now)) = (last, now) {
+                let own;
+                (others, own) = last.shares_until(&now);
+                if duty > 0.0 && own > 0.0 {
+                    full_share = (own / duty).min(1.0);
+                }
+                duty = limit.max_load.map_or(1.0, |max_load| duty_cycle(max_load, others, full_share));
+            }
+            last = now;
+            if let Some(max_temperature) = limit.max_temperature {
+                hot = overheated(hot, max_cpu_temperature(), max_temperature);
+            }
+            if hot || (limit.only_when_idle && !idle(others, last_input_age())) {
+                duty = 0.0;
+            }
+        }
+    })
+}
+
+// Function to decide the share of the next period workers may run: what's
+// left under the limit after other processes, over what the run uses running
+// flat out
+fn duty_cycle(max_load: f64, others: f64, full_share: f64) -> f64 {
+    if full_share <= 0.0 {
+        return 1.0;
+    }
+    ((max_load - others) / full_share).clamp(0.0, 1.0)
+}
+
+// Function to tell whether the machine is idle: other processes barely use
+// the CPU, and nobody typed for a while (or there are no terminals)
+fn idle(others: f64, input_age: Option<Duration>) -> bool {
+    others < IDLE_MAX_LOAD && input_age.is_none_or(|age| age >= IDLE_AFTER)
+}
+
+// Function to track whether the CPU is too hot, with some hysteresis so the
+// workers don't flap around the limit
+fn overheated(hot: bool, temperature: Option<f64>, max_temperature: f64) -> bool {
+    match temperature {
+        Some(temperature) if hot => temperature > max_temperature - COOLDOWN_DEGREES,
+        Some(temperature) => temperature >= max_temperature,
+        None => false,
+    }
+}
+
+// Cumulative CPU time in clock ticks: busy and total across all CPUs, and
+// this process's own
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+    own: u64,
+}
+
+impl CpuTimes {
+    // Function to split the time since `self` into the shares of the machine
+    // used by other processes and by this one
+    fn shares_until(&self, later: &CpuTimes) -> (f64, f64) {
+        let total = later.total.saturating_sub(self.total);
+        if total == 0 {
+            return (0.0, 0.0);
+        }
+        let own = later.own.saturating_sub(self.own);
+        let others = later.busy.saturating_sub(self.busy).saturating_sub(own);
+        (others as f64 / total as f64, own as f64 / total as f64)
+    }
+}
+
+// Function to sample the machine's and this process's CPU time
+#[cfg(target_os = "linux")]
+fn read_cpu_times() -> Option<CpuTimes> {
+    let (busy, total) = parse_proc_stat(&fs::read_to_string("/proc/stat").ok()?)?;
+    let own = parse_process_stat(&fs::read_to_string("/proc/self/stat").ok()?)?;
+    Some(CpuTimes { busy, total, own })
+}
+
+// Function to read the busy and total ticks from the `cpu` line of /proc/stat;
+// idle and iowait count as idle, guest time is already part of user time
+fn parse_proc_stat(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let ticks: Vec<u64> = line.split_whitespace().skip(1).map(|t| t.parse().ok()).collect::<Option<_>>()?;
+    let total: u64 = ticks.iter().take(8).sum();
+    let idle = ticks.get(3)? + ticks.get(4).unwrap_or(&0);
+    Some((total - idle, total))
+}
+
+// Function to read the user and system ticks from /proc/<pid>/stat, whose
+// fields after the parenthesized command name are space-separated
+fn parse_process_stat(stat: &str) -> Option<u64> {
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 2..)?.split_whitespace().collect();
+    // utime and stime are fields 14 and 15, the 12th and 13th after the name
+    Some(fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?)
+}
+
+// Function to find how long ago someone last typed on a console or terminal,
+// from when its device was last read, as w(1) reports idle time
+#[cfg(target_os = "linux")]
+fn last_input_age() -> Option<Duration> {
+    let consoles = fs::read_dir("/dev")
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().strip_prefix("tty").is_some_and(|n| n.parse::<u32>().is_ok()));
+    let terminals = fs::read_dir("/dev/pts").into_iter().flatten().flatten();
+    let now = SystemTime::now();
+    consoles
+        .chain(terminals)
+        .filter_map(|entry| entry.metadata().ok()?.accessed().ok())
+        .map(|accessed| now.duration_since(accessed).unwrap_or_default())
+        .min()
+}
+
+// Function to read the hottest thermal zone, in degrees Celsius
+#[cfg(target_os = "linux")]
+fn max_cpu_temperature() -> Option<f64> {
+    fs::read_dir("/sys/class/thermal")
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("temp")).ok()?.trim().parse::<f64>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+        .reduce(f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn pauses_and_resumes_waiting_workers() {
+        let throttle = Arc::new(Throttle::new());
+        throttle.wait();
+        throttle.pause();
+        let worker = {
+            let throttle = Arc::clone(&throttle);
+            thread::spawn(move || throttle.wait())
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!worker.is_finished());
+        throttle.resume();
+        worker.join().unwrap();
+        assert!(!throttle.is_paused());
+    }
+
+    #[test]
+    fn leaves_the_run_what_others_dont_use() {
+        assert_eq!(duty_cycle(0.8, 0.0, 0.5), 1.0);
+        assert_eq!(duty_cycle(0.8, 0.4, 0.8), 0.5);
+        assert_eq!(duty_cycle(0.8, 0.9, 0.5), 0.0);
+        assert_eq!(duty_cycle(0.8, 0.4, 0.0), 1.0);
+
+        assert!(overheated(false, Some(85.0), 85.0));
+        assert!(!overheated(false, Some(84.0), 85.0));
+        assert!(overheated(true, Some(81.0), 85.0));
+        assert!(!overheated(true, Some(80.0), 85.0));
+        assert!(!overheated(true, None, 85.0));
+    }
+
+    #[test]
+    fn runs_only_while_idle() {
+        // Without terminals, only the CPU use of other processes counts
+        assert!(idle(0.1, None));
+        assert!(!idle(IDLE_MAX_LOAD, None));
+        assert!(idle(0.1, Some(Duration::from_secs(600))));
+        assert!(idle(0.1, Some(IDLE_AFTER)));
+        assert!(!idle(0.1, Some(IDLE_AFTER - Duration::from_secs(1))));
+        assert!(!idle(0.1, Some(Duration::from_secs(30))));
+        assert!(!idle(0.5, Some(Duration::from_secs(600))));
+        assert!(!idle(0.5, None));
+    }
+
+    #[test]
+    fn measures_cpu_time() {
+        let stat = "cpu  100 5 50 800 20 3 2 10 4 0\ncpu0 50 2 25 400 10 1 1 5 2 0\nintr 12345\n";
+        assert_eq!(parse_proc_stat(stat), Some((170, 990)));
+        assert_eq!(parse_process_stat("4242 (prime gen) (x)) R 1 2 3 4 5 6 7 8 9 10 30 12 0 0"), Some(42));
+        assert_eq!(parse_process_stat("4242 (short) R 1"), None);
+
+        let before = CpuTimes { busy: 100, total: 1000, own: 20 };
+        let after = CpuTimes { busy: 700, total: 2000, own: 420 };
+        assert_eq!(before.shares_until(&after), (0.2, 0.4));
+        assert_eq!(after.shares_until(&after), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,118 @@
+// Time spent in each phase of a run, collected by scoped timers into
+// process-wide counters so the engine, the sinks and the tool can all add to
+// them without passing anything around. Phases timed on worker threads add up
+// across threads, so together they can exceed the wall-clock time.
+
+use std::io::{Result, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// A phase of a run that is timed on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Enumerating candidates and collecting the primes among them.
+    Candidates,
+    /// Testing candidates, or sieving segments.
+    Primality,
+    /// Computing the powers of each prime.
+    Powers,
+    /// Formatting records for the output, on the writer thread.
+    Serialization,
+    /// Writing formatted records to files.
+    Io,
+    /// Publishing the output.
+    Upload,
+}
+
+const PHASES: [Phase; 6] = [Phase::Candidates, Phase::Primality, Phase::Powers, Phase::Serialization, Phase::Io, Phase::Upload];
+
+// Nanoseconds spent in each phase, indexed like `PHASES`
+static NANOS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+/// Seconds spent in each phase so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PhaseTimes {
+    pub candidates: f64,
+    pub primality: f64,
+    pub powers: f64,
+    pub serialization: f64,
+    pub io: f64,
+    pub upload: f64,
+}
+
+/// Adds the time until it is dropped to a phase.
+pub struct Timer {
+    phase: Phase,
+    started: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        add(self.phase, self.started.elapsed());
+    }
+}
+
+/// Starts timing `phase` until the returned timer is dropped.
+pub fn time(phase: Phase) -> Timer {
+    Timer { phase, started: Instant::now() }
+}
+
+/// Adds `duration` to `phase`.
+pub fn add(phase: Phase, duration: Duration) {
+    NANOS[phase as usize].fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Returns the time spent in `phase` so far.
+pub fn elapsed(phase: Phase) -> Duration {
+    Duration::from_nanos(NANOS[phase as usize].load(Ordering::Relaxed))
+}
+
+/// Returns the time spent in every phase so far.
+pub fn phase_times() -> PhaseTimes {
+    let [candidates, primality, powers, serialization, io, upload] = PHASES.map(|phase| elapsed(phase).as_secs_f64());
+    PhaseTimes { candidates, primality, powers, serialization, io, upload }
+}
+
+/// Counts the time spent writing to the wrapped writer as I/O.
+pub struct TimedWriter<W>(pub W);
+
+impl<W: Write> Write for TimedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let _timer = time(Phase::Io);
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let _timer = time(Phase::Io);
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn adds_up_scoped_timers() {
+        let before = elapsed(Phase::Upload);
+        {
+            let _timer = time(Phase::Upload);
+            thread::sleep(Duration::from_millis(20));
+        }
+        add(Phase::Upload, Duration::from_millis(5));
+        assert!(elapsed(Phase::Upload) - before >= Duration::from_millis(25));
+        assert!(phase_times().upload >= 0.025);
+
+        let before = elapsed(Phase::Io);
+        let mut writer = TimedWriter(Vec::new());
+        writer.write_all(b"2,4,8,16\n").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.0, b"2,4,8,16\n");
+        assert!(elapsed(Phase::Io) > before);
+    }
+}
@@ -0,0 +1,164 @@
+// Append-only file writer that submits writes through io_uring (Linux).
+//
+// Bytes are gathered into buffers of `BUFFER_SIZE`; a full buffer is queued
+// as a write at the file's next offset and the caller carries on without
+// waiting for it. Completions are reaped when every slot is busy and on
+// `finish`, and their buffers reused.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Result, Write};
+use std::mem;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use io_uring::{opcode, types, IoUring};
+
+// Bytes gathered before a write is submitted
+const BUFFER_SIZE: usize = 1 << 20;
+
+// Writes in flight at once
+const QUEUE_DEPTH: u32 = 16;
+
+/// Appends bytes to a file with asynchronous io_uring writes.
+pub struct UringWriter {
+    file: File,
+    ring: IoUring,
+    // File offset of the next write
+    offset: u64,
+    buffer: Vec<u8>,
+    // Submitted buffers with their file offsets, indexed by the entry's user data
+    in_flight: Vec<Option<(u64, Vec<u8>)>>,
+    spare: Vec<Vec<u8>>,
+}
+
+impl UringWriter {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<UringWriter> {
+        let file = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+        let offset = file.metadata()?.len();
+        Ok(UringWriter {
+            file,
+            ring: IoUring::new(QUEUE_DEPTH)?,
+            offset,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            in_flight: (0..QUEUE_DEPTH).map(|_| None).collect(),
+            spare: Vec::new(),
+        })
+    }
+
+    // Function to queue the gathered bytes as a write at the current offset
+    fn submit_buffer(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let slot = match self.in_flight.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                self.reap(1)?;
+                self.in_flight.iter().position(Option::is_none).expect("A write completed")
+            }
+        };
+        let next = self.spare.pop().unwrap_or_else(|| Vec::with_capacity(BUFFER_SIZE));
+        let buffer = mem::replace(&mut self.buffer, next);
+        let (ptr, len) = (buffer.as_ptr(), buffer.len());
+        // Park the buffer before the kernel can see it; moving the Vec keeps its heap allocation in place
+        self.in_flight[slot] = Some((self.offset, buffer));
+        let entry = opcode::Write::new(types::Fd(self.file.as_raw_fd()), ptr, len as u32)
+            .offset(self.offset)
+            .build()
+            .user_data(slot as u64);
+        // Safety: the buffer stays alive in `in_flight` until its completion is reaped
+        if let Err(e) = unsafe { self.ring.submission().push(&entry) } {
+            // Never queued, so the slot can be released
+            self.in_flight[slot] = None;
+            return Err(Error::other(e));
+        }
+        self.offset += len as u64;
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    // Function to wait for at least `min` completions and recycle their buffers
+    fn reap(&mut self, min: usize) -> Result<()> {
+        self.ring.submit_and_wait(min)?;
+        let completed: Vec<(usize, i32)> = self.ring.completion().map(|cqe| (cqe.user_data() as usize, cqe.result())).collect();
+        let mut outcome = Ok(());
+        for (slot, result) in completed {
+            let (offset, mut buffer) = self.in_flight[slot].take().expect("Completion for a submitted write");
+            if result < 0 {
+                outcome = outcome.and(Err(Error::from_raw_os_error(-result)));
+                continue;
+            }
+            // Finish short writes synchronously
+            let written = result as usize;
+            if written < buffer.len() {
+                outcome = outcome.and(self.file.write_all_at(&buffer[written..], offset + written as u64));
+            }
+            buffer.clear();
+            self.spare.push(buffer);
+        }
+        outcome
+    }
+
+    /// Submits the remaining bytes and waits for every write to complete.
+    pub fn finish(&mut self) -> Result<()> {
+        self.submit_buffer()?;
+        while self.in_flight.iter().any(Option::is_some) {
+            self.reap(1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+        let n = bytes.len().min(BUFFER_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&bytes[..n]);
+        if self.buffer.len() == BUFFER_SIZE {
+            self.submit_buffer()?;
+        }
+        Ok(n)
+    }
+
+    // Writes complete in the background; `finish` waits for them
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        // Errors can't be reported here; `finish` reports them
+        if self.finish().is_err() {
+            // Leak buffers the kernel may still be reading rather than free them under it
+            for (_, buffer) in self.in_flight.iter_mut().filter_map(Option::take) {
+                mem::forget(buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn appends_more_buffers_than_slots_in_order() {
+        let path = std::env::temp_dir().join(format!("prime_generator_uring_{}.bin", std::process::id()));
+        fs::write(&path, b"head").unwrap();
+        // Enough full buffers to wrap around every slot more than once
+        let bytes: Vec<u8> = (0..BUFFER_SIZE * (QUEUE_DEPTH as usize * 2 + 1) + 12345).map(|i| (i % 251) as u8).collect();
+        let mut writer = UringWriter::append(&path).unwrap();
+        for piece in bytes.chunks(100_003) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        let written = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&written[..4], b"head");
+        assert!(written[4..] == bytes[..], "contents differ");
+    }
+}
@@ -0,0 +1,140 @@
+// Checks of command-line values that say which argument is wrong, why, and
+// how to fix it, rather than panicking on the first value that doesn't parse.
+
+use std::fmt;
+use std::str::FromStr;
+
+use num_traits::{Bounded, ToPrimitive};
+
+use crate::ranges::parse_number;
+
+/// A command-line value that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidArgument {
+    /// The argument as written on the command line, such as `-s/--start`.
+    pub argument: String,
+    pub reason: String,
+    /// How to fix it, if there is an obvious way.
+    pub hint: Option<String>,
+}
+
+impl InvalidArgument {
+    /// Reports `argument` as invalid for `reason`.
+    pub fn new<R: Into<String>>(argument: &str, reason: R) -> InvalidArgument {
+        InvalidArgument { argument: argument.to_string(), reason: reason.into(), hint: None }
+    }
+
+    /// Adds how to fix it.
+    pub fn hint<H: Into<String>>(mut self, hint: H) -> InvalidArgument {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.argument, self.reason)?;
+        match &self.hint {
+            Some(hint) => write!(f, "; {}", hint),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for InvalidArgument {}
+
+/// Parses a number written as [`parse_number`] accepts that must fit into
+/// `T`, explaining what is wrong with it otherwise.
+pub fn number<T: TryFrom<u128> + Bounded + ToPrimitive>(argument: &str, value: &str) -> Result<T, InvalidArgument> {
+    let value = value.trim();
+    let Some(n) = parse_number(value) else {
+        return Err(unparsable_number(argument, value));
+    };
+    T::try_from(n).map_err(|_| {
+        let max = T::max_value().to_u128().unwrap_or(u128::MAX);
+        InvalidArgument::new(argument, format!("{} is too large", value)).hint(format!("it can be at most {}", max))
+    })
+}
+
+// Function to explain why a value isn't a number parse_number accepts
+fn unparsable_number(argument: &str, value: &str) -> InvalidArgument {
+    const FORMATS: &str = "write it in decimal (1000000), as a power (10^6), in scientific notation (1e6) or as a sum or difference of those (10^9+7)";
+    if value.is_empty() {
+        return InvalidArgument::new(argument, "no number was given").hint(FORMATS);
+    }
+    if value.starts_with('-') {
+        return InvalidArgument::new(argument, format!("{} is negative", value)).hint("it must be 0 or more");
+    }
+    let digits: String = value.chars().filter(|c| !matches!(c, ',' | '_' | '\'' | ' ')).collect();
+    if digits != value && parse_number(&digits).is_some() {
+        return InvalidArgument::new(argument, format!("{} has digit separators", value)).hint(format!("write it as {}", digits));
+    }
+    if value.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'^' | b'e' | b'E' | b'+' | b'-')) {
+        return InvalidArgument::new(argument, format!("{} is larger than 2^128 - 1", value));
+    }
+    InvalidArgument::new(argument, format!("{:?} is not a number", value)).hint(FORMATS)
+}
+
+/// Parses a value with its [`FromStr`] implementation, such as an algorithm
+/// name or a rate, keeping its error as the reason.
+pub fn parsed<T: FromStr>(argument: &str, value: &str) -> Result<T, InvalidArgument>
+where
+    T::Err: fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| InvalidArgument::new(argument, format!("{:?} is invalid: {}", value, e)))
+}
+
+/// Checks that a range given by its start and end arguments isn't reversed,
+/// suggesting they were swapped if it is.
+pub fn range(start_argument: &str, start: (&str, u128), end_argument: &str, end: (&str, u128)) -> Result<(), InvalidArgument> {
+    if start.1 <= end.1 {
+        return Ok(());
+    }
+    let short = |argument: &str| argument.split('/').next().unwrap_or(argument).to_string();
+    let long = |argument: &str| argument.rsplit('-').next().unwrap_or(argument).to_string();
+    Err(InvalidArgument::new(start_argument, format!("{} ({}) must be ≤ {} ({})", long(start_argument), start.0, long(end_argument), end.0))
+        .hint(format!("did you swap {} and {}?", short(start_argument), short(end_argument))))
+}
+
+/// Warnings about a valid range that is probably not what was meant, such
+/// as one with no primes in it.
+pub fn range_warnings(start: u128, end: u128) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if end < 2 {
+        warnings.push(format!("the range {}-{} holds no primes; the smallest prime is 2", start, end));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explains_invalid_numbers() {
+        assert_eq!(number::<u64>("-s/--start", "10^9"), Ok(1_000_000_000));
+        let message = |value: &str| number::<u32>("-n", value).unwrap_err().to_string();
+        assert_eq!(message("1,000,000"), "-n: 1,000,000 has digit separators; write it as 1000000");
+        assert_eq!(message("2^40"), "-n: 2^40 is too large; it can be at most 4294967295");
+        assert_eq!(message("-5"), "-n: -5 is negative; it must be 0 or more");
+        assert_eq!(message("10^40"), "-n: 10^40 is larger than 2^128 - 1");
+        assert!(message("ten").starts_with("-n: \"ten\" is not a number; write it in decimal"));
+        assert_eq!(parsed::<f64>("--max-load", "0.5"), Ok(0.5));
+        assert!(parsed::<f64>("--max-load", "half").unwrap_err().to_string().starts_with("--max-load: \"half\" is invalid: "));
+    }
+
+    #[test]
+    fn catches_swapped_ranges() {
+        assert_eq!(range("-s/--start", ("2", 2), "-e/--end", ("100", 100)), Ok(()));
+        assert_eq!(
+            range("-s/--start", ("10^9", 1_000_000_000), "-e/--end", ("10^6", 1_000_000)).unwrap_err().to_string(),
+            "-s/--start: start (10^9) must be ≤ end (10^6); did you swap -s and -e?"
+        );
+        assert_eq!(
+            range("-s/--start", ("10", 10), "--target", ("5", 5)).unwrap_err().to_string(),
+            "-s/--start: start (10) must be ≤ target (5); did you swap -s and --target?"
+        );
+        assert!(range_warnings(2, 1_000_000).is_empty());
+        assert_eq!(range_warnings(0, 1), vec!["the range 0-1 holds no primes; the smallest prime is 2".to_string()]);
+    }
+}
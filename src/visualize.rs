@@ -0,0 +1,335 @@
+// Rendering of prime visualizations to PNG or SVG images.
+
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use crate::sieve::sieve_range;
+
+type Color = [u8; 3];
+
+const BACKGROUND: Color = [255, 255, 255];
+const PRIME_COLOR: Color = [0, 0, 0];
+
+// Image formats we can write, chosen from the output file extension
+enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    fn from_path(path: &Path) -> Result<ImageFormat> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("svg") => Ok(ImageFormat::Svg),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported image format for {} (expected .png or .svg)", path.display()),
+            )),
+        }
+    }
+}
+
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    color: Color,
+}
+
+// A drawing surface made of filled rectangles, so the same drawing can be
+// rasterized to PNG or emitted as SVG shapes
+struct Canvas {
+    width: u32,
+    height: u32,
+    rects: Vec<Rect>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas { width, height, rects: Vec::new() }
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        self.rects.push(Rect { x, y, width, height, color });
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let format = ImageFormat::from_path(path)?;
+        let file = BufWriter::new(File::create(path)?);
+        match format {
+            ImageFormat::Png => self.write_png(file),
+            ImageFormat::Svg => self.write_svg(file),
+        }
+    }
+
+    fn write_png<W: Write>(&self, out: W) -> Result<()> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut pixels = BACKGROUND.repeat(width * height);
+        for rect in &self.rects {
+            let x_end = (rect.x + rect.width).min(self.width) as usize;
+            let y_end = (rect.y + rect.height).min(self.height) as usize;
+            for y in rect.y as usize..y_end {
+                for x in rect.x as usize..x_end {
+                    let offset = (y * width + x) * 3;
+                    pixels[offset..offset + 3].copy_from_slice(&rect.color);
+                }
+            }
+        }
+
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(Error::other)?;
+        writer.write_image_data(&pixels).map_err(Error::other)?;
+        writer.finish().map_err(Error::other)
+    }
+
+    fn write_svg<W: Write>(&self, mut out: W) -> Result<()> {
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" shape-rendering="crispEdges">"#,
+            w = self.width,
+            h = self.height
+        )?;
+        writeln!(out, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex_color(BACKGROUND))?;
+        for rect in &self.rects {
+            writeln!(
+                out,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                hex_color(rect.color)
+            )?;
+        }
+        writeln!(out, "</svg>")?;
+        out.flush()
+    }
+}
+
+fn hex_color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+// Walks the cells of an Ulam spiral outward from the origin: right 1, up 1,
+// left 2, down 2, right 3, ...
+struct Spiral {
+    x: i64,
+    y: i64,
+    dx: i64,
+    dy: i64,
+    leg_length: u64,
+    leg_position: u64,
+    legs_done: u64,
+}
+
+impl Spiral {
+    fn new() -> Spiral {
+        Spiral { x: 0, y: 0, dx: 1, dy: 0, leg_length: 1, leg_position: 0, legs_done: 0 }
+    }
+}
+
+impl Iterator for Spiral {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<(i64, i64)> {
+        let cell = (self.x, self.y);
+        self.x += self.dx;
+        self.y += self.dy;
+        self.leg_position += 1;
+        if self.leg_position == self.leg_length {
+            // Turn counter-clockwise (screen y grows downwards)
+            (self.dx, self.dy) = (self.dy, -self.dx);
+            self.leg_position = 0;
+            self.legs_done += 1;
+            if self.legs_done.is_multiple_of(2) {
+                self.leg_length += 1;
+            }
+        }
+        Some(cell)
+    }
+}
+
+// Function to render the Ulam spiral of a `size` x `size` image centered on `start`.
+//
+// Primality of the whole spiral is taken from a single sieve of the covered range.
+pub fn render_ulam(start: u64, size: u32, output: &Path) -> Result<()> {
+    // Walk an odd-sided square so the spiral is complete, cropping any overhang
+    let side = (size | 1) as u64;
+    let cells = side * side;
+    let end = start
+        .checked_add(cells - 1)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Ulam spiral range exceeds u64"))?;
+    let primes = sieve_range(start, end);
+
+    let center = (side / 2) as i64;
+    let mut canvas = Canvas::new(size, size);
+    for (is_prime, (x, y)) in primes.into_iter().zip(Spiral::new()) {
+        let (px, py) = (x + center, y + center);
+        if is_prime && px < size as i64 && py < size as i64 {
+            canvas.fill_rect(px as u32, py as u32, 1, 1, PRIME_COLOR);
+        }
+    }
+    canvas.save(output)
+}
+
+const GAP_IMAGE_WIDTH: u32 = 800;
+const GAP_IMAGE_HEIGHT: u32 = 400;
+const GAP_BAR_COLOR: Color = [70, 110, 180];
+
+// What a gap histogram shows, for the caller to report
+pub struct GapHistogram {
+    pub gaps: usize,
+    pub min_gap: u128,
+    pub max_gap: u128,
+    pub bin_width: u128,
+}
+
+// Function to render a histogram of the gaps between consecutive primes.
+//
+// Gap sizes are split into `bins` equal-width buckets between the smallest and
+// largest gap found. `primes` does not need to be sorted.
+pub fn render_gap_histogram(primes: &mut [u128], bins: usize, output: &Path) -> Result<GapHistogram> {
+    primes.sort_unstable();
+    let gaps: Vec<u128> = primes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let (min_gap, max_gap) = match (gaps.iter().min(), gaps.iter().max()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "At least two primes are needed to compute gaps",
+            ))
+        }
+    };
+
+    let bins = bins.max(1);
+    let bin_width = (max_gap - min_gap) / bins as u128 + 1;
+    let mut counts = vec![0u64; bins];
+    for gap in &gaps {
+        counts[((gap - min_gap) / bin_width) as usize] += 1;
+    }
+
+    let tallest = *counts.iter().max().unwrap_or(&1);
+    let bar_width = (GAP_IMAGE_WIDTH / bins as u32).max(1);
+    let mut canvas = Canvas::new(bar_width * bins as u32, GAP_IMAGE_HEIGHT);
+    for (bin, count) in counts.iter().enumerate() {
+        let height = (*count as f64 / tallest as f64 * GAP_IMAGE_HEIGHT as f64).round() as u32;
+        if height > 0 {
+            canvas.fill_rect(bin as u32 * bar_width, GAP_IMAGE_HEIGHT - height, bar_width, height, GAP_BAR_COLOR);
+        }
+    }
+    canvas.save(output)?;
+    Ok(GapHistogram { gaps: gaps.len(), min_gap, max_gap, bin_width })
+}
+
+#[cfg(feature = "registry")]
+const COVERAGE_IMAGE_WIDTH: u32 = 1000;
+#[cfg(feature = "registry")]
+const COVERAGE_BAR_HEIGHT: u32 = 60;
+#[cfg(feature = "registry")]
+const COVERAGE_TICK_HEIGHT: u32 = 10;
+#[cfg(feature = "registry")]
+const UNCOVERED_COLOR: Color = [225, 225, 225];
+#[cfg(feature = "registry")]
+const COVERED_COLOR: Color = [60, 150, 80];
+#[cfg(feature = "registry")]
+const TICK_COLOR: Color = [120, 120, 120];
+
+// Function to render which parts of the number line `coverage` covers.
+//
+// The line runs from 1 to the power of ten past the last covered number on a
+// log scale, so each decade gets the same width and small early ranges stay
+// visible next to large later ones; every covered range is at least a pixel
+// wide. Ticks under the bar mark the powers of ten. Returns the number of
+// decades the line spans.
+#[cfg(feature = "registry")]
+pub fn render_coverage(coverage: &[(u128, u128)], output: &Path) -> Result<u32> {
+    let last = coverage
+        .iter()
+        .map(|&(_, end)| end)
+        .max()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "No ranges recorded"))?;
+    let decades = (last as f64 + 1.0).log10().ceil().max(1.0) as u32;
+    let x = |n: u128| ((n.max(1) as f64).log10() / decades as f64 * COVERAGE_IMAGE_WIDTH as f64).round() as u32;
+
+    let mut canvas = Canvas::new(COVERAGE_IMAGE_WIDTH, COVERAGE_BAR_HEIGHT + COVERAGE_TICK_HEIGHT);
+    canvas.fill_rect(0, 0, COVERAGE_IMAGE_WIDTH, COVERAGE_BAR_HEIGHT, UNCOVERED_COLOR);
+    for &(start, end) in coverage {
+        let (from, to) = (x(start), x(end.saturating_add(1)));
+        let from = from.min(COVERAGE_IMAGE_WIDTH - 1);
+        canvas.fill_rect(from, 0, to.saturating_sub(from).max(1), COVERAGE_BAR_HEIGHT, COVERED_COLOR);
+    }
+    for decade in 0..=decades {
+        let tick = (decade * COVERAGE_IMAGE_WIDTH / decades).min(COVERAGE_IMAGE_WIDTH - 1);
+        canvas.fill_rect(tick, COVERAGE_BAR_HEIGHT, 1, COVERAGE_TICK_HEIGHT, TICK_COLOR);
+    }
+    canvas.save(output)?;
+    Ok(decades)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to render into a temporary SVG and return the filled shapes
+    // drawn on top of the background
+    fn shapes(name: &str, render: impl FnOnce(&Path) -> Result<()>) -> Result<Vec<String>> {
+        let path = std::env::temp_dir().join(format!("prime_generator_{}_{}.svg", name, std::process::id()));
+        let rendered = render(&path);
+        let svg = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+        rendered?;
+        Ok(svg?.lines().filter(|line| line.starts_with("<rect x=")).map(String::from).collect())
+    }
+
+    #[test]
+    fn walks_the_spiral_outward() {
+        let cells: Vec<(i64, i64)> = Spiral::new().take(10).collect();
+        assert_eq!(cells, [(0, 0), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1), (2, 1)]);
+
+        // 1 to 9 around 1 in the center: 2 right of it, 3 above 2, 5 up left, 7 down left
+        let primes = shapes("ulam", |path| render_ulam(1, 3, path)).unwrap();
+        assert_eq!(primes.len(), 4);
+        assert!(primes[0].starts_with(r#"<rect x="2" y="1" "#), "{}", primes[0]);
+    }
+
+    #[test]
+    fn bins_the_gaps() {
+        // Gaps 1, 2, 2, 4 and 2
+        let mut primes = [13, 2, 3, 5, 7, 11];
+        let mut histogram = None;
+        let bars = shapes("gaps", |path| render_gap_histogram(&mut primes, 2, path).map(|h| histogram = Some(h))).unwrap();
+        let histogram = histogram.unwrap();
+        assert_eq!((histogram.gaps, histogram.min_gap, histogram.max_gap), (5, 1, 4));
+        // (4 - 1) / 2 + 1: gaps 1 and 2 share the first bin, 4 is alone in the second
+        assert_eq!(histogram.bin_width, 2);
+        assert_eq!(bars.len(), 2);
+        assert!(bars[0].contains(r#"y="0""#) && !bars[1].contains(r#"y="0""#));
+
+        // More bins than gap sizes leaves empty bins without bars
+        let bars = shapes("gaps_fine", |path| render_gap_histogram(&mut primes, 10, path).map(|_| ())).unwrap();
+        assert_eq!(bars.len(), 3);
+
+        let error = shapes("gaps_none", |path| render_gap_histogram(&mut [7], 10, path).map(|_| ())).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn draws_coverage_on_a_log_scale() {
+        let mut decades = 0;
+        let shapes = shapes("coverage", |path| render_coverage(&[(2, 999)], path).map(|d| decades = d)).unwrap();
+        assert_eq!(decades, 3);
+        // The uncovered line, the range from log10(2) to the end and a tick per power of ten
+        assert_eq!(shapes.len(), 1 + 1 + 4);
+        assert!(shapes[1].starts_with(r#"<rect x="100" y="0" width="900" "#), "{}", shapes[1]);
+        assert_eq!(render_coverage(&[], Path::new("unused.svg")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,23 @@
+// JavaScript bindings for browsers and edge runtimes.
+//
+// Numbers cross the boundary as `u64`, which wasm-bindgen maps to JS `BigInt`.
+
+use wasm_bindgen::prelude::*;
+
+/// Returns `true` if `n` is prime.
+#[wasm_bindgen(js_name = isPrime)]
+pub fn is_prime(n: u64) -> bool {
+    crate::primality::is_prime(n as u128)
+}
+
+/// Returns all primes in the inclusive range `[start, end]` as a `BigUint64Array`.
+#[wasm_bindgen(js_name = primesInRange)]
+pub fn primes_in_range(start: u64, end: u64) -> Vec<u64> {
+    crate::sieve::primes_in_range(start, end)
+}
+
+/// Returns the smallest prime greater than `n`, or `undefined` past the `u64` range.
+#[wasm_bindgen(js_name = nextPrime)]
+pub fn next_prime(n: u64) -> Option<u64> {
+    crate::primality::next_prime(n as u128).and_then(|p| u64::try_from(p).ok())
+}